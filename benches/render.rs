@@ -0,0 +1,57 @@
+// Rendering cost of the engine, a frame at a time as the rodio backend pulls it and a
+// block at a time as the cpal and JACK backends do, with more and more voices held and
+// with an effects chain on top. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer, Waveform};
+
+const SAMPLE_RATE: u32 = 44_100;
+const BLOCK: usize = 512;
+const VOICES: [usize; 4] = [1, 8, 32, 64];
+
+const EFFECTS: &str = r#"
+    [[effects]]
+    type = "chorus"
+    [[effects]]
+    type = "distortion"
+    [[effects]]
+    type = "compressor"
+"#;
+
+// A synth with `voices` notes held and past their attack
+fn playing(preset: &Preset, voices: usize) -> Synthesizer {
+    let mut synth = Synthesizer::offline(SAMPLE_RATE, preset).with_waveform(Waveform::Saw).with_polyphony(voices);
+    let notes: Vec<_> = (0..voices).map(|voice| (0, SynthCommand::NoteOn(24 + voice as u8))).collect();
+    synth.render(&notes, SAMPLE_RATE as usize / 10);
+    synth
+}
+
+fn bench_voices(c: &mut Criterion, name: &str, preset: &Preset) {
+    let mut group = c.benchmark_group(name);
+    for voices in VOICES {
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("frame", voices), &voices, |b, &voices| {
+            let mut synth = playing(preset, voices);
+            b.iter(|| black_box(synth.render_frame()));
+        });
+        group.throughput(Throughput::Elements(BLOCK as u64));
+        group.bench_with_input(BenchmarkId::new("block", voices), &voices, |b, &voices| {
+            let mut synth = playing(preset, voices);
+            b.iter(|| black_box(synth.render(&[], BLOCK)));
+        });
+    }
+    group.finish();
+}
+
+fn dry(c: &mut Criterion) {
+    bench_voices(c, "dry", &Preset::default());
+}
+
+fn with_effects(c: &mut Criterion) {
+    bench_voices(c, "effects", &Preset::parse(EFFECTS).expect("Benchmark preset should parse"));
+}
+
+criterion_group!(benches, dry, with_effects);
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+use crate::precision::{to_f32, Real, PI};
+
+// Second-order IIR filter section (Direct Form I) with the RBJ "Audio EQ Cookbook" designs
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad {
+    b0: Real,
+    b1: Real,
+    b2: Real,
+    a1: Real,
+    a2: Real,
+    x1: Real,
+    x2: Real,
+    y1: Real,
+    y2: Real,
+}
+
+impl Biquad {
+    // A filter that passes the signal through unchanged
+    pub fn identity() -> Self {
+        Self::from_coefficients(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn low_shelf(frequency: f32, gain_db: f32, sample_rate: u32) -> Self {
+        let a = Real::from(10.0_f32.powf(gain_db / 40.0));
+        let (cos_w, alpha) = Self::omega(frequency, core::f32::consts::FRAC_1_SQRT_2, sample_rate);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) - (a - 1.0) * cos_w + sqrt_a_alpha),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w),
+            a * ((a + 1.0) - (a - 1.0) * cos_w - sqrt_a_alpha),
+            (a + 1.0) + (a - 1.0) * cos_w + sqrt_a_alpha,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos_w),
+            (a + 1.0) + (a - 1.0) * cos_w - sqrt_a_alpha,
+        )
+    }
+
+    pub fn high_shelf(frequency: f32, gain_db: f32, sample_rate: u32) -> Self {
+        let a = Real::from(10.0_f32.powf(gain_db / 40.0));
+        let (cos_w, alpha) = Self::omega(frequency, core::f32::consts::FRAC_1_SQRT_2, sample_rate);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) + (a - 1.0) * cos_w + sqrt_a_alpha),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w),
+            a * ((a + 1.0) + (a - 1.0) * cos_w - sqrt_a_alpha),
+            (a + 1.0) - (a - 1.0) * cos_w + sqrt_a_alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos_w),
+            (a + 1.0) - (a - 1.0) * cos_w - sqrt_a_alpha,
+        )
+    }
+
+    pub fn low_pass(frequency: f32, q: f32, sample_rate: u32) -> Self {
+        let (cos_w, alpha) = Self::omega(frequency, q, sample_rate);
+        Self::from_coefficients(
+            (1.0 - cos_w) / 2.0,
+            1.0 - cos_w,
+            (1.0 - cos_w) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w,
+            1.0 - alpha,
+        )
+    }
+
+    // Passes a band around `frequency` at unity gain, narrower for a higher `q`
+    pub fn band_pass(frequency: f32, q: f32, sample_rate: u32) -> Self {
+        let (cos_w, alpha) = Self::omega(frequency, q, sample_rate);
+        Self::from_coefficients(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    pub fn peaking(frequency: f32, q: f32, gain_db: f32, sample_rate: u32) -> Self {
+        let a = Real::from(10.0_f32.powf(gain_db / 40.0));
+        let (cos_w, alpha) = Self::omega(frequency, q, sample_rate);
+        Self::from_coefficients(
+            1.0 + alpha * a,
+            -2.0 * cos_w,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos_w,
+            1.0 - alpha / a,
+        )
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let input = Real::from(input);
+        let output = self.b0 * input + self.b1 * self.x1 + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+        to_f32(output)
+    }
+
+    // Swaps in the coefficients of `other` while keeping this filter's history, so
+    // parameters can change while audio is running without a discontinuity
+    pub fn set_coefficients(&mut self, other: &Biquad) {
+        self.b0 = other.b0;
+        self.b1 = other.b1;
+        self.b2 = other.b2;
+        self.a1 = other.a1;
+        self.a2 = other.a2;
+    }
+
+    fn omega(frequency: f32, q: f32, sample_rate: u32) -> (Real, Real) {
+        let nyquist = sample_rate as f32 * 0.5;
+        let w = 2.0 * PI * Real::from(frequency.clamp(10.0, nyquist * 0.95)) / sample_rate as Real;
+        (w.cos(), w.sin() / (2.0 * Real::from(q.max(0.05))))
+    }
+
+    // Normalizes everything by a0
+    fn from_coefficients(b0: Real, b1: Real, b2: Real, a0: Real, a1: Real, a2: Real) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+// What happens to a voice that's still sounding when its note is played again
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Retrigger {
+    #[default]
+    Restart,  // The envelope starts over from silence, for a clear new attack
+    Continue, // The attack picks up from the current level, with no click or jump
+    Legato,   // A held voice carries on untouched; one in its release continues as above
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvelopeSettings {
+    pub attack: f32,  // Seconds to fade in from silence
+    pub release: f32, // Seconds to fade out after the key is released
+    pub retrigger: Retrigger,
+}
+
+impl Default for EnvelopeSettings {
+    fn default() -> Self {
+        Self {
+            attack: 0.01,
+            release: 0.5,
+            retrigger: Retrigger::Restart,
+        }
+    }
+}
+
+impl EnvelopeSettings {
+    // Organ-style gating: notes start and stop with the key, faded just enough not to click
+    pub fn gate() -> Self {
+        Self {
+            attack: 0.005,
+            release: 0.005,
+            retrigger: Retrigger::Continue,
+        }
+    }
+
+    // The settings the synth's "envelope.<name>" parameters change, with their current values
+    pub fn params(&self) -> [(&'static str, f32); 2] {
+        [("attack", self.attack), ("release", self.release)]
+    }
+
+    // Per-sample envelope increments for the attack and release ramps
+    pub fn rates(&self, sample_rate: u32) -> (f32, f32) {
+        let rate = |seconds: f32| 1.0 / (sample_rate as f32 * seconds.max(0.001));
+        (rate(self.attack), rate(self.release))
+    }
+}
+
+// The attack and release of one voice: a linear rise from silence while the note is held
+// and, once it's released, a linear fall to silence from wherever the level had got to
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    attack_phase: f32,  // Progress of the attack, 0.0 to 1.0
+    attack_rate: f32,   // Added to the attack every sample
+    release_phase: f32, // Level during the release, falling to 0.0
+    release_rate: f32,  // Taken off the release every sample
+    releasing: bool,
+    sample_rate: u32,
+}
+
+impl Envelope {
+    pub fn new(settings: &EnvelopeSettings, sample_rate: u32) -> Self {
+        let (attack_rate, release_rate) = settings.rates(sample_rate);
+        Self {
+            attack_phase: 0.0,
+            attack_rate,
+            release_phase: 1.0,
+            release_rate,
+            releasing: false,
+            sample_rate,
+        }
+    }
+
+    // Applies new attack and release times, also partway through a note
+    pub fn set_rates(&mut self, settings: &EnvelopeSettings) {
+        (self.attack_rate, self.release_rate) = settings.rates(self.sample_rate);
+    }
+
+    pub fn is_releasing(&self) -> bool {
+        self.releasing
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.releasing && self.release_phase <= 0.0
+    }
+
+    // Where the envelope is, 0.0 (silent) to 1.0 (fully open), before the next sample
+    pub fn level(&self) -> f32 {
+        if self.releasing {
+            self.release_phase
+        } else {
+            self.attack_phase
+        }
+    }
+
+    // Starts the attack over from silence
+    pub fn restart(&mut self) {
+        self.attack_phase = 0.0;
+        self.releasing = false;
+    }
+
+    // Starts the attack again from the current level, so there's no jump
+    pub fn resume(&mut self) {
+        self.attack_phase = self.level();
+        self.releasing = false;
+    }
+
+    // Starts the release from the current level, which may be partway up the attack
+    pub fn release(&mut self) {
+        self.release_phase = self.level();
+        self.releasing = true;
+    }
+
+    // Releases over about 5 ms, whatever the settings say
+    pub fn fade_out(&mut self) {
+        self.release();
+        self.release_rate = 1.0 / (0.005 * self.sample_rate as f32);
+    }
+
+    // Moves on by one sample and returns the gain for it
+    pub fn next_level(&mut self) -> f32 {
+        if self.releasing {
+            self.release_phase = (self.release_phase - self.release_rate).max(0.0);
+        } else {
+            self.attack_phase = (self.attack_phase + self.attack_rate).min(1.0);
+        }
+        self.level()
+    }
+}
@@ -0,0 +1,14 @@
+// The synth's DSP without std: waveforms, envelopes, filters and a small polyphonic
+// mixer of voices made from them. The `rodio-synth` engine plays these same parts, and
+// a board without an operating system can run `poly::PolySynth` from its audio interrupt.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod biquad;
+pub mod envelope;
+// Tests link std, whose float methods take the place of these
+#[cfg(not(any(feature = "std", test)))]
+mod math;
+pub mod oscillator;
+pub mod poly;
+pub mod precision;
@@ -0,0 +1,45 @@
+// The float functions std has and core lacks, from libm, for builds without std. With
+// std the methods of f32 and f64 themselves are used, so nothing sounds any different.
+
+pub(crate) trait Float {
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+}
+
+impl Float for f32 {
+    fn sin(self) -> f32 {
+        libm::sinf(self)
+    }
+
+    fn cos(self) -> f32 {
+        libm::cosf(self)
+    }
+
+    fn sqrt(self) -> f32 {
+        libm::sqrtf(self)
+    }
+
+    fn powf(self, exponent: f32) -> f32 {
+        libm::powf(self, exponent)
+    }
+}
+
+impl Float for f64 {
+    fn sin(self) -> f64 {
+        libm::sin(self)
+    }
+
+    fn cos(self) -> f64 {
+        libm::cos(self)
+    }
+
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+
+    fn powf(self, exponent: f64) -> f64 {
+        libm::pow(self, exponent)
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+use crate::precision::{to_f32, Real, PI};
+use core::f32::consts::PI as PI_F32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum Waveform {
+    Sine,
+    Square,
+    Saw,
+    Triangle,
+}
+
+impl Waveform {
+    // The waveform's value at `phase`, which runs from 0 to 2π over one cycle
+    pub fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Sine => phase.sin(),
+            Waveform::Square => if phase < PI_F32 { 1.0 } else { -1.0 },
+            Waveform::Saw => phase / PI_F32 - 1.0,
+            Waveform::Triangle => 2.0 * (phase / PI_F32 - 1.0).abs() - 1.0,
+        }
+    }
+}
+
+// Equal temperament, A4 (note 69) = 440 Hz
+pub fn frequency_from_note(note: u8) -> f32 {
+    440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+// A waveform played at a steady pitch, its phase kept in `Real`
+#[derive(Clone, Copy, Debug)]
+pub struct Oscillator {
+    waveform: Waveform,
+    phase: Real,
+    increment: Real, // Added to the phase every sample
+    sample_rate: u32,
+}
+
+impl Oscillator {
+    pub fn new(waveform: Waveform, sample_rate: u32) -> Self {
+        Self { waveform, phase: 0.0, increment: 0.0, sample_rate }
+    }
+
+    // Plays `frequency` from the start of a cycle
+    pub fn start(&mut self, frequency: f32) {
+        self.phase = 0.0;
+        self.increment = 2.0 * PI * Real::from(frequency) / self.sample_rate as Real;
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let sample = self.waveform.sample(to_f32(self.phase));
+        self.phase += self.increment;
+        if self.phase > 2.0 * PI {
+            self.phase -= 2.0 * PI;
+        }
+        sample
+    }
+}
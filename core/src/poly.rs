@@ -0,0 +1,118 @@
+use crate::biquad::Biquad;
+use crate::envelope::{Envelope, EnvelopeSettings};
+use crate::oscillator::{frequency_from_note, Oscillator, Waveform};
+
+// A polyphonic synth of `N` voices, each an oscillator through its own envelope and
+// low-pass filter, mixed to mono as the full engine mixes its voices. Everything is
+// made in `new`, so playing never allocates; a note played with every voice sounding
+// takes over the voice started longest ago.
+pub struct PolySynth<const N: usize> {
+    voices: [Voice; N],
+    filter: Biquad, // What each voice's filter is set to, with no history
+    sample_rate: u32,
+    started: u32, // Notes started so far, to tell which voice is oldest
+}
+
+#[derive(Clone, Debug)]
+struct Voice {
+    note: Option<u8>, // The key holding it, None once released
+    oscillator: Oscillator,
+    envelope: Envelope,
+    filter: Biquad,
+    velocity: f32, // Gain from how hard the note was played, 0.0 to 1.0
+    sounding: bool, // Until its release has faded out
+    started: u32,
+}
+
+impl<const N: usize> PolySynth<N> {
+    pub fn new(waveform: Waveform, envelope: &EnvelopeSettings, sample_rate: u32) -> Self {
+        let voice = Voice {
+            note: None,
+            oscillator: Oscillator::new(waveform, sample_rate),
+            envelope: Envelope::new(envelope, sample_rate),
+            filter: Biquad::identity(),
+            velocity: 1.0,
+            sounding: false,
+            started: 0,
+        };
+        Self { voices: core::array::from_fn(|_| voice.clone()), filter: Biquad::identity(), sample_rate, started: 0 }
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        for voice in &mut self.voices {
+            voice.oscillator.set_waveform(waveform);
+        }
+    }
+
+    // Applies new attack and release times, also to notes already sounding
+    pub fn set_envelope(&mut self, envelope: &EnvelopeSettings) {
+        for voice in &mut self.voices {
+            voice.envelope.set_rates(envelope);
+        }
+    }
+
+    // Retunes every voice's low-pass filter, keeping what they hold so nothing clicks
+    pub fn set_filter(&mut self, cutoff: f32, q: f32) {
+        self.filter = Biquad::low_pass(cutoff, q, self.sample_rate);
+        for voice in &mut self.voices {
+            voice.filter.set_coefficients(&self.filter);
+        }
+    }
+
+    // Starts `note` (MIDI, 60 = C4) at `velocity`, 0.0 to 1.0: again on the voice already
+    // playing it, or else on a silent voice or the oldest
+    pub fn note_on(&mut self, note: u8, velocity: f32) {
+        let index = self
+            .voices
+            .iter()
+            .position(|voice| voice.sounding && voice.note == Some(note))
+            .or_else(|| self.voices.iter().position(|voice| !voice.sounding))
+            .or_else(|| (0..N).min_by_key(|&index| self.voices[index].started));
+        let Some(voice) = index.map(|index| &mut self.voices[index]) else { return }; // No voices at all
+        if !voice.sounding {
+            voice.filter = self.filter; // A fresh filter, with no history from the last note
+        }
+        voice.oscillator.start(frequency_from_note(note));
+        voice.envelope.restart();
+        voice.note = Some(note);
+        voice.velocity = velocity.clamp(0.0, 1.0);
+        voice.sounding = true;
+        voice.started = self.started;
+        self.started = self.started.wrapping_add(1);
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.note == Some(note)) {
+            voice.envelope.release();
+            voice.note = None;
+        }
+    }
+
+    // Fades every voice out within a few milliseconds
+    pub fn all_notes_off(&mut self) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.sounding) {
+            voice.envelope.fade_out();
+            voice.note = None;
+        }
+    }
+
+    pub fn is_silent(&self) -> bool {
+        !self.voices.iter().any(|voice| voice.sounding)
+    }
+
+    // The next sample of the mix, the voices sounding summed and divided among them
+    pub fn next_sample(&mut self) -> f32 {
+        let (mut sum, mut sounding) = (0.0, 0);
+        for voice in self.voices.iter_mut().filter(|voice| voice.sounding) {
+            let level = voice.envelope.next_level() * voice.velocity;
+            sum += voice.filter.process(voice.oscillator.next_sample() * level);
+            sounding += 1;
+            voice.sounding = !voice.envelope.is_finished();
+        }
+        if sounding > 0 {
+            sum / sounding as f32
+        } else {
+            0.0
+        }
+    }
+}
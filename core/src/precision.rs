@@ -0,0 +1,20 @@
+// The float type the DSP keeps running state in: oscillator and LFO phases and the
+// memories of the recursive filters. f32 is plenty for a session, but a phase summed
+// in f32 for days drifts audibly in pitch, and a low filter's poles sit so close to 1
+// that its rounding error can push it unstable. The `f64` feature keeps that state in
+// f64 instead; samples go on as f32, converted where they leave it.
+#[cfg(not(feature = "f64"))]
+pub type Real = f32;
+#[cfg(feature = "f64")]
+pub type Real = f64;
+
+#[cfg(not(feature = "f64"))]
+pub use core::f32::consts::PI;
+#[cfg(feature = "f64")]
+pub use core::f64::consts::PI;
+
+// Back to f32, where a value leaves that state for the signal path
+#[allow(clippy::unnecessary_cast)] // Without the feature it's already f32
+pub fn to_f32(value: Real) -> f32 {
+    value as f32
+}
@@ -0,0 +1,43 @@
+// The polyphonic mixer of the no_std core: notes sound, fade out when released, and
+// take over the oldest voice once every voice is sounding
+
+use rodio_synth_core::envelope::EnvelopeSettings;
+use rodio_synth_core::oscillator::Waveform;
+use rodio_synth_core::poly::PolySynth;
+
+const SAMPLE_RATE: u32 = 8_000;
+
+fn run<const N: usize>(synth: &mut PolySynth<N>, samples: usize) -> Vec<f32> {
+    (0..samples).map(|_| synth.next_sample()).collect()
+}
+
+#[test]
+fn released_notes_fade_to_silence() {
+    let envelope = EnvelopeSettings { attack: 0.01, release: 0.05, ..EnvelopeSettings::default() };
+    let mut synth = PolySynth::<4>::new(Waveform::Saw, &envelope, SAMPLE_RATE);
+    assert!(synth.is_silent());
+    synth.note_on(69, 1.0);
+    assert!(run(&mut synth, 800).iter().any(|sample| sample.abs() > 0.5));
+
+    synth.note_off(69);
+    let release = run(&mut synth, 800);
+    assert!(synth.is_silent());
+    assert!(release[400..].iter().all(|&sample| sample == 0.0));
+}
+
+#[test]
+fn a_note_past_the_voices_takes_over_the_oldest() {
+    let envelope = EnvelopeSettings::default();
+    let mut stolen = PolySynth::<2>::new(Waveform::Sine, &envelope, SAMPLE_RATE);
+    for note in [60, 64, 67] {
+        stolen.note_on(note, 0.8);
+    }
+    let mut played = PolySynth::<2>::new(Waveform::Sine, &envelope, SAMPLE_RATE);
+    for note in [64, 67] {
+        played.note_on(note, 0.8);
+    }
+    assert_eq!(run(&mut stolen, 1_000), run(&mut played, 1_000));
+
+    stolen.note_off(60); // Its voice plays 67 now, so nothing changes
+    assert_eq!(run(&mut stolen, 1_000), run(&mut played, 1_000));
+}
@@ -0,0 +1,74 @@
+#![no_main]
+
+// Feeds arbitrary command sequences into the engine and checks every rendered sample
+// is a finite number within [-1.0, 1.0]. Each five input bytes are one command and
+// the number of frames to render after it:
+//
+//   [kind, argument, value (2 bytes, little-endian), frames]
+
+use libfuzzer_sys::fuzz_target;
+
+use rodio_synth::param_id::ParamId;
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000; // Low, so more commands fit in each run
+
+// Most of the engine switched on, so parameter commands reach every stage
+const PRESET: &str = r#"
+    [layer]
+    enabled = true
+    [morph]
+    enabled = true
+    [fold]
+    enabled = true
+    [filter]
+    enabled = true
+    [split]
+    enabled = true
+    [pitch_envelope]
+    depth = 12.0
+
+    [[effects]]
+    type = "chorus"
+    [[effects]]
+    type = "distortion"
+    [[effects]]
+    type = "compressor"
+
+    [[macros]]
+    targets = [{ param = "filter.cutoff", min = 100.0, max = 8000.0, curve = "exponential" }]
+
+    [[parts]]
+    channel = 2
+"#;
+
+fuzz_target!(|data: &[u8]| {
+    let preset = Preset::parse(PRESET).expect("Fuzz preset should parse");
+    let params: Vec<ParamId> = preset.params().iter().map(|(name, _)| ParamId::known(name)).collect();
+    let mut synth = Synthesizer::offline(SAMPLE_RATE, &preset);
+
+    for chunk in data.chunks_exact(5) {
+        let (kind, argument, frames) = (chunk[0], chunk[1], chunk[4] as usize);
+        let value = i16::from_le_bytes([chunk[2], chunk[3]]) as f32 / 64.0; // -512..512, past every parameter's range
+        let param = || params[argument as usize % params.len()];
+        let command = match kind % 11 {
+            0 => SynthCommand::NoteOn(argument % 128),
+            1 => SynthCommand::NoteOff(argument % 128),
+            2 => SynthCommand::SetParam(param(), value),
+            3 => SynthCommand::GlideParam(param(), value),
+            4 => SynthCommand::SetTempo(value),
+            5 => SynthCommand::SetVelocity(value),
+            6 => SynthCommand::SetMacro(argument as usize % 2, value),
+            7 => SynthCommand::channel_note(argument % 17, chunk[2] % 128, chunk[3] % 2 == 0),
+            8 => SynthCommand::ToggleHold,
+            9 => SynthCommand::ToggleMetronome,
+            _ => SynthCommand::Panic,
+        };
+        for frame in synth.render(&[(0, command)], frames) {
+            for sample in frame {
+                assert!(sample.is_finite() && (-1.0..=1.0).contains(&sample), "Sample {} out of range", sample);
+            }
+        }
+    }
+});
@@ -0,0 +1,204 @@
+use nih_plug::prelude::*;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer, Waveform};
+
+// How many of the synth's parameters the host can automate, besides the waveform
+const PARAMS: usize = 6;
+
+// The engine as a DAW instrument: notes come from the host's MIDI, and a fixed set of
+// the synth's live parameters is exposed for automation. The synth plays the default
+// preset; each automated value goes to `Synthesizer::set_param` when it changes. Both
+// are handed to the synth directly on the host's audio thread, which nothing here
+// allocates on.
+struct RodioSynthPlugin {
+    params: Arc<SynthParams>,
+    synth: Option<Synthesizer>,
+    waveform: Option<PluginWaveform>, // Waveform the synth was last given
+    applied: [f32; PARAMS],           // Last value set for each of `SynthParams::paths`, NaN for none yet
+}
+
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PluginWaveform {
+    Sine,
+    Square,
+    Saw,
+    Triangle,
+}
+
+impl From<PluginWaveform> for Waveform {
+    fn from(waveform: PluginWaveform) -> Self {
+        match waveform {
+            PluginWaveform::Sine => Waveform::Sine,
+            PluginWaveform::Square => Waveform::Square,
+            PluginWaveform::Saw => Waveform::Saw,
+            PluginWaveform::Triangle => Waveform::Triangle,
+        }
+    }
+}
+
+#[derive(Params)]
+struct SynthParams {
+    #[id = "waveform"]
+    waveform: EnumParam<PluginWaveform>,
+    #[id = "attack"]
+    attack: FloatParam,
+    #[id = "release"]
+    release: FloatParam,
+    #[id = "width"]
+    width: FloatParam,
+    #[id = "low_gain"]
+    low_gain: FloatParam,
+    #[id = "mid_gain"]
+    mid_gain: FloatParam,
+    #[id = "high_gain"]
+    high_gain: FloatParam,
+}
+
+fn seconds(name: &str, default: f32, max: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Skewed { min: 0.001, max, factor: FloatRange::skew_factor(-2.0) })
+        .with_unit(" s")
+        .with_value_to_string(formatters::v2s_f32_rounded(3))
+}
+
+fn decibels(name: &str) -> FloatParam {
+    FloatParam::new(name, 0.0, FloatRange::Linear { min: -18.0, max: 18.0 })
+        .with_unit(" dB")
+        .with_value_to_string(formatters::v2s_f32_rounded(1))
+}
+
+impl Default for SynthParams {
+    fn default() -> Self {
+        let preset = Preset::default();
+        Self {
+            waveform: EnumParam::new("Waveform", PluginWaveform::Sine),
+            attack: seconds("Attack", preset.envelope.attack, 5.0),
+            release: seconds("Release", preset.envelope.release, 10.0),
+            width: FloatParam::new("Width", preset.stereo.width, FloatRange::Linear { min: 0.0, max: 2.0 }),
+            low_gain: decibels("Low"),
+            mid_gain: decibels("Mid"),
+            high_gain: decibels("High"),
+        }
+    }
+}
+
+impl SynthParams {
+    // The automatable parameters with their paths for `Synthesizer::set_param`
+    fn paths(&self) -> [(&'static str, f32); PARAMS] {
+        [
+            ("envelope.attack", self.attack.value()),
+            ("envelope.release", self.release.value()),
+            ("stereo.width", self.width.value()),
+            ("eq.low_gain", self.low_gain.value()),
+            ("eq.mid_gain", self.mid_gain.value()),
+            ("eq.high_gain", self.high_gain.value()),
+        ]
+    }
+}
+
+impl Default for RodioSynthPlugin {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(SynthParams::default()),
+            synth: None,
+            waveform: None,
+            applied: [f32::NAN; PARAMS],
+        }
+    }
+}
+
+impl Plugin for RodioSynthPlugin {
+    const NAME: &'static str = "rodio-synth";
+    const VENDOR: &'static str = "vaknin";
+    const URL: &'static str = "https://github.com/vaknin/crthesizer";
+    const EMAIL: &'static str = "";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: None,
+        main_output_channels: NonZeroU32::new(2),
+        ..AudioIOLayout::const_default()
+    }];
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs; // For CC 123, all notes off
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn initialize(&mut self, _: &AudioIOLayout, buffer_config: &BufferConfig, _: &mut impl InitContext<Self>) -> bool {
+        self.synth = Some(Synthesizer::offline(buffer_config.sample_rate as u32, &Preset::default()));
+        self.waveform = None;
+        self.applied = [f32::NAN; PARAMS]; // Everything is set again on the new synth
+        true
+    }
+
+    fn process(&mut self, buffer: &mut Buffer, _: &mut AuxiliaryBuffers, context: &mut impl ProcessContext<Self>) -> ProcessStatus {
+        let Some(synth) = &mut self.synth else {
+            return ProcessStatus::Error("Not initialized");
+        };
+
+        // Automation is applied once per block, which is plenty for these parameters
+        let waveform = self.params.waveform.value();
+        if self.waveform != Some(waveform) {
+            synth.set_waveform(waveform.into());
+            self.waveform = Some(waveform);
+        }
+        for (applied, (path, value)) in self.applied.iter_mut().zip(self.params.paths()) {
+            if *applied != value {
+                synth.set_param(path, value);
+                *applied = value;
+            }
+        }
+
+        // Notes are started on the sample the host placed them at
+        let mut next_event = context.next_event();
+        for (frame, samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > frame as u32 {
+                    break;
+                }
+                match event {
+                    NoteEvent::NoteOn { note, velocity, .. } if velocity > 0.0 => {
+                        synth.handle_command(SynthCommand::SetVelocity(velocity));
+                        synth.handle_command(SynthCommand::NoteOn(note));
+                    }
+                    NoteEvent::NoteOn { note, .. } | NoteEvent::NoteOff { note, .. } => {
+                        synth.handle_command(SynthCommand::NoteOff(note));
+                    }
+                    NoteEvent::MidiCC { cc: 123, .. } => {
+                        synth.handle_command(SynthCommand::Panic);
+                    }
+                    _ => {}
+                }
+                next_event = context.next_event();
+            }
+
+            let rendered = synth.render_frame();
+            for (sample, value) in samples.into_iter().zip(rendered) {
+                *sample = value;
+            }
+        }
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for RodioSynthPlugin {
+    const CLAP_ID: &'static str = "com.github.vaknin.rodio-synth";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Polyphonic synthesizer with effects");
+    const CLAP_MANUAL_URL: Option<&'static str> = None;
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[ClapFeature::Instrument, ClapFeature::Synthesizer, ClapFeature::Stereo];
+}
+
+impl Vst3Plugin for RodioSynthPlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"RodioSynthVaknin";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(RodioSynthPlugin);
+nih_export_vst3!(RodioSynthPlugin);
@@ -0,0 +1,88 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::scale::{ScaleKind, ScaleSettings};
+use crate::SynthCommand;
+
+const MAX_LEAP: i32 = 7; // Semitones the melody moves at most from one note to the next
+
+// Ambient mode: the synth plays itself, starting long notes now and then, in a scale and
+// a range of notes, so a pad preset can drone on for hours with nobody at the keys.
+// Each note wanders a little from the last, and they overlap into slowly shifting chords.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbientSettings {
+    pub enabled: bool,
+    pub density: f32,         // Notes started a minute, on average
+    pub low: u8,              // Lowest note played, C3 by default
+    pub high: u8,             // Highest, C5 by default
+    pub length: f32,          // Seconds a note is held, on average
+    pub scale: ScaleSettings, // The notes it picks from, whether or not the scale is enabled
+    pub seed: Option<u64>,    // Different every run without one
+}
+
+impl Default for AmbientSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            density: 8.0,
+            low: 48,
+            high: 72,
+            length: 8.0,
+            scale: ScaleSettings {
+                scale: ScaleKind::MajorPentatonic,
+                ..ScaleSettings::default()
+            },
+            seed: None,
+        }
+    }
+}
+
+// Runs ambient mode on its own thread, playing into `output` until the synth goes away
+pub fn spawn(settings: AmbientSettings, output: mpsc::Sender<SynthCommand>) {
+    thread::spawn(move || {
+        let mut rng = match settings.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let (low, high) = (settings.low.min(settings.high), settings.high.max(settings.low));
+        let gap = 60.0 / settings.density.max(0.1); // Seconds between notes, on average
+        let mut note = (low as i32 + high as i32) / 2;
+        let mut next_note = Instant::now();
+        let mut sounding: Vec<(u8, Instant)> = Vec::new(); // Each note and when it ends
+
+        loop {
+            let next_off = sounding.iter().map(|&(_, off)| off).min();
+            let wake = next_off.map_or(next_note, |off| off.min(next_note));
+            thread::sleep(wake.saturating_duration_since(Instant::now()));
+            let now = Instant::now();
+
+            for &(ended, _) in sounding.iter().filter(|&&(_, off)| off <= now) {
+                if output.send(SynthCommand::NoteOff(ended)).is_err() {
+                    return;
+                }
+            }
+            sounding.retain(|&(_, off)| off > now);
+
+            if now >= next_note {
+                // A small step from the last note, snapped into the scale and the range
+                note = (note + rng.gen_range(-MAX_LEAP..=MAX_LEAP)).clamp(low as i32, high as i32);
+                let played = settings.scale.quantize(note as u8).clamp(low, high);
+                let length = settings.length.max(0.1) * rng.gen_range(0.5..1.5);
+                // Quieter than the keys, and never changing how loud they play
+                if output.send(SynthCommand::ScaledNoteOn(played, rng.gen_range(0.3..0.7))).is_err() {
+                    return;
+                }
+                sounding.retain(|&(held, _)| held != played); // Played again, so its old note-off would cut the new one
+                sounding.push((played, now + Duration::from_secs_f32(length)));
+                // Random gaps with the chosen average, like raindrops rather than a clock
+                let wait = -gap * (1.0 - rng.gen::<f32>()).ln();
+                next_note = now + Duration::from_secs_f32(wait.min(gap * 5.0));
+            }
+        }
+    });
+}
@@ -0,0 +1,128 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::effects::gain_to_db;
+use crate::error::Error;
+
+const FFT_SIZE: usize = 2048;
+const HEIGHT: usize = FFT_SIZE / 4;  // Rows of the image, two bins each, 0 Hz at the bottom and half the sample rate at the top
+const MAX_WIDTH: usize = 1600;       // Columns, fewer for a short file
+const MIN_HOP: usize = FFT_SIZE / 8; // Frames between columns at the least, so a short file isn't smeared over a wide image
+const FLOOR_DB: f32 = -120.0;        // Black; low enough to show aliasing and filter leakage in a float render
+
+// Colours from FLOOR_DB (black) up to 0 dB (pale yellow), evenly spaced
+const PALETTE: [[f32; 3]; 5] = [[0.0, 0.0, 0.0], [40.0, 0.0, 100.0], [180.0, 0.0, 90.0], [255.0, 120.0, 0.0], [255.0, 255.0, 160.0]];
+
+// Prints the peak and RMS level of each channel of a WAV file and writes its
+// spectrogram as a PNG image, for checking a render or recording and for bug reports.
+// The frequency axis is linear, so partials folded back by aliasing show as lines
+// running the wrong way.
+pub fn analyze(path: &Path, image: &Path) -> Result<(), Error> {
+    let read_failed = |source| Error::Read { path: path.to_path_buf(), source };
+    let mut reader = hound::WavReader::open(path).map_err(read_failed)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let full_scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>().map(|sample| sample.map(|sample| sample as f32 / full_scale)).collect()
+        }
+    }
+    .map_err(read_failed)?;
+    let frames = samples.len() / channels.max(1);
+
+    println!(
+        "{}: {:.2} s, {} Hz, {} channel{}",
+        path.display(),
+        frames as f32 / spec.sample_rate as f32,
+        spec.sample_rate,
+        channels,
+        if channels == 1 { "" } else { "s" }
+    );
+    for channel in 0..channels {
+        let samples = samples.iter().skip(channel).step_by(channels);
+        let (mut peak, mut squares, mut clipped) = (0.0_f32, 0.0_f64, 0);
+        for &sample in samples {
+            peak = peak.max(sample.abs());
+            squares += (sample as f64).powi(2);
+            clipped += usize::from(sample.abs() >= 1.0);
+        }
+        let rms = (squares / frames.max(1) as f64).sqrt() as f32;
+        println!(
+            "  Channel {}: peak {:.1} dBFS, RMS {:.1} dBFS, {} clipped sample{}",
+            channel + 1,
+            gain_to_db(peak),
+            gain_to_db(rms),
+            clipped,
+            if clipped == 1 { "" } else { "s" }
+        );
+    }
+
+    // The channels are mixed down, so the image shows everything in the file
+    let mono: Vec<f32> = samples.chunks_exact(channels.max(1)).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+    let (width, pixels) = spectrogram(&mono);
+    write_png(image, width, &pixels).map_err(Error::file("write spectrogram", image))?;
+    println!(
+        "Spectrogram: {} ({:.2} s across, 0 to {} Hz upwards, {} to 0 dB)",
+        image.display(),
+        frames as f32 / spec.sample_rate as f32,
+        spec.sample_rate / 2,
+        FLOOR_DB
+    );
+    Ok(())
+}
+
+// Hann-windowed FFTs across the sound, as RGB rows from the top; returns the width too
+fn spectrogram(samples: &[f32]) -> (usize, Vec<u8>) {
+    let hop = samples.len().div_ceil(MAX_WIDTH).max(MIN_HOP);
+    let width = samples.len().div_ceil(hop).max(1);
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+    let window: Vec<f32> = (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).collect();
+    let window_gain: f32 = window.iter().sum(); // Scales a full-scale sine to 0 dB
+
+    let mut levels = vec![FLOOR_DB; width * HEIGHT]; // Column by column, from the bottom
+    let mut buffer = vec![Complex::new(0.0, 0.0); FFT_SIZE];
+    for column in 0..width {
+        // Each window is centred on its column, with silence beyond the ends
+        let start = (column * hop) as isize - (FFT_SIZE / 2) as isize;
+        for (i, (bin, &window)) in buffer.iter_mut().zip(&window).enumerate() {
+            let sample = usize::try_from(start + i as isize).ok().and_then(|index| samples.get(index)).copied().unwrap_or(0.0);
+            *bin = Complex::new(sample * window, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (row, bins) in buffer[..FFT_SIZE / 2].chunks_exact(2).enumerate() {
+            let peak = bins.iter().map(|bin| 2.0 * bin.norm() / window_gain).fold(0.0, f32::max);
+            levels[column * HEIGHT + row] = gain_to_db(peak).max(FLOOR_DB);
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(width * HEIGHT * 3);
+    for row in (0..HEIGHT).rev() {
+        for column in 0..width {
+            pixels.extend(colour(levels[column * HEIGHT + row]));
+        }
+    }
+    (width, pixels)
+}
+
+fn colour(db: f32) -> [u8; 3] {
+    let position = ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0) * (PALETTE.len() - 1) as f32;
+    let index = (position as usize).min(PALETTE.len() - 2);
+    let fraction = position - index as f32;
+    let (low, high) = (PALETTE[index], PALETTE[index + 1]);
+    [0, 1, 2].map(|i| (low[i] + (high[i] - low[i]) * fraction) as u8)
+}
+
+fn write_png(path: &Path, width: usize, pixels: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
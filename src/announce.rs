@@ -0,0 +1,83 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+// Tells the player what a key, program change or reload just changed: as a line on the
+// console, and with speech on (`--speak`) aloud too, so the synth can be played without
+// seeing a screen. Speech goes through the system's own voice: espeak-ng or espeak on
+// Linux, `say` on macOS and SAPI on Windows.
+#[derive(Clone)]
+pub struct Announcer {
+    speech: Option<mpsc::Sender<String>>,
+}
+
+impl Announcer {
+    pub fn new(speak: bool) -> Self {
+        let speech = speak.then(|| {
+            let (tx, rx) = mpsc::channel::<String>();
+            thread::spawn(move || {
+                while let Ok(text) = rx.recv() {
+                    // Only the newest of a burst is spoken, so holding a key doesn't queue up a speech
+                    let text = rx.try_iter().last().unwrap_or(text);
+                    if let Err(e) = speak_aloud(&speakable(&text)) {
+                        eprintln!("Failed to speak (is espeak-ng installed?): {}", e);
+                        return;
+                    }
+                }
+            });
+            tx
+        });
+        Self { speech }
+    }
+
+    pub fn say(&self, text: impl Into<String>) {
+        let text = text.into();
+        println!("{}", text);
+        if let Some(speech) = &self.speech {
+            let _ = speech.send(text);
+        }
+    }
+}
+
+// Reads parameter paths out as words: "filter.cutoff_lfo" as "filter cutoff lfo", leaving "0.5" a number
+fn speakable(text: &str) -> String {
+    let mut spoken = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '_' => spoken.push(' '),
+            '.' if chars.peek().is_some_and(|next| next.is_alphabetic()) => spoken.push(' '),
+            c => spoken.push(c),
+        }
+    }
+    spoken
+}
+
+// Speaks `text` and waits until it's been said; the text goes in on stdin so nothing needs quoting
+fn speak_aloud(text: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak([Console]::In.ReadToEnd())",
+        ]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("say")
+    } else {
+        let mut command = Command::new("espeak-ng");
+        command.arg("--stdin");
+        command
+    };
+    let mut child = match command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && cfg!(target_os = "linux") => {
+            Command::new("espeak").arg("--stdin").stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?
+        }
+        child => child?,
+    };
+    child.stdin.take().expect("piped").write_all(text.as_bytes())?;
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::humanize::{HumanizeSettings, Humanizer};
+use crate::tempo::{NoteDivision, SharedTempo};
+use crate::SynthCommand;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArpPattern {
+    Up,
+    Down,
+    UpDown, // Up then back down without repeating the top and bottom notes
+    Random,
+}
+
+const BUILT_IN: [(ArpPattern, &str); 4] = [
+    (ArpPattern::Up, "up"),
+    (ArpPattern::Down, "down"),
+    (ArpPattern::UpDown, "up_down"),
+    (ArpPattern::Random, "random"),
+];
+
+const MAX_OCTAVE_SHIFT: i64 = 10; // Octaves a custom step can move its note, beyond which no MIDI note is left
+
+// One step of a custom pattern
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArpStep {
+    Note { index: usize, octave: i8 }, // A held note, 0 the lowest, moved by whole octaves
+    Rest,
+    Tie, // Holds the note before on through this step
+}
+
+// A custom pattern's steps, written in a preset as a string of them: a number picks a
+// held note counting up from 1 (round again past the top, over `octaves`), each `+` or
+// `-` after it moves it an octave, `.` is a rest and `_` ties the note before over the
+// step. "1 3 2+ . 1 _ 3- _" plays the lowest note, the third, the second an octave up,
+// rests, then holds the lowest and the third an octave down for two steps each.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ArpSteps(pub Vec<ArpStep>);
+
+impl FromStr for ArpSteps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = s
+            .split_whitespace()
+            .map(|step| match step {
+                "." => Ok(ArpStep::Rest),
+                "_" => Ok(ArpStep::Tie),
+                _ => {
+                    let digits = step.trim_end_matches(['+', '-']);
+                    let shifts = &step[digits.len()..];
+                    let number: usize = digits.parse().map_err(|_| format!("arp step '{}' should be a note number, '.' or '_'", step))?;
+                    if number == 0 {
+                        return Err(format!("arp step '{}': notes count from 1", step));
+                    }
+                    let octave = shifts.chars().map(|shift| if shift == '+' { 1 } else { -1 }).sum::<i64>();
+                    if octave.abs() > MAX_OCTAVE_SHIFT {
+                        return Err(format!("arp step '{}' moves more than {} octaves", step, MAX_OCTAVE_SHIFT));
+                    }
+                    Ok(ArpStep::Note { index: number - 1, octave: octave as i8 })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if steps.is_empty() {
+            return Err("an arp pattern needs at least one step".to_string());
+        }
+        Ok(ArpSteps(steps))
+    }
+}
+
+impl TryFrom<String> for ArpSteps {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ArpSteps> for String {
+    fn from(steps: ArpSteps) -> Self {
+        steps.to_string()
+    }
+}
+
+impl fmt::Display for ArpSteps {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let steps: Vec<String> = self
+            .0
+            .iter()
+            .map(|step| match *step {
+                ArpStep::Note { index, octave } => {
+                    let shift = if octave > 0 { "+" } else { "-" };
+                    format!("{}{}", index + 1, shift.repeat(octave.unsigned_abs() as usize))
+                }
+                ArpStep::Rest => ".".to_string(),
+                ArpStep::Tie => "_".to_string(),
+            })
+            .collect();
+        write!(f, "{}", steps.join(" "))
+    }
+}
+
+// A pattern of the user's own, e.g. in a preset:
+//
+//     [[arp.patterns]]
+//     name = "climb"
+//     steps = "1 2 3 1+ . 3 _ 2"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CustomPattern {
+    pub name: String,
+    pub steps: ArpSteps,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArpSettings {
+    pub enabled: bool,
+    pub pattern: ArpPattern,
+    pub custom: String,     // The name of one of `patterns` to play instead of `pattern`, or empty
+    pub patterns: Vec<CustomPattern>,
+    pub rate: NoteDivision, // Length of each step, e.g. "1/16"
+    pub octaves: u8,        // How many octaves the held chord is repeated over, 1..=4
+    pub gate: f32,          // Fraction of each step the note is held for, 0.05..=1.0
+}
+
+impl Default for ArpSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pattern: ArpPattern::Up,
+            custom: String::new(),
+            patterns: Vec::new(),
+            rate: NoteDivision::SIXTEENTH,
+            octaves: 1,
+            gate: 0.5,
+        }
+    }
+}
+
+impl ArpSettings {
+    // Every pattern there is to switch to, the built-in ones first, as the "arp.pattern"
+    // parameter numbers them from 0
+    pub fn pattern_names(&self) -> Vec<String> {
+        BUILT_IN
+            .iter()
+            .map(|(_, name)| name.to_string())
+            .chain(self.patterns.iter().map(|pattern| pattern.name.clone()))
+            .collect()
+    }
+
+    // Whether the custom pattern to start on is one of `patterns`
+    pub fn check(&self) -> Result<(), String> {
+        if self.custom.is_empty() || self.patterns.iter().any(|pattern| pattern.name == self.custom) {
+            Ok(())
+        } else {
+            Err(format!("no arp pattern named '{}' in arp.patterns", self.custom))
+        }
+    }
+
+    // The number of the pattern the preset starts on, among `pattern_names`
+    pub fn selected(&self) -> usize {
+        match self.patterns.iter().position(|pattern| pattern.name == self.custom) {
+            Some(index) => BUILT_IN.len() + index,
+            None => BUILT_IN.iter().position(|&(pattern, _)| pattern == self.pattern).unwrap_or(0),
+        }
+    }
+}
+
+// Turns the set of held notes into a stream of single notes, one per step
+pub struct Arpeggiator {
+    settings: ArpSettings,
+    custom: Option<usize>, // The custom pattern playing, if not a built-in one
+    held: Vec<u8>,         // Sorted ascending
+    step: usize,
+}
+
+impl Arpeggiator {
+    pub fn new(settings: ArpSettings) -> Result<Self, String> {
+        settings.check()?;
+        let mut arp = Self {
+            settings,
+            custom: None,
+            held: Vec::new(),
+            step: 0,
+        };
+        arp.select(arp.settings.selected());
+        Ok(arp)
+    }
+
+    // Switches to pattern `index` of `ArpSettings::pattern_names`, from its first step;
+    // returns false if there's no such pattern
+    pub fn select(&mut self, index: usize) -> bool {
+        match BUILT_IN.get(index) {
+            Some(&(pattern, _)) => {
+                self.settings.pattern = pattern;
+                self.custom = None;
+            }
+            None if index - BUILT_IN.len() < self.settings.patterns.len() => self.custom = Some(index - BUILT_IN.len()),
+            None => return false,
+        }
+        self.step = 0;
+        true
+    }
+
+    pub fn press(&mut self, note: u8) {
+        if let Err(index) = self.held.binary_search(&note) {
+            self.held.insert(index, note);
+        }
+    }
+
+    pub fn release(&mut self, note: u8) {
+        self.held.retain(|&held| held != note);
+        if self.held.is_empty() {
+            self.step = 0; // The next chord starts the pattern from the beginning
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.step = 0;
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    // The held notes spread over the octave range, in ascending order
+    fn ladder(&self) -> Vec<u8> {
+        (0..self.settings.octaves.clamp(1, 4))
+            .flat_map(|octave| self.held.iter().map(move |&note| note.saturating_add(12 * octave)))
+            .filter(|&note| note <= 127)
+            .collect()
+    }
+
+    // The next step's note and how many steps it lasts (more than one when ties follow
+    // it), or None for a rest
+    pub fn next_note(&mut self) -> Option<(u8, u32)> {
+        let ladder = self.ladder();
+        if ladder.is_empty() {
+            return None;
+        }
+        if let Some(custom) = self.custom {
+            let steps = &self.settings.patterns[custom].steps.0;
+            let at = self.step % steps.len();
+            self.step += 1;
+            let ArpStep::Note { index, octave } = steps[at] else { return None }; // A tie with no note before is a rest
+            let ties = (1..steps.len()).take_while(|ahead| steps[(at + ahead) % steps.len()] == ArpStep::Tie).count();
+            let note = ladder[index % ladder.len()] as i32 + 12 * octave as i32;
+            return u8::try_from(note).ok().filter(|&note| note <= 127).map(|note| (note, 1 + ties as u32));
+        }
+
+        let note = match self.settings.pattern {
+            ArpPattern::Up => ladder[self.step % ladder.len()],
+            ArpPattern::Down => ladder[ladder.len() - 1 - self.step % ladder.len()],
+            ArpPattern::UpDown => {
+                // A ladder of n notes has a cycle of 2n - 2 steps (at least 1)
+                let cycle = (2 * ladder.len()).saturating_sub(2).max(1);
+                let position = self.step % cycle;
+                if position < ladder.len() {
+                    ladder[position]
+                } else {
+                    ladder[cycle - position]
+                }
+            }
+            ArpPattern::Random => ladder[rand::thread_rng().gen_range(0..ladder.len())],
+        };
+        self.step += 1;
+        Some((note, 1))
+    }
+}
+
+// Runs the arpeggiator on its own clock thread. Note commands sent to the returned
+// sender are consumed as held notes; the generated notes (and any other command)
+// are forwarded to `output`, the synthesizer's command channel.
+pub fn spawn(settings: ArpSettings, humanize: HumanizeSettings, tempo: SharedTempo, output: mpsc::Sender<SynthCommand>) -> io::Result<mpsc::Sender<SynthCommand>> {
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    let gate = settings.gate.clamp(0.05, 1.0);
+    let rate = settings.rate;
+    let mut arp = Arpeggiator::new(settings).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    thread::spawn(move || {
+        let mut humanizer = Humanizer::new(humanize);
+
+        let mut next_step = Instant::now();
+        let mut delay = Duration::ZERO; // How far behind the step its note comes in
+        let mut velocity = 1.0; // The last velocity played at, which the humanizer varies
+        let mut sounding: Option<(u8, Instant)> = None; // Current note and when its gate closes
+
+        loop {
+            let now = Instant::now();
+            let mut deadline = next_step + delay;
+            if let Some((_, gate_off)) = sounding {
+                deadline = deadline.min(gate_off);
+            }
+            let timeout = if arp.is_idle() && sounding.is_none() {
+                Duration::from_secs(1) // Nothing to play, just wait for keys
+            } else {
+                deadline.saturating_duration_since(now)
+            };
+
+            match rx.recv_timeout(timeout) {
+                Ok(SynthCommand::NoteOn(note)) => {
+                    if arp.is_idle() {
+                        next_step = tempo.first_step(Instant::now(), rate.beats()); // Start on the first key press, not on the old grid
+                    }
+                    arp.press(note);
+                }
+                Ok(SynthCommand::NoteOff(note)) => arp.release(note),
+                Ok(SynthCommand::SetVelocity(value)) => {
+                    velocity = value;
+                    if output.send(SynthCommand::SetVelocity(value)).is_err() {
+                        return;
+                    }
+                }
+                // Switching patterns is the arp's own business; the synth never sees it
+                Ok(SynthCommand::SetParam(id, value)) if id.as_str() == "arp.pattern" => {
+                    if !arp.select(value.max(0.0) as usize) {
+                        eprintln!("No arp pattern {}", value);
+                    }
+                }
+                Ok(SynthCommand::Panic) => {
+                    arp.clear(); // The synth silences the sounding note itself
+                    sounding = None;
+                    if output.send(SynthCommand::Panic).is_err() {
+                        return;
+                    }
+                }
+                Ok(command) => {
+                    if output.send(command).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            if let Some((note, gate_off)) = sounding {
+                if now >= gate_off || arp.is_idle() {
+                    if output.send(SynthCommand::NoteOff(note)).is_err() {
+                        return;
+                    }
+                    sounding = None;
+                }
+            }
+
+            if now >= next_step + delay && !arp.is_idle() {
+                let step_length = Duration::from_secs_f32(rate.seconds(tempo.get()));
+                if let Some((note, steps)) = arp.next_note() {
+                    if let Some((previous, _)) = sounding.take() {
+                        let _ = output.send(SynthCommand::NoteOff(previous));
+                    }
+                    for command in humanizer.before_note(velocity).into_iter().chain([humanizer.note_on(note)]) {
+                        if output.send(command).is_err() {
+                            return;
+                        }
+                    }
+                    // A tied note is held through the steps it's tied over, then gated in the last
+                    sounding = Some((note, now + step_length * (steps - 1) + step_length.mul_f32(gate)));
+                }
+                // Stay on the grid, but don't try to catch up after a long stall
+                next_step = tempo.step_after(next_step, rate.beats());
+                if next_step < now {
+                    next_step = tempo.step_after(now, rate.beats());
+                }
+                delay = humanizer.delay();
+            }
+        }
+    });
+
+    Ok(tx)
+}
@@ -0,0 +1,711 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BufferSize, FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig, SupportedBufferSize, SupportedStreamConfig};
+use rodio::{source::Source, OutputStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cpu_meter::CpuMeter;
+use crate::denormals;
+use crate::error::Error;
+use crate::latency::{LatencyProbe, OnsetListener};
+use crate::live_input::InputRing;
+use crate::surround::Layout;
+use crate::xrun::XrunMonitor;
+use crate::Synthesizer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    Rodio, // The synth is a rodio `Source`, mixed and resampled by rodio
+    Cpal,  // The synth renders straight into cpal's callback buffers
+    #[cfg(feature = "jack")]
+    Jack, // The synth is a JACK client with its own stereo output ports
+}
+
+const RODIO_BLOCK: u32 = 512; // Default frames rendered ahead when rodio pulls the synth a sample at a time, 11.6 ms at 44.1 kHz
+const DEVICE_CHECK: Duration = Duration::from_secs(1); // How often the output device is checked for
+
+// The synth is shared between the stream playing it and any stream that replaces it
+// after the device goes away; only one of them is ever rendering
+type SharedSynth = Arc<Mutex<Synthesizer>>;
+
+// Buffer size asked for on the command line, in frames or as a latency
+#[derive(Clone, Copy, Debug)]
+pub enum BufferRequest {
+    Frames(u32),
+    Millis(f32),
+}
+
+impl BufferRequest {
+    fn frames(self, sample_rate: u32) -> u32 {
+        match self {
+            BufferRequest::Frames(frames) => frames,
+            BufferRequest::Millis(ms) => (ms * 0.001 * sample_rate as f32).round() as u32,
+        }
+        .max(1)
+    }
+}
+
+// The output's share of the latency from a key press to the sound leaving the device,
+// as found once playing. The buffer is what the synth renders ahead; the device adds its
+// own delay on top where it reports one.
+pub struct OutputLatency {
+    buffer_frames: u32,
+    sample_rate: u32,
+    device_delay: Option<Duration>,
+}
+
+impl OutputLatency {
+    // Prints the whole latency, with the keyboard's polling interval when it's polled
+    // (see `Keyboard::poll_interval`); keys read as events add nothing
+    pub fn report(&self, key_poll: Option<Duration>) {
+        let buffer_ms = self.buffer_frames as f32 * 1000.0 / self.sample_rate as f32;
+        let device_ms = self.device_delay.map_or(0.0, |delay| delay.as_secs_f32() * 1000.0);
+        let poll_ms = key_poll.map_or(0.0, |poll| poll.as_secs_f32() * 1000.0);
+        let polling = if key_poll.is_some() { format!(" + {:.0} ms key polling", poll_ms) } else { String::new() };
+        println!(
+            "Latency: {} frame buffer ({:.1} ms) + {:.1} ms device{} = {:.1} ms from key to sound",
+            self.buffer_frames,
+            buffer_ms,
+            device_ms,
+            polling,
+            buffer_ms + device_ms + poll_ms
+        );
+    }
+}
+
+// Prints every output device with its index, marking the default one and showing
+// its preferred configuration, so one can be picked with `--device`
+pub fn list_devices() -> Result<(), Error> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|device| device.name().ok());
+    let devices = host.output_devices().map_err(Error::audio("Failed to list output devices"))?;
+
+    println!("Output devices ({}):", host.id().name());
+    for (index, device) in devices.enumerate() {
+        let name = device.name().unwrap_or_else(|_| "(unnamed)".to_string());
+        let marker = if Some(&name) == default_name.as_ref() { "*" } else { " " };
+        let config = match device.default_output_config() {
+            Ok(config) => format!("{} Hz, {} channels", config.sample_rate().0, config.channels()),
+            Err(_) => "no usable output configuration".to_string(),
+        };
+        println!("{} {:>2}: {} ({})", marker, index, name, config);
+    }
+
+    // For `--input`
+    let default_name = host.default_input_device().and_then(|device| device.name().ok());
+    let devices = host.input_devices().map_err(Error::audio("Failed to list input devices"))?;
+    println!("Input devices:");
+    for (index, device) in devices.enumerate() {
+        let name = device.name().unwrap_or_else(|_| "(unnamed)".to_string());
+        let marker = if Some(&name) == default_name.as_ref() { "*" } else { " " };
+        let config = match device.default_input_config() {
+            Ok(config) => format!("{} Hz, {} channels", config.sample_rate().0, config.channels()),
+            Err(_) => "no usable input configuration".to_string(),
+        };
+        println!("{} {:>2}: {} ({})", marker, index, name, config);
+    }
+    Ok(())
+}
+
+// The output device picked with `--device`, by its exact name or its index in
+// `list-devices`, or the system default when none was given
+pub fn output_device(selection: Option<&str>) -> Result<cpal::Device, Error> {
+    let host = cpal::default_host();
+    let Some(selection) = selection else {
+        return host.default_output_device().ok_or(Error::NoOutputDevice);
+    };
+
+    let devices = host.output_devices().map_err(Error::audio("Failed to list output devices"))?;
+    pick_device(devices.collect(), selection).ok_or_else(|| Error::UnknownDevice(selection.to_string()))
+}
+
+// The device named `selection` exactly, or at that index
+fn pick_device(mut devices: Vec<cpal::Device>, selection: &str) -> Option<cpal::Device> {
+    let by_name = devices.iter().position(|device| device.name().is_ok_and(|name| name == selection));
+    let by_index = selection.parse::<usize>().ok().filter(|&index| index < devices.len());
+    by_name.or(by_index).map(|index| devices.swap_remove(index))
+}
+
+// Starts capturing from the input device picked with `--input`, by name or index like
+// `--device`, or the system default, into `ring`. Nothing resamples the input, so the
+// device has to run at the synth's rate. Capture goes on until the stream is dropped.
+pub fn open_input(selection: Option<&str>, sample_rate: u32, ring: InputRing) -> Result<cpal::Stream, Error> {
+    let host = cpal::default_host();
+    let device = match selection {
+        None => host.default_input_device().ok_or(Error::NoInputDevice)?,
+        Some(selection) => {
+            let devices = host.input_devices().map_err(Error::audio("Failed to list input devices"))?;
+            pick_device(devices.collect(), selection).ok_or_else(|| Error::UnknownInputDevice(selection.to_string()))?
+        }
+    };
+    let default_config = device.default_input_config().map_err(Error::audio("Input device has no usable configuration"))?;
+    let config = if default_config.sample_rate().0 == sample_rate {
+        default_config
+    } else {
+        let mut configs = device.supported_input_configs().map_err(Error::audio("Input device has no usable configuration"))?;
+        configs
+            .find(|config| (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&sample_rate))
+            .map(|config| config.with_sample_rate(SampleRate(sample_rate)))
+            .ok_or_else(|| Error::Audio(format!("Input device can't run at {} Hz like the output; try another --sample-rate", sample_rate)))?
+    };
+
+    let sample_format = config.sample_format();
+    let config: StreamConfig = config.into();
+    let stream = match sample_format {
+        SampleFormat::F32 => build_input_stream::<f32>(&device, &config, ring),
+        SampleFormat::I16 => build_input_stream::<i16>(&device, &config, ring),
+        SampleFormat::U16 => build_input_stream::<u16>(&device, &config, ring),
+        SampleFormat::I32 => build_input_stream::<i32>(&device, &config, ring),
+        format => return Err(Error::Audio(format!("Unsupported input sample format {}", format))),
+    }
+    .map_err(Error::audio("Failed to open input stream"))?;
+    stream.play().map_err(Error::audio("Failed to start input stream"))?;
+    println!("Mixing in live input from {}", device.name().unwrap_or_default());
+    Ok(stream)
+}
+
+// Pushes every captured frame into `ring`: a mono device on both sides, otherwise its
+// first two channels
+fn build_input_stream<T>(device: &cpal::Device, config: &StreamConfig, ring: InputRing) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            for frame in data.chunks(channels) {
+                match *frame {
+                    [mono] => ring.push([mono.to_sample(); 2]),
+                    [left, right, ..] => ring.push([left.to_sample(), right.to_sample()]),
+                    [] => {}
+                }
+            }
+        },
+        |err| eprintln!("Audio input error: {}", err),
+        None,
+    )
+}
+
+// The device's default configuration moved to `rate`, if the device can run at it
+fn config_at_rate(device: &cpal::Device, default_config: &SupportedStreamConfig, rate: u32) -> Option<SupportedStreamConfig> {
+    if rate == default_config.sample_rate().0 {
+        return Some(default_config.clone());
+    }
+    // Keep the default channel count and sample format, only change the rate
+    let mut configs = device.supported_output_configs().ok()?;
+    configs
+        .find(|config| {
+            config.channels() == default_config.channels()
+                && config.sample_format() == default_config.sample_format()
+                && (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&rate)
+        })
+        .map(|config| config.with_sample_rate(SampleRate(rate)))
+}
+
+// `config`, or one at the same rate and sample format with enough channels for a surround
+// layout if it has too few; the fewest that will do, as extra ones are left silent
+fn config_with_channels(device: &cpal::Device, config: SupportedStreamConfig, channels: u16) -> Option<SupportedStreamConfig> {
+    if config.channels() >= channels {
+        return Some(config);
+    }
+    let rate = config.sample_rate();
+    device
+        .supported_output_configs()
+        .ok()?
+        .filter(|supported| {
+            supported.channels() >= channels
+                && supported.sample_format() == config.sample_format()
+                && (supported.min_sample_rate()..=supported.max_sample_rate()).contains(&rate)
+        })
+        .min_by_key(|supported| supported.channels())
+        .map(|supported| supported.with_sample_rate(rate))
+}
+
+// An opened output device, ready to play a synth rendering at `sample_rate()`
+pub enum Output {
+    Rodio {
+        device: cpal::Device,
+        config: Option<SupportedStreamConfig>, // None to let rodio resample from the device default
+        sample_rate: u32,
+        block: u32, // Frames the synth renders ahead of rodio at a time
+    },
+    Cpal {
+        device: cpal::Device,
+        config: SupportedStreamConfig,
+        buffer_size: Option<u32>, // Fixed callback size in frames, the device default if `None`
+        channels: u16,            // Fewest the device must have, more than two for a surround layout
+    },
+    #[cfg(feature = "jack")]
+    Jack {
+        client: jack::Client,
+        left: jack::Port<jack::AudioOut>,
+        right: jack::Port<jack::AudioOut>,
+    },
+}
+
+impl Output {
+    // Opens the `device` selection at `requested` Hz, or at its preferred rate when none
+    // was asked for. Through rodio, a rate the device can't run at is still rendered as
+    // asked and resampled; through cpal there is no resampler, so the preferred rate is
+    // used instead. JACK runs at the server's rate and buffer size whatever is asked.
+    // A surround `layout` plays through cpal, the one backend that reaches every channel.
+    pub fn open(backend: Backend, device: Option<&str>, requested: Option<u32>, buffer_size: Option<BufferRequest>, layout: Layout) -> Result<Self, Error> {
+        if layout != Layout::Stereo {
+            if backend != Backend::Cpal {
+                eprintln!("Note: surround output plays through the cpal backend");
+            }
+            return Self::open_cpal(output_device(device)?, requested, buffer_size, layout.channels() as u16);
+        }
+        match backend {
+            Backend::Rodio => Self::open_rodio(output_device(device)?, requested, buffer_size),
+            Backend::Cpal => Self::open_cpal(output_device(device)?, requested, buffer_size, 2),
+            #[cfg(feature = "jack")]
+            Backend::Jack => Self::open_jack(device, requested, buffer_size),
+        }
+    }
+
+    // Rodio picks its own device buffer, so --buffer-size and --latency set the synth's
+    // blocks instead, which add their length to the latency on top of rodio's buffer
+    fn open_rodio(device: cpal::Device, requested: Option<u32>, buffer_size: Option<BufferRequest>) -> Result<Self, Error> {
+        let default_config = device.default_output_config().map_err(Error::audio("Output device has no usable configuration"))?;
+        let preferred = default_config.sample_rate().0;
+        let rate = requested.unwrap_or(preferred);
+        let config = config_at_rate(&device, &default_config, rate);
+        if config.is_none() {
+            eprintln!("Output device doesn't support {} Hz, resampling to {} Hz", rate, preferred);
+        }
+        let block = buffer_size.map_or(RODIO_BLOCK, |request| request.frames(rate));
+        Ok(Output::Rodio { device, config, sample_rate: rate, block })
+    }
+
+    fn open_cpal(device: cpal::Device, requested: Option<u32>, buffer_size: Option<BufferRequest>, channels: u16) -> Result<Self, Error> {
+        let default_config = device.default_output_config().map_err(Error::audio("Output device has no usable configuration"))?;
+        let preferred = default_config.sample_rate().0;
+        let rate = requested.unwrap_or(preferred);
+        let config = config_at_rate(&device, &default_config, rate).unwrap_or_else(|| {
+            eprintln!("Output device doesn't support {} Hz, rendering at {} Hz", rate, preferred);
+            default_config
+        });
+        let config = config_with_channels(&device, config, channels)
+            .ok_or_else(|| Error::Audio(format!("Output device has no configuration with {} channels for the surround layout", channels)))?;
+
+        // Keep a requested size inside what the device accepts
+        let buffer_size = buffer_size.map(|request| {
+            let frames = request.frames(config.sample_rate().0);
+            match *config.buffer_size() {
+                SupportedBufferSize::Range { min, max } if !(min..=max).contains(&frames) => {
+                    let clamped = frames.clamp(min, max);
+                    eprintln!("Output device doesn't support a {} frame buffer, using {} frames", frames, clamped);
+                    clamped
+                }
+                _ => frames,
+            }
+        });
+        Ok(Output::Cpal { device, config, buffer_size, channels })
+    }
+
+    #[cfg(feature = "jack")]
+    fn open_jack(device: Option<&str>, requested: Option<u32>, buffer_size: Option<BufferRequest>) -> Result<Self, Error> {
+        let (client, _status) = jack::Client::new("rodio-synth", jack::ClientOptions::NO_START_SERVER)
+            .map_err(Error::audio("Failed to connect to the JACK server (is it running?)"))?;
+        let left = client.register_port("out_left", jack::AudioOut).map_err(Error::audio("Failed to register JACK port"))?;
+        let right = client.register_port("out_right", jack::AudioOut).map_err(Error::audio("Failed to register JACK port"))?;
+
+        if device.is_some() || buffer_size.is_some() {
+            eprintln!("Note: --device, --buffer-size and --latency are ignored with JACK, connect the ports and set the buffer size in JACK");
+        }
+        let sample_rate = client.sample_rate();
+        if requested.is_some_and(|rate| rate as usize != sample_rate) {
+            eprintln!("Note: JACK runs at {} Hz, rendering at that rate", sample_rate);
+        }
+        Ok(Output::Jack { client, left, right })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        match self {
+            Output::Rodio { sample_rate, .. } => *sample_rate,
+            Output::Cpal { config, .. } => config.sample_rate().0,
+            #[cfg(feature = "jack")]
+            Output::Jack { client, .. } => client.sample_rate() as u32,
+        }
+    }
+
+    // Starts playing `synth` and gives the latency achieved, for reporting once the
+    // keyboard's is known too (rodio reports what it can here). Rendering time goes to
+    // `cpu`, and underruns are counted in `xruns`, except through rodio, which hides its
+    // callback; so are the onsets of notes for a latency `probe`, which only cpal can time.
+    // Sound stops when the returned stream is dropped.
+    //
+    // With rodio and cpal, a watchdog thread notices when the device goes away (unplugged
+    // or disconnected) and carries on playing through whatever is then the default device.
+    // Rodio streams are opened on the watchdog's thread from the start, so each one can be
+    // dropped before the next is opened.
+    pub fn play(self, synth: Synthesizer, xruns: XrunMonitor, cpu: CpuMeter, probe: Option<LatencyProbe>) -> Result<(Playing, Option<OutputLatency>), Error> {
+        match self {
+            Output::Rodio { device, config, sample_rate, block } => {
+                let synth = Arc::new(Mutex::new(synth));
+                let name = device.name().unwrap_or_default();
+                let (started_tx, started) = mpsc::sync_channel(1);
+                // Rodio keeps stream errors to itself, so nothing is sent; the channel only
+                // disconnects, when playing stops
+                let (stop, lost) = mpsc::channel();
+                thread::spawn(move || {
+                    let stream = match open_rodio_stream(&device, config, &synth, &cpu, block) {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            let _ = started_tx.send(Err(e));
+                            return;
+                        }
+                    };
+                    let _ = started_tx.send(Ok(()));
+                    let reopen = || {
+                        let device = cpal::default_host().default_output_device()?;
+                        let config = config_at_rate(&device, &device.default_output_config().ok()?, sample_rate);
+                        open_rodio_stream(&device, config, &synth, &cpu, block).ok().map(Stream::Rodio)
+                    };
+                    watch(name, lost, Some(Stream::Rodio(stream)), reopen);
+                });
+                started.recv().map_err(|_| Error::Audio("Failed to open output device".to_string()))??;
+
+                let block_ms = block as f32 * 1000.0 / sample_rate as f32;
+                println!("Latency: {} frame blocks ({:.1} ms) + the device buffer rodio picks; use --backend cpal to see all of it", block, block_ms);
+                println!("Underruns aren't detected through rodio; use --backend cpal to count them");
+                Ok((Playing::Rodio(stop), None))
+            }
+            Output::Cpal { device, config, buffer_size, channels } => {
+                let sample_rate = config.sample_rate().0;
+                let synth = Arc::new(Mutex::new(synth));
+                let (lost_tx, lost) = mpsc::channel();
+                let (latency_tx, latency_rx) = mpsc::sync_channel(1);
+                let stream = open_cpal_stream(&device, config, buffer_size, &synth, latency_tx, &xruns, &cpu, probe.as_ref(), lost_tx.clone())
+                    .ok_or_else(|| Error::Audio("Failed to open output stream".to_string()))?;
+
+                // The first callback tells how big the buffers really are
+                let latency = match latency_rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok((buffer_frames, device_delay)) => Some(OutputLatency { buffer_frames, sample_rate, device_delay }),
+                    Err(_) => {
+                        eprintln!("Output stream hasn't asked for audio yet, latency unknown");
+                        None
+                    }
+                };
+
+                let name = device.name().unwrap_or_default();
+                let reopen = move || {
+                    let device = cpal::default_host().default_output_device()?;
+                    let config = config_at_rate(&device, &device.default_output_config().ok()?, sample_rate)
+                        .and_then(|config| config_with_channels(&device, config, channels));
+                    let Some(config) = config else {
+                        eprintln!("{} can't play at {} Hz", device.name().unwrap_or_default(), sample_rate);
+                        return None;
+                    };
+                    let (latency_tx, _) = mpsc::sync_channel(1);
+                    open_cpal_stream(&device, config, buffer_size, &synth, latency_tx, &xruns, &cpu, probe.as_ref(), lost_tx.clone()).map(Stream::Cpal)
+                };
+                watch_device(name, lost, reopen);
+                Ok((Playing::Cpal(stream), latency))
+            }
+            #[cfg(feature = "jack")]
+            Output::Jack { client, left, right } => {
+                let outputs = [left.name(), right.name()];
+                let client = client
+                    .activate_async(JackNotifications { xruns }, JackProcess { synth, left, right, cpu })
+                    .map_err(Error::audio("Failed to activate JACK client"))?;
+
+                // Wire the ports to the first two physical outputs, like most JACK synths do;
+                // they can be rewired freely in the session afterwards
+                let playback = client.as_client().ports(None, Some(jack::jack_sys::FLOAT_MONO_AUDIO), jack::PortFlags::IS_INPUT | jack::PortFlags::IS_PHYSICAL);
+                for (output, input) in outputs.iter().zip(&playback) {
+                    if let Ok(output) = output {
+                        if let Err(e) = client.as_client().connect_ports_by_name(output, input) {
+                            eprintln!("Failed to connect {} to {}: {}", output, input, e);
+                        }
+                    }
+                }
+
+                let client_ref = client.as_client();
+                let device_delay = outputs[0]
+                    .as_ref()
+                    .ok()
+                    .and_then(|name| client_ref.port_by_name(name))
+                    .map(|port| port.get_latency_range(jack::LatencyType::Playback).1)
+                    .map(|frames| Duration::from_secs_f32(frames as f32 / client_ref.sample_rate() as f32));
+                let latency = OutputLatency { buffer_frames: client_ref.buffer_size(), sample_rate: client_ref.sample_rate() as u32, device_delay };
+                Ok((Playing::Jack(client), Some(latency)))
+            }
+        }
+    }
+}
+
+// Opens `device` through rodio at `config`, or at its default for rodio to resample to,
+// and starts the synth on it
+fn open_rodio_stream(device: &cpal::Device, config: Option<SupportedStreamConfig>, synth: &SharedSynth, cpu: &CpuMeter, block: u32) -> Result<OutputStream, Error> {
+    let (stream, handle) = match config {
+        Some(config) => OutputStream::try_from_device_config(device, config),
+        None => OutputStream::try_from_device(device),
+    }
+    .map_err(Error::audio("Failed to open output device"))?;
+    let source = RodioSource::new(synth.clone(), cpu.clone(), block);
+    handle.play_raw(source.convert_samples()).map_err(Error::audio("Failed to start playing"))?;
+    Ok(stream)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn open_cpal_stream(
+    device: &cpal::Device,
+    config: SupportedStreamConfig,
+    buffer_size: Option<u32>,
+    synth: &SharedSynth,
+    latency: mpsc::SyncSender<(u32, Option<Duration>)>,
+    xruns: &XrunMonitor,
+    cpu: &CpuMeter,
+    probe: Option<&LatencyProbe>,
+    lost: mpsc::Sender<()>,
+) -> Option<cpal::Stream> {
+    let sample_format = config.sample_format();
+    let mut config: StreamConfig = config.into();
+    if let Some(frames) = buffer_size {
+        config.buffer_size = BufferSize::Fixed(frames);
+    }
+    let (synth, xruns, cpu) = (synth.clone(), xruns.clone(), cpu.clone());
+    let onsets = probe.map(|probe| probe.listener(config.sample_rate.0));
+    let stream = match sample_format {
+        SampleFormat::F32 => build_stream::<f32>(device, &config, synth, latency, xruns, cpu, onsets, lost),
+        SampleFormat::I16 => build_stream::<i16>(device, &config, synth, latency, xruns, cpu, onsets, lost),
+        SampleFormat::U16 => build_stream::<u16>(device, &config, synth, latency, xruns, cpu, onsets, lost),
+        SampleFormat::I32 => build_stream::<i32>(device, &config, synth, latency, xruns, cpu, onsets, lost),
+        format => {
+            eprintln!("Unsupported sample format {}", format);
+            return None;
+        }
+    };
+    match stream.and_then(|stream| stream.play().map_err(|e| e.to_string()).map(|()| stream)) {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            eprintln!("Failed to start output stream: {}", e);
+            None
+        }
+    }
+}
+
+// A stream owned by the watchdog thread that opened it
+enum Stream {
+    Rodio(OutputStream),
+    Cpal(cpal::Stream),
+}
+
+// Watches for the output device disappearing, from a stream error sent on `lost` or
+// from the device dropping out of the device list, on a thread of its own (see `watch`).
+// Streams can't move between threads, so this first one stays where it was opened and
+// the replacements live on the watchdog's thread.
+fn watch_device(name: String, lost: mpsc::Receiver<()>, reopen: impl Fn() -> Option<Stream> + Send + 'static) {
+    thread::spawn(move || watch(name, lost, None, reopen));
+}
+
+// Keeps `current` playing until the device goes away, then drops it and calls `reopen`
+// until it gets a stream going again, and so on until `lost` disconnects
+fn watch(mut name: String, lost: mpsc::Receiver<()>, mut current: Option<Stream>, reopen: impl Fn() -> Option<Stream>) {
+    loop {
+        let error = match lost.recv_timeout(DEVICE_CHECK) {
+            Ok(()) => true,
+            Err(mpsc::RecvTimeoutError::Timeout) => false,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return, // Playing stopped
+        };
+        let present = || {
+            cpal::default_host()
+                .output_devices()
+                .is_ok_and(|mut devices| devices.any(|device| device.name().is_ok_and(|found| found == name)))
+        };
+        if !error && (name.is_empty() || present()) {
+            continue;
+        }
+
+        eprintln!("Output device '{}' went away, waiting for another one", name);
+        drop(current.take());
+        loop {
+            if let Some(stream) = reopen() {
+                current = Some(stream);
+                break;
+            }
+            thread::sleep(DEVICE_CHECK);
+        }
+        name = cpal::default_host().default_output_device().and_then(|device| device.name().ok()).unwrap_or_default();
+        println!("Playing through '{}'", name);
+        while lost.try_recv().is_ok() {} // Errors from the old stream
+    }
+}
+
+// Keeps the output stream alive while the synth plays
+pub enum Playing {
+    Rodio(mpsc::Sender<()>), // Dropping it stops the watchdog thread, which owns the rodio stream
+    Cpal(cpal::Stream),
+    #[cfg(feature = "jack")]
+    Jack(jack::AsyncClient<JackNotifications, JackProcess>),
+}
+
+// JACK reports its own xruns, for any client in the graph
+#[cfg(feature = "jack")]
+pub struct JackNotifications {
+    xruns: XrunMonitor,
+}
+
+#[cfg(feature = "jack")]
+impl jack::NotificationHandler for JackNotifications {
+    fn xrun(&mut self, _: &jack::Client) -> jack::Control {
+        self.xruns.record();
+        jack::Control::Continue
+    }
+}
+
+// Renders each JACK period straight into the two output ports
+#[cfg(feature = "jack")]
+pub struct JackProcess {
+    synth: Synthesizer,
+    left: jack::Port<jack::AudioOut>,
+    right: jack::Port<jack::AudioOut>,
+    cpu: CpuMeter,
+}
+
+#[cfg(feature = "jack")]
+impl jack::ProcessHandler for JackProcess {
+    fn process(&mut self, client: &jack::Client, scope: &jack::ProcessScope) -> jack::Control {
+        let start = Instant::now();
+        denormals::flush_to_zero();
+        let left = self.left.as_mut_slice(scope);
+        let right = self.right.as_mut_slice(scope);
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            [*left, *right] = self.synth.render_frame();
+        }
+        let budget = Duration::from_secs_f32(scope.n_frames() as f32 / client.sample_rate() as f32);
+        self.cpu.record(start.elapsed(), budget);
+        jack::Control::Continue
+    }
+}
+
+// Renders whole blocks into cpal's buffer: left and right go to the first two channels,
+// a mono device gets their average and any further channels stay silent. In surround
+// each channel of the layout gets its own, and any beyond those stay silent. The first
+// callback sends its size and the device's playback delay to `latency`.
+//
+// Each block should start playing right where the previous one ends. When it is due
+// later than that, the device ran out of audio in between, which is counted in `xruns`.
+// Losing the device is reported on `lost`, and notes starting to `onsets`, with when
+// they'll be heard.
+#[allow(clippy::too_many_arguments)]
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    synth: SharedSynth,
+    latency: mpsc::SyncSender<(u32, Option<Duration>)>,
+    xruns: XrunMonitor,
+    cpu: CpuMeter,
+    mut onsets: Option<OnsetListener>,
+    lost: mpsc::Sender<()>,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let sample_rate = config.sample_rate.0 as f32;
+    let mut latency = Some(latency);
+    let mut expected_playback: Option<cpal::StreamInstant> = None;
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+                let start = Instant::now();
+                denormals::flush_to_zero();
+                let timestamp = info.timestamp();
+                let heard = start + timestamp.playback.duration_since(&timestamp.callback).unwrap_or_default();
+                let block = Duration::from_secs_f32((data.len() / channels) as f32 / sample_rate);
+                if let Some(latency) = latency.take() {
+                    let _ = latency.try_send(((data.len() / channels) as u32, timestamp.playback.duration_since(&timestamp.callback)));
+                }
+                let gap = expected_playback.and_then(|expected| timestamp.playback.duration_since(&expected));
+                if gap.is_some_and(|gap| gap > block / 2) {
+                    xruns.record();
+                }
+                expected_playback = timestamp.playback.add(block);
+                let mut synth = synth.lock().unwrap();
+                for (index, frame) in data.chunks_mut(channels).enumerate() {
+                    let [left, right] = synth.render_frame();
+                    if let Some(onsets) = &mut onsets {
+                        onsets.listen([left, right], heard + Duration::from_secs_f32(index as f32 / sample_rate));
+                    }
+                    if let Some(surround) = synth.surround_output() {
+                        for (index, sample) in frame.iter_mut().enumerate() {
+                            *sample = T::from_sample(surround.get(index).copied().unwrap_or(0.0));
+                        }
+                        continue;
+                    }
+                    match frame {
+                        [mono] => *mono = T::from_sample((left + right) * 0.5),
+                        [first, second, rest @ ..] => {
+                            *first = T::from_sample(left);
+                            *second = T::from_sample(right);
+                            rest.fill(T::EQUILIBRIUM);
+                        }
+                        [] => {}
+                    }
+                }
+                cpu.record(start.elapsed(), block);
+            },
+            move |err| {
+                eprintln!("Audio stream error: {}", err);
+                if let cpal::StreamError::DeviceNotAvailable = err {
+                    let _ = lost.send(());
+                }
+            },
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
+
+// The synth as a rodio `Source`. Rodio pulls it a sample at a time from inside its
+// own callback, so the synth renders a block at a time into `samples`, and the time
+// that takes is recorded against the block's length.
+struct RodioSource {
+    synth: SharedSynth,
+    sample_rate: u32,
+    cpu: CpuMeter,
+    block: u32,        // Frames rendered at a time
+    samples: Vec<f32>, // Interleaved, consumed from `position`
+    position: usize,
+}
+
+impl RodioSource {
+    fn new(synth: SharedSynth, cpu: CpuMeter, block: u32) -> Self {
+        let sample_rate = synth.lock().unwrap().sample_rate();
+        Self { synth, sample_rate, cpu, block, samples: Vec::with_capacity(block as usize * 2), position: 0 }
+    }
+}
+
+impl Iterator for RodioSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position == self.samples.len() {
+            let start = Instant::now();
+            denormals::flush_to_zero();
+            let mut synth = self.synth.lock().unwrap();
+            self.samples.clear();
+            for _ in 0..self.block {
+                self.samples.extend(synth.render_frame());
+            }
+            let budget = Duration::from_secs_f32(self.block as f32 / self.sample_rate as f32);
+            self.cpu.record(start.elapsed(), budget);
+            self.position = 0;
+        }
+        self.position += 1;
+        Some(self.samples[self.position - 1])
+    }
+}
+
+impl Source for RodioSource {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 2 }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::preset::Preset;
+
+// Presets numbered for MIDI program changes: the .toml files in a directory, in file
+// name order, as programs 0, 1, 2 and so on. Names like "00-bass.toml" and
+// "01-lead.toml" keep the numbers where they're meant to be. Every preset is read up
+// front, so a broken one is reported at start rather than in the middle of a song.
+#[derive(Clone, Debug, Default)]
+pub struct PresetBank {
+    presets: Vec<(PathBuf, Preset)>,
+}
+
+impl PresetBank {
+    pub fn load(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "toml"));
+        paths.sort();
+        let presets = paths
+            .into_iter()
+            .map(|path| match Preset::load(&path) {
+                Ok(preset) => Ok((path, preset)),
+                Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { presets })
+    }
+
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+
+    // The preset for a program number, if the bank has that many
+    pub fn get(&self, program: u8) -> Option<&Preset> {
+        self.presets.get(program as usize).map(|(_, preset)| preset)
+    }
+
+    // What to call a program: the preset's name, or its file's when it has none
+    pub fn name(&self, program: u8) -> Option<String> {
+        let (path, preset) = self.presets.get(program as usize)?;
+        if !preset.name.is_empty() {
+            return Some(preset.name.clone());
+        }
+        Some(path.file_stem().unwrap_or_default().to_string_lossy().into_owned())
+    }
+}
@@ -0,0 +1,84 @@
+use std::f64::consts::TAU;
+
+const LEVEL: f32 = 0.25; // Same as the reference tone: clearly there without being loud on headphones
+
+// A session of beating tones: `beat` Hz apart between the ears, or one tone pulsing
+// `beat` times a second in both
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BinauralSettings {
+    pub carrier: f64,        // Hz, midway between the two ears' tones
+    pub beat: f64,           // Hz
+    pub isochronic: bool,    // Pulse one tone on and off rather than detune the ears, for speakers
+    pub length: Option<f32>, // Seconds the session lasts, fades included; None runs until stopped
+    pub fade: f32,           // Seconds fading in at the start and out at the end
+}
+
+impl BinauralSettings {
+    // The tones heard in the left and right ears
+    pub fn frequencies(&self) -> [f64; 2] {
+        if self.isochronic {
+            [self.carrier; 2]
+        } else {
+            [self.carrier - self.beat / 2.0, self.carrier + self.beat / 2.0]
+        }
+    }
+}
+
+// The session as the synth plays it, dry and after the effects so nothing blurs the two
+// ears together. Binaural beats need headphones: the beat is made in the head, from a
+// tone in each ear. Isochronic tones are pulsed with a raised cosine, so each pulse
+// starts and stops without a click, and work on speakers too.
+pub struct BinauralTone {
+    settings: BinauralSettings,
+    phases: [f64; 2], // Of the left and right tones, in cycles
+    pulse: f64,       // Of the isochronic pulse, in cycles
+    elapsed: u64,     // Samples since the start
+    gain: f32,        // Of the fade, or the stop
+    stopping: bool,
+    sample_rate: u32,
+}
+
+impl BinauralTone {
+    pub fn new(settings: BinauralSettings, sample_rate: u32) -> Self {
+        Self { settings, phases: [0.0; 2], pulse: 0.0, elapsed: 0, gain: 0.0, stopping: false, sample_rate }
+    }
+
+    // Fades out early, over the session's fade
+    pub fn stop(&mut self) {
+        self.stopping = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.gain == 0.0 && (self.stopping || self.settings.length.is_some_and(|length| self.seconds() >= length))
+    }
+
+    fn seconds(&self) -> f32 {
+        self.elapsed as f32 / self.sample_rate as f32
+    }
+
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        let fade = self.settings.fade.max(0.02); // Never so short it clicks
+        let step = 1.0 / (fade * self.sample_rate as f32);
+        // How far the session's own fades would have it; a stop fades down from wherever it is
+        let seconds = self.seconds();
+        let scheduled = match self.settings.length {
+            Some(length) => (seconds / fade).min((length - seconds) / fade).clamp(0.0, 1.0),
+            None => (seconds / fade).min(1.0),
+        };
+        self.gain = if self.stopping { (self.gain - step).max(0.0).min(scheduled) } else { scheduled };
+        self.elapsed += 1;
+
+        let sample_rate = self.sample_rate as f64;
+        let mut frame = [0.0; 2];
+        for ((phase, frequency), sample) in self.phases.iter_mut().zip(self.settings.frequencies()).zip(&mut frame) {
+            *sample = (TAU * *phase).sin() as f32;
+            *phase = (*phase + frequency / sample_rate).fract();
+        }
+        if self.settings.isochronic {
+            let pulse = (0.5 - 0.5 * (TAU * self.pulse).cos()) as f32;
+            self.pulse = (self.pulse + self.settings.beat / sample_rate).fract();
+            frame = frame.map(|sample| sample * pulse);
+        }
+        frame.map(|sample| sample * LEVEL * self.gain)
+    }
+}
@@ -0,0 +1,62 @@
+use crate::param_bank::ParamBank;
+use crate::params::ParamRange;
+use crate::preset::Preset;
+
+// Interpolates between two presets, A and B: every numeric parameter the two share
+// moves from A's value to B's as the amount goes from 0.0 to 1.0, on a log scale for
+// frequencies and times so the middle sounds like the middle. What can't be blended
+// stays as A has it: the waveform, which sections are on, the order of the effects,
+// and the settings of an effect slot that holds a different effect in B.
+#[derive(Clone, Debug, Default)]
+pub struct PresetBlend {
+    params: Vec<BlendParam>,
+}
+
+// What's worked out once per blended parameter, rather than on every move of the amount
+#[derive(Clone, Debug, Default)]
+struct BlendParam {
+    path: String,
+    from: f32, // A's value
+    to: f32,   // B's
+    logarithmic: bool,
+    slot: Option<usize>, // In the bank the views read, once bound to one
+}
+
+impl PresetBlend {
+    pub fn new(a: &Preset, b: &Preset) -> Self {
+        let b_params = b.params();
+        let same_effect = |path: &str| {
+            let Some(slot) = path.strip_prefix("effects.").and_then(|rest| rest.split('.').next()) else { return true };
+            let name = |preset: &Preset| slot.parse::<usize>().ok().and_then(|slot| preset.effects.get(slot)).map(|effect| effect.name());
+            name(a).is_some() && name(a) == name(b)
+        };
+        let params = a
+            .params()
+            .into_iter()
+            .filter(|(path, _)| same_effect(path))
+            .filter_map(|(path, from)| {
+                let &(_, to) = b_params.iter().find(|(other, _)| *other == path)?;
+                let logarithmic = ParamRange::of(&path).logarithmic && from * to > 0.0;
+                Some(BlendParam { path, from, to, logarithmic, slot: None })
+            })
+            .collect();
+        Self { params }
+    }
+
+    // Looks up where each parameter sits in `bank`, so the values the blend sets can be
+    // written back for the views to show
+    pub fn bind(&mut self, bank: &ParamBank) {
+        for param in &mut self.params {
+            param.slot = bank.index(&param.path);
+        }
+    }
+
+    // Each parameter's value `amount` of the way from A to B, with its slot in the bound bank
+    pub fn at(&self, amount: f32) -> impl Iterator<Item = (&str, Option<usize>, f32)> {
+        let amount = amount.clamp(0.0, 1.0);
+        self.params.iter().map(move |&BlendParam { ref path, from, to, logarithmic, slot }| {
+            let value = if logarithmic { from * (to / from).powf(amount) } else { from + (to - from) * amount };
+            (path.as_str(), slot, value)
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rodio_synth::record_tap::{RecordTap, RecordedFrame};
+use rodio_synth::stereo::Frame;
+
+use crate::recording;
+
+pub const DEFAULT_SECONDS: f32 = 60.0;
+
+// Keeps the last few seconds of what the synth played, always, so a good moment can be
+// saved after it happened without having started a recording. Saving copies them out
+// and writes them on a thread of their own, so the buffer goes on filling meanwhile.
+pub struct Capture {
+    saves: mpsc::Sender<()>,
+}
+
+impl Capture {
+    // Returns the tap for `Synthesizer::with_recording`
+    pub fn spawn(seconds: f32, sample_rate: u32) -> (Self, RecordTap) {
+        let (tap, frames) = RecordTap::new(sample_rate, false);
+        let (saves, requests) = mpsc::channel();
+        let length = (seconds * sample_rate as f32) as usize;
+        thread::spawn(move || {
+            let mut buffer = VecDeque::with_capacity(length);
+            for RecordedFrame { mix, .. } in frames {
+                if buffer.len() == length {
+                    buffer.pop_front();
+                }
+                buffer.push_back(mix);
+                if requests.try_recv().is_ok() {
+                    let frames: Vec<Frame> = buffer.iter().copied().collect();
+                    thread::spawn(move || save(frames, sample_rate));
+                }
+            }
+        });
+        (Self { saves }, tap)
+    }
+
+    // Writes what the buffer holds now to capture-<date>-<time>.wav in the current directory
+    pub fn save(&self) {
+        let _ = self.saves.send(());
+    }
+}
+
+fn save(frames: Vec<Frame>, sample_rate: u32) {
+    let path = PathBuf::from(format!("capture-{}.wav", timestamp()));
+    match write(&path, &frames, sample_rate) {
+        Ok(()) => println!("Saved the last {:.1} s to {}", frames.len() as f32 / sample_rate as f32, path.display()),
+        Err(e) => eprintln!("Failed to save the capture to {}: {}", path.display(), e),
+    }
+}
+
+fn write(path: &Path, frames: &[Frame], sample_rate: u32) -> io::Result<()> {
+    let mut writer = recording::create(path, sample_rate)?;
+    for &[left, right] in frames {
+        writer.write_sample(left).and_then(|_| writer.write_sample(right)).map_err(io::Error::other)?;
+    }
+    writer.finalize().map_err(io::Error::other)
+}
+
+// The time now in UTC, as 2024-05-17-213045
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()) as i64;
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    // The civil date from days since 1970-01-01, counting years from March so leap days come last
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+const CPU_CLOCK: f32 = 1_789_773.0; // Hz, of an NTSC NES, which every channel's timer divides
+const DUTIES: [f32; 4] = [0.125, 0.25, 0.5, 0.75];
+const NOISE_PERIODS: [f32; 16] = [4.0, 8.0, 16.0, 32.0, 64.0, 96.0, 128.0, 160.0, 202.0, 254.0, 380.0, 508.0, 762.0, 1016.0, 2034.0, 4068.0];
+
+// The channels of the NES's 2A03 sound chip, each of which plays one note at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChipChannel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+}
+
+// The order notes take the tonal channels in
+pub const TONAL_CHANNELS: [ChipChannel; 3] = [ChipChannel::Pulse1, ChipChannel::Pulse2, ChipChannel::Triangle];
+
+// "2A03 mode": the voices become the NES's sound chip, with all its limits. Notes take
+// the two pulse channels and then the triangle, one note each, and a fourth note cuts
+// off one of them; notes below `noise_below` play the noise channel, each key one of
+// its sixteen rates. Pitches snap to what the chip's timers can make, which puts high
+// notes audibly out of tune, and loudness to its sixteen volume steps, except on the
+// triangle, which has none.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChipSettings {
+    pub enabled: bool,
+    pub duty: [u8; 2],   // Each pulse channel's duty cycle: 0 is 12.5%, 1 is 25%, 2 is 50%, 3 is 75%
+    pub noise_below: u8, // Notes under this one play the noise channel
+}
+
+impl Default for ChipSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duty: [2, 1],
+            noise_below: 48, // C3
+        }
+    }
+}
+
+impl ChipSettings {
+    // The settings the synth's "chip.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("duty_1", self.duty[0] as f32), ("duty_2", self.duty[1] as f32)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let pulse = match name {
+            "duty_1" => 0,
+            "duty_2" => 1,
+            _ => return false,
+        };
+        self.duty[pulse] = value.round().clamp(0.0, 3.0) as u8;
+        true
+    }
+}
+
+// One voice on one of the chip's channels
+pub struct ChipVoice {
+    channel: ChipChannel,
+    phase: f32,      // 0.0 to 1.0 through the pulse or triangle cycle
+    shift: u16,      // The noise channel's 15-bit shift register
+    noise_time: f32, // Timer clocks since the register last shifted
+}
+
+impl ChipVoice {
+    pub fn new(channel: ChipChannel) -> Self {
+        Self {
+            channel,
+            phase: 0.0,
+            shift: 1, // As at power on
+            noise_time: 0.0,
+        }
+    }
+
+    pub fn channel(&self) -> ChipChannel {
+        self.channel
+    }
+
+    // The loudness the chip can give a note played at `velocity`
+    pub fn quantize_velocity(&self, velocity: f32) -> f32 {
+        match self.channel {
+            ChipChannel::Triangle => 1.0,
+            _ => (velocity.clamp(0.0, 1.0) * 15.0).round() / 15.0,
+        }
+    }
+
+    // The next sample for `note` at `frequency` (after bends and glides), with the
+    // pulses' `duty` cycles
+    pub fn next_sample(&mut self, note: u8, frequency: f32, duty: [u8; 2], sample_rate: u32) -> f32 {
+        if self.channel == ChipChannel::Noise {
+            // Higher keys clock the register faster, for a brighter hiss
+            let period = NOISE_PERIODS[15 - (note % 16) as usize];
+            self.noise_time += CPU_CLOCK / sample_rate as f32;
+            while self.noise_time >= period {
+                self.noise_time -= period;
+                let feedback = (self.shift ^ (self.shift >> 1)) & 1;
+                self.shift = (self.shift >> 1) | (feedback << 14);
+            }
+            return if self.shift & 1 == 0 { 0.5 } else { -0.5 };
+        }
+
+        // The chip divides its clock by a whole 11-bit timer, 16 steps a cycle for the
+        // pulses and 32 for the triangle, so only those pitches exist
+        let steps = if self.channel == ChipChannel::Triangle { 32.0 } else { 16.0 };
+        let timer = (CPU_CLOCK / (steps * frequency.max(1.0)) - 1.0).round().clamp(8.0, 2047.0);
+        self.phase = (self.phase + CPU_CLOCK / (steps * (timer + 1.0)) / sample_rate as f32).fract();
+        match self.channel {
+            ChipChannel::Pulse1 | ChipChannel::Pulse2 => {
+                let duty = duty[(self.channel == ChipChannel::Pulse2) as usize].min(3);
+                if self.phase < DUTIES[duty as usize] { 1.0 } else { -1.0 }
+            }
+            // Sixteen levels down and back up
+            _ => {
+                let step = (self.phase * 32.0) as u8;
+                let level = if step < 16 { 15 - step } else { step - 16 };
+                level as f32 / 7.5 - 1.0
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use crate::notes::NoteSet;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordShape {
+    Major,
+    Minor,
+    Seventh, // Dominant 7th
+    MajorSeventh,
+    MinorSeventh,
+    Sus4,
+    Custom, // Uses `intervals` from the settings
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChordSettings {
+    pub enabled: bool,
+    pub shape: ChordShape,
+    pub intervals: Vec<i8>, // Semitones above (or below) the played note, for the custom shape
+}
+
+impl Default for ChordSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shape: ChordShape::Major,
+            intervals: vec![0, 7, 12],
+        }
+    }
+}
+
+impl ChordSettings {
+    fn intervals(&self) -> &[i8] {
+        match self.shape {
+            ChordShape::Major => &[0, 4, 7],
+            ChordShape::Minor => &[0, 3, 7],
+            ChordShape::Seventh => &[0, 4, 7, 10],
+            ChordShape::MajorSeventh => &[0, 4, 7, 11],
+            ChordShape::MinorSeventh => &[0, 3, 7, 10],
+            ChordShape::Sus4 => &[0, 5, 7],
+            ChordShape::Custom => &self.intervals,
+        }
+    }
+
+    // The notes of the chord built on `root`, skipping any that fall outside the MIDI range
+    pub fn expand(&self, root: u8) -> NoteSet {
+        self.intervals()
+            .iter()
+            .filter_map(|&interval| u8::try_from(root as i16 + interval as i16).ok())
+            .filter(|&note| note <= 127)
+            .collect()
+    }
+}
@@ -0,0 +1,296 @@
+use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::audio::{Backend, BufferRequest};
+use crate::capture;
+use crate::dither::Dither;
+use crate::effects::crossfeed::CrossfeedLevel;
+use crate::icecast::{Mount, StreamFormat};
+use crate::jam::DEFAULT_JAM_PORT;
+use crate::keyboard::KeyboardBackend;
+#[cfg(feature = "midi")]
+use crate::midi::MidiChannel;
+use crate::quiz::QuizKind;
+use crate::surround::Layout;
+use crate::switch::PresetSwitch;
+use crate::voices::StealPolicy;
+use crate::{Waveform, DEFAULT_POLYPHONY};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "A polyphonic synthesizer played from the computer keyboard")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(help = "Preset file to start with (TOML)")]
+    pub preset: Option<PathBuf>,
+
+    #[arg(long, value_name = "SEED", num_args = 0..=1, conflicts_with = "preset", help = "Start with a randomly generated patch, optionally from a fixed seed")]
+    pub random: Option<Option<u64>>,
+
+    #[arg(long, value_name = "FILE", help = "Second preset to blend towards with the blend.amount parameter (0 to 1), from a macro, slider or controller")]
+    pub blend_with: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["preset", "random"], help = "Set list of scenes to perform, starting with the first: [ and ] step through them, as do MIDI program changes")]
+    pub scenes: Option<PathBuf>,
+
+    #[arg(long, value_name = "HZ", help = "Rate the synth renders at, resampled if the device can't run at it (default: the device's preferred rate)")]
+    pub sample_rate: Option<u32>,
+
+    #[arg(long, help = "Output device to play through, by name or by its number in `list-devices` (default: the system default)")]
+    pub device: Option<String>,
+
+    #[arg(long, value_name = "DEVICE", num_args = 0..=1, help = "Run live audio from an input device, by name or number, through the filter and effects with the synth (default: the system default input)")]
+    pub input: Option<Option<String>>,
+
+    #[arg(long, value_enum, default_value_t = Backend::Rodio, help = "How audio reaches the device")]
+    pub backend: Backend,
+
+    #[arg(long, value_enum, default_value_t = Layout::Stereo, help = "Speaker layout: quad or 5.1 places voices round the room, as the preset's [surround] settings say (plays through cpal)")]
+    pub surround: Layout,
+
+    #[arg(long, value_enum, value_name = "LEVEL", num_args = 0..=1, default_missing_value = "medium", help = "Bleed some of each channel into the other, for hard-panned sounds on headphones")]
+    pub crossfeed: Option<CrossfeedLevel>,
+
+    #[arg(long, value_name = "FRAMES", help = "Audio buffer size in frames; through rodio, which picks its own, the size of the blocks the synth renders ahead of it [default: 512]")]
+    pub buffer_size: Option<u32>,
+
+    #[arg(long, value_name = "MS", conflicts_with = "buffer_size", help = "Audio buffer size as a latency in milliseconds, like --buffer-size")]
+    pub latency: Option<f32>,
+
+    #[arg(long, conflicts_with = "headless", help = "Time every note from its key press to the sound leaving the device, and print a histogram on exit, for tuning --latency and --keyboard (plays through cpal)")]
+    pub measure_latency: bool,
+
+    #[arg(long, value_enum, default_value_t = Waveform::Sine, help = "Oscillator waveform")]
+    pub waveform: Waveform,
+
+    #[arg(long, help = "Play notes as plain on/off gates with only a short declick fade, replacing the preset's envelope")]
+    pub no_envelope: bool,
+
+    #[arg(long, help = "Let the synth play itself: slow notes in the preset's ambient scale and range, best with a pad")]
+    pub ambient: bool,
+
+    #[arg(long, default_value_t = DEFAULT_POLYPHONY, help = "Most voices that can sound at once")]
+    pub polyphony: usize,
+
+    #[arg(long, value_enum, default_value_t = StealPolicy::Oldest, help = "Which held voice a new note takes when all are sounding")]
+    pub voice_stealing: StealPolicy,
+
+    #[arg(long, value_enum, default_value_t = PresetSwitch::Cut, help = "What a preset change does to the notes sounding: cut them over to the new sound, keep them on the old one (for up to 10 seconds), or crossfade the effects")]
+    pub preset_switch: PresetSwitch,
+
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.5, help = "How long a crossfading preset change takes")]
+    pub switch_time: f32,
+
+    #[arg(long, value_name = "FILE", help = "TOML file mapping keys to notes, replacing the built-in layout")]
+    pub keymap: Option<PathBuf>,
+
+    #[arg(long, conflicts_with = "keymap", help = "Use the built-in two-octave layout: Z-M plays C3-B3 with the sharps on S, D, G, H and J, and Q-P plays C4-E5 with the sharps on the number row")]
+    pub two_octaves: bool,
+
+    #[arg(long, help = "Redraw the key map on the console whenever the held keys change, with the held ones highlighted")]
+    pub show_keys: bool,
+
+    #[arg(long, help = "Reload the preset and key map files whenever they're saved, without restarting or losing held notes")]
+    pub watch: bool,
+
+    #[arg(long, value_enum, default_value_t = KeyboardBackend::Auto, help = "Where key presses are read from")]
+    pub keyboard: KeyboardBackend,
+
+    #[arg(long, value_name = "MS", default_value_t = 0.0, help = "How long a key must stay up before it can play again, to stop worn keyboards from retriggering notes")]
+    pub debounce: f32,
+
+    #[arg(long, value_name = "PORT", help = "Listen for OSC messages (notes, parameters, macros, tempo) on this UDP port")]
+    pub osc_port: Option<u16>,
+
+    #[arg(long, value_name = "HOST:PORT", help = "Jam with another instance: send it our notes and play its notes")]
+    pub jam: Option<SocketAddr>,
+
+    #[arg(long, value_name = "PORT", default_value_t = DEFAULT_JAM_PORT, help = "UDP port the jam peer sends to")]
+    pub jam_port: u16,
+
+    #[arg(long, value_name = "MS", value_parser = bounded(0.0, 10_000.0), help = "Fixed delay for the jam peer's notes, instead of the measured latency")]
+    pub jam_delay: Option<f32>,
+
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "PORT", num_args = 0..=1, help = "Play from a MIDI input port, by part of its name or its number (default: the first port)")]
+    pub midi: Option<Option<String>>,
+
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "1-16|omni", default_value = "1", help = "MIDI channel the main patch listens on; parts also hear their own channels")]
+    pub midi_channel: MidiChannel,
+
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "PORT", num_args = 0..=1, requires = "midi", help = "Pass all MIDI input on to an output port, by part of its name or its number (default: the first port)")]
+    pub midi_thru: Option<Option<String>>,
+
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "1-16", requires = "midi_thru", value_parser = clap::value_parser!(u8).range(1..=16), help = "Move the channel messages passed through to this channel")]
+    pub midi_thru_channel: Option<u8>,
+
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "DIR", requires = "midi", help = "Directory of presets for MIDI program changes, numbered from 0 in file name order")]
+    pub bank: Option<PathBuf>,
+
+    #[cfg(feature = "midi")]
+    #[arg(long, requires = "bank", help = "Hold program changes until the next note starts instead of switching at once")]
+    pub queue_program_change: bool,
+
+    #[cfg(feature = "websocket")]
+    #[arg(long, value_name = "PORT", help = "Serve the JSON control protocol over WebSocket on this TCP port")]
+    pub websocket_port: Option<u16>,
+
+    #[cfg(feature = "link")]
+    #[arg(long, help = "Lock the tempo and beats of the sequencer, arpeggiator and rhythm generator to Ableton Link apps on the network")]
+    pub link: bool,
+
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "FILE", help = "Rhai script that runs on a timer and on key presses to play notes and change parameters")]
+    pub script: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "Keep everything played and write it to this Standard MIDI file on exit, or at any time with the Insert key")]
+    pub record_midi: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "Record what you hear, the final mix with effects and live input, to this WAV file")]
+    pub record: Option<PathBuf>,
+
+    #[arg(long, requires = "record", help = "Also record the main patch and each part to their own WAV files beside the recording, dry")]
+    pub stems: bool,
+
+    #[arg(long, value_name = "PORT", help = "Stream what you hear to TCP listeners on this port as length-prefixed 16-bit PCM, to monitor the synth from another machine")]
+    pub stream_port: Option<u16>,
+
+    #[arg(long, value_name = "URL", value_parser = crate::icecast::parse_mount, help = "Broadcast what you hear to an Icecast mountpoint, such as http://source@host:8000/jam.ogg, with the password in ICECAST_PASSWORD (needs oggenc, or lame for MP3)")]
+    pub icecast: Option<Mount>,
+
+    #[arg(long, value_enum, default_value_t = StreamFormat::Ogg, requires = "icecast", help = "Encoding of the Icecast stream")]
+    pub icecast_format: StreamFormat,
+
+    #[arg(long, value_name = "SECONDS", default_value_t = capture::DEFAULT_SECONDS, help = "Keep this much of what you hear for the / key to save to a timestamped WAV file after the fact; 0 keeps none")]
+    pub capture_seconds: f32,
+
+    #[arg(long, value_name = "FILE", help = "Save the patch edits, sequencer pattern and loop to this session file every 30 seconds, and offer to restore them after a crash or power loss")]
+    pub autosave: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "Write a diagnostic log of notes, voice allocation, parameter changes and xruns; RUST_LOG picks the detail (debug by default)")]
+    pub log: Option<PathBuf>,
+
+    #[arg(long, requires = "log", help = "Write the log as JSON lines")]
+    pub log_json: bool,
+
+    #[arg(long, help = "Accessibility: speak preset changes, parameter values and other changes aloud as well as printing them (espeak-ng on Linux, say on macOS, SAPI on Windows)")]
+    pub speak: bool,
+
+    #[arg(long, conflicts_with = "keymap", help = "Run without reading the keyboard or opening a UI, taking input only from OSC, WebSocket, MIDI or a jam peer (for servers and services)")]
+    pub headless: bool,
+}
+
+impl Cli {
+    pub fn buffer_request(&self) -> Option<BufferRequest> {
+        self.buffer_size.map(BufferRequest::Frames).or(self.latency.map(BufferRequest::Millis))
+    }
+
+    // Whether anything besides the keyboard can play the synth
+    pub fn has_remote_input(&self) -> bool {
+        #[cfg(feature = "websocket")]
+        if self.websocket_port.is_some() {
+            return true;
+        }
+        #[cfg(feature = "midi")]
+        if self.midi.is_some() {
+            return true;
+        }
+        self.osc_port.is_some() || self.jam.is_some()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "List the available output devices and exit")]
+    ListDevices,
+    #[command(about = "Play a text score through the synth and exit")]
+    PlayScore {
+        #[arg(help = "Score file: note names such as C4 or C4+E4+G4:1/2, rests (r) and tempo/length lines")]
+        file: PathBuf,
+    },
+    #[command(about = "Render a text score to a 16-bit WAV file with the preset, without a sound card, and exit")]
+    RenderScore {
+        #[arg(help = "Score file, as for play-score")]
+        file: PathBuf,
+        #[arg(help = "WAV file to write")]
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Dither::Tpdf, help = "Noise added when rounding to 16 bits")]
+        dither: Dither,
+    },
+    #[command(about = "Render a Standard MIDI file to a 16-bit WAV file, without a sound card, and exit")]
+    Render {
+        #[arg(help = "MIDI file to play; channel 1 plays the main patch and the others the preset's parts")]
+        file: PathBuf,
+        #[arg(long, value_name = "FILE", help = "Preset to render with, instead of the one given before the command")]
+        preset: Option<PathBuf>,
+        #[arg(short, long, value_name = "FILE", help = "WAV file to write")]
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Dither::Tpdf, help = "Noise added when rounding to 16 bits")]
+        dither: Dither,
+    },
+    #[command(about = "Play along with a Standard MIDI file: its backing plays while one part's notes are shown and your playing is scored")]
+    Practice {
+        #[arg(help = "MIDI file with the part to play and its backing")]
+        file: PathBuf,
+        #[arg(long, value_name = "1-16", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=16), help = "Channel of the part to play; the others are the backing")]
+        channel: u8,
+    },
+    #[command(about = "Ear training: name the intervals or chords the synth plays by playing the answer, and get a score")]
+    Quiz {
+        #[arg(long, value_enum, default_value_t = QuizKind::Intervals, help = "What to be asked")]
+        kind: QuizKind,
+        #[arg(long, default_value_t = 10, help = "How many questions")]
+        rounds: usize,
+    },
+    #[command(about = "Play a steady reference tone at an exact pitch to tune an instrument against, until stopped")]
+    Tune {
+        #[arg(default_value = "A4", value_parser = parse_note, help = "Note to sound, such as A4 or C#3")]
+        note: u8,
+        #[arg(long, default_value_t = 0.0, allow_hyphen_values = true, help = "Cents to move the note by, for stretched or historical tunings")]
+        cents: f32,
+        #[arg(long, default_value_t = 440.0, help = "Frequency of A4 in Hz")]
+        a4: f32,
+        #[arg(long, value_name = "HZ", help = "Also sound an octave this many Hz out of tune, to hear the beating")]
+        beat: Option<f32>,
+    },
+    #[command(about = "Play binaural beats, or isochronic tones with --isochronic, for a session or until stopped")]
+    Binaural {
+        #[arg(long, default_value_t = 200.0, help = "Tone in Hz, midway between the ears' tones")]
+        carrier: f64,
+        #[arg(long, default_value_t = 10.0, help = "Beats a second: the difference between the ears, or the pulse rate")]
+        beat: f64,
+        #[arg(long, help = "Pulse one tone in both ears instead, which also works without headphones")]
+        isochronic: bool,
+        #[arg(long, value_name = "MINUTES", value_parser = bounded(0.0, 1440.0), help = "Session length, after which it ends by itself [default: until stopped]")]
+        minutes: Option<f32>,
+        #[arg(long, value_name = "SECONDS", default_value_t = 10.0, value_parser = bounded(0.0, 600.0), help = "Fade in at the start and out at the end")]
+        fade: f32,
+    },
+    #[cfg(feature = "analyze")]
+    #[command(about = "Print the peak and RMS levels of a WAV file, such as a render or recording, and write its spectrogram as a PNG image, then exit")]
+    Analyze {
+        #[arg(help = "WAV file to analyze")]
+        file: PathBuf,
+        #[arg(short, long, value_name = "FILE", help = "PNG file to write [default: the WAV file's name with .png]")]
+        output: Option<PathBuf>,
+    },
+}
+
+fn parse_note(text: &str) -> Result<u8, String> {
+    crate::notes::parse_note_name(text).ok_or_else(|| format!("not a note name: {}", text))
+}
+
+// Parses a number from `min` to `max`, which also keeps out NaN and infinity
+fn bounded(min: f32, max: f32) -> impl Fn(&str) -> Result<f32, String> + Clone + Send + Sync + 'static {
+    move |text| match text.parse::<f32>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        Ok(_) => Err(format!("{} is not in {}..={}", text, min, max)),
+        Err(error) => Err(error.to_string()),
+    }
+}
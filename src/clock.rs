@@ -0,0 +1,18 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// Frames the synth has rendered since it started, readable from any thread without
+// locking. Commands wrapped in `SynthCommand::At` are timed against it, so a thread that
+// reads it can place an event on an exact frame however late in the block it's sent.
+#[derive(Clone, Default)]
+pub struct FrameClock(Arc<AtomicU64>);
+
+impl FrameClock {
+    pub fn now(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn advance(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
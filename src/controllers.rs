@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::macros::{MacroCurve, MacroTarget};
+use crate::smoothed::SmoothedControl;
+
+// What the mod wheel (CC 1) and expression pedal (CC 11) move, so any keyboard plays
+// expressively without setting anything up: the wheel brings in vibrato and the pedal
+// sets the level. A preset can give either its own targets, as a macro has, or none:
+//
+//     [[controllers.mod_wheel]]
+//     param = "filter.cutoff"
+//     min = 500.0
+//     max = 5000.0
+//     curve = "exponential"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControllerSettings {
+    pub smoothing: f32,               // Milliseconds a controller takes to settle on a new value, since they move in steps
+    pub mod_wheel: Vec<MacroTarget>,  // From `min` with the wheel down to `max` fully up
+    pub expression: Vec<MacroTarget>, // From `min` with the pedal up to `max` fully down
+}
+
+impl Default for ControllerSettings {
+    fn default() -> Self {
+        Self {
+            smoothing: 20.0,
+            mod_wheel: vec![MacroTarget {
+                param: "vibrato.depth".to_string(),
+                min: 0.0,
+                max: 40.0,
+                curve: MacroCurve::Linear,
+            }],
+            expression: vec![MacroTarget {
+                param: "master.expression".to_string(),
+                min: 0.0,
+                max: 1.0,
+                curve: MacroCurve::Linear,
+            }],
+        }
+    }
+}
+
+// One controller's position, 0.0..1.0, and the parameters it moves
+#[derive(Default)]
+struct Control {
+    targets: Vec<MacroTarget>,
+    position: SmoothedControl,
+}
+
+impl Control {
+    // Until it moves from `rest` the targets keep the values the preset gives them
+    fn new(targets: &[MacroTarget], rest: f32, settings: &ControllerSettings, sample_rate: u32) -> Self {
+        Self {
+            targets: targets.to_vec(),
+            position: SmoothedControl::new(rest, settings.smoothing, sample_rate),
+        }
+    }
+}
+
+// The mod wheel and expression pedal, as "controllers.mod_wheel" and
+// "controllers.expression" (0.0..1.0), which MIDI input sets from CC 1 and CC 11
+#[derive(Default)]
+pub struct Controllers {
+    mod_wheel: Control,
+    expression: Control,
+}
+
+impl Controllers {
+    pub fn new(settings: &ControllerSettings, sample_rate: u32) -> Self {
+        Self {
+            mod_wheel: Control::new(&settings.mod_wheel, 0.0, settings, sample_rate),
+            expression: Control::new(&settings.expression, 1.0, settings, sample_rate), // A keyboard without a pedal plays at full level
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        let control = match name {
+            "mod_wheel" => &mut self.mod_wheel,
+            "expression" => &mut self.expression,
+            _ => return false,
+        };
+        control.position.set(value.clamp(0.0, 1.0));
+        true
+    }
+
+    // Carries the wheel and pedal over from another preset's, where the player left them;
+    // the new preset's targets move there from its own values
+    pub fn carry(&mut self, other: &Controllers) {
+        self.mod_wheel.position.set(other.mod_wheel.position.value());
+        self.expression.position.set(other.expression.position.value());
+    }
+
+    // Called once per frame; a block at a time, hands `apply` each target of the
+    // controllers that have moved, with its value
+    pub fn advance(&mut self, mut apply: impl FnMut(&str, f32)) {
+        for control in [&mut self.mod_wheel, &mut self.expression] {
+            if let Some(position) = control.position.advance() {
+                for target in &control.targets {
+                    apply(&target.param, target.value_at(position));
+                }
+            }
+        }
+    }
+}
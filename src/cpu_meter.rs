@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const CPU_WARNING: f32 = 0.8; // Load above which the console and UIs warn, 20% headroom left
+const SMOOTHING_SECONDS: f32 = 1.0; // Time constant of the rolling average
+
+// Share of the real-time budget spent rendering audio. The backend times each block it
+// renders and records it against the block's length; 1.0 means rendering takes as long
+// as playing, and anything near that will underrun. Only the audio thread writes, so a
+// load-then-store update is enough.
+#[derive(Clone)]
+pub struct CpuMeter {
+    load: Arc<AtomicU32>, // f32 bits, smoothed fraction of the budget
+}
+
+impl CpuMeter {
+    pub fn new() -> Self {
+        Self {
+            load: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    pub fn record(&self, busy: Duration, budget: Duration) {
+        if budget.is_zero() {
+            return;
+        }
+        let ratio = busy.as_secs_f32() / budget.as_secs_f32();
+        let amount = 1.0 - (-budget.as_secs_f32() / SMOOTHING_SECONDS).exp();
+        let load = self.load();
+        self.load.store((load + (ratio - load) * amount).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.load.load(Ordering::Relaxed))
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.load() > CPU_WARNING
+    }
+
+    // "CPU 42%"
+    pub fn summary(&self) -> String {
+        format!("CPU {:.0}%", self.load() * 100.0)
+    }
+}
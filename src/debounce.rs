@@ -0,0 +1,43 @@
+use device_query::Keycode;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Hides keyboard chatter: a key that comes up still counts as held until it has stayed
+// up for the whole window, so contacts bouncing open and shut can't retrigger its note.
+// Each key has its own window, so a quick trill on two keys isn't held back.
+pub struct Debounce {
+    window: Duration,
+    held: Vec<Keycode>,                  // Keys reported last time
+    up_since: HashMap<Keycode, Instant>, // Keys physically up but still reported held
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            held: Vec::new(),
+            up_since: HashMap::new(),
+        }
+    }
+
+    // The keys to act on, given those physically down at `now`
+    pub fn filter(&mut self, down: Vec<Keycode>, now: Instant) -> Vec<Keycode> {
+        for &key in self.held.iter().filter(|key| !down.contains(key)) {
+            self.up_since.entry(key).or_insert(now);
+        }
+        for key in &down {
+            self.up_since.remove(key);
+        }
+        let window = self.window;
+        self.up_since.retain(|_, &mut since| now.duration_since(since) < window);
+
+        self.held = down;
+        self.held.extend(self.up_since.keys());
+        self.held.clone()
+    }
+
+    // How long until a key held over by the window has to be let go, if any is
+    pub fn wake(&self, now: Instant) -> Option<Duration> {
+        self.up_since.values().map(|&since| (since + self.window).saturating_duration_since(now)).min()
+    }
+}
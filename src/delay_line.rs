@@ -0,0 +1,39 @@
+// A circular buffer of past samples that can be read at fractional delays,
+// shared by the modulated-delay effects (chorus, flanger, ...).
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    write_pos: usize,
+}
+
+impl DelayLine {
+    pub fn new(max_delay_samples: usize) -> Self {
+        Self {
+            buffer: vec![0.0; max_delay_samples.max(1) + 2], // Room for interpolation past the longest delay
+            write_pos: 0,
+        }
+    }
+
+    // Forgets every sample, as if it had just been made
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+    }
+
+    pub fn write(&mut self, sample: f32) {
+        self.buffer[self.write_pos] = sample;
+        self.write_pos = (self.write_pos + 1) % self.buffer.len();
+    }
+
+    // Reads the sample written `delay` samples before the last one written (so 0 is that
+    // one itself), linearly interpolating between neighbours
+    pub fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(0.0, (len - 2) as f32);
+        let whole = delay.floor() as usize;
+        let frac = delay - whole as f32;
+
+        let newer = self.buffer[(self.write_pos + len - 1 - whole) % len];
+        let older = self.buffer[(self.write_pos + len - 2 - whole) % len];
+        newer + (older - newer) * frac
+    }
+}
@@ -0,0 +1,33 @@
+// Denormal (subnormal) floats show up as reverb tails, filter states and envelopes
+// decay towards zero, and on most CPUs each operation on one costs many times the
+// usual. Setting flush-to-zero (and denormals-are-zero on x86) makes the FPU treat
+// them as 0.0 instead, which is inaudible. The setting is per thread, so it's made at
+// the start of every block on whichever thread the audio callback runs on.
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse")))]
+pub fn flush_to_zero() {
+    const FTZ: u32 = 1 << 15;
+    const DAZ: u32 = 1 << 6;
+    let mut csr: u32 = 0;
+    // Only reads and writes the SSE control register of this thread
+    unsafe {
+        std::arch::asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack, preserves_flags));
+        csr |= FTZ | DAZ;
+        std::arch::asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, preserves_flags));
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn flush_to_zero() {
+    const FZ: u64 = 1 << 24;
+    let mut fpcr: u64;
+    // Only reads and writes the floating-point control register of this thread
+    unsafe {
+        std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags));
+        fpcr |= FZ;
+        std::arch::asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack, preserves_flags));
+    }
+}
+
+// Elsewhere denormals are left as they are
+#[cfg(not(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse"), target_arch = "aarch64")))]
+pub fn flush_to_zero() {}
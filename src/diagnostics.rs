@@ -0,0 +1,27 @@
+use std::sync::mpsc;
+
+use crate::param_id::ParamId;
+use crate::switch::PresetSwitch;
+use crate::voices::StealPolicy;
+
+// How many diagnostics can wait to be logged before the synth starts dropping them
+pub const QUEUED: usize = 256;
+
+// Something the audio thread saw that's worth a log line. It can't format or write
+// one itself, so it queues these in a channel made with room for `QUEUED` and the
+// logging side drains them; when that side falls behind the rest are dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Diagnostic {
+    VoiceStarted { note: u8, voices: usize },
+    VoiceRetriggered { note: u8, releasing: bool },
+    VoiceStolen { note: u8, releasing: bool, policy: StealPolicy }, // `note` is the stolen voice's
+    NoteDropped { note: u8, polyphony: usize },
+    UnknownParam(ParamId), // In a section the synth has, but not a parameter of it
+    ScheduleFull { frame: u64 }, // A command for `frame` refused, with `MAX_SCHEDULED` already waiting
+    SwitchCut(PresetSwitch), // A preset switch of this kind made as a cut, with no room left for the old preset to play out
+}
+
+// The channel to hand to `Synthesizer::with_diagnostics`, and its other end
+pub fn channel() -> (mpsc::SyncSender<Diagnostic>, mpsc::Receiver<Diagnostic>) {
+    mpsc::sync_channel(QUEUED)
+}
@@ -0,0 +1,50 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::stereo::Frame;
+
+// How the float output is rounded to 16 bits. Plain rounding turns quiet passages such
+// as release tails into a gritty staircase whose error follows the signal; adding a
+// little noise first makes the error a steady, much less noticeable hiss.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dither {
+    None,
+    #[default]
+    Tpdf,   // Triangular noise of ±1 step, which fully decorrelates the error from the signal
+    Shaped, // TPDF with the error fed back, moving the hiss up to where hearing is less sensitive
+}
+
+// Converts frames to 16-bit samples, keeping the state the dither needs between them
+pub struct Quantizer {
+    dither: Dither,
+    rng: StdRng, // Seeded, so the same audio always renders to the same file
+    error: Frame, // Each channel's last rounding error, for noise shaping
+}
+
+impl Quantizer {
+    pub fn new(dither: Dither) -> Self {
+        Self {
+            dither,
+            rng: StdRng::seed_from_u64(0),
+            error: [0.0; 2],
+        }
+    }
+
+    pub fn quantize(&mut self, frame: Frame) -> [i16; 2] {
+        let mut output = [0; 2];
+        for (channel, output) in output.iter_mut().enumerate() {
+            let mut target = frame[channel] * i16::MAX as f32;
+            if self.dither == Dither::Shaped {
+                target -= self.error[channel];
+            }
+            let noise = match self.dither {
+                Dither::None => 0.0,
+                Dither::Tpdf | Dither::Shaped => self.rng.gen::<f32>() - self.rng.gen::<f32>(),
+            };
+            let rounded = (target + noise).round().clamp(i16::MIN as f32, i16::MAX as f32);
+            self.error[channel] = rounded - target;
+            *output = rounded as i16;
+        }
+        output
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use super::{apart, together, Effect};
+use crate::stereo::Frame;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BitcrusherSettings {
+    pub bits: f32,        // Bit depth, 1.0..=24.0 (fractional values step smoothly)
+    pub sample_rate: f32, // Rate of the sample-and-hold downsampler in Hz
+    pub mix: f32,         // 0.0 = dry only, 1.0 = wet only
+}
+
+impl Default for BitcrusherSettings {
+    fn default() -> Self {
+        Self {
+            bits: 8.0,
+            sample_rate: 11_025.0,
+            mix: 1.0,
+        }
+    }
+}
+
+// Lo-fi degradation: holds each sample for several output samples (aliasing included)
+// and rounds it to a coarse number of amplitude steps.
+pub struct Bitcrusher {
+    settings: BitcrusherSettings,
+    hold_increment: f32, // How far the hold counter advances per output sample
+    hold_phase: f32,
+    held_frame: Frame,
+    output_rate: u32, // The synth's sample rate, as opposed to the crushed `settings.sample_rate`
+}
+
+impl Bitcrusher {
+    pub fn new(settings: BitcrusherSettings, sample_rate: u32) -> Self {
+        let hold_increment = (settings.sample_rate / sample_rate as f32).clamp(0.001, 1.0);
+        Self {
+            settings,
+            hold_increment,
+            hold_phase: 1.0, // Grab the very first sample immediately
+            held_frame: [0.0; 2],
+            output_rate: sample_rate,
+        }
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        self.hold_phase += self.hold_increment;
+        if self.hold_phase >= 1.0 {
+            self.hold_phase -= 1.0;
+            self.held_frame = input.map(|sample| quantize(sample, self.settings.bits));
+        }
+
+        apart(self.held_frame, self.settings.mix)
+    }
+
+    fn reset(&mut self) {
+        self.hold_phase = 1.0;
+        self.held_frame = [0.0; 2];
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "bits" => self.settings.bits = value,
+            "sample_rate" => {
+                self.settings.sample_rate = value;
+                self.hold_increment = (value / self.output_rate as f32).clamp(0.001, 1.0);
+            }
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Rounds a [-1.0, 1.0] sample to the nearest of 2^bits levels
+fn quantize(sample: f32, bits: f32) -> f32 {
+    let steps = 2.0_f32.powf(bits.clamp(1.0, 24.0) - 1.0);
+    (sample * steps).round() / steps
+}
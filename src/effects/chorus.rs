@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use super::{apart, together, ms_to_samples, Effect};
+use crate::delay_line::DelayLine;
+use crate::lfo::Lfo;
+use crate::stereo::Frame;
+use crate::tempo::{Rate, DEFAULT_TEMPO};
+
+const BASE_DELAY_MS: f32 = 15.0; // Centre of the modulated delay, long enough to avoid flanging
+const MAX_DEPTH_MS: f32 = 10.0;
+const MAX_VOICES: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChorusSettings {
+    pub rate: Rate,   // LFO rate, in Hz or as a tempo division
+    pub depth: f32,   // Delay modulation depth in milliseconds
+    pub voices: usize, // Number of delayed copies, each with its own LFO phase
+    pub mix: f32,     // 0.0 = dry only, 1.0 = wet only
+}
+
+impl Default for ChorusSettings {
+    fn default() -> Self {
+        Self {
+            rate: Rate::Hz(0.8),
+            depth: 3.0,
+            voices: 2,
+            mix: 0.5,
+        }
+    }
+}
+
+// Modulated-delay chorus: several copies of the signal, each delayed by a slowly
+// wobbling amount, are mixed back in to thicken single-oscillator patches.
+// The right channel's LFOs run a quarter cycle behind the left's for a wider image.
+pub struct Chorus {
+    settings: ChorusSettings,
+    delay_lines: [DelayLine; 2],
+    lfos: [Vec<Lfo>; 2],
+    sample_rate: u32,
+}
+
+impl Chorus {
+    pub fn new(settings: ChorusSettings, sample_rate: u32) -> Self {
+        let voices = settings.voices.clamp(1, MAX_VOICES);
+        let max_delay_samples = ms_to_samples(BASE_DELAY_MS + MAX_DEPTH_MS, sample_rate) as usize;
+
+        // Spread the voices' LFOs evenly around the cycle so they never line up
+        let make_lfos = |offset: f32| -> Vec<Lfo> {
+            (0..voices)
+                .map(|i| Lfo::new(settings.rate.hz(DEFAULT_TEMPO), sample_rate).with_phase(lfo_phase(i, voices, offset)))
+                .collect()
+        };
+        let lfos = [make_lfos(0.0), make_lfos(FRAC_PI_2)];
+
+        Self {
+            settings,
+            delay_lines: [DelayLine::new(max_delay_samples), DelayLine::new(max_delay_samples)],
+            lfos,
+            sample_rate,
+        }
+    }
+}
+
+// Where the LFO of one of `voices` chorus voices starts, `offset` being the channel's
+fn lfo_phase(voice: usize, voices: usize, offset: f32) -> f32 {
+    2.0 * PI * voice as f32 / voices as f32 + offset
+}
+
+impl Effect for Chorus {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        let depth_ms = self.settings.depth.clamp(0.0, MAX_DEPTH_MS);
+        let mut wet = [0.0; 2];
+        for (channel, wet) in wet.iter_mut().enumerate() {
+            let delay_line = &mut self.delay_lines[channel];
+            delay_line.write(input[channel]);
+
+            let lfos = &mut self.lfos[channel];
+            for lfo in lfos.iter_mut() {
+                let delay_ms = BASE_DELAY_MS + depth_ms * lfo.next_value();
+                *wet += delay_line.read(ms_to_samples(delay_ms, self.sample_rate));
+            }
+            *wet /= lfos.len() as f32;
+        }
+
+        apart(wet, self.settings.mix)
+    }
+
+    fn set_tempo(&mut self, tempo: f32) {
+        for lfo in self.lfos.iter_mut().flatten() {
+            lfo.set_rate(self.settings.rate.hz(tempo));
+        }
+    }
+
+    fn reset(&mut self) {
+        for (lfos, offset) in self.lfos.iter_mut().zip([0.0, FRAC_PI_2]) {
+            let voices = lfos.len();
+            for (voice, lfo) in lfos.iter_mut().enumerate() {
+                lfo.set_phase(lfo_phase(voice, voices, offset));
+            }
+        }
+        for line in &mut self.delay_lines {
+            line.clear();
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate" => {
+                self.settings.rate = Rate::Hz(value);
+                for lfo in self.lfos.iter_mut().flatten() {
+                    lfo.set_rate(value);
+                }
+            }
+            "depth" => self.settings.depth = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
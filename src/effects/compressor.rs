@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use super::{db_to_gain, gain_to_db, ms_to_samples, Effect};
+use crate::stereo::Frame;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressorSettings {
+    pub threshold: f32, // Level in dBFS above which gain reduction starts
+    pub ratio: f32,     // 4.0 means 4 dB over the threshold comes out as 1 dB over
+    pub attack: f32,    // Milliseconds for the gain reduction to react to a louder signal
+    pub release: f32,   // Milliseconds for the gain to recover once the signal drops
+    pub makeup: f32,    // Gain in dB applied after compression
+    pub sidechain: bool, // Key from the live input (`--input`) instead of the signal itself, to duck under it
+    pub amount: f32,    // 0.0..1.0, how much of the gain reduction is applied
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold: -18.0,
+            ratio: 4.0,
+            attack: 10.0,
+            release: 150.0,
+            makeup: 6.0,
+            sidechain: false,
+            amount: 1.0,
+        }
+    }
+}
+
+// Feed-forward compressor: the input level is measured, turned into a gain
+// reduction in dB, smoothed with separate attack/release times and applied.
+// Sidechained, the level measured is the live input's, so a pad ducks under a kick
+// or a voice coming in; set the input's level to 0 to hear only the ducking.
+pub struct Compressor {
+    settings: CompressorSettings,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    makeup_gain: f32,
+    envelope_db: f32, // Smoothed gain reduction, always <= 0
+    key: Frame,       // Latest live input frame, for the sidechain
+    sample_rate: u32,
+}
+
+impl Compressor {
+    pub fn new(settings: CompressorSettings, sample_rate: u32) -> Self {
+        Self {
+            attack_coefficient: time_coefficient(settings.attack, sample_rate),
+            release_coefficient: time_coefficient(settings.release, sample_rate),
+            makeup_gain: db_to_gain(settings.makeup),
+            settings,
+            envelope_db: 0.0,
+            key: [0.0; 2],
+            sample_rate,
+        }
+    }
+
+    // Computes the gain to apply for the level of `detector`; split out from
+    // `process` so a different signal than the one being compressed can drive it
+    pub fn gain_for(&mut self, detector: f32) -> f32 {
+        let level_db = gain_to_db(detector.abs());
+        let over = level_db - self.settings.threshold;
+        let ratio = self.settings.ratio.max(1.0);
+        let target_db = if over > 0.0 { -over * (1.0 - 1.0 / ratio) } else { 0.0 };
+
+        // More reduction is the "attack" direction, less reduction is "release"
+        let coefficient = if target_db < self.envelope_db {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.envelope_db += (target_db - self.envelope_db) * coefficient;
+
+        db_to_gain(self.envelope_db * self.settings.amount.clamp(0.0, 1.0)) * self.makeup_gain
+    }
+
+    // Current gain reduction in dB (negative while compressing), for metering
+    pub fn gain_reduction(&self) -> f32 {
+        self.envelope_db
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, input: Frame) -> Frame {
+        // Stereo-linked: both channels get the same gain so the image doesn't shift
+        let detector = if self.settings.sidechain { self.key } else { input };
+        let gain = self.gain_for(detector[0].abs().max(detector[1].abs()));
+        input.map(|sample| sample * gain)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold" => self.settings.threshold = value,
+            "ratio" => self.settings.ratio = value,
+            "attack" => {
+                self.settings.attack = value;
+                self.attack_coefficient = time_coefficient(value, self.sample_rate);
+            }
+            "release" => {
+                self.settings.release = value;
+                self.release_coefficient = time_coefficient(value, self.sample_rate);
+            }
+            "makeup" => {
+                self.settings.makeup = value;
+                self.makeup_gain = db_to_gain(value);
+            }
+            "amount" => self.settings.amount = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn sidechain(&mut self, key: Frame) {
+        self.key = key;
+    }
+}
+
+// One-pole smoothing coefficient that covers ~63% of a step in `ms` milliseconds
+fn time_coefficient(ms: f32, sample_rate: u32) -> f32 {
+    let samples = ms_to_samples(ms, sample_rate).max(1.0);
+    1.0 - (-1.0 / samples).exp()
+}
@@ -0,0 +1,66 @@
+use super::{ms_to_samples, Effect};
+use crate::biquad::Biquad;
+use crate::delay_line::DelayLine;
+use crate::stereo::{Frame, LEFT, RIGHT};
+
+const DELAY_MS: f32 = 0.3; // About how much later a sound from one side reaches the far ear
+
+// How much of each channel bleeds into the other, from a hint to about what speakers give
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CrossfeedLevel {
+    Light,
+    #[default]
+    Medium,
+    Strong,
+}
+
+impl CrossfeedLevel {
+    // The bleed's cutoff in Hz and its level in dB below the direct sound
+    fn settings(self) -> (f32, f32) {
+        match self {
+            CrossfeedLevel::Light => (650.0, 9.5),
+            CrossfeedLevel::Medium => (700.0, 6.0),
+            CrossfeedLevel::Strong => (700.0, 4.5),
+        }
+    }
+}
+
+// Headphone crossfeed on the master output. On speakers each ear hears both of them, the
+// far one a little later and duller for the head in the way; on headphones a hard-panned
+// voice is in one ear only, which tires on long listening. This feeds each channel a
+// delayed, low-passed copy of the other, as the head would, and scales the sum back so a
+// centred sound's bass comes out at the level it went in.
+pub struct Crossfeed {
+    lines: [DelayLine; 2],
+    filters: [Biquad; 2],
+    delay: f32,  // In samples
+    bleed: f32,  // Gain of the far channel
+    makeup: f32, // Brings a centred sound back to its own level
+}
+
+impl Crossfeed {
+    pub fn new(level: CrossfeedLevel, sample_rate: u32) -> Self {
+        let (cutoff, below) = level.settings();
+        let delay = ms_to_samples(DELAY_MS, sample_rate);
+        let bleed = 10.0_f32.powf(-below / 20.0);
+        let filter = Biquad::low_pass(cutoff, std::f32::consts::FRAC_1_SQRT_2, sample_rate);
+        Self {
+            lines: [DelayLine::new(delay.ceil() as usize + 1), DelayLine::new(delay.ceil() as usize + 1)],
+            filters: [filter; 2],
+            delay,
+            bleed,
+            makeup: 1.0 / (1.0 + bleed),
+        }
+    }
+}
+
+impl Effect for Crossfeed {
+    fn process(&mut self, input: Frame) -> Frame {
+        let mut far = [0.0; 2];
+        for channel in [LEFT, RIGHT] {
+            self.lines[channel].write(input[channel]);
+            far[channel] = self.filters[channel].process(self.lines[channel].read(self.delay)) * self.bleed;
+        }
+        [(input[LEFT] + far[RIGHT]) * self.makeup, (input[RIGHT] + far[LEFT]) * self.makeup]
+    }
+}
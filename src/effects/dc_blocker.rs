@@ -0,0 +1,33 @@
+use std::f32::consts::PI;
+
+use super::Effect;
+use crate::stereo::Frame;
+
+const CUTOFF: f32 = 10.0; // Hz, well below anything audible
+
+// One-pole high-pass that removes DC offset, y[n] = x[n] - x[n-1] + r * y[n-1].
+// Asymmetric shaping (folding, distortion, narrow pulses) leaves the mix sitting off
+// zero, which wastes headroom and pushes speaker cones; this pulls it back.
+pub struct DcBlocker {
+    r: f32,
+    last_input: Frame,
+    last_output: Frame,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            r: 1.0 - 2.0 * PI * CUTOFF / sample_rate as f32,
+            last_input: [0.0; 2],
+            last_output: [0.0; 2],
+        }
+    }
+}
+
+impl Effect for DcBlocker {
+    fn process(&mut self, input: Frame) -> Frame {
+        self.last_output = [0, 1].map(|channel| input[channel] - self.last_input[channel] + self.r * self.last_output[channel]);
+        self.last_input = input;
+        self.last_output
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use super::{apart, together, Effect};
+use crate::oversample::{Oversampler, Oversampling};
+use crate::stereo::Frame;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistortionCurve {
+    SoftClip,   // tanh saturation, warm and rounded
+    HardClip,   // Flat-topped clipping, buzzy
+    Foldback,   // Peaks fold back down instead of clipping, very bright
+    Asymmetric, // Different shapes for each polarity, adds even harmonics
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DistortionSettings {
+    pub curve: DistortionCurve,
+    pub drive: f32, // Input gain before the curve, 1.0 = unity
+    pub tone: f32,  // 0.0 = dark, 1.0 = bright (post-shaper low-pass)
+    pub level: f32, // Output gain after the curve
+    pub mix: f32,   // 0.0 = dry only, 1.0 = wet only
+}
+
+impl Default for DistortionSettings {
+    fn default() -> Self {
+        Self {
+            curve: DistortionCurve::SoftClip,
+            drive: 4.0,
+            tone: 0.7,
+            level: 0.5,
+            mix: 1.0,
+        }
+    }
+}
+
+// Waveshaping distortion followed by a simple one-pole tone control
+pub struct Distortion {
+    settings: DistortionSettings,
+    tone_coefficient: f32,
+    tone_state: Frame,
+    oversamplers: [Oversampler; 2], // Run the curve at a higher rate, one per channel
+    sample_rate: u32,
+}
+
+impl Distortion {
+    pub fn new(settings: DistortionSettings, sample_rate: u32) -> Self {
+        Self {
+            tone_coefficient: tone_coefficient(settings.tone, sample_rate),
+            settings,
+            tone_state: [0.0; 2],
+            oversamplers: [Oversampler::new(Oversampling::Off), Oversampler::new(Oversampling::Off)],
+            sample_rate,
+        }
+    }
+}
+
+// Map tone 0..1 exponentially onto a 500 Hz .. 12 kHz low-pass cutoff
+fn tone_coefficient(tone: f32, sample_rate: u32) -> f32 {
+    let cutoff = 500.0 * 24.0_f32.powf(tone.clamp(0.0, 1.0));
+    1.0 - (-2.0 * PI * cutoff / sample_rate as f32).exp()
+}
+
+impl Effect for Distortion {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        let mut wet = [0.0; 2];
+        for (channel, wet) in wet.iter_mut().enumerate() {
+            let driven = input[channel] * self.settings.drive.max(0.0);
+            let curve = self.settings.curve;
+            let shaped = self.oversamplers[channel].process(driven, |x| shape(curve, x));
+
+            let tone_state = &mut self.tone_state[channel];
+            *tone_state += (shaped - *tone_state) * self.tone_coefficient;
+            *wet = *tone_state * self.settings.level;
+        }
+
+        apart(wet, self.settings.mix)
+    }
+
+    fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.oversamplers = [Oversampler::new(oversampling), Oversampler::new(oversampling)];
+    }
+
+    fn reset(&mut self) {
+        self.tone_state = [0.0; 2];
+        for oversampler in &mut self.oversamplers {
+            oversampler.reset();
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "drive" => self.settings.drive = value,
+            "tone" => {
+                self.settings.tone = value;
+                self.tone_coefficient = tone_coefficient(value, self.sample_rate);
+            }
+            "level" => self.settings.level = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Transfer curves; all of them keep the output within [-1.0, 1.0]
+pub fn shape(curve: DistortionCurve, x: f32) -> f32 {
+    match curve {
+        DistortionCurve::SoftClip => x.tanh(),
+        DistortionCurve::HardClip => x.clamp(-1.0, 1.0),
+        DistortionCurve::Foldback => fold(x),
+        DistortionCurve::Asymmetric => {
+            if x >= 0.0 {
+                x.tanh()
+            } else {
+                // Negative half clips earlier and harder
+                (x * 2.0).clamp(-1.0, 0.0) * 0.6
+            }
+        }
+    }
+}
+
+// Reflects the signal back from ±1.0 as many times as needed (a triangle-wave transfer curve)
+pub fn fold(x: f32) -> f32 {
+    let wrapped = (x + 1.0).rem_euclid(4.0);
+    if wrapped < 2.0 {
+        wrapped - 1.0
+    } else {
+        3.0 - wrapped
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use super::Effect;
+use crate::biquad::Biquad;
+use crate::stereo::Frame;
+
+const MAX_GAIN_DB: f32 = 24.0; // Boost or cut for each band when changed while playing
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    pub low_gain: f32,       // dB, low shelf
+    pub low_frequency: f32,  // Hz, shelf corner
+    pub mid_gain: f32,       // dB, peaking band
+    pub mid_frequency: f32,  // Hz, band centre
+    pub mid_q: f32,          // Band width, higher is narrower
+    pub high_gain: f32,      // dB, high shelf
+    pub high_frequency: f32, // Hz, shelf corner
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        // Flat response until the preset says otherwise
+        Self {
+            low_gain: 0.0,
+            low_frequency: 200.0,
+            mid_gain: 0.0,
+            mid_frequency: 1000.0,
+            mid_q: 0.7,
+            high_gain: 0.0,
+            high_frequency: 5000.0,
+        }
+    }
+}
+
+impl EqSettings {
+    pub fn is_flat(&self) -> bool {
+        self.low_gain == 0.0 && self.mid_gain == 0.0 && self.high_gain == 0.0
+    }
+
+    // The settings `Equalizer::set_param` accepts, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("low_gain", self.low_gain),
+            ("low_frequency", self.low_frequency),
+            ("mid_gain", self.mid_gain),
+            ("mid_frequency", self.mid_frequency),
+            ("mid_q", self.mid_q),
+            ("high_gain", self.high_gain),
+            ("high_frequency", self.high_frequency),
+        ]
+    }
+}
+
+// Low shelf -> mid peak -> high shelf, the last stage of the master chain
+pub struct Equalizer {
+    settings: EqSettings,
+    bands: [[Biquad; 3]; 2], // Per channel: low, mid, high
+    bypassed: bool,
+    sample_rate: u32,
+}
+
+impl Equalizer {
+    pub fn new(settings: &EqSettings, sample_rate: u32) -> Self {
+        let bands = Self::design(settings, sample_rate);
+        Self {
+            settings: settings.clone(),
+            bands: [bands, bands],
+            bypassed: settings.is_flat(),
+            sample_rate,
+        }
+    }
+
+    fn design(settings: &EqSettings, sample_rate: u32) -> [Biquad; 3] {
+        [
+            Biquad::low_shelf(settings.low_frequency, settings.low_gain, sample_rate),
+            Biquad::peaking(settings.mid_frequency, settings.mid_q, settings.mid_gain, sample_rate),
+            Biquad::high_shelf(settings.high_frequency, settings.high_gain, sample_rate),
+        ]
+    }
+}
+
+impl Effect for Equalizer {
+    fn process(&mut self, input: Frame) -> Frame {
+        if self.bypassed {
+            return input;
+        }
+        let mut output = input;
+        for (channel, bands) in self.bands.iter_mut().enumerate() {
+            for band in bands.iter_mut() {
+                output[channel] = band.process(output[channel]);
+            }
+        }
+        output
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let nyquist = self.sample_rate as f32 * 0.45; // Just below, where the filters still behave
+        let (field, low, high) = match name {
+            "low_gain" => (&mut self.settings.low_gain, -MAX_GAIN_DB, MAX_GAIN_DB),
+            "low_frequency" => (&mut self.settings.low_frequency, 20.0, nyquist),
+            "mid_gain" => (&mut self.settings.mid_gain, -MAX_GAIN_DB, MAX_GAIN_DB),
+            "mid_frequency" => (&mut self.settings.mid_frequency, 20.0, nyquist),
+            "mid_q" => (&mut self.settings.mid_q, 0.1, 10.0),
+            "high_gain" => (&mut self.settings.high_gain, -MAX_GAIN_DB, MAX_GAIN_DB),
+            "high_frequency" => (&mut self.settings.high_frequency, 20.0, nyquist),
+            _ => return false,
+        };
+        *field = value.clamp(low, high);
+
+        // Swap in the new coefficients but keep the filter state, so the change doesn't click
+        let designed = Self::design(&self.settings, self.sample_rate);
+        for bands in &mut self.bands {
+            for (band, designed) in bands.iter_mut().zip(&designed) {
+                band.set_coefficients(designed);
+            }
+        }
+        self.bypassed = self.settings.is_flat();
+        true
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use super::{apart, together, ms_to_samples, Effect};
+use crate::delay_line::DelayLine;
+use crate::lfo::Lfo;
+use crate::stereo::Frame;
+use crate::tempo::{Rate, DEFAULT_TEMPO};
+
+const MIN_DELAY_MS: f32 = 0.5;
+const MAX_DELAY_MS: f32 = 10.0;
+const MAX_FEEDBACK: f32 = 0.95; // Keeps the feedback loop from running away
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlangerSettings {
+    pub rate: Rate,     // Sweep rate, in Hz or as a tempo division like "1/2"
+    pub delay: f32,     // Shortest delay of the sweep, in milliseconds
+    pub depth: f32,     // How far the sweep travels above `delay`, in milliseconds
+    pub feedback: f32,  // -0.95..0.95, negative values give a hollower sound
+    pub mix: f32,       // 0.0 = dry only, 1.0 = wet only
+}
+
+impl Default for FlangerSettings {
+    fn default() -> Self {
+        Self {
+            rate: Rate::Hz(0.25),
+            delay: 1.0,
+            depth: 3.0,
+            feedback: 0.6,
+            mix: 0.5,
+        }
+    }
+}
+
+// A very short modulated delay fed back into itself, producing the classic jet-plane comb sweep
+pub struct Flanger {
+    settings: FlangerSettings,
+    delay_lines: [DelayLine; 2],
+    lfo: Lfo, // Shared by both channels so the sweep stays centred
+    sample_rate: u32,
+    last_wet: Frame,
+}
+
+impl Flanger {
+    pub fn new(settings: FlangerSettings, sample_rate: u32) -> Self {
+        let lfo = Lfo::new(settings.rate.hz(DEFAULT_TEMPO), sample_rate);
+        let max_delay_samples = ms_to_samples(2.0 * MAX_DELAY_MS, sample_rate) as usize;
+        Self {
+            settings,
+            delay_lines: [DelayLine::new(max_delay_samples), DelayLine::new(max_delay_samples)],
+            lfo,
+            sample_rate,
+            last_wet: [0.0; 2],
+        }
+    }
+}
+
+impl Effect for Flanger {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        let feedback = self.settings.feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+
+        // Sweep between `delay` and `delay + depth` using the unipolar LFO value
+        let sweep = 0.5 + 0.5 * self.lfo.next_value();
+        let delay_ms = self.settings.delay.clamp(MIN_DELAY_MS, MAX_DELAY_MS)
+            + self.settings.depth.clamp(0.0, MAX_DELAY_MS) * sweep;
+        let delay = ms_to_samples(delay_ms, self.sample_rate);
+
+        for (channel, delay_line) in self.delay_lines.iter_mut().enumerate() {
+            delay_line.write(input[channel] + self.last_wet[channel] * feedback);
+            self.last_wet[channel] = delay_line.read(delay);
+        }
+
+        apart(self.last_wet, self.settings.mix)
+    }
+
+    fn set_tempo(&mut self, tempo: f32) {
+        self.lfo.set_rate(self.settings.rate.hz(tempo));
+    }
+
+    fn reset(&mut self) {
+        self.lfo.set_phase(0.0);
+        for line in &mut self.delay_lines {
+            line.clear();
+        }
+        self.last_wet = [0.0; 2];
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate" => {
+                // Setting the rate directly always gives a free-running rate in Hz
+                self.settings.rate = Rate::Hz(value);
+                self.lfo.set_rate(value);
+            }
+            "delay" => self.settings.delay = value,
+            "depth" => self.settings.depth = value,
+            "feedback" => self.settings.feedback = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,162 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use super::{together, Effect};
+use crate::stereo::Frame;
+
+const FFT_SIZE: usize = 2048; // Long enough to hold a chord's partials apart, short enough to catch a moment
+const HOP: usize = FFT_SIZE / 4; // Hann windows at a quarter apart overlap-add evenly
+const MAX_BLUR: usize = 12; // Bins either side each bin spreads over at full smear
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FreezeSettings {
+    pub frozen: bool, // Holding a captured spectrum; turning it on captures what's playing
+    pub smear: f32,   // 0.0..1.0, how far the frozen partials blur into a wash
+    pub mix: f32,     // Level of the frozen pad over the untouched signal
+}
+
+impl Default for FreezeSettings {
+    fn default() -> Self {
+        Self {
+            frozen: false,
+            smear: 0.3,
+            mix: 1.0,
+        }
+    }
+}
+
+// Spectral freeze: turning "frozen" on (above 0.5, so a macro or pedal can throw it)
+// takes the spectrum of the last FFT_SIZE samples and plays it back for as long as it
+// stays on, as a pad under whatever is played next, which passes through untouched.
+// The held magnitudes are resynthesized every hop with their phases running on, so the
+// pad doesn't buzz at the hop rate; smear blurs each bin into its neighbours and
+// scatters the phases, from a shimmering copy of the chord to a soft wash of noise.
+// Each side is captured and scattered on its own, which keeps the pad wide.
+pub struct Freeze {
+    settings: FreezeSettings,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    history: Vec<Frame>,           // The last FFT_SIZE input frames, a ring
+    written: usize,                // Position in the history
+    magnitudes: [Vec<f32>; 2],     // The captured spectrum of each side
+    captured: bool,                // Whether there's been a capture to hold yet
+    phases: [Vec<f32>; 2],
+    output: Vec<Frame>,            // Overlap-add ring of resynthesized grains
+    read: usize,                   // Position in the output
+    buffer: Vec<Complex<f32>>,     // Scratch for the transforms
+    rng: StdRng,
+}
+
+impl Freeze {
+    pub fn new(settings: FreezeSettings, _sample_rate: u32) -> Self {
+        let mut planner = FftPlanner::new();
+        let mut freeze = Self {
+            fft: planner.plan_fft_forward(FFT_SIZE),
+            ifft: planner.plan_fft_inverse(FFT_SIZE),
+            window: (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).collect(),
+            history: vec![[0.0; 2]; FFT_SIZE],
+            written: 0,
+            magnitudes: [vec![0.0; FFT_SIZE / 2 + 1], vec![0.0; FFT_SIZE / 2 + 1]],
+            captured: false,
+            phases: [vec![0.0; FFT_SIZE / 2 + 1], vec![0.0; FFT_SIZE / 2 + 1]],
+            output: vec![[0.0; 2]; FFT_SIZE],
+            read: 0,
+            buffer: vec![Complex::new(0.0, 0.0); FFT_SIZE],
+            rng: StdRng::seed_from_u64(0),
+            settings: FreezeSettings { frozen: false, ..settings.clone() },
+        };
+        freeze.set_frozen(settings.frozen); // Frozen from the start holds silence until retriggered
+        freeze
+    }
+
+    fn set_frozen(&mut self, frozen: bool) {
+        if frozen && !self.settings.frozen {
+            self.capture();
+        }
+        self.settings.frozen = frozen;
+    }
+
+    // Takes the magnitudes of the history, oldest sample first
+    fn capture(&mut self) {
+        for side in 0..2 {
+            for (i, bin) in self.buffer.iter_mut().enumerate() {
+                let sample = self.history[(self.written + i) % FFT_SIZE][side];
+                *bin = Complex::new(sample * self.window[i], 0.0);
+            }
+            self.fft.process(&mut self.buffer);
+            for (magnitude, bin) in self.magnitudes[side].iter_mut().zip(&self.buffer) {
+                *magnitude = bin.norm();
+            }
+        }
+        self.captured = true;
+    }
+
+    // Adds the next grain of the held spectrum into the output, from the read position on
+    fn resynthesize(&mut self) {
+        let smear = self.settings.smear.clamp(0.0, 1.0);
+        let blur = (smear * MAX_BLUR as f32).round() as usize;
+        let bins = FFT_SIZE / 2 + 1;
+        for side in 0..2 {
+            let magnitudes = &self.magnitudes[side];
+            for bin in 0..bins {
+                // Each bin's own phase advance over a hop, scattered by the smear
+                let phase = &mut self.phases[side][bin];
+                *phase = (*phase + 2.0 * PI * (bin * HOP) as f32 / FFT_SIZE as f32 + smear * self.rng.gen_range(-PI..PI)).rem_euclid(2.0 * PI);
+                let (low, high) = (bin.saturating_sub(blur), (bin + blur).min(bins - 1));
+                // Spread as power, so a blurred partial is as loud as a sharp one
+                let power = magnitudes[low..=high].iter().map(|m| m * m).sum::<f32>() / (high - low + 1) as f32;
+                let magnitude = power.sqrt();
+                self.buffer[bin] = Complex::from_polar(magnitude, *phase);
+            }
+            // Mirrored for a real signal
+            for bin in 1..FFT_SIZE / 2 {
+                self.buffer[FFT_SIZE - bin] = self.buffer[bin].conj();
+            }
+            self.buffer[0].im = 0.0;
+            self.buffer[FFT_SIZE / 2].im = 0.0;
+            self.ifft.process(&mut self.buffer);
+            // Left at the transform's own scale, the overlapped grains come out a little over
+            // the captured level unsmeared and a little under it fully smeared
+            for (i, sample) in self.buffer.iter().enumerate() {
+                let slot = (self.read + i) % FFT_SIZE;
+                self.output[slot][side] += sample.re * self.window[i] / FFT_SIZE as f32;
+            }
+        }
+    }
+}
+
+impl Effect for Freeze {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        self.history[self.written] = input;
+        self.written = (self.written + 1) % FFT_SIZE;
+        if self.read.is_multiple_of(HOP) && self.settings.frozen && self.captured {
+            self.resynthesize();
+        }
+        // Grains already started finish after a thaw, so the pad fades over one window
+        let pad = std::mem::take(&mut self.output[self.read]);
+        self.read = (self.read + 1) % FFT_SIZE;
+        let mix = self.settings.mix.max(0.0);
+        (1.0, pad.map(|sample| sample * mix))
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "frozen" => self.set_frozen(value >= 0.5),
+            "smear" => self.settings.smear = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,257 @@
+pub mod bitcrusher;
+pub mod chorus;
+pub mod compressor;
+pub mod crossfeed;
+pub mod dc_blocker;
+pub mod distortion;
+pub mod eq;
+pub mod flanger;
+#[cfg(feature = "freeze")]
+pub mod freeze;
+pub mod phaser;
+pub mod pitch_shift;
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+pub mod plugin;
+pub mod rotary;
+pub mod width;
+
+use serde::{Deserialize, Serialize};
+
+use crate::oversample::Oversampling;
+use crate::stereo::Frame;
+use crate::tempo::Rate;
+use bitcrusher::{Bitcrusher, BitcrusherSettings};
+use chorus::{Chorus, ChorusSettings};
+use compressor::{Compressor, CompressorSettings};
+use distortion::{Distortion, DistortionSettings};
+use flanger::{Flanger, FlangerSettings};
+#[cfg(feature = "freeze")]
+use freeze::{Freeze, FreezeSettings};
+use phaser::{Phaser, PhaserSettings};
+use pitch_shift::{PitchShift, PitchShiftSettings};
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+use plugin::{Plugin, PluginSettings};
+use rotary::{Rotary, RotarySettings};
+
+// A processor that sits on the mixed stereo output of the synthesizer, on a send bus or,
+// for those that can (see `EffectConfig::per_voice`), in each voice before the mix
+pub trait Effect: Send {
+    fn process(&mut self, input: Frame) -> Frame;
+
+    // `process` with its output kept in two: the share of `input` the effect's dry/wet
+    // mix passes through untouched, and what the effect made, so that `process(input)` is
+    // `input * dry + wet`. Effects without a mix keep nothing apart.
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        (0.0, self.process(input))
+    }
+
+    // Called whenever the global tempo changes, for effects with tempo-synced rates
+    fn set_tempo(&mut self, _tempo: f32) {}
+
+    // Called with the preset's quality setting, for effects with nonlinear stages
+    fn set_oversampling(&mut self, _oversampling: Oversampling) {}
+
+    // Called before `process` with the frame of live input, for effects it can key
+    fn sidechain(&mut self, _key: Frame) {}
+
+    // Forgets the sound it has heard, as if just made, for a voice's chain that's handed
+    // on to the next note (see `VoiceParts`). Only effects that can run per voice need it.
+    fn reset(&mut self) {}
+
+    // Changes one numeric setting while running, for macros and live editing.
+    // Returns false if the effect has no parameter by that name.
+    fn set_param(&mut self, _name: &str, _value: f32) -> bool {
+        false
+    }
+}
+
+// The serializable description of one effect slot, as stored in presets
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectConfig {
+    Chorus(ChorusSettings),
+    Flanger(FlangerSettings),
+    Phaser(PhaserSettings),
+    Distortion(DistortionSettings),
+    Bitcrusher(BitcrusherSettings),
+    Compressor(CompressorSettings),
+    Rotary(RotarySettings),
+    #[cfg(feature = "freeze")]
+    Freeze(FreezeSettings),
+    PitchShift(PitchShiftSettings),
+    #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+    Plugin(PluginSettings),
+}
+
+impl EffectConfig {
+    pub fn build(&self, sample_rate: u32) -> Box<dyn Effect> {
+        match self {
+            EffectConfig::Chorus(settings) => Box::new(Chorus::new(settings.clone(), sample_rate)),
+            EffectConfig::Flanger(settings) => Box::new(Flanger::new(settings.clone(), sample_rate)),
+            EffectConfig::Phaser(settings) => Box::new(Phaser::new(settings.clone(), sample_rate)),
+            EffectConfig::Distortion(settings) => Box::new(Distortion::new(settings.clone(), sample_rate)),
+            EffectConfig::Bitcrusher(settings) => Box::new(Bitcrusher::new(settings.clone(), sample_rate)),
+            EffectConfig::Compressor(settings) => Box::new(Compressor::new(settings.clone(), sample_rate)),
+            EffectConfig::Rotary(settings) => Box::new(Rotary::new(settings.clone(), sample_rate)),
+            #[cfg(feature = "freeze")]
+            EffectConfig::Freeze(settings) => Box::new(Freeze::new(settings.clone(), sample_rate)),
+            EffectConfig::PitchShift(settings) => Box::new(PitchShift::new(settings.clone(), sample_rate)),
+            #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+            EffectConfig::Plugin(settings) => Box::new(Plugin::new(settings.clone(), sample_rate)),
+        }
+    }
+
+    // Whether the effect can run in every voice (see `VoiceEffects`): it has to be cheap
+    // and hold little, as there's one for each note sounding. Reverb-like tails, spectral
+    // and pitch effects and plugins stay on the master bus.
+    pub fn per_voice(&self) -> bool {
+        matches!(
+            self,
+            EffectConfig::Chorus(_) | EffectConfig::Flanger(_) | EffectConfig::Phaser(_) | EffectConfig::Distortion(_) | EffectConfig::Bitcrusher(_)
+        )
+    }
+
+    // The effect's name as written in presets
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectConfig::Chorus(_) => "chorus",
+            EffectConfig::Flanger(_) => "flanger",
+            EffectConfig::Phaser(_) => "phaser",
+            EffectConfig::Distortion(_) => "distortion",
+            EffectConfig::Bitcrusher(_) => "bitcrusher",
+            EffectConfig::Compressor(_) => "compressor",
+            EffectConfig::Rotary(_) => "rotary",
+            #[cfg(feature = "freeze")]
+            EffectConfig::Freeze(_) => "freeze",
+            EffectConfig::PitchShift(_) => "pitch_shift",
+            #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+            EffectConfig::Plugin(_) => "plugin",
+        }
+    }
+
+    // The numeric settings that `Effect::set_param` accepts, with their preset values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let hz = |rate: &Rate| rate.editable_param("rate");
+        match self {
+            EffectConfig::Chorus(s) => hz(&s.rate).into_iter().chain([("depth", s.depth), ("mix", s.mix)]).collect(),
+            EffectConfig::Flanger(s) => hz(&s.rate)
+                .into_iter()
+                .chain([("delay", s.delay), ("depth", s.depth), ("feedback", s.feedback), ("mix", s.mix)])
+                .collect(),
+            EffectConfig::Phaser(s) => hz(&s.rate)
+                .into_iter()
+                .chain([
+                    ("min_frequency", s.min_frequency),
+                    ("max_frequency", s.max_frequency),
+                    ("feedback", s.feedback),
+                    ("mix", s.mix),
+                ])
+                .collect(),
+            EffectConfig::Distortion(s) => vec![("drive", s.drive), ("tone", s.tone), ("level", s.level), ("mix", s.mix)],
+            EffectConfig::Bitcrusher(s) => vec![("bits", s.bits), ("sample_rate", s.sample_rate), ("mix", s.mix)],
+            EffectConfig::Compressor(s) => vec![
+                ("threshold", s.threshold),
+                ("ratio", s.ratio),
+                ("attack", s.attack),
+                ("release", s.release),
+                ("makeup", s.makeup),
+                ("amount", s.amount),
+            ],
+            EffectConfig::Rotary(s) => vec![("fast", if s.fast { 1.0 } else { 0.0 }), ("depth", s.depth), ("mix", s.mix)],
+            #[cfg(feature = "freeze")]
+            EffectConfig::Freeze(s) => vec![("frozen", if s.frozen { 1.0 } else { 0.0 }), ("smear", s.smear), ("mix", s.mix)],
+            EffectConfig::PitchShift(s) => vec![("semitones", s.semitones), ("mix", s.mix)],
+            // The plugin's own parameters are only known once it's loaded
+            #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+            EffectConfig::Plugin(s) => vec![("mix", s.mix)],
+        }
+    }
+}
+
+// Runs the output through each effect in preset order
+pub struct EffectsChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectsChain {
+    pub fn new(configs: &[EffectConfig], sample_rate: u32) -> Self {
+        Self {
+            effects: configs.iter().map(|config| config.build(sample_rate)).collect(),
+        }
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        for effect in &mut self.effects {
+            effect.set_tempo(tempo);
+        }
+    }
+
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        for effect in &mut self.effects {
+            effect.set_oversampling(oversampling);
+        }
+    }
+
+    // Sets a parameter of the effect in the given slot, see `Effect::set_param`
+    pub fn set_param(&mut self, slot: usize, name: &str, value: f32) -> bool {
+        self.effects.get_mut(slot).is_some_and(|effect| effect.set_param(name, value))
+    }
+
+    pub fn reset(&mut self) {
+        for effect in &mut self.effects {
+            effect.reset();
+        }
+    }
+
+    // Hands every effect the live input frame that goes with the next `process`
+    pub fn sidechain(&mut self, key: Frame) {
+        for effect in &mut self.effects {
+            effect.sidechain(key);
+        }
+    }
+
+    pub fn process(&mut self, frame: Frame) -> Frame {
+        self.effects.iter_mut().fold(frame, |frame, effect| effect.process(frame))
+    }
+
+    // `process` for the chain, as `Effect::process_apart`: what's left of `frame` itself
+    // after every effect's mix, as a share of it, and all the effects made of it
+    pub fn process_apart(&mut self, frame: Frame) -> (f32, Frame) {
+        let mut apart = (1.0, [0.0; 2]);
+        for effect in &mut self.effects {
+            let (dry, wet) = effect.process_apart(together(frame, apart));
+            apart = (apart.0 * dry, [apart.1[0] * dry + wet[0], apart.1[1] * dry + wet[1]]);
+        }
+        apart
+    }
+}
+
+// Blends a processed frame with the untouched input, `mix` being the wet proportion
+pub fn mix_frames(dry: Frame, wet: Frame, mix: f32) -> Frame {
+    let mix = mix.clamp(0.0, 1.0);
+    [dry[0] * (1.0 - mix) + wet[0] * mix, dry[1] * (1.0 - mix) + wet[1] * mix]
+}
+
+// What an effect made, `wet`, set apart from its input by the dry/wet `mix`, as
+// `Effect::process_apart` returns them
+pub fn apart(wet: Frame, mix: f32) -> (f32, Frame) {
+    let mix = mix.clamp(0.0, 1.0);
+    (1.0 - mix, [wet[0] * mix, wet[1] * mix])
+}
+
+// The output of an effect given `input`, from the two parts `Effect::process_apart` returns
+pub fn together(input: Frame, (dry, wet): (f32, Frame)) -> Frame {
+    [input[0] * dry + wet[0], input[1] * dry + wet[1]]
+}
+
+pub fn ms_to_samples(ms: f32, sample_rate: u32) -> f32 {
+    ms * 0.001 * sample_rate as f32
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-6).log10()
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use super::{apart, together, Effect};
+use crate::lfo::Lfo;
+use crate::stereo::Frame;
+use crate::tempo::{Rate, DEFAULT_TEMPO};
+
+const MAX_FEEDBACK: f32 = 0.9;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhaserSettings {
+    pub rate: Rate,          // Sweep rate, in Hz or as a tempo division
+    pub stages: usize,       // Number of allpass stages: 4, 6 or 8
+    pub min_frequency: f32,  // Bottom of the sweep in Hz
+    pub max_frequency: f32,  // Top of the sweep in Hz
+    pub feedback: f32,       // -0.9..0.9, emphasises the notches
+    pub mix: f32,            // 0.5 gives the deepest notches
+}
+
+impl Default for PhaserSettings {
+    fn default() -> Self {
+        Self {
+            rate: Rate::Hz(0.5),
+            stages: 4,
+            min_frequency: 200.0,
+            max_frequency: 2000.0,
+            feedback: 0.5,
+            mix: 0.5,
+        }
+    }
+}
+
+// First-order allpass section: flat magnitude, phase shift that depends on the coefficient
+#[derive(Clone, Copy, Default)]
+struct AllpassStage {
+    last_input: f32,
+    last_output: f32,
+}
+
+impl AllpassStage {
+    fn process(&mut self, input: f32, coefficient: f32) -> f32 {
+        let output = -coefficient * input + self.last_input + coefficient * self.last_output;
+        self.last_input = input;
+        self.last_output = output;
+        output
+    }
+}
+
+// Cascaded allpass stages swept by an LFO; mixing the result with the dry signal
+// produces moving notches (one per pair of stages).
+pub struct Phaser {
+    settings: PhaserSettings,
+    stages: [Vec<AllpassStage>; 2],
+    lfo: Lfo,
+    sample_rate: u32,
+    last_output: Frame,
+}
+
+impl Phaser {
+    pub fn new(settings: PhaserSettings, sample_rate: u32) -> Self {
+        // Only even stage counts make sense (each notch needs two stages), limited to 4..8
+        let stage_count = (settings.stages.clamp(4, 8) / 2) * 2;
+        let lfo = Lfo::new(settings.rate.hz(DEFAULT_TEMPO), sample_rate);
+        Self {
+            settings,
+            stages: [vec![AllpassStage::default(); stage_count], vec![AllpassStage::default(); stage_count]],
+            lfo,
+            sample_rate,
+            last_output: [0.0; 2],
+        }
+    }
+
+    // Allpass coefficient that places the stage's 90° point at `frequency`
+    fn coefficient(&self, frequency: f32) -> f32 {
+        let nyquist = self.sample_rate as f32 * 0.5;
+        let t = (PI * frequency.clamp(20.0, nyquist * 0.9) / self.sample_rate as f32).tan();
+        (1.0 - t) / (1.0 + t)
+    }
+}
+
+impl Effect for Phaser {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        // Sweep exponentially so the movement sounds even across the range
+        let sweep = 0.5 + 0.5 * self.lfo.next_value();
+        let min = self.settings.min_frequency.max(20.0);
+        let max = self.settings.max_frequency.max(min);
+        let frequency = min * (max / min).powf(sweep);
+        let coefficient = self.coefficient(frequency);
+
+        let feedback = self.settings.feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+        for (channel, stages) in self.stages.iter_mut().enumerate() {
+            let mut wet = input[channel] + self.last_output[channel] * feedback;
+            for stage in stages.iter_mut() {
+                wet = stage.process(wet, coefficient);
+            }
+            self.last_output[channel] = wet;
+        }
+
+        apart(self.last_output, self.settings.mix)
+    }
+
+    fn set_tempo(&mut self, tempo: f32) {
+        self.lfo.set_rate(self.settings.rate.hz(tempo));
+    }
+
+    fn reset(&mut self) {
+        self.lfo.set_phase(0.0);
+        for stages in &mut self.stages {
+            stages.fill(AllpassStage::default());
+        }
+        self.last_output = [0.0; 2];
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate" => {
+                self.settings.rate = Rate::Hz(value);
+                self.lfo.set_rate(value);
+            }
+            "min_frequency" => self.settings.min_frequency = value,
+            "max_frequency" => self.settings.max_frequency = value,
+            "feedback" => self.settings.feedback = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use super::{apart, together, ms_to_samples, Effect};
+use crate::delay_line::DelayLine;
+use crate::stereo::Frame;
+
+const WINDOW_MS: f32 = 60.0; // Length of each grain; shorter warbles less on chords, longer less on low notes
+const MAX_SEMITONES: f32 = 12.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PitchShiftSettings {
+    pub semitones: f32, // -12.0..12.0
+    pub mix: f32,       // 0.0 = dry only, 1.0 = wet only
+    pub input: bool,    // Shift the live input rather than everything reaching the effect
+}
+
+impl Default for PitchShiftSettings {
+    fn default() -> Self {
+        Self {
+            semitones: 7.0,
+            mix: 0.5,
+            input: false,
+        }
+    }
+}
+
+// Granular pitch shifter: two taps sweep through a delay line at the new speed, each
+// jumping back by a window when it runs out of room, and fade into each other so the
+// jumps aren't heard. With `input` set it shifts the live audio input (see `--input`)
+// instead and adds it over what reaches it, as a harmony at `mix`; turning the input's
+// level down leaves only the shifted voice.
+pub struct PitchShift {
+    settings: PitchShiftSettings,
+    delay_lines: [DelayLine; 2],
+    phase: f32,  // 0.0..1.0 through the window, of the first tap; the second is half a window on
+    window: f32, // In samples
+    key: Frame,  // The live input, for `input`
+}
+
+impl PitchShift {
+    pub fn new(settings: PitchShiftSettings, sample_rate: u32) -> Self {
+        let window = ms_to_samples(WINDOW_MS, sample_rate);
+        Self {
+            settings,
+            delay_lines: [DelayLine::new(window as usize + 1), DelayLine::new(window as usize + 1)],
+            phase: 0.0,
+            window,
+            key: [0.0; 2],
+        }
+    }
+}
+
+impl Effect for PitchShift {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        let ratio = 2.0_f32.powf(self.settings.semitones.clamp(-MAX_SEMITONES, MAX_SEMITONES) / 12.0);
+        // Reading faster than writing shortens the delay, so higher notes run the phase down
+        self.phase = (self.phase + (1.0 - ratio) / self.window).rem_euclid(1.0);
+
+        let source = if self.settings.input { self.key } else { input };
+        let mut wet = [0.0; 2];
+        for (channel, wet) in wet.iter_mut().enumerate() {
+            let delay_line = &mut self.delay_lines[channel];
+            delay_line.write(source[channel]);
+            for offset in [0.0, 0.5] {
+                let phase = (self.phase + offset).fract();
+                // The two gains are sin² and cos² of the same angle, so they always sum to one
+                let gain = (PI * phase).sin().powi(2);
+                *wet += delay_line.read(1.0 + phase * self.window) * gain;
+            }
+        }
+
+        if self.settings.input {
+            let mix = self.settings.mix.clamp(0.0, 1.0);
+            (1.0, wet.map(|sample| sample * mix))
+        } else {
+            apart(wet, self.settings.mix)
+        }
+    }
+
+    fn sidechain(&mut self, key: Frame) {
+        self.key = key;
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "semitones" => self.settings.semitones = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,600 @@
+use clap_sys::audio_buffer::clap_audio_buffer;
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::events::{
+    clap_event_header, clap_event_param_value, clap_input_events, clap_output_events, CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::audio_ports::{clap_audio_port_info, clap_plugin_audio_ports, CLAP_EXT_AUDIO_PORTS};
+use clap_sys::ext::params::{clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::id::clap_id;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::{clap_process, CLAP_PROCESS_ERROR};
+use clap_sys::version::CLAP_VERSION;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::PathBuf;
+use std::{mem, ptr};
+
+use super::{mix_frames, Effect};
+use crate::stereo::Frame;
+
+const BLOCK: usize = 64; // Frames handed to the plugin at a time, which is also the latency it adds
+const MAX_EVENTS: usize = 256; // Parameter changes held for the next block; more in one block are dropped
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginSettings {
+    pub path: PathBuf,                 // The .clap file
+    pub id: String,                    // Which plugin in the file, e.g. "com.example.reverb"; empty for the first
+    pub params: BTreeMap<String, f32>, // Starting values by parameter name, in the plugin's own units
+    pub mix: f32,                      // 0.0 = dry only, 1.0 = wet only
+}
+
+impl Default for PluginSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            id: String::new(),
+            params: BTreeMap::new(),
+            mix: 1.0,
+        }
+    }
+}
+
+// A CLAP effect plugin from another vendor, loaded from its .clap file. Audio goes to
+// it a block at a time, so it comes back BLOCK frames late; the dry side of the mix is
+// held back to match. Its parameters are set by name, lowercased with anything but
+// letters and digits turned into underscores ("Room Size" is "room_size"), or by their
+// CLAP id, so a macro target like "effects.2.room_size" moves them. Values are in the
+// plugin's own units and kept to its range. A plugin that can't be loaded is reported
+// and passes the sound through untouched, so a preset made elsewhere still plays.
+pub struct Plugin {
+    settings: PluginSettings,
+    instance: Option<Instance>,
+    input: [Vec<f32>; 2],  // The block being filled, per side
+    output: [Vec<f32>; 2], // The last block the plugin returned
+    position: usize,       // Frame within the blocks
+}
+
+impl Plugin {
+    pub fn new(settings: PluginSettings, sample_rate: u32) -> Self {
+        let instance = Instance::load(&settings, sample_rate);
+        Self::with_instance(settings, instance)
+    }
+
+    fn with_instance(settings: PluginSettings, instance: Result<Instance, String>) -> Self {
+        let instance = match instance {
+            Ok(instance) => Some(instance),
+            Err(error) => {
+                tracing::error!(path = %settings.path.display(), error, "couldn't load the plugin, passing the sound through");
+                None
+            }
+        };
+        let mut plugin = Self {
+            settings,
+            instance,
+            input: [vec![0.0; BLOCK], vec![0.0; BLOCK]],
+            output: [vec![0.0; BLOCK], vec![0.0; BLOCK]],
+            position: 0,
+        };
+        for (name, value) in plugin.settings.params.clone() {
+            if !plugin.set_param(&name, value) {
+                tracing::warn!(name, "the plugin has no parameter by that name");
+            }
+        }
+        plugin
+    }
+}
+
+impl Effect for Plugin {
+    fn process(&mut self, input: Frame) -> Frame {
+        let Some(instance) = &mut self.instance else { return input };
+        let dry = [self.input[0][self.position], self.input[1][self.position]];
+        let wet = [self.output[0][self.position], self.output[1][self.position]];
+        self.input[0][self.position] = input[0];
+        self.input[1][self.position] = input[1];
+        self.position += 1;
+        if self.position == BLOCK {
+            self.position = 0;
+            instance.process(&mut self.input, &mut self.output);
+        }
+        mix_frames(dry, wet, self.settings.mix)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if name == "mix" {
+            self.settings.mix = value;
+            return true;
+        }
+        self.instance.as_mut().is_some_and(|instance| instance.set_param(name, value))
+    }
+}
+
+// A parameter of the plugin as `set_param` finds it
+struct Param {
+    name: String,
+    id: clap_id,
+    min: f64,
+    max: f64,
+}
+
+// The loaded plugin and what it was made with. Everything the CLAP spec asks for on the
+// main thread (making, activating and destroying it) happens where the effect is built
+// and dropped, and processing where it runs; the synth never does both at once.
+struct Instance {
+    plugin: *const clap_plugin,
+    active: bool,
+    entry: *const clap_plugin_entry,
+    params: Vec<Param>,
+    events: Vec<clap_event_param_value>, // Waiting for the next block
+    channels: [u32; 2],                  // Of the main input and output, 1 or 2
+    mono: Vec<f32>,                      // The block folded down, for a plugin that takes mono
+    frames: i64,                         // Processed so far, the plugin's steady time
+    _host: Box<clap_host>,               // The plugin keeps a pointer to it
+    _library: Option<libloading::Library>, // Dropped last, once the plugin is gone; None for one linked in
+}
+
+// The plugin is only ever called from the thread that holds the effect
+unsafe impl Send for Instance {}
+
+impl Instance {
+    fn load(settings: &PluginSettings, sample_rate: u32) -> Result<Self, String> {
+        let path = CString::new(settings.path.to_string_lossy().as_bytes()).map_err(|e| e.to_string())?;
+        // SAFETY: loading a plugin runs its initializers, which is the point of asking for it
+        let library = unsafe { libloading::Library::new(&settings.path) }.map_err(|e| e.to_string())?;
+        // SAFETY: the entry is the plugin's own, as the CLAP ABI defines it
+        unsafe {
+            let entry = *library.get::<*const clap_plugin_entry>(b"clap_entry\0").map_err(|e| e.to_string())?;
+            Self::start(entry, &path, Some(library), &settings.id, sample_rate)
+        }
+    }
+
+    // Brings up the plugin `id` (or the first) from `entry`, which came from `library`
+    unsafe fn start(
+        entry: *const clap_plugin_entry,
+        path: &CStr,
+        library: Option<libloading::Library>,
+        id: &str,
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        let host = Box::new(clap_host {
+            clap_version: CLAP_VERSION,
+            host_data: ptr::null_mut(),
+            name: c"rodio-synth".as_ptr(),
+            vendor: c"".as_ptr(),
+            url: c"".as_ptr(),
+            version: c"0.1.0".as_ptr(),
+            get_extension: Some(host_get_extension),
+            request_restart: Some(host_request),
+            request_process: Some(host_request),
+            request_callback: Some(host_request),
+        });
+        // Every pointer comes from the plugin as the CLAP ABI defines it, and is checked
+        // for null before use
+        if entry.is_null() || (*entry).clap_version.major < 1 {
+            return Err("not a CLAP 1.x plugin".into());
+        }
+        if !(*entry).init.is_some_and(|init| init(path.as_ptr())) {
+            return Err("the plugin failed to start".into());
+        }
+        let mut instance = Self {
+            plugin: ptr::null(),
+            active: false,
+            entry,
+            params: Vec::new(),
+            events: Vec::with_capacity(MAX_EVENTS),
+            channels: [2, 2],
+            mono: vec![0.0; BLOCK],
+            frames: 0,
+            _host: host,
+            _library: library,
+        };
+        instance.create(id, sample_rate)?;
+        Ok(instance)
+    }
+
+    // Makes and activates the plugin; on an error, whatever was made is undone on drop
+    unsafe fn create(&mut self, id: &str, sample_rate: u32) -> Result<(), String> {
+        let factory = (*self.entry).get_factory.map_or(ptr::null(), |get| get(CLAP_PLUGIN_FACTORY_ID.as_ptr())) as *const clap_plugin_factory;
+        if factory.is_null() {
+            return Err("the file has no plugin factory".into());
+        }
+        let count = (*factory).get_plugin_count.map_or(0, |count| count(factory));
+        let descriptor = (0..count)
+            .filter_map(|index| (*factory).get_plugin_descriptor.map(|get| get(factory, index)))
+            .find(|&descriptor| !descriptor.is_null() && (id.is_empty() || CStr::from_ptr((*descriptor).id).to_str() == Ok(id)))
+            .ok_or_else(|| if id.is_empty() { "the file holds no plugins".to_string() } else { format!("the file has no plugin {}", id) })?;
+        let plugin = (*factory).create_plugin.map_or(ptr::null(), |create| create(factory, &*self._host, (*descriptor).id));
+        if plugin.is_null() {
+            return Err("the plugin couldn't be made".into());
+        }
+        self.plugin = plugin;
+        if !(*plugin).init.is_some_and(|init| init(plugin)) {
+            return Err("the plugin couldn't be made".into());
+        }
+        self.channels = [self.main_channels(true)?, self.main_channels(false)?];
+        self.params = self.read_params();
+        if !(*plugin).activate.is_some_and(|activate| activate(plugin, sample_rate as f64, 1, BLOCK as u32)) {
+            return Err("the plugin couldn't be activated".into());
+        }
+        self.active = true;
+        (*plugin).start_processing.inspect(|start| {
+            start(plugin);
+        });
+        Ok(())
+    }
+
+    unsafe fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        let extension = (*self.plugin).get_extension.map_or(ptr::null(), |get| get(self.plugin, id.as_ptr()));
+        (extension as *const T).as_ref()
+    }
+
+    // The channels of the first input or output port, assumed stereo when the plugin
+    // doesn't say
+    unsafe fn main_channels(&self, input: bool) -> Result<u32, String> {
+        let Some(ports) = self.extension::<clap_plugin_audio_ports>(CLAP_EXT_AUDIO_PORTS) else { return Ok(2) };
+        let mut info: clap_audio_port_info = mem::zeroed();
+        let found = ports.count.is_some_and(|count| count(self.plugin, input) > 0)
+            && ports.get.is_some_and(|get| get(self.plugin, 0, input, &mut info));
+        match (found, info.channel_count) {
+            (true, 1 | 2) => Ok(info.channel_count),
+            (false, _) if input => Err("the plugin takes no audio in; only effects can be loaded".into()),
+            (false, _) => Err("the plugin puts no audio out".into()),
+            (true, channels) => Err(format!("the plugin's main port has {} channels; only mono and stereo work", channels)),
+        }
+    }
+
+    unsafe fn read_params(&self) -> Vec<Param> {
+        let Some(params) = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS) else { return Vec::new() };
+        let count = params.count.map_or(0, |count| count(self.plugin));
+        (0..count)
+            .filter_map(|index| {
+                let mut info: clap_param_info = mem::zeroed();
+                params.get_info?(self.plugin, index, &mut info).then_some(())?;
+                let name = CStr::from_ptr(info.name.as_ptr()).to_string_lossy();
+                Some(Param { name: param_name(&name), id: info.id, min: info.min_value, max: info.max_value })
+            })
+            .collect()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let Some(param) = self.params.iter().find(|param| param.name == name || name.parse() == Ok(param.id)) else { return false };
+        if self.events.len() < MAX_EVENTS {
+            self.events.push(clap_event_param_value {
+                header: clap_event_header {
+                    size: mem::size_of::<clap_event_param_value>() as u32,
+                    time: 0,
+                    space_id: CLAP_CORE_EVENT_SPACE_ID,
+                    type_: CLAP_EVENT_PARAM_VALUE,
+                    flags: 0,
+                },
+                param_id: param.id,
+                cookie: ptr::null_mut(),
+                note_id: -1,
+                port_index: -1,
+                channel: -1,
+                key: -1,
+                value: (value as f64).clamp(param.min.min(param.max), param.max.max(param.min)),
+            });
+        }
+        true
+    }
+
+    // Runs one block through the plugin, folding a mono side down and back up. The input
+    // is left as it was, as it's the dry side of the mix too. A plugin that reports an
+    // error gives silence for the block.
+    fn process(&mut self, input: &mut [Vec<f32>; 2], output: &mut [Vec<f32>; 2]) {
+        let [input_left, input_right] = input;
+        let [output_left, output_right] = output;
+        let mut inputs = [input_left.as_mut_ptr(), input_right.as_mut_ptr()];
+        if self.channels[0] == 1 {
+            for ((mono, left), right) in self.mono.iter_mut().zip(input_left.iter()).zip(input_right.iter()) {
+                *mono = (left + right) * 0.5;
+            }
+            inputs[0] = self.mono.as_mut_ptr();
+        }
+        let mut outputs = [output_left.as_mut_ptr(), output_right.as_mut_ptr()];
+        let mut audio_input = clap_audio_buffer {
+            data32: inputs.as_mut_ptr(),
+            data64: ptr::null_mut(),
+            channel_count: self.channels[0],
+            latency: 0,
+            constant_mask: 0,
+        };
+        let mut audio_output = clap_audio_buffer { data32: outputs.as_mut_ptr(), channel_count: self.channels[1], ..audio_input };
+        let in_events = clap_input_events {
+            ctx: &mut self.events as *mut Vec<clap_event_param_value> as *mut c_void,
+            size: Some(events_size),
+            get: Some(events_get),
+        };
+        let out_events = clap_output_events { ctx: ptr::null_mut(), try_push: Some(events_push) };
+        let process = clap_process {
+            steady_time: self.frames,
+            frames_count: BLOCK as u32,
+            transport: ptr::null(),
+            audio_inputs: &mut audio_input,
+            audio_outputs: &mut audio_output,
+            audio_inputs_count: 1,
+            audio_outputs_count: 1,
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+        // SAFETY: the buffers and event lists outlive the call, and the plugin is active
+        let status = unsafe { (*self.plugin).process.map_or(CLAP_PROCESS_ERROR, |process_block| process_block(self.plugin, &process)) };
+        self.events.clear();
+        self.frames += BLOCK as i64;
+        if status == CLAP_PROCESS_ERROR {
+            output_left.fill(0.0);
+            output_right.fill(0.0);
+        } else if self.channels[1] == 1 {
+            output_right.copy_from_slice(output_left);
+        }
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // SAFETY: the plugin is torn down in the reverse of how far it was brought up
+        unsafe {
+            if self.active {
+                (*self.plugin).stop_processing.inspect(|stop| stop(self.plugin));
+                (*self.plugin).deactivate.inspect(|deactivate| deactivate(self.plugin));
+            }
+            if !self.plugin.is_null() {
+                (*self.plugin).destroy.inspect(|destroy| destroy(self.plugin));
+            }
+            (*self.entry).deinit.inspect(|deinit| deinit());
+        }
+    }
+}
+
+// "Room Size" -> "room_size"
+fn param_name(name: &str) -> String {
+    let name: String = name.trim().chars().map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect();
+    name.trim_matches('_').to_string()
+}
+
+// The host offers no extensions, and has nothing to do when the plugin asks for a
+// restart or a callback; it's processing whenever the synth plays anyway
+unsafe extern "C" fn host_get_extension(_host: *const clap_host, _id: *const c_char) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+unsafe extern "C" fn events_size(list: *const clap_input_events) -> u32 {
+    (*((*list).ctx as *const Vec<clap_event_param_value>)).len() as u32
+}
+
+unsafe extern "C" fn events_get(list: *const clap_input_events, index: u32) -> *const clap_event_header {
+    let events = &*((*list).ctx as *const Vec<clap_event_param_value>);
+    events.get(index as usize).map_or(ptr::null(), |event| &event.header)
+}
+
+// What the plugin reports back (its own parameter moves) isn't used
+unsafe extern "C" fn events_push(_list: *const clap_output_events, _event: *const clap_event_header) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap_sys::events::clap_event_header;
+    use clap_sys::plugin::clap_plugin_descriptor;
+    use clap_sys::process::{clap_process_status, CLAP_PROCESS_CONTINUE};
+
+    // Plugins linked into the tests rather than loaded: they scale what they're given by
+    // their one parameter, "Output Gain" (id 7, 0 to 2), "test.mono" taking mono in
+    const SAMPLE_RATE: u32 = 48_000;
+
+    struct Gain {
+        plugin: clap_plugin,
+        mono: bool,
+        gain: f64,
+    }
+
+    static DESCRIPTORS: [clap_plugin_descriptor; 2] = [descriptor(c"test.stereo"), descriptor(c"test.mono")];
+
+    const fn descriptor(id: &'static CStr) -> clap_plugin_descriptor {
+        clap_plugin_descriptor {
+            clap_version: CLAP_VERSION,
+            id: id.as_ptr(),
+            name: c"Gain".as_ptr(),
+            vendor: c"".as_ptr(),
+            url: c"".as_ptr(),
+            manual_url: c"".as_ptr(),
+            support_url: c"".as_ptr(),
+            version: c"1".as_ptr(),
+            description: c"".as_ptr(),
+            features: ptr::null(),
+        }
+    }
+
+    static FACTORY: clap_plugin_factory = clap_plugin_factory {
+        get_plugin_count: Some(plugin_count),
+        get_plugin_descriptor: Some(plugin_descriptor),
+        create_plugin: Some(create_plugin),
+    };
+
+    static ENTRY: clap_plugin_entry = clap_plugin_entry {
+        clap_version: CLAP_VERSION,
+        init: Some(entry_init),
+        deinit: Some(entry_deinit),
+        get_factory: Some(get_factory),
+    };
+
+    static PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports { count: Some(port_count), get: Some(port_info) };
+
+    static PARAMS: clap_plugin_params = clap_plugin_params {
+        count: Some(param_count),
+        get_info: Some(param_info),
+        get_value: None,
+        value_to_text: None,
+        text_to_value: None,
+        flush: None,
+    };
+
+    unsafe extern "C" fn entry_init(_path: *const c_char) -> bool {
+        true
+    }
+
+    unsafe extern "C" fn entry_deinit() {}
+
+    unsafe extern "C" fn get_factory(id: *const c_char) -> *const c_void {
+        if CStr::from_ptr(id) == CLAP_PLUGIN_FACTORY_ID {
+            &FACTORY as *const clap_plugin_factory as *const c_void
+        } else {
+            ptr::null()
+        }
+    }
+
+    unsafe extern "C" fn plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+        DESCRIPTORS.len() as u32
+    }
+
+    unsafe extern "C" fn plugin_descriptor(_factory: *const clap_plugin_factory, index: u32) -> *const clap_plugin_descriptor {
+        DESCRIPTORS.get(index as usize).map_or(ptr::null(), |descriptor| descriptor)
+    }
+
+    unsafe extern "C" fn create_plugin(_factory: *const clap_plugin_factory, _host: *const clap_host, id: *const c_char) -> *const clap_plugin {
+        let mono = CStr::from_ptr(id) == c"test.mono";
+        let gain = Box::into_raw(Box::new(Gain {
+            plugin: clap_plugin {
+                desc: &DESCRIPTORS[mono as usize],
+                plugin_data: ptr::null_mut(),
+                init: Some(plugin_true),
+                destroy: Some(destroy),
+                activate: Some(activate),
+                deactivate: Some(plugin_nothing),
+                start_processing: Some(plugin_true),
+                stop_processing: Some(plugin_nothing),
+                reset: Some(plugin_nothing),
+                process: Some(process),
+                get_extension: Some(get_extension),
+                on_main_thread: Some(plugin_nothing),
+            },
+            mono,
+            gain: 1.0,
+        }));
+        (*gain).plugin.plugin_data = gain as *mut c_void;
+        &(*gain).plugin
+    }
+
+    unsafe extern "C" fn plugin_true(_plugin: *const clap_plugin) -> bool {
+        true
+    }
+
+    unsafe extern "C" fn plugin_nothing(_plugin: *const clap_plugin) {}
+
+    unsafe extern "C" fn destroy(plugin: *const clap_plugin) {
+        drop(Box::from_raw((*plugin).plugin_data as *mut Gain));
+    }
+
+    unsafe extern "C" fn activate(_plugin: *const clap_plugin, _sample_rate: f64, _min: u32, _max: u32) -> bool {
+        true
+    }
+
+    unsafe extern "C" fn get_extension(_plugin: *const clap_plugin, id: *const c_char) -> *const c_void {
+        match CStr::from_ptr(id) {
+            id if id == CLAP_EXT_AUDIO_PORTS => &PORTS as *const clap_plugin_audio_ports as *const c_void,
+            id if id == CLAP_EXT_PARAMS => &PARAMS as *const clap_plugin_params as *const c_void,
+            _ => ptr::null(),
+        }
+    }
+
+    unsafe extern "C" fn port_count(_plugin: *const clap_plugin, _input: bool) -> u32 {
+        1
+    }
+
+    unsafe extern "C" fn port_info(plugin: *const clap_plugin, _index: u32, input: bool, info: *mut clap_audio_port_info) -> bool {
+        let mono = (*((*plugin).plugin_data as *const Gain)).mono;
+        (*info).channel_count = if input && mono { 1 } else { 2 };
+        true
+    }
+
+    unsafe extern "C" fn param_count(_plugin: *const clap_plugin) -> u32 {
+        1
+    }
+
+    unsafe extern "C" fn param_info(_plugin: *const clap_plugin, _index: u32, info: *mut clap_param_info) -> bool {
+        for (to, &from) in (*info).name.iter_mut().zip(c"Output Gain".to_bytes()) {
+            *to = from as c_char;
+        }
+        (*info).id = 7;
+        (*info).max_value = 2.0;
+        true
+    }
+
+    unsafe extern "C" fn process(plugin: *const clap_plugin, process: *const clap_process) -> clap_process_status {
+        let gain = &mut *((*plugin).plugin_data as *mut Gain);
+        let events = (*process).in_events;
+        for index in 0..(*events).size.unwrap()(events) {
+            let header: *const clap_event_header = (*events).get.unwrap()(events, index);
+            gain.gain = (*(header as *const clap_event_param_value)).value;
+        }
+        let (input, output) = (&*(*process).audio_inputs, &*(*process).audio_outputs);
+        for channel in 0..2 {
+            let from = *input.data32.add(channel.min(input.channel_count as usize - 1));
+            let to = *output.data32.add(channel);
+            for frame in 0..(*process).frames_count as usize {
+                *to.add(frame) = *from.add(frame) * gain.gain as f32;
+            }
+        }
+        CLAP_PROCESS_CONTINUE
+    }
+
+    fn gain_plugin(mono: bool, mix: f32) -> Plugin {
+        let id = if mono { "test.mono" } else { "" }; // The first is the stereo one
+        let settings = PluginSettings { id: id.to_string(), mix, ..PluginSettings::default() };
+        // SAFETY: the entry above keeps to the CLAP ABI
+        let instance = unsafe { Instance::start(&ENTRY, c"", None, &settings.id, SAMPLE_RATE) };
+        Plugin::with_instance(settings, instance)
+    }
+
+    // The plugin's answer to a block of `input`, which comes back a block late
+    fn run(plugin: &mut Plugin, input: Frame) -> Frame {
+        for _ in 0..BLOCK {
+            plugin.process(input);
+        }
+        plugin.process(input)
+    }
+
+    #[test]
+    fn mono_plugins_get_both_sides_folded_down() {
+        let mut plugin = gain_plugin(true, 1.0);
+        assert_eq!(plugin.instance.as_ref().unwrap().channels, [1, 2]);
+        assert_eq!(run(&mut plugin, [1.0, 0.0]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn folding_down_leaves_the_dry_side_alone() {
+        let mut plugin = gain_plugin(true, 0.0);
+        assert_eq!(run(&mut plugin, [1.0, 0.0]), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn parameters_are_set_by_name_or_id_and_kept_to_range() {
+        let mut plugin = gain_plugin(false, 1.0);
+        assert!(plugin.set_param("output_gain", 0.5));
+        assert_eq!(run(&mut plugin, [1.0, -1.0]), [0.5, -0.5]);
+        assert!(plugin.set_param("7", 5.0));
+        assert_eq!(run(&mut plugin, [1.0, -1.0]), [2.0, -2.0]);
+        assert!(!plugin.set_param("room_size", 1.0));
+    }
+
+    #[test]
+    fn a_plugin_that_cant_load_passes_the_sound_through() {
+        let settings = PluginSettings { path: "no/such/plugin.clap".into(), ..PluginSettings::default() };
+        let mut plugin = Plugin::new(settings, SAMPLE_RATE);
+        assert!(plugin.instance.is_none());
+        assert_eq!(plugin.process([0.25, -0.5]), [0.25, -0.5]);
+    }
+
+    #[test]
+    fn parameter_names_are_lowercased_with_underscores() {
+        assert_eq!(param_name("Room Size"), "room_size");
+        assert_eq!(param_name(" Dry/Wet (%) "), "dry_wet");
+    }
+}
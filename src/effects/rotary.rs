@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use super::{apart, together, ms_to_samples, Effect};
+use crate::biquad::Biquad;
+use crate::delay_line::DelayLine;
+use crate::stereo::Frame;
+
+const CROSSOVER: f32 = 800.0; // Hz between the drum and the horn
+const SLOW: [f32; 2] = [0.67, 0.83]; // Rotations per second of the drum and the horn, on chorale
+const FAST: [f32; 2] = [5.7, 6.7]; // And on tremolo
+const INERTIA: [f32; 2] = [3.0, 0.7]; // Seconds each takes to get most of the way to a new speed; the drum is heavier
+const HORN_SWING_MS: f32 = 0.6; // How far the horn's mouth moves towards and away from the listener
+const DRUM_TREMOLO: f32 = 0.3; // Of the drum's level, against the horn's 0.5 at full depth
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RotarySettings {
+    pub fast: bool, // Tremolo rather than chorale speed
+    pub depth: f32, // 0.0..1.0, how strongly the rotors move the sound
+    pub mix: f32,   // 0.0 = dry only, 1.0 = wet only
+}
+
+impl Default for RotarySettings {
+    fn default() -> Self {
+        Self {
+            fast: false,
+            depth: 0.7,
+            mix: 1.0,
+        }
+    }
+}
+
+// A rotating speaker cabinet: the sound is split at the crossover into a low drum and
+// a high horn, each spinning past a pair of microphones left and right. The horn's
+// mouth moving to and fro bends the pitch (a modulated delay) as well as the level;
+// the drum mostly throbs. Switching speed, which the "fast" parameter does above 0.5 so
+// a macro or pedal can throw it, the rotors speed up and slow down at their own pace.
+pub struct Rotary {
+    settings: RotarySettings,
+    crossover: Biquad,
+    horn: DelayLine,
+    angles: [f32; 2], // Of the drum and the horn, 0 to 2π
+    speeds: [f32; 2], // Rotations per second, on the way to the setting's speed
+    sample_rate: u32,
+}
+
+impl Rotary {
+    pub fn new(settings: RotarySettings, sample_rate: u32) -> Self {
+        Self {
+            speeds: if settings.fast { FAST } else { SLOW },
+            settings,
+            crossover: Biquad::low_pass(CROSSOVER, std::f32::consts::FRAC_1_SQRT_2, sample_rate),
+            horn: DelayLine::new(ms_to_samples(HORN_SWING_MS * 2.0, sample_rate) as usize + 1),
+            angles: [0.0; 2],
+            sample_rate,
+        }
+    }
+}
+
+impl Effect for Rotary {
+    fn process(&mut self, input: Frame) -> Frame {
+        together(input, self.process_apart(input))
+    }
+
+    fn process_apart(&mut self, input: Frame) -> (f32, Frame) {
+        let targets = if self.settings.fast { FAST } else { SLOW };
+        for rotor in 0..2 {
+            self.speeds[rotor] += (targets[rotor] - self.speeds[rotor]) / (INERTIA[rotor] * self.sample_rate as f32);
+            self.angles[rotor] = (self.angles[rotor] + 2.0 * PI * self.speeds[rotor] / self.sample_rate as f32).rem_euclid(2.0 * PI);
+        }
+        let depth = self.settings.depth.clamp(0.0, 1.0);
+
+        let mono = (input[0] + input[1]) * 0.5;
+        let drum = self.crossover.process(mono);
+        self.horn.write(mono - drum);
+
+        // Facing one microphone the horn is nearest the other's side, so the two swing opposite ways
+        let [drum_angle, horn_angle] = self.angles;
+        let swing = ms_to_samples(HORN_SWING_MS, self.sample_rate) * depth;
+        let wet = [1.0, -1.0].map(|side: f32| {
+            let horn = self.horn.read(1.0 + swing * (1.0 + side * horn_angle.cos())) * (1.0 + side * 0.5 * depth * horn_angle.sin());
+            let drum = drum * (1.0 + side * DRUM_TREMOLO * depth * drum_angle.sin());
+            horn + drum
+        });
+        apart(wet, self.settings.mix)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "fast" => self.settings.fast = value >= 0.5,
+            "depth" => self.settings.depth = value,
+            "mix" => self.settings.mix = value,
+            _ => return false,
+        }
+        true
+    }
+}
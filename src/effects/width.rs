@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ms_to_samples, Effect};
+use crate::delay_line::DelayLine;
+use crate::stereo::{Frame, LEFT, RIGHT};
+
+const MAX_HAAS_DELAY_MS: f32 = 40.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WidthSettings {
+    pub width: f32,       // 0.0 = mono, 1.0 = unchanged, up to 2.0 = exaggerated
+    pub haas_delay: f32,  // Milliseconds of delay for the Haas widener
+    pub haas_amount: f32, // 0.0 disables the Haas widener
+}
+
+impl Default for WidthSettings {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            haas_delay: 15.0,
+            haas_amount: 0.0,
+        }
+    }
+}
+
+impl WidthSettings {
+    // The settings `StereoWidener::set_param` accepts, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("width", self.width), ("haas_delay", self.haas_delay), ("haas_amount", self.haas_amount)]
+    }
+}
+
+// Mid/side width control plus a mono-safe Haas widener. A plain Haas effect delays
+// one channel, which comb-filters when the mix is summed to mono. Here the delayed
+// mid signal is added to the side channel instead: the left and right channels get
+// it with opposite polarity, so it cancels exactly in a mono sum.
+pub struct StereoWidener {
+    settings: WidthSettings,
+    haas_line: DelayLine,
+    haas_delay_samples: f32,
+    sample_rate: u32,
+}
+
+impl StereoWidener {
+    pub fn new(settings: &WidthSettings, sample_rate: u32) -> Self {
+        let haas_delay_ms = settings.haas_delay.clamp(1.0, MAX_HAAS_DELAY_MS);
+        Self {
+            settings: settings.clone(),
+            haas_line: DelayLine::new(ms_to_samples(MAX_HAAS_DELAY_MS, sample_rate) as usize),
+            haas_delay_samples: ms_to_samples(haas_delay_ms, sample_rate),
+            sample_rate,
+        }
+    }
+
+    fn is_neutral(&self) -> bool {
+        self.settings.width == 1.0 && self.settings.haas_amount == 0.0
+    }
+}
+
+impl Effect for StereoWidener {
+    fn process(&mut self, input: Frame) -> Frame {
+        if self.is_neutral() {
+            return input;
+        }
+
+        let mid = (input[LEFT] + input[RIGHT]) * 0.5;
+        let mut side = (input[LEFT] - input[RIGHT]) * 0.5 * self.settings.width.clamp(0.0, 2.0);
+
+        self.haas_line.write(mid);
+        side += self.haas_line.read(self.haas_delay_samples) * self.settings.haas_amount.clamp(0.0, 1.0);
+
+        [mid + side, mid - side]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "width" => self.settings.width = value,
+            "haas_delay" => {
+                self.settings.haas_delay = value;
+                self.haas_delay_samples = ms_to_samples(value.clamp(1.0, MAX_HAAS_DELAY_MS), self.sample_rate);
+            }
+            "haas_amount" => self.settings.haas_amount = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::clock::FrameClock;
+use crate::param_id::ParamId;
+use crate::preset::Preset;
+use crate::switch::build_presets;
+use crate::{SynthCommand, Synthesizer, DEFAULT_POLYPHONY};
+
+// The synth for embedding in another program, such as a game: `embed` gives a
+// `SynthSource` to hand to a rodio `Sink` or `OutputStreamHandle::play_raw`, and a
+// `SynthHandle` the program keeps to play it. Events can be played now or scheduled
+// for an exact frame of the output, counted from the first frame the source renders,
+// so music driven by game logic keeps time to the sample however late it's sent.
+//
+//     let (synth, source) = rodio_synth::embed::embed(44_100, &preset);
+//     handle.play_raw(source)?;
+//     let bar = synth.now() + 2 * 44_100;
+//     synth.schedule_at(bar, SynthEvent::NoteOn { note: 60, velocity: 100 });
+//     synth.schedule_at(bar + 22_050, SynthEvent::NoteOff(60));
+pub fn embed(sample_rate: u32, preset: &Preset) -> (SynthHandle, SynthSource) {
+    let (commands, receiver) = mpsc::channel();
+    let synth = Synthesizer::new(sample_rate, preset, build_presets(receiver, sample_rate, DEFAULT_POLYPHONY));
+    let handle = SynthHandle { commands, clock: synth.clock() };
+    (handle, SynthSource { synth, right: None })
+}
+
+// Something to play, now or at a given frame. Velocities are MIDI's, 1..127.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SynthEvent {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff(u8),
+    SetParam(ParamId, f32), // A path as `Synthesizer::set_param` takes
+    Command(SynthCommand), // Anything else the synth takes
+}
+
+impl SynthEvent {
+    // The command the synth plays it as. A note's velocity goes with it, so it changes
+    // nothing for notes from anywhere else.
+    pub fn command(self) -> SynthCommand {
+        match self {
+            SynthEvent::NoteOn { note, velocity } => SynthCommand::VelocityNoteOn(note, velocity.min(127) as f32 / 127.0),
+            SynthEvent::NoteOff(note) => SynthCommand::NoteOff(note),
+            SynthEvent::SetParam(id, value) => SynthCommand::SetParam(id, value),
+            SynthEvent::Command(command) => command,
+        }
+    }
+}
+
+// Plays the synth from any thread. Once the source has been dropped events go nowhere.
+#[derive(Clone)]
+pub struct SynthHandle {
+    commands: mpsc::Sender<SynthCommand>,
+    clock: FrameClock,
+}
+
+impl SynthHandle {
+    pub fn note_on(&self, note: u8, velocity: u8) {
+        self.send(None, SynthEvent::NoteOn { note, velocity });
+    }
+
+    pub fn note_off(&self, note: u8) {
+        self.send(None, SynthEvent::NoteOff(note));
+    }
+
+    // Fails, sending nothing, for a path that can't be a parameter (see `ParamId::new`)
+    pub fn set_param(&self, path: &str, value: f32) -> Result<(), String> {
+        self.send(None, SynthEvent::SetParam(ParamId::new(path)?, value));
+        Ok(())
+    }
+
+    // Plays `event` just before frame `frame` is rendered, or at once if that's passed.
+    // Events for the same frame play in the order they were scheduled.
+    pub fn schedule_at(&self, frame: u64, event: SynthEvent) {
+        self.send(Some(frame), event);
+    }
+
+    // Frames rendered so far, the time to schedule from. The output device plays them a
+    // buffer's length later, so events should be scheduled at least that far ahead.
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    fn send(&self, frame: Option<u64>, event: SynthEvent) {
+        let command = match frame {
+            Some(frame) => SynthCommand::At(frame, Box::new(event.command())),
+            None => event.command(),
+        };
+        let _ = self.commands.send(command);
+    }
+}
+
+// The synth as a rodio source of interleaved stereo samples, which never ends
+pub struct SynthSource {
+    synth: Synthesizer,
+    right: Option<f32>, // The second half of the frame last rendered
+}
+
+impl SynthSource {
+    // The synth itself, for settings the handle doesn't reach
+    pub fn synth(&mut self) -> &mut Synthesizer {
+        &mut self.synth
+    }
+}
+
+impl Iterator for SynthSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let [left, right] = self.synth.render_frame();
+        self.right = Some(right);
+        Some(left)
+    }
+}
+
+impl rodio::Source for SynthSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.synth.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
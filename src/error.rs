@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::{fmt, io};
+
+// Everything that stops the program, worded for whoever is running it. `main` prints
+// it and exits with a failure status instead of panicking with a backtrace.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("No default output device; connect one or pick one with --device (see `rodio-synth list-devices`)")]
+    NoOutputDevice,
+    #[error("No output device '{0}' (see `rodio-synth list-devices`)")]
+    UnknownDevice(String),
+    #[error("No default input device; connect one or pick one with --input (see `rodio-synth list-devices`)")]
+    NoInputDevice,
+    #[error("No input device '{0}' (see `rodio-synth list-devices`)")]
+    UnknownInputDevice(String),
+    #[error("{0}")]
+    Audio(String), // The backend's own message, with what was being done
+    #[error("Failed to {action} {}: {source}", path.display())]
+    File { action: &'static str, path: PathBuf, source: io::Error },
+    #[error("Failed to render to {}: {source}", path.display())]
+    Render { path: PathBuf, source: hound::Error },
+    #[error("Failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: hound::Error },
+    #[error("Failed to start {what}: {source}")]
+    Start { what: &'static str, source: io::Error },
+    #[cfg(all(feature = "evdev", target_os = "linux"))]
+    #[error("No keyboard readable in /dev/input (the user may need to be in the `input` group)")]
+    NoKeyboard,
+    #[cfg(any(feature = "tui", feature = "gui"))]
+    #[error("The {what} failed: {message}")]
+    Ui { what: &'static str, message: String },
+    #[cfg(feature = "midi")]
+    #[error("{0}")]
+    Midi(String),
+    #[cfg(feature = "midi")]
+    #[error("No MIDI {direction} port {} (available: {})", wanted.as_deref().map_or(String::new(), |wanted| format!("'{}'", wanted)), if available.is_empty() { "none".to_string() } else { available.join(", ") })]
+    NoMidiPort { direction: &'static str, wanted: Option<String>, available: Vec<String> },
+    #[error("The synth stopped taking commands")]
+    Disconnected,
+}
+
+impl Error {
+    // For `map_err` on reading or writing `path`, e.g. `Error::file("load preset", path)`
+    pub fn file(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| Error::File { action, path, source }
+    }
+
+    // For `map_err` on starting a server or thread
+    pub fn start(what: &'static str) -> impl FnOnce(io::Error) -> Self {
+        move |source| Error::Start { what, source }
+    }
+
+    // For `map_err` on the audio backends, whose errors differ in type but all print well
+    pub fn audio<E: fmt::Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| Error::Audio(format!("{}: {}", context, e))
+    }
+
+    // Like `audio`, for the MIDI input
+    #[cfg(feature = "midi")]
+    pub fn midi<E: fmt::Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| Error::Midi(format!("{}: {}", context, e))
+    }
+}
+
+// The synth's receiver only goes away on the way out, or if the audio thread died
+impl<T> From<mpsc::SendError<T>> for Error {
+    fn from(_: mpsc::SendError<T>) -> Self {
+        Error::Disconnected
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sequencer::Step;
+use crate::tempo::{NoteDivision, SharedTempo};
+use crate::SynthCommand;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EuclidTrack {
+    pub hits: u32,     // Number of notes spread as evenly as possible over the steps
+    pub steps: u32,    // Length of the track's cycle; tracks of different lengths drift against each other
+    pub rotation: u32, // Shifts the pattern later by this many steps
+    pub note: Step,    // Fixed note such as "C2" for a drum-like voice, or "-" to play the held notes
+}
+
+impl Default for EuclidTrack {
+    fn default() -> Self {
+        Self {
+            hits: 3,
+            steps: 8,
+            rotation: 0,
+            note: Step(None),
+        }
+    }
+}
+
+impl EuclidTrack {
+    // Whether step `index` of the cycle is a hit. Spacing the hits with integer division
+    // gives the same patterns as Bjorklund's algorithm, up to rotation.
+    pub fn is_hit(&self, index: usize) -> bool {
+        let steps = self.steps.max(1) as usize;
+        let hits = (self.hits as usize).min(steps);
+        let position = (index % steps + steps - self.rotation as usize % steps) % steps;
+        position * hits % steps < hits
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EuclidSettings {
+    pub enabled: bool,
+    pub rate: NoteDivision, // Length of each step
+    pub gate: f32,          // Fraction of the step each note is held for
+    pub tracks: Vec<EuclidTrack>,
+}
+
+impl Default for EuclidSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate: NoteDivision::SIXTEENTH,
+            gate: 0.5,
+            tracks: vec![EuclidTrack::default()],
+        }
+    }
+}
+
+// Runs the Euclidean rhythm generator on its own clock thread. Like the arpeggiator it
+// takes the keyboard's note commands as held notes and forwards everything else to
+// `output`. While any key is held, each track plays its pattern: tracks without a fixed
+// note retrigger the held notes, tracks with one play that note.
+pub fn spawn(settings: EuclidSettings, tempo: SharedTempo, output: mpsc::Sender<SynthCommand>) -> mpsc::Sender<SynthCommand> {
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+
+    thread::spawn(move || {
+        let gate = settings.gate.clamp(0.05, 1.0);
+        let mut held: Vec<u8> = Vec::new();
+        let mut step = 0;
+        let mut next_step = Instant::now();
+        let mut sounding: Vec<(u8, Instant)> = Vec::new(); // Notes playing and when their gates close
+
+        loop {
+            let now = Instant::now();
+            let gate_off = sounding.iter().map(|&(_, gate_off)| gate_off).min();
+            let timeout = match (held.is_empty(), gate_off) {
+                (true, None) => Duration::from_secs(1), // Nothing to play, just wait for keys
+                (true, Some(gate_off)) => gate_off.saturating_duration_since(now),
+                (false, gate_off) => gate_off.map_or(next_step, |gate_off| gate_off.min(next_step)).saturating_duration_since(now),
+            };
+
+            match rx.recv_timeout(timeout) {
+                Ok(SynthCommand::NoteOn(note)) => {
+                    if held.is_empty() {
+                        step = 0;
+                        next_step = tempo.first_step(Instant::now(), settings.rate.beats()); // Start every track together on the first key press
+                    }
+                    if !held.contains(&note) {
+                        held.push(note);
+                    }
+                }
+                Ok(SynthCommand::NoteOff(note)) => held.retain(|&held| held != note),
+                Ok(SynthCommand::Panic) => {
+                    held.clear(); // The synth silences the sounding notes itself
+                    sounding.clear();
+                    if output.send(SynthCommand::Panic).is_err() {
+                        return;
+                    }
+                }
+                Ok(command) => {
+                    if output.send(command).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            let mut closed = Vec::new();
+            sounding.retain(|&(note, gate_off)| {
+                let open = now < gate_off && !held.is_empty();
+                if !open {
+                    closed.push(note);
+                }
+                open
+            });
+            for note in closed {
+                if output.send(SynthCommand::NoteOff(note)).is_err() {
+                    return;
+                }
+            }
+
+            if now >= next_step && !held.is_empty() {
+                let step_length = Duration::from_secs_f32(settings.rate.seconds(tempo.get()));
+                let mut notes: Vec<u8> = Vec::new();
+                for track in settings.tracks.iter().filter(|track| track.is_hit(step)) {
+                    match track.note {
+                        Step(Some(note)) => notes.push(note),
+                        Step(None) => notes.extend_from_slice(&held),
+                    }
+                }
+                notes.sort_unstable();
+                notes.dedup();
+
+                for note in notes {
+                    // Retriggering a note that is still sounding closes its old gate first
+                    if let Some(index) = sounding.iter().position(|&(sounding, _)| sounding == note) {
+                        sounding.swap_remove(index);
+                        let _ = output.send(SynthCommand::NoteOff(note));
+                    }
+                    if output.send(SynthCommand::NoteOn(note)).is_err() {
+                        return;
+                    }
+                    sounding.push((note, now + step_length.mul_f32(gate)));
+                }
+
+                step += 1;
+                next_step = tempo.step_after(next_step, settings.rate.beats());
+                if next_step < now {
+                    next_step = tempo.step_after(now, settings.rate.beats());
+                }
+            }
+        }
+    });
+
+    tx
+}
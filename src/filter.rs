@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+use crate::biquad::Biquad;
+use crate::lfo::LfoShape;
+use crate::tempo::Rate;
+
+// Frequency at which key tracking leaves the cutoff where it is (C4)
+const TRACKING_CENTRE: f32 = 261.63;
+
+// A resonant low-pass filter in every voice. With key tracking the cutoff follows the
+// note: at 1.0 it moves an octave for every octave played, so the filter colours every
+// note alike; at 0.0 it stays put, and high notes come out dull and low ones buzzy.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    pub enabled: bool,
+    pub cutoff: f32,       // Hz, for notes around C4
+    pub resonance: f32,    // Q; 0.707 has no peak, higher values ring at the cutoff
+    pub key_tracking: f32, // 0.0..1.0, how closely the cutoff follows the note's pitch
+    pub lfo_rate: Rate,    // In Hz or as a tempo division
+    pub lfo_depth: f32,    // Octaves the LFO moves the cutoff either way, 0.0 for none
+    pub lfo_shape: LfoShape,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cutoff: 2000.0,
+            resonance: std::f32::consts::FRAC_1_SQRT_2,
+            key_tracking: 0.5,
+            lfo_rate: Rate::Hz(4.0),
+            lfo_depth: 0.0,
+            lfo_shape: LfoShape::SampleAndHold,
+        }
+    }
+}
+
+impl FilterSettings {
+    // The settings the synth's "filter.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params = vec![("cutoff", self.cutoff), ("resonance", self.resonance), ("key_tracking", self.key_tracking)];
+        params.extend(self.lfo_rate.editable_param("lfo_rate"));
+        params.push(("lfo_depth", self.lfo_depth));
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "cutoff" => self.cutoff = value.clamp(20.0, 20_000.0),
+            "resonance" => self.resonance = value.clamp(0.1, 20.0),
+            "key_tracking" => self.key_tracking = value.clamp(0.0, 1.0),
+            "lfo_rate" => self.lfo_rate = Rate::Hz(value.max(0.0)),
+            "lfo_depth" => self.lfo_depth = value.clamp(0.0, 5.0),
+            _ => return false,
+        }
+        true
+    }
+
+    // The cutoff for a note at `frequency`, after key tracking
+    pub fn cutoff_for(&self, frequency: f32) -> f32 {
+        self.cutoff * (frequency / TRACKING_CENTRE).powf(self.key_tracking)
+    }
+
+    // The filter for sound with no note of its own, such as live input, at the plain cutoff
+    pub fn untracked_biquad(&self, octaves: f32, sample_rate: u32) -> Biquad {
+        self.biquad(TRACKING_CENTRE, octaves, sample_rate)
+    }
+
+    // The filter for a voice playing at `frequency`, with the LFO `octaves` away from the centre
+    pub fn biquad(&self, frequency: f32, octaves: f32, sample_rate: u32) -> Biquad {
+        Biquad::low_pass(self.cutoff_for(frequency) * 2.0_f32.powf(octaves), self.resonance, sample_rate)
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use crate::delay_line::DelayLine;
+
+const LOWEST_FREQUENCY: f32 = 20.0; // Hz; the bore is made long enough for this
+const OVERBLOW: f32 = 2.0 / 3.0; // The bore is tuned below the note and blown up to it, as in the STK
+const JET_RATIO: f32 = 0.32; // Length of the jet against the bore; shorter overblows to higher modes
+const REFLECTION: f32 = 0.5; // Of the bore's returning wave, into the jet and back down the bore
+const DC_POLE: f32 = 0.995;
+const OUTPUT_GAIN: f32 = 0.6;
+
+// A physical model of a blown pipe in place of the oscillators: every voice is a bore
+// (a delay line a period long) driven by a jet of breath, with noise in the breath and
+// a low-pass where the wave reflects off the open end. The envelope is the player's
+// breath, so a slow attack gives the breathy swell of a real flute. Like a real one it
+// needs enough breath to speak at all, and only breathes and sputters below that.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FluteSettings {
+    pub enabled: bool,
+    pub pressure: f32, // Breath at the envelope's full level, 0.0..1.5; the pipe only sounds above about 0.8
+    pub noise: f32,    // Breath noise, 0.0..1.0 of the pressure
+    pub damping: f32,  // 0.0..0.95, how much the end reflection darkens the tone and shortens its ring
+}
+
+impl Default for FluteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pressure: 0.9,
+            noise: 0.15,
+            damping: 0.7,
+        }
+    }
+}
+
+impl FluteSettings {
+    // The settings the synth's "flute.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("pressure", self.pressure), ("noise", self.noise), ("damping", self.damping)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "pressure" => self.pressure = value.clamp(0.0, 1.5),
+            "noise" => self.noise = value.clamp(0.0, 1.0),
+            "damping" => self.damping = value.clamp(0.0, 0.95),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// One voice's pipe, after Cook's STK flute
+pub struct Flute {
+    bore: DelayLine,
+    jet: DelayLine,
+    reflection: f32, // State of the end reflection's low-pass
+    dc: (f32, f32),  // Last input and output of the DC blocker in the bore
+    random: u32,     // Xorshift state for the breath noise
+}
+
+impl Flute {
+    pub fn new(sample_rate: u32, seed: u32) -> Self {
+        let longest = (sample_rate as f32 / (LOWEST_FREQUENCY * OVERBLOW)) as usize;
+        Self {
+            bore: DelayLine::new(longest),
+            jet: DelayLine::new((longest as f32 * JET_RATIO) as usize + 1),
+            reflection: 0.0,
+            dc: (0.0, 0.0),
+            random: seed | 1, // Never zero, which xorshift can't leave
+        }
+    }
+
+    // Empties the pipe for a new note, its breath noise starting from `seed`
+    pub fn reset(&mut self, seed: u32) {
+        self.bore.clear();
+        self.jet.clear();
+        self.reflection = 0.0;
+        self.dc = (0.0, 0.0);
+        self.random = seed | 1;
+    }
+
+    // The next sample for a pipe sounding `frequency`, blown with `breath` (the pressure
+    // scaled by the envelope)
+    pub fn next_sample(&mut self, frequency: f32, breath: f32, settings: &FluteSettings, sample_rate: u32) -> f32 {
+        // Less the sample each of the reflection filter and the bore's own read take
+        let length = sample_rate as f32 / (frequency.max(LOWEST_FREQUENCY) * OVERBLOW) - 2.0;
+        let breath = breath * (1.0 + settings.noise * self.next_noise());
+
+        // The wave coming back up the bore, darkened and inverted at the open end
+        let returning = self.bore.read(length);
+        self.reflection = returning * (1.0 - settings.damping) + self.reflection * settings.damping;
+        let reflected = self.block_dc(-self.reflection);
+
+        // The jet of breath across the embouchure, bent by the returning wave
+        self.jet.write(breath - REFLECTION * reflected);
+        let jet = self.jet.read(length * JET_RATIO);
+        let blown = (jet * (jet * jet - 1.0)).clamp(-1.0, 1.0);
+        self.bore.write(blown + REFLECTION * reflected);
+        returning * OUTPUT_GAIN
+    }
+
+    fn block_dc(&mut self, sample: f32) -> f32 {
+        let output = sample - self.dc.0 + DC_POLE * self.dc.1;
+        self.dc = (sample, output);
+        output
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
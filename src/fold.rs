@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+// A West-coast style wavefolder after the oscillator: the wave is driven past full scale
+// and every peak that overshoots is reflected back down, so even a sine grows a rich,
+// bright set of harmonics as the drive goes up. The envelope can add drive, making notes
+// brighter as they open and mellower as they die away.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FoldSettings {
+    pub enabled: bool,
+    pub amount: f32,   // Extra gain into the folder: 0.0 leaves the wave alone, each 1.0 adds about one more fold
+    pub envelope: f32, // Amount added at the envelope's full level
+}
+
+impl Default for FoldSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amount: 1.0,
+            envelope: 0.0,
+        }
+    }
+}
+
+impl FoldSettings {
+    // The settings the synth's "fold.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("amount", self.amount), ("envelope", self.envelope)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "amount" => self.amount = value.clamp(0.0, 10.0),
+            "envelope" => self.envelope = value.clamp(-10.0, 10.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Folds `sample` driven by 1.0 + `amount` back into -1.0..1.0, like a triangle wave of the input
+pub fn fold(sample: f32, amount: f32) -> f32 {
+    let driven = sample * (1.0 + amount.max(0.0));
+    ((driven - 1.0).rem_euclid(4.0) - 2.0).abs() - 1.0
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use crate::biquad::Biquad;
+use crate::lfo::LfoShape;
+use crate::tempo::Rate;
+
+// The first three formants of each vowel, as (frequency in Hz, gain in dB, bandwidth in
+// Hz), for a low adult voice
+const VOWELS: [[(f32, f32, f32); 3]; 5] = [
+    [(800.0, 0.0, 80.0), (1150.0, -6.0, 90.0), (2900.0, -32.0, 120.0)],  // A
+    [(400.0, 0.0, 70.0), (1600.0, -24.0, 80.0), (2700.0, -30.0, 100.0)], // E
+    [(350.0, 0.0, 50.0), (1700.0, -20.0, 100.0), (2700.0, -30.0, 120.0)], // I
+    [(450.0, 0.0, 70.0), (800.0, -11.0, 80.0), (2830.0, -22.0, 100.0)],  // O
+    [(325.0, 0.0, 50.0), (700.0, -16.0, 60.0), (2530.0, -35.0, 170.0)],  // U
+];
+const MAKEUP: f32 = 4.0; // The narrow bands pass little of a wave, so the vowel is brought back up
+
+// A bank of band-pass filters in every voice, tuned to the formants of a vowel, so the
+// synth seems to sing. The vowel position moves smoothly A → E → I → O → U; an LFO
+// can sweep it and each voice's envelope can move it as the note opens, for wah-like
+// "yeah" and "wow" sounds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormantSettings {
+    pub enabled: bool,
+    pub vowel: f32,     // 0.0 A, 1.0 E, 2.0 I, 3.0 O, 4.0 U, blends in between
+    pub lfo_rate: Rate, // In Hz or as a tempo division
+    pub lfo_depth: f32, // How far the LFO moves the vowel either way, 0.0 for no sweep
+    pub lfo_shape: LfoShape,
+    pub envelope: f32,  // How far the voice's envelope moves the vowel at full level; negative moves it back
+}
+
+impl Default for FormantSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vowel: 0.0,
+            lfo_rate: Rate::Hz(0.5),
+            lfo_depth: 0.0,
+            lfo_shape: LfoShape::Sine,
+            envelope: 0.0,
+        }
+    }
+}
+
+impl FormantSettings {
+    // The settings the synth's "formant.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params = vec![("vowel", self.vowel)];
+        params.extend(self.lfo_rate.editable_param("lfo_rate"));
+        params.extend([("lfo_depth", self.lfo_depth), ("envelope", self.envelope)]);
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "vowel" => self.vowel = value.clamp(0.0, 4.0),
+            "lfo_rate" => self.lfo_rate = Rate::Hz(value.max(0.0)),
+            "lfo_depth" => self.lfo_depth = value.clamp(0.0, 4.0),
+            "envelope" => self.envelope = value.clamp(-4.0, 4.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// One voice's formant filters, run side by side and summed
+pub struct FormantFilter {
+    bands: [Biquad; 3],
+    gains: [f32; 3],
+}
+
+impl FormantFilter {
+    // Filters tuned to `vowel` (0.0 to 4.0)
+    pub fn new(vowel: f32, sample_rate: u32) -> Self {
+        let mut filter = Self { bands: [Biquad::identity(); 3], gains: [0.0; 3] };
+        filter.tune(vowel, sample_rate);
+        filter
+    }
+
+    // Moves the formants to `vowel`, keeping the filters' state so a sweep doesn't click
+    pub fn tune(&mut self, vowel: f32, sample_rate: u32) {
+        let vowel = vowel.clamp(0.0, 4.0);
+        let index = (vowel as usize).min(VOWELS.len() - 2);
+        let amount = vowel - index as f32;
+        for (formant, (band, gain)) in self.bands.iter_mut().zip(&mut self.gains).enumerate() {
+            let (from, to) = (VOWELS[index][formant], VOWELS[index + 1][formant]);
+            let blend = |a: f32, b: f32| a + (b - a) * amount;
+            let (frequency, decibels, bandwidth) = (blend(from.0, to.0), blend(from.1, to.1), blend(from.2, to.2));
+            band.set_coefficients(&Biquad::band_pass(frequency, frequency / bandwidth, sample_rate));
+            *gain = 10.0_f32.powf(decibels / 20.0) * MAKEUP;
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.bands.iter_mut().zip(&self.gains).map(|(band, gain)| band.process(sample) * gain).sum()
+    }
+}
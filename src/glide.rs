@@ -0,0 +1,112 @@
+// Parameter changes that arrive in steps (mouse movements, pixel by pixel, or a UI
+// slider sending a value per redraw) are smoothed on the audio thread so they don't
+// zipper. Values move towards their target every `BLOCK` frames instead of every
+// frame, since some parameters recalculate filter coefficients when set.
+
+use serde::{Deserialize, Serialize};
+
+const BLOCK: u32 = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingCurve {
+    #[default]
+    OnePole, // Fast at first and easing in, about two thirds of the way after `time`
+    Linear,  // A straight ramp that arrives after exactly `time`
+}
+
+// How edits of the parameters that zipper audibly are smoothed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmoothingSettings {
+    pub curve: SmoothingCurve,
+    pub time: f32, // Milliseconds; 0 sets edits straight away
+}
+
+impl Default for SmoothingSettings {
+    fn default() -> Self {
+        Self {
+            curve: SmoothingCurve::OnePole,
+            time: 20.0,
+        }
+    }
+}
+
+impl SmoothingSettings {
+    // Whether a `SetParam` for `path` glides rather than jumps: filter cutoffs, volumes,
+    // pans, pitch bend, the pitch wheel and detune, wherever they are in the synth
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.time > 0.0 && matches!(path.rsplit('.').next(), Some("cutoff" | "volume" | "pan" | "bend" | "wheel" | "detune"))
+    }
+}
+
+struct Glide {
+    path: String,
+    value: f32,
+    target: f32,
+    step: f32, // Distance moved each block on a linear ramp
+}
+
+#[derive(Default)]
+pub struct Glides {
+    glides: Vec<Glide>,
+    curve: SmoothingCurve,
+    coefficient: f32, // Fraction of the remaining distance covered each block
+    blocks: f32,      // Blocks a linear ramp takes
+    countdown: u32,
+}
+
+impl Glides {
+    pub fn new(sample_rate: u32, settings: &SmoothingSettings) -> Self {
+        let blocks = settings.time.max(0.0) / 1000.0 * sample_rate as f32 / BLOCK as f32;
+        Self {
+            glides: Vec::new(),
+            curve: settings.curve,
+            coefficient: 1.0 - (-1.0 / blocks).exp(),
+            blocks: blocks.max(1.0),
+            countdown: 0,
+        }
+    }
+
+    // Glides to `target`, or returns false for a parameter not recorded with `start_at`,
+    // which the caller then sets straight away. Nothing is allocated, as this runs on the
+    // audio thread.
+    pub fn set(&mut self, path: &str, target: f32) -> bool {
+        let Some(glide) = self.glides.iter_mut().find(|glide| glide.path == path) else { return false };
+        glide.target = target;
+        glide.step = (target - glide.value) / self.blocks;
+        true
+    }
+
+    // Records where a parameter is, so that its next edit glides from there instead of
+    // jumping. Every parameter that may glide is recorded when the preset is built.
+    pub fn start_at(&mut self, path: &str, value: f32) {
+        match self.glides.iter_mut().find(|glide| glide.path == path) {
+            Some(glide) => (glide.value, glide.target, glide.step) = (value, value, 0.0),
+            None => self.glides.push(Glide { path: path.to_string(), value, target: value, step: 0.0 }),
+        }
+    }
+
+    // Called once per frame; hands `apply` each parameter that moved this block
+    pub fn advance(&mut self, mut apply: impl FnMut(&str, f32)) {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return;
+        }
+        self.countdown = BLOCK - 1;
+
+        for glide in &mut self.glides {
+            if glide.value == glide.target {
+                continue;
+            }
+            let remaining = glide.target - glide.value;
+            glide.value = match self.curve {
+                _ if remaining.abs() < 1e-4 * glide.target.abs().max(1.0) => glide.target,
+                SmoothingCurve::OnePole => glide.value + remaining * self.coefficient,
+                SmoothingCurve::Linear if remaining.abs() <= glide.step.abs() => glide.target,
+                SmoothingCurve::Linear => glide.value + glide.step,
+            };
+            apply(&glide.path, glide.value);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use eframe::egui;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::cpu_meter::CpuMeter;
+use crate::effects::gain_to_db;
+use crate::notes::{note_name, NoteSet};
+use crate::params::{ParamRange, ParamStore};
+use crate::preset::Preset;
+use crate::scope::{ScopeTap, SCOPE_WIDTH};
+use crate::shutdown::Shutdown;
+use crate::snapshot::{Snapshot, SNAPSHOTS_PER_SECOND};
+use crate::spectrum::{Spectrum, BANDS, FLOOR_DB};
+use crate::stereo::{Frame, LEFT, RIGHT};
+use crate::xrun::XrunMonitor;
+
+// Graphical front end: a slider for every live parameter, grouped by section, plus
+// the held notes and output meters. Notes are still played on the computer keyboard.
+struct GuiApp {
+    preset_name: String,
+    effect_names: Vec<&'static str>, // For the section headings of the effect slots
+    params: ParamStore,
+    snapshots: mpsc::Receiver<Snapshot>,
+    notes: NoteSet,
+    levels: Frame,
+    rms: Frame,
+    clips: u64,
+    xruns: XrunMonitor,
+    cpu: CpuMeter,
+    scope: ScopeTap,
+    spectrum: Spectrum,
+    shutdown: Shutdown, // Closes the window on Ctrl-C or SIGTERM
+}
+
+// Opens the window on the calling thread and returns when it is closed
+pub fn run(
+    preset: &Preset,
+    params: ParamStore,
+    snapshots: mpsc::Receiver<Snapshot>,
+    scope: ScopeTap,
+    xruns: XrunMonitor,
+    cpu: CpuMeter,
+    shutdown: Shutdown,
+) -> eframe::Result<()> {
+    let app = GuiApp {
+        preset_name: if preset.name.is_empty() { "(unnamed)".to_string() } else { preset.name.clone() },
+        effect_names: preset.effects.iter().map(|effect| effect.name()).collect(),
+        params,
+        snapshots,
+        notes: NoteSet::default(),
+        levels: [0.0; 2],
+        rms: [0.0; 2],
+        clips: 0,
+        xruns,
+        cpu,
+        spectrum: Spectrum::new(scope.sample_rate()),
+        scope,
+        shutdown,
+    };
+    eframe::run_native("rodio-synth", eframe::NativeOptions::default(), Box::new(|_| Ok(Box::new(app))))
+}
+
+impl GuiApp {
+    // "effects.1.mix" belongs to the "Effect 2: flanger" section, "eq.low_gain" to "eq"
+    fn section(&self, path: &str) -> String {
+        let (prefix, _) = path.rsplit_once('.').unwrap_or(("", path));
+        match prefix.strip_prefix("effects.").and_then(|slot| slot.parse::<usize>().ok()) {
+            Some(slot) => format!("Effect {}: {}", slot + 1, self.effect_names.get(slot).unwrap_or(&"?")),
+            None => prefix.to_string(),
+        }
+    }
+
+    fn draw_scope(&self, ui: &mut egui::Ui) {
+        let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width(), 120.0), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, egui::Color32::from_gray(20));
+        let points: Vec<egui::Pos2> = self
+            .scope
+            .triggered()
+            .iter()
+            .enumerate()
+            .map(|(i, &sample)| {
+                let x = rect.left() + rect.width() * i as f32 / SCOPE_WIDTH as f32;
+                let y = rect.center().y - rect.height() * 0.5 * sample.clamp(-1.0, 1.0);
+                egui::pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, egui::Color32::LIGHT_GREEN)));
+    }
+
+    // Log frequency across, dB up, with the top of the box at 0 dB
+    fn draw_spectrum(&mut self, ui: &mut egui::Ui) {
+        let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width(), 120.0), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, egui::Color32::from_gray(20));
+        let points: Vec<egui::Pos2> = self
+            .spectrum
+            .bands(&self.scope)
+            .iter()
+            .enumerate()
+            .map(|(band, &level)| {
+                let x = rect.left() + rect.width() * band as f32 / (BANDS - 1) as f32;
+                let y = rect.top() + rect.height() * level / FLOOR_DB;
+                egui::pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, egui::Color32::LIGHT_BLUE)));
+    }
+}
+
+impl eframe::App for GuiApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.shutdown.requested() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        while let Ok(snapshot) = self.snapshots.try_recv() {
+            self.notes = snapshot.notes;
+            self.levels = snapshot.peak;
+            self.rms = snapshot.rms;
+            self.clips = snapshot.clips;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(&self.preset_name);
+            let held: Vec<String> = self.notes.iter().map(note_name).collect();
+            ui.label(format!("Notes: {}", held.join(" ")));
+            for channel in [LEFT, RIGHT] {
+                let (peak, rms) = (self.levels[channel], self.rms[channel]);
+                let label = if channel == LEFT { "L" } else { "R" };
+                let text = format!("{} peak {:.1} dB  rms {:.1} dB", label, gain_to_db(peak), gain_to_db(rms));
+                let fill = if peak >= 1.0 { egui::Color32::RED } else { egui::Color32::DARK_GREEN };
+                ui.add(egui::ProgressBar::new(peak.clamp(0.0, 1.0)).text(text).fill(fill));
+            }
+            if self.clips > 0 {
+                ui.colored_label(egui::Color32::RED, format!("Clipped {} samples", self.clips));
+            }
+            if self.xruns.count() > 0 {
+                ui.colored_label(egui::Color32::YELLOW, self.xruns.summary());
+            }
+            let cpu_color = if self.cpu.is_high() { egui::Color32::RED } else { ui.visuals().text_color() };
+            ui.colored_label(cpu_color, self.cpu.summary());
+            self.draw_scope(ui);
+            self.draw_spectrum(ui);
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let values = self.params.values();
+                let mut current_section = String::new();
+                for (path, mut value) in values {
+                    let section = self.section(&path);
+                    if section != current_section {
+                        ui.strong(&section);
+                        current_section = section;
+                    }
+                    let range = ParamRange::of(&path);
+                    let name = path.rsplit('.').next().unwrap_or(&path);
+                    let slider = egui::Slider::new(&mut value, range.min..=range.max)
+                        .text(name)
+                        .logarithmic(range.logarithmic);
+                    if ui.add(slider).changed() {
+                        self.params.set(&path, value);
+                    }
+                }
+            });
+        });
+
+        // Keep the meters moving even when the mouse is still
+        ctx.request_repaint_after(Duration::from_millis(1000 / SNAPSHOTS_PER_SECOND as u64));
+    }
+}
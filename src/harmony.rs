@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::notes::NoteSet;
+use crate::scale::ScaleSettings;
+
+// Harmonizer: every played note (or every note of the chord it plays) brings in more
+// voices at fixed intervals above or below it. Diatonic harmonies move each added note
+// to the nearest note of the preset's scale, whether or not played notes are snapped to
+// it, so a third above follows the key rather than always being major.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarmonySettings {
+    pub enabled: bool,
+    pub intervals: Vec<i8>, // Semitones from the played note, e.g. 7 for a fifth above or -12 for an octave below
+    pub diatonic: bool,     // Keep the added notes in the scale
+    pub level: f32,         // Velocity of the added voices against the played one's, 0.0..1.0
+}
+
+impl Default for HarmonySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intervals: vec![7, 12],
+            diatonic: false,
+            level: 0.8,
+        }
+    }
+}
+
+impl HarmonySettings {
+    // The notes added to `notes`, skipping any that fall outside the MIDI range
+    pub fn expand(&self, notes: NoteSet, scale: &ScaleSettings) -> NoteSet {
+        notes
+            .iter()
+            .flat_map(|note| self.intervals.iter().map(move |&interval| note as i16 + interval as i16))
+            .filter_map(|note| u8::try_from(note).ok().filter(|&note| note <= 127))
+            .map(|note| if self.diatonic { scale.quantize(note) } else { note })
+            .collect()
+    }
+}
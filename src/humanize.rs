@@ -0,0 +1,78 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::SynthCommand;
+
+// Small random imperfections in the notes the arpeggiator and the sequencer play, so
+// their patterns feel played rather than programmed. With a seed the same imperfections
+// come out every time, for takes that can be repeated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HumanizeSettings {
+    pub timing: f32,       // Milliseconds a note may land behind the beat, 0.0 for none
+    pub velocity: f32,     // How far a note's velocity may stray either way, 0.0..1.0
+    pub pitch: f32,        // Cents a note may drift either way, 0.0 for none
+    pub seed: Option<u64>, // Different every run without one
+}
+
+impl Default for HumanizeSettings {
+    fn default() -> Self {
+        Self {
+            timing: 0.0,
+            velocity: 0.0,
+            pitch: 0.0,
+            seed: None,
+        }
+    }
+}
+
+pub struct Humanizer {
+    settings: HumanizeSettings,
+    rng: StdRng,
+}
+
+impl Humanizer {
+    pub fn new(settings: HumanizeSettings) -> Self {
+        let rng = match settings.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { settings, rng }
+    }
+
+    // How late the next note comes in
+    pub fn delay(&mut self) -> Duration {
+        let ms = self.settings.timing.clamp(0.0, 100.0);
+        if ms <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f32(self.rng.gen_range(0.0..ms) / 1000.0)
+    }
+
+    // What to send just before a note, which would otherwise play at `velocity`
+    pub fn before_note(&mut self, velocity: f32) -> Option<SynthCommand> {
+        let spread = self.settings.velocity.clamp(0.0, 1.0);
+        if spread <= 0.0 {
+            return None;
+        }
+        let varied = velocity + self.rng.gen_range(-spread..spread);
+        Some(SynthCommand::SetVelocity(varied.clamp(0.05, 1.0)))
+    }
+
+    // The note itself, detuned on its own when the pitch strays, leaving other notes be
+    pub fn note_on(&mut self, note: u8) -> SynthCommand {
+        let cents = self.settings.pitch.clamp(0.0, 100.0);
+        if cents <= 0.0 {
+            return SynthCommand::NoteOn(note);
+        }
+        SynthCommand::DetunedNoteOn(note, self.rng.gen_range(-cents..cents) / 100.0)
+    }
+
+    // What to send after the note has started, to put the velocity back to `velocity`
+    // for the notes played alongside
+    pub fn after_note(&self, velocity: f32) -> Option<SynthCommand> {
+        (self.settings.velocity > 0.0).then_some(SynthCommand::SetVelocity(velocity))
+    }
+}
@@ -0,0 +1,153 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::SynthCommand;
+
+pub const DEFAULT_JAM_PORT: u16 = 57_400;
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+const JITTER_MARGIN: Duration = Duration::from_millis(10); // Added to the measured latency so late packets still land in time
+const MAX_DELAY: Duration = Duration::from_millis(500);
+
+pub struct JamSettings {
+    pub peer: SocketAddr,
+    pub port: u16,               // Local UDP port, the one the peer sends to
+    pub delay: Option<Duration>, // Fixed playout delay for the peer's notes, measured if `None`
+}
+
+// What is known about the peer's clock, from ping round trips. Timestamps are in
+// microseconds since the Unix epoch on each machine; the clocks needn't agree.
+#[derive(Clone, Copy, Default)]
+struct ClockSync {
+    offset: i64,             // Peer clock minus local clock
+    round_trip: Option<u64>, // Best round trip seen, the one with the least queueing
+}
+
+// Peer mode: this instance and one other exchange note events over UDP, each stamped
+// with the time it was played. The peer's notes are played here at a constant delay
+// after they were played there, so network jitter doesn't smear their timing. The
+// delay is half the best round trip plus a margin, or `delay` when given.
+//
+// Notes sent to the returned sender are played locally at once, forwarded to
+// `output`, and sent to the peer. Notes from the peer go to `synth`, the synth's own
+// channel, so they aren't arpeggiated a second time.
+pub fn spawn(
+    settings: JamSettings,
+    output: mpsc::Sender<SynthCommand>,
+    synth: mpsc::Sender<SynthCommand>,
+) -> io::Result<mpsc::Sender<SynthCommand>> {
+    let socket = UdpSocket::bind(("0.0.0.0", settings.port))?;
+    let receiver = socket.try_clone()?;
+    let sync = Arc::new(Mutex::new(ClockSync::default()));
+    println!("Jamming with {} from UDP port {}", settings.peer, settings.port);
+
+    // Incoming: answer pings, learn from pongs and schedule the peer's notes
+    let (schedule_tx, schedule_rx) = mpsc::channel::<(Instant, SynthCommand)>();
+    thread::spawn({
+        let sync = sync.clone();
+        let peer = settings.peer;
+        let fixed_delay = settings.delay;
+        move || {
+            let mut buffer = [0; 512];
+            loop {
+                let Ok((length, sender)) = receiver.recv_from(&mut buffer) else { continue };
+                if sender.ip() != peer.ip() {
+                    continue;
+                }
+                let Ok(text) = std::str::from_utf8(&buffer[..length]) else { continue };
+                let fields: Vec<&str> = text.split_whitespace().collect();
+                match fields.as_slice() {
+                    ["ping", sent] => {
+                        let _ = receiver.send_to(format!("pong {} {}", sent, now_micros()).as_bytes(), peer);
+                    }
+                    ["pong", sent, answered] => {
+                        let (Ok(sent), Ok(answered)) = (sent.parse::<u64>(), answered.parse::<u64>()) else { continue };
+                        let round_trip = now_micros().saturating_sub(sent);
+                        let mut sync = sync.lock().unwrap();
+                        if sync.round_trip.is_none_or(|best| round_trip < best) {
+                            // The peer answered halfway through the round trip
+                            sync.offset = answered as i64 - (sent + round_trip / 2) as i64;
+                            sync.round_trip = Some(round_trip);
+                            println!("Jam latency {:.1} ms", round_trip as f32 / 2000.0);
+                        }
+                    }
+                    [kind @ ("on" | "off"), note, played] => {
+                        let (Ok(note), Ok(played)) = (note.parse::<u8>(), played.parse::<i64>()) else { continue };
+                        let sync = *sync.lock().unwrap();
+                        let delay = fixed_delay.unwrap_or_else(|| {
+                            let one_way = Duration::from_micros(sync.round_trip.unwrap_or(0) / 2);
+                            (one_way + JITTER_MARGIN).min(MAX_DELAY)
+                        });
+                        // How long ago the note was played, in local time
+                        let age = Duration::from_micros((now_micros() as i64 - (played - sync.offset)).max(0) as u64);
+                        let due = Instant::now() + delay.saturating_sub(age);
+                        let command = if *kind == "on" { SynthCommand::NoteOn(note) } else { SynthCommand::NoteOff(note) };
+                        if schedule_tx.send((due, command)).is_err() {
+                            return;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    });
+
+    // Plays the peer's notes when they fall due
+    thread::spawn(move || {
+        let mut pending: Vec<(Instant, SynthCommand)> = Vec::new();
+        loop {
+            let next_due = pending.iter().map(|&(due, _)| due).min();
+            let timeout = next_due.map_or(Duration::from_secs(1), |due| due.saturating_duration_since(Instant::now()));
+            match schedule_rx.recv_timeout(timeout) {
+                Ok(event) => pending.push(event),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let now = Instant::now();
+            pending.sort_by_key(|&(due, _)| due);
+            while pending.first().is_some_and(|&(due, _)| due <= now) {
+                let (_, command) = pending.remove(0);
+                if synth.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    // Outgoing: local notes to the peer, plus a ping every second to track the latency
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    thread::spawn(move || {
+        let mut next_ping = Instant::now();
+        loop {
+            if Instant::now() >= next_ping {
+                let _ = socket.send_to(format!("ping {}", now_micros()).as_bytes(), settings.peer);
+                next_ping += PING_INTERVAL;
+            }
+            let command = match rx.recv_timeout(next_ping.saturating_duration_since(Instant::now())) {
+                Ok(command) => command,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            let message = match command {
+                SynthCommand::NoteOn(note) => Some(format!("on {} {}", note, now_micros())),
+                SynthCommand::NoteOff(note) => Some(format!("off {} {}", note, now_micros())),
+                _ => None,
+            };
+            if let Some(message) = message {
+                let _ = socket.send_to(message.as_bytes(), settings.peer);
+            }
+            if output.send(command).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(tx)
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_micros() as u64)
+}
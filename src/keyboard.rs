@@ -0,0 +1,180 @@
+use device_query::{DeviceQuery, DeviceState, Keycode};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Error;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1); // How often device_query is asked for the keys
+
+// Where key presses are read from. device_query asks the X server (or the Windows
+// and macOS equivalents), so on Linux it needs X11; evdev reads the keyboards in
+// /dev/input directly, which also works under Wayland and on a bare console but
+// needs read access to the devices (usually membership of the `input` group).
+// device_query can only be polled; evdev delivers events, so the input thread sleeps
+// until a key actually changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyboardBackend {
+    Auto, // evdev if built with it and a keyboard is readable, device_query otherwise
+    DeviceQuery,
+    #[cfg(all(feature = "evdev", target_os = "linux"))]
+    Evdev,
+}
+
+pub enum Keyboard {
+    DeviceQuery(DeviceState),
+    #[cfg(all(feature = "evdev", target_os = "linux"))]
+    Evdev {
+        events: std::sync::mpsc::Receiver<(Keycode, bool)>, // Key and whether it went down, from every keyboard
+        held: Vec<Keycode>,
+    },
+}
+
+impl Keyboard {
+    pub fn open(backend: KeyboardBackend) -> Result<Self, Error> {
+        Ok(match backend {
+            // Without an X display polling can't work, so evdev is the only choice left
+            #[cfg(all(feature = "evdev", target_os = "linux"))]
+            KeyboardBackend::Auto if std::env::var_os("DISPLAY").is_none() || std::env::var_os("WAYLAND_DISPLAY").is_some() => {
+                Self::open_evdev().ok_or(Error::NoKeyboard)?
+            }
+            #[cfg(all(feature = "evdev", target_os = "linux"))]
+            KeyboardBackend::Auto => Self::open_evdev().unwrap_or_else(|| Keyboard::DeviceQuery(DeviceState::new())),
+            #[cfg(all(feature = "evdev", target_os = "linux"))]
+            KeyboardBackend::Evdev => Self::open_evdev().ok_or(Error::NoKeyboard)?,
+            #[cfg(not(all(feature = "evdev", target_os = "linux")))]
+            KeyboardBackend::Auto => Keyboard::DeviceQuery(DeviceState::new()),
+            KeyboardBackend::DeviceQuery => Keyboard::DeviceQuery(DeviceState::new()),
+        })
+    }
+
+    // Every input device that has letter keys, each read on its own thread
+    #[cfg(all(feature = "evdev", target_os = "linux"))]
+    fn open_evdev() -> Option<Self> {
+        let keyboards: Vec<evdev::Device> = evdev::enumerate()
+            .map(|(_, device)| device)
+            .filter(|device| device.supported_keys().is_some_and(|keys| keys.contains(evdev::Key::KEY_A)))
+            .collect();
+        if keyboards.is_empty() {
+            return None;
+        }
+        let (tx, events) = std::sync::mpsc::channel();
+        for mut keyboard in keyboards {
+            println!("Reading keys from {}", keyboard.name().unwrap_or("(unnamed keyboard)"));
+            let tx = tx.clone();
+            thread::spawn(move || {
+                // Blocks until the kernel has events; ends when the keyboard is unplugged
+                while let Ok(events) = keyboard.fetch_events() {
+                    for event in events {
+                        // Values are 1 for down, 0 for up and 2 for auto-repeat, which is ignored
+                        if let (evdev::InputEventKind::Key(key), 0 | 1) = (event.kind(), event.value()) {
+                            if let Some(keycode) = evdev_keys::keycode(key) {
+                                if tx.send((keycode, event.value() == 1)).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        }
+        Some(Keyboard::Evdev { events, held: Vec::new() })
+    }
+
+    // Waits until the held keys may have changed and returns them: for the next key
+    // event with evdev (or `wake`, if that comes first), for the next poll with device_query
+    pub fn next_keys(&mut self, wake: Option<Duration>) -> Vec<Keycode> {
+        match self {
+            Keyboard::DeviceQuery(state) => {
+                thread::sleep(POLL_INTERVAL);
+                state.get_keys()
+            }
+            #[cfg(all(feature = "evdev", target_os = "linux"))]
+            Keyboard::Evdev { events, held } => {
+                use std::sync::mpsc::RecvTimeoutError;
+                let first = match wake {
+                    Some(timeout) => events.recv_timeout(timeout),
+                    None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                let first = match first {
+                    Ok(first) => first,
+                    Err(RecvTimeoutError::Timeout) => return held.clone(), // Woken without any key changing
+                    Err(RecvTimeoutError::Disconnected) => {
+                        // Every keyboard is gone; nothing will change, so don't spin
+                        thread::sleep(Duration::from_secs(1));
+                        return held.clone();
+                    }
+                };
+                // Take everything that arrived together, e.g. a chord struck at once
+                for (keycode, down) in std::iter::once(first).chain(events.try_iter()) {
+                    held.retain(|&key| key != keycode);
+                    if down {
+                        held.push(keycode);
+                    }
+                }
+                held.clone()
+            }
+        }
+    }
+
+    // How long a key press can wait to be noticed: the polling interval with device_query,
+    // and nothing with evdev, which delivers the press as it happens
+    pub fn poll_interval(&self) -> Option<Duration> {
+        match self {
+            Keyboard::DeviceQuery(_) => Some(POLL_INTERVAL),
+            #[cfg(all(feature = "evdev", target_os = "linux"))]
+            Keyboard::Evdev { .. } => None,
+        }
+    }
+
+    // The pointer position in screen pixels, where the backend can tell
+    pub fn mouse(&self) -> Option<(i32, i32)> {
+        match self {
+            Keyboard::DeviceQuery(state) => Some(state.get_mouse().coords),
+            #[cfg(all(feature = "evdev", target_os = "linux"))]
+            Keyboard::Evdev { .. } => None, // evdev only has relative mouse movement
+        }
+    }
+}
+
+#[cfg(all(feature = "evdev", target_os = "linux"))]
+mod evdev_keys {
+    use device_query::Keycode;
+    use evdev::Key;
+
+    // The keys the synth has a use for, as device_query names them
+    const KEYS: &[(Key, Keycode)] = &[
+        (Key::KEY_A, Keycode::A), (Key::KEY_B, Keycode::B), (Key::KEY_C, Keycode::C), (Key::KEY_D, Keycode::D),
+        (Key::KEY_E, Keycode::E), (Key::KEY_F, Keycode::F), (Key::KEY_G, Keycode::G), (Key::KEY_H, Keycode::H),
+        (Key::KEY_I, Keycode::I), (Key::KEY_J, Keycode::J), (Key::KEY_K, Keycode::K), (Key::KEY_L, Keycode::L),
+        (Key::KEY_M, Keycode::M), (Key::KEY_N, Keycode::N), (Key::KEY_O, Keycode::O), (Key::KEY_P, Keycode::P),
+        (Key::KEY_Q, Keycode::Q), (Key::KEY_R, Keycode::R), (Key::KEY_S, Keycode::S), (Key::KEY_T, Keycode::T),
+        (Key::KEY_U, Keycode::U), (Key::KEY_V, Keycode::V), (Key::KEY_W, Keycode::W), (Key::KEY_X, Keycode::X),
+        (Key::KEY_Y, Keycode::Y), (Key::KEY_Z, Keycode::Z),
+        (Key::KEY_0, Keycode::Key0), (Key::KEY_1, Keycode::Key1), (Key::KEY_2, Keycode::Key2), (Key::KEY_3, Keycode::Key3),
+        (Key::KEY_4, Keycode::Key4), (Key::KEY_5, Keycode::Key5), (Key::KEY_6, Keycode::Key6), (Key::KEY_7, Keycode::Key7),
+        (Key::KEY_8, Keycode::Key8), (Key::KEY_9, Keycode::Key9),
+        (Key::KEY_F1, Keycode::F1), (Key::KEY_F2, Keycode::F2), (Key::KEY_F3, Keycode::F3), (Key::KEY_F4, Keycode::F4),
+        (Key::KEY_F5, Keycode::F5), (Key::KEY_F6, Keycode::F6), (Key::KEY_F7, Keycode::F7), (Key::KEY_F8, Keycode::F8),
+        (Key::KEY_F9, Keycode::F9), (Key::KEY_F10, Keycode::F10), (Key::KEY_F11, Keycode::F11), (Key::KEY_F12, Keycode::F12),
+        (Key::KEY_LEFTSHIFT, Keycode::LShift), (Key::KEY_RIGHTSHIFT, Keycode::RShift),
+        (Key::KEY_LEFTCTRL, Keycode::LControl), (Key::KEY_RIGHTCTRL, Keycode::RControl),
+        (Key::KEY_LEFTALT, Keycode::LAlt), (Key::KEY_RIGHTALT, Keycode::RAlt),
+        (Key::KEY_SPACE, Keycode::Space), (Key::KEY_ENTER, Keycode::Enter), (Key::KEY_TAB, Keycode::Tab),
+        (Key::KEY_BACKSPACE, Keycode::Backspace), (Key::KEY_CAPSLOCK, Keycode::CapsLock), (Key::KEY_ESC, Keycode::Escape),
+        (Key::KEY_GRAVE, Keycode::Grave), (Key::KEY_MINUS, Keycode::Minus), (Key::KEY_EQUAL, Keycode::Equal),
+        (Key::KEY_LEFTBRACE, Keycode::LeftBracket), (Key::KEY_RIGHTBRACE, Keycode::RightBracket),
+        (Key::KEY_SEMICOLON, Keycode::Semicolon), (Key::KEY_APOSTROPHE, Keycode::Apostrophe),
+        (Key::KEY_COMMA, Keycode::Comma), (Key::KEY_DOT, Keycode::Dot), (Key::KEY_SLASH, Keycode::Slash),
+        (Key::KEY_BACKSLASH, Keycode::BackSlash), (Key::KEY_PAGEUP, Keycode::PageUp), (Key::KEY_PAGEDOWN, Keycode::PageDown),
+        (Key::KEY_HOME, Keycode::Home), (Key::KEY_END, Keycode::End), (Key::KEY_INSERT, Keycode::Insert),
+        (Key::KEY_DELETE, Keycode::Delete),
+        (Key::KEY_KP0, Keycode::Numpad0), (Key::KEY_KP1, Keycode::Numpad1), (Key::KEY_KP2, Keycode::Numpad2), (Key::KEY_KP3, Keycode::Numpad3),
+        (Key::KEY_KP4, Keycode::Numpad4), (Key::KEY_KP5, Keycode::Numpad5), (Key::KEY_KP6, Keycode::Numpad6), (Key::KEY_KP7, Keycode::Numpad7),
+        (Key::KEY_KP8, Keycode::Numpad8), (Key::KEY_KP9, Keycode::Numpad9),
+        (Key::KEY_UP, Keycode::Up), (Key::KEY_DOWN, Keycode::Down), (Key::KEY_LEFT, Keycode::Left), (Key::KEY_RIGHT, Keycode::Right),
+    ];
+
+    pub fn keycode(key: Key) -> Option<Keycode> {
+        KEYS.iter().find(|&&(evdev, _)| evdev == key).map(|&(_, keycode)| keycode)
+    }
+}
@@ -0,0 +1,264 @@
+use device_query::Keycode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::{fs, io, path::Path};
+
+use crate::notes::{note_name, parse_note_name};
+
+pub const CELL: usize = 4; // Characters each key takes in the drawn map, enough for "C#4" and a space
+
+// The rows of the keyboard's main block, each starting half a key further right than
+// the one above, as they're staggered, so a black key sits between its two white keys.
+// The number row starts a key further left, with the grave key.
+const ROWS: [(usize, &[Keycode]); 4] = {
+    use Keycode::*;
+    [
+        (0, &[Grave, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, Minus, Equal]),
+        (CELL + 2, &[Q, W, E, R, T, Y, U, I, O, P, LeftBracket, RightBracket, BackSlash]),
+        (CELL + 4, &[A, S, D, F, G, H, J, K, L, Semicolon, Apostrophe]),
+        (CELL + 6, &[Z, X, C, V, B, N, M, Comma, Dot, Slash]),
+    ]
+};
+
+// Which computer keys play which notes. Keys are physical positions, named after the
+// key in that place on a US QWERTY keyboard, so the piano has the same shape on every
+// layout (evdev, and device_query on Linux and macOS, report positions, not the
+// letters printed on the keys). A key-map file lists them by key name:
+//
+//     layout = "azerty" # Optional: names below are as printed on this layout
+//
+//     [notes]
+//     A = "C4"
+//     W = "C#4"
+//     Semicolon = "D5"
+//
+// Saved in a session the same way, by position.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "KeyMapFile", into = "KeyMapFile")]
+pub struct KeyMap {
+    notes: HashMap<Keycode, u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyMapFile {
+    #[serde(default)]
+    layout: Layout,
+    notes: BTreeMap<String, String>, // Sorted, so a saved map reads the same each time
+}
+
+impl TryFrom<KeyMapFile> for KeyMap {
+    type Error = String;
+
+    fn try_from(file: KeyMapFile) -> Result<Self, String> {
+        let mut notes = HashMap::new();
+        for (key, note) in file.notes {
+            let keycode: Keycode = key.parse().map_err(|_| format!("'{}' is not a key name", key))?;
+            let note = parse_note_name(&note).ok_or_else(|| format!("'{}' is not a note name", note))?;
+            notes.insert(file.layout.position(keycode), note);
+        }
+        Ok(Self { notes })
+    }
+}
+
+impl From<KeyMap> for KeyMapFile {
+    fn from(map: KeyMap) -> Self {
+        let notes = map.notes.into_iter().map(|(key, note)| (key.to_string(), note_name(note))).collect();
+        Self { layout: Layout::Qwerty, notes }
+    }
+}
+
+// The keyboard layout a key-map file's key names are written for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+}
+
+impl Layout {
+    // The position of the key printed with `label` on this layout
+    pub fn position(self, label: Keycode) -> Keycode {
+        use Keycode::*;
+        let moved: &[(Keycode, Keycode)] = match self {
+            Layout::Qwerty => &[],
+            Layout::Azerty => &[(A, Q), (Z, W), (Q, A), (W, Z), (M, Semicolon), (Comma, M), (Semicolon, Comma)],
+            Layout::Qwertz => &[(Z, Y), (Y, Z)],
+            Layout::Dvorak => &[
+                (Apostrophe, Q), (Comma, W), (Dot, E), (P, R), (Y, T), (F, Y), (G, U), (C, I), (R, O), (L, P),
+                (Slash, LeftBracket), (Equal, RightBracket), (LeftBracket, Minus), (RightBracket, Equal),
+                (O, S), (E, D), (U, F), (I, G), (D, H), (H, J), (T, K), (N, L), (S, Semicolon), (Minus, Apostrophe),
+                (Semicolon, Z), (Q, X), (J, C), (K, V), (X, B), (B, N), (W, Comma), (V, Dot), (Z, Slash),
+            ],
+        };
+        moved.iter().find(|&&(from, _)| from == label).map_or(label, |&(_, to)| to)
+    }
+}
+
+impl Default for KeyMap {
+    // The home row plays the white keys from C4 and the row above it the black keys. The
+    // bottom row plays the white keys an octave lower; its black keys would fall on the
+    // home row, which is taken, so that octave has naturals only (see `two_octaves` for
+    // one with them).
+    fn default() -> Self {
+        let notes = [
+            (Keycode::Z, 48), // C3
+            (Keycode::X, 50), // D3
+            (Keycode::C, 52), // E3
+            (Keycode::V, 53), // F3
+            (Keycode::B, 55), // G3
+            (Keycode::N, 57), // A3
+            (Keycode::M, 59), // B3
+            (Keycode::A, 60), // C4
+            (Keycode::W, 61), // C#4/Db4
+            (Keycode::S, 62), // D4
+            (Keycode::E, 63), // D#4/Eb4
+            (Keycode::D, 64), // E4
+            (Keycode::F, 65), // F4
+            (Keycode::T, 66), // F#4/Gb4
+            (Keycode::G, 67), // G4
+            (Keycode::Y, 68), // G#4/Ab4
+            (Keycode::H, 69), // A4
+            (Keycode::U, 70), // A#4/Bb4
+            (Keycode::J, 71), // B4
+            (Keycode::K, 72), // C5
+        ];
+        Self {
+            notes: notes.into_iter().collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    // Two full octaves, as trackers lay them out: the bottom row plays the white keys from
+    // C3 with the black keys on the home row above them, and the top letter row the white
+    // keys from C4 with the black keys on the number row
+    pub fn two_octaves() -> Self {
+        use Keycode::*;
+        let notes = [
+            (Z, 48), (S, 49), (X, 50), (D, 51), (C, 52), (V, 53), (G, 54), (B, 55), (H, 56), (N, 57), (J, 58), (M, 59),
+            (Q, 60), (Key2, 61), (W, 62), (Key3, 63), (E, 64), (R, 65), (Key5, 66), (T, 67), (Key6, 68), (Y, 69), (Key7, 70), (U, 71),
+            (I, 72), (Key9, 73), (O, 74), (Key0, 75), (P, 76),
+        ];
+        Self { notes: notes.into_iter().collect() }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    pub fn note(&self, key: Keycode) -> Option<u8> {
+        self.notes.get(&key).copied()
+    }
+
+    // The keys that play notes, laid out as on the keyboard: for each row with any, the
+    // column (in characters) each key starts at, the key and its note. Keys outside the
+    // main block, such as the function keys, follow on a row of their own.
+    pub fn rows(&self) -> Vec<Vec<(usize, Keycode, u8)>> {
+        let mut rows: Vec<Vec<(usize, Keycode, u8)>> = ROWS
+            .iter()
+            .map(|&(indent, keys)| {
+                keys.iter()
+                    .enumerate()
+                    .filter_map(|(i, &key)| Some((indent + i * CELL, key, self.note(key)?)))
+                    .collect()
+            })
+            .collect();
+        let mut others: Vec<(Keycode, u8)> = self
+            .notes
+            .iter()
+            .filter(|(key, _)| !ROWS.iter().any(|(_, keys)| keys.contains(key)))
+            .map(|(&key, &note)| (key, note))
+            .collect();
+        others.sort_by_key(|&(_, note)| note);
+        rows.push(others.into_iter().enumerate().map(|(i, (key, note))| (i * CELL, key, note)).collect());
+        rows.retain(|row| !row.is_empty());
+        // Starting from the leftmost key used
+        let indent = rows.iter().filter_map(|row| row.first()).map(|&(column, _, _)| column).min().unwrap_or(0);
+        for row in &mut rows {
+            for (column, _, _) in row {
+                *column -= indent;
+            }
+        }
+        rows
+    }
+
+    // The map drawn for the console, each row as a line of keys over a line of the notes
+    // they play, with the keys in `held` in reverse video
+    pub fn diagram(&self, held: &[Keycode]) -> String {
+        let mut text = String::new();
+        for row in self.rows() {
+            let (mut keys, mut notes, mut width) = (String::new(), String::new(), 0);
+            for (column, key, note) in row {
+                let (on, off) = if held.contains(&key) { ("\x1b[7m", "\x1b[0m") } else { ("", "") };
+                let padding = " ".repeat(column.saturating_sub(width)); // The escapes take no room
+                keys.push_str(&format!("{}{}{:<3}{} ", padding, on, key_label(key), off));
+                notes.push_str(&format!("{}{}{:<3}{} ", padding, on, note_name(note), off));
+                width = column.max(width) + CELL;
+            }
+            text.push_str(&format!("{}\n{}\n", keys.trim_end(), notes.trim_end()));
+        }
+        text
+    }
+}
+
+// What's printed on the key, for drawing the map
+pub fn key_label(key: Keycode) -> String {
+    use Keycode::*;
+    let label = match key {
+        Grave => "`",
+        Minus => "-",
+        Equal => "=",
+        LeftBracket => "[",
+        RightBracket => "]",
+        BackSlash => "\\",
+        Semicolon => ";",
+        Apostrophe => "'",
+        Comma => ",",
+        Dot => ".",
+        Slash => "/",
+        _ => return key.to_string().trim_start_matches("Key").to_string(),
+    };
+    label.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_map_plays_c4_to_c5_and_the_naturals_below() {
+        let map = KeyMap::default();
+        assert_eq!(map.note(Keycode::A), Some(60));
+        assert_eq!(map.note(Keycode::K), Some(72));
+        assert_eq!(map.note(Keycode::Z), Some(48));
+        let mut notes: Vec<u8> = map.notes.into_values().collect();
+        notes.sort();
+        assert_eq!(notes, [48, 50, 52, 53, 55, 57, 59].into_iter().chain(60..=72).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn two_octave_map_plays_every_note_from_c3_to_e5() {
+        let map = KeyMap::two_octaves();
+        assert_eq!((map.note(Keycode::Z), map.note(Keycode::S), map.note(Keycode::Q)), (Some(48), Some(49), Some(60)));
+        let mut notes: Vec<u8> = map.notes.into_values().collect();
+        notes.sort();
+        assert_eq!(notes, (48..=76).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn black_keys_sit_between_their_white_keys() {
+        for map in [KeyMap::default(), KeyMap::two_octaves()] {
+            for row in map.rows().chunks(2) {
+                let [black, white] = row else { continue };
+                for &(column, _, note) in black {
+                    let neighbours: Vec<u8> = white.iter().filter(|&&(at, _, _)| at.abs_diff(column) == CELL / 2).map(|&(_, _, note)| note).collect();
+                    assert_eq!(neighbours, [note - 1, note + 1], "{}", note_name(note));
+                }
+            }
+        }
+    }
+}
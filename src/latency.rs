@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use rodio_synth::stereo::Frame;
+
+const ONSET_LEVEL: f32 = 1e-3; // A note has started once the output gets this loud
+const QUIET_LEVEL: f32 = 1e-4; // And the next can be timed once it's been this quiet
+const QUIET_SECONDS: f32 = 0.02;
+const ONSET_CAPACITY: usize = 1024; // Onsets kept for the report, far more notes than a measurement takes
+const HISTOGRAM_ROWS: u32 = 12;
+const BAR_WIDTH: usize = 40;
+
+// Measures the latency from a key press to its note leaving the device (`--measure-latency`).
+// The keyboard thread timestamps each press, and the audio callback timestamps the
+// frame where sound starts after a silence: when the callback ran, plus how far into
+// its block the frame is, plus the delay the device reports before the block plays.
+// Each onset is paired with the last press before it.
+#[derive(Clone)]
+pub struct LatencyProbe {
+    presses: mpsc::Sender<Instant>,
+    onsets: Onsets,
+}
+
+impl LatencyProbe {
+    pub fn new() -> (Self, LatencyReport) {
+        let (presses, pressed) = mpsc::channel();
+        let onsets = Onsets::new();
+        (Self { presses, onsets: onsets.clone() }, LatencyReport { pressed, sounded: onsets })
+    }
+
+    pub fn press(&self) {
+        let _ = self.presses.send(Instant::now());
+    }
+
+    // For the audio callback, to watch the output for notes starting
+    pub fn listener(&self, sample_rate: u32) -> OnsetListener {
+        OnsetListener { onsets: self.onsets.clone(), quiet: 0, armed: false, rearm: (QUIET_SECONDS * sample_rate as f32) as u32 }
+    }
+}
+
+// Where the audio callback leaves the onsets it times: a ring of times, as microseconds
+// since `start`, that it writes without allocating or waiting. Only the most recent
+// ONSET_CAPACITY are kept.
+#[derive(Clone)]
+struct Onsets {
+    start: Instant,
+    times: Arc<[AtomicU64]>,
+    written: Arc<AtomicUsize>, // Onsets pushed so far, the ring position is this modulo ONSET_CAPACITY
+}
+
+impl Onsets {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            times: (0..ONSET_CAPACITY).map(|_| AtomicU64::new(0)).collect(),
+            written: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn push(&self, at: Instant) {
+        let position = self.written.load(Ordering::Relaxed);
+        let micros = at.saturating_duration_since(self.start).as_micros() as u64;
+        self.times[position % ONSET_CAPACITY].store(micros, Ordering::Relaxed);
+        self.written.store(position.wrapping_add(1), Ordering::Release);
+    }
+
+    // The onsets kept, oldest first
+    fn all(&self) -> Vec<Instant> {
+        let written = self.written.load(Ordering::Acquire);
+        (written.saturating_sub(ONSET_CAPACITY)..written)
+            .map(|position| self.start + Duration::from_micros(self.times[position % ONSET_CAPACITY].load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+pub struct OnsetListener {
+    onsets: Onsets,
+    quiet: u32, // Frames the output has been quiet for
+    armed: bool,
+    rearm: u32, // Frames of quiet that arm it for the next onset
+}
+
+impl OnsetListener {
+    // `frame` leaves the device at `at`
+    pub fn listen(&mut self, frame: Frame, at: Instant) {
+        let level = frame[0].abs().max(frame[1].abs());
+        if level < QUIET_LEVEL {
+            self.quiet += 1;
+            self.armed |= self.quiet >= self.rearm;
+            return;
+        }
+        self.quiet = 0;
+        if self.armed && level >= ONSET_LEVEL {
+            self.armed = false;
+            self.onsets.push(at);
+        }
+    }
+}
+
+pub struct LatencyReport {
+    pressed: mpsc::Receiver<Instant>,
+    sounded: Onsets,
+}
+
+impl LatencyReport {
+    // The latencies measured so far, as a histogram with their spread
+    pub fn summary(&self) -> String {
+        let presses: Vec<Instant> = self.pressed.try_iter().collect();
+        summarize(pair(&presses, &self.sounded.all()))
+    }
+}
+
+// Pairs each onset with the last press before it that no earlier onset was paired with
+fn pair(presses: &[Instant], onsets: &[Instant]) -> Vec<Duration> {
+    let mut latencies = Vec::new();
+    let mut next = 0; // The first press not yet paired
+    for &onset in onsets {
+        let before = presses[next..].partition_point(|&press| press <= onset);
+        if before > 0 {
+            latencies.push(onset - presses[next + before - 1]);
+            next += before;
+        }
+    }
+    latencies
+}
+
+fn summarize(mut latencies: Vec<Duration>) -> String {
+    if latencies.is_empty() {
+        return "No latencies measured: press a note key after a moment's silence, with the cpal backend".to_string();
+    }
+    latencies.sort();
+    let ms = |latency: Duration| latency.as_secs_f32() * 1000.0;
+    let (min, max) = (ms(latencies[0]), ms(latencies[latencies.len() - 1]));
+    let mean = latencies.iter().map(|&latency| ms(latency)).sum::<f32>() / latencies.len() as f32;
+    let mut summary = format!(
+        "Key-to-sound latency over {} notes: {:.1} ms median, {:.1} ms mean, {:.1} to {:.1} ms\n",
+        latencies.len(),
+        ms(latencies[latencies.len() / 2]),
+        mean,
+        min,
+        max
+    );
+
+    // Rows a whole number of milliseconds wide, starting on a whole millisecond
+    let width = ((max - min) / HISTOGRAM_ROWS as f32).ceil().max(1.0);
+    let start = min.floor();
+    let mut counts = vec![0; ((max - start) / width) as usize + 1];
+    for &latency in &latencies {
+        counts[((ms(latency) - start) / width) as usize] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(1);
+    for (row, &count) in counts.iter().enumerate() {
+        let from = start + row as f32 * width;
+        let bar = "#".repeat(count * BAR_WIDTH / most);
+        summary.push_str(&format!("{:>6.0}-{:<4.0} ms |{:<width$}| {}\n", from, from + width, bar, count, width = BAR_WIDTH));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onsets_are_paired_with_the_last_press_before_them() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let presses = [at(0), at(100), at(110), at(300)];
+        let onsets = [at(5), at(50), at(118), at(290)];
+        let ms = |latency: Vec<Duration>| latency.iter().map(Duration::as_millis).collect::<Vec<_>>();
+        assert_eq!(ms(pair(&presses, &onsets)), [5, 8]); // The second press is passed over, the last never sounded
+    }
+
+    #[test]
+    fn the_listener_times_onsets_after_a_silence() {
+        let (probe, report) = LatencyProbe::new();
+        let mut listener = probe.listener(1_000); // 20 frames of quiet arm it
+        let at = |frame| report.sounded.start + Duration::from_millis(frame);
+        for frame in 0..30 {
+            listener.listen([0.0; 2], at(frame));
+        }
+        listener.listen([0.5, 0.0], at(30));
+        listener.listen([0.0, 0.0], at(31)); // Too short a silence to time the next
+        listener.listen([0.0, -0.5], at(32));
+        assert_eq!(report.sounded.all(), [at(30)]);
+    }
+
+    #[test]
+    fn the_summary_draws_a_histogram() {
+        let latencies = [12, 14, 14, 15, 40].map(Duration::from_millis).to_vec();
+        let summary = summarize(latencies);
+        let mut lines = summary.lines();
+        assert_eq!(lines.next(), Some("Key-to-sound latency over 5 notes: 14.0 ms median, 19.0 ms mean, 12.0 to 40.0 ms"));
+        let rows: Vec<_> = lines.collect();
+        assert_eq!(rows.len(), 10); // Rows 3 ms wide, from 12 ms to past 40 ms
+        assert_eq!(rows[0], format!("    12-15   ms |{:<40}| 3", "#".repeat(40)));
+        assert_eq!(rows[1], format!("    15-18   ms |{:<40}| 1", "#".repeat(13)));
+        assert!(rows[2..9].iter().all(|row| row.ends_with("| 0")));
+        assert_eq!(rows[9], format!("    39-42   ms |{:<40}| 1", "#".repeat(13)));
+        assert!(summarize(Vec::new()).starts_with("No latencies measured"));
+    }
+}
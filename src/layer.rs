@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Waveform;
+
+// A second oscillator in every voice, layered on the first: an octave up or down for
+// weight, or a few cents off for the beating, chorused sound of classic analog patches
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerSettings {
+    pub enabled: bool,
+    pub waveform: Waveform,
+    pub octave: i8,  // Octaves above (negative: below) the played note
+    pub detune: f32, // Cents above (negative: below) that
+    pub mix: f32,    // Level of the second oscillator, taken from the first: 0.0 only the first, 1.0 only the second
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            waveform: Waveform::Saw,
+            octave: 0,
+            detune: 7.0,
+            mix: 0.5,
+        }
+    }
+}
+
+impl LayerSettings {
+    // The settings the synth's "layer.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("octave", self.octave as f32), ("detune", self.detune), ("mix", self.mix)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "octave" => self.octave = value.round().clamp(-3.0, 3.0) as i8,
+            "detune" => self.detune = value.clamp(-100.0, 100.0),
+            "mix" => self.mix = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+
+    // Frequency of the second oscillator relative to the first
+    pub fn ratio(&self) -> f32 {
+        2.0_f32.powf(self.octave as f32 + self.detune / 1200.0)
+    }
+}
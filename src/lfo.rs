@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use crate::precision::{to_f32, Real, PI};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    SampleAndHold, // A new random level every cycle, held until the next: the classic "burbling" filter
+}
+
+// Low-frequency oscillator used to sweep effect and voice parameters
+pub struct Lfo {
+    phase: Real,
+    phase_increment: Real,
+    sample_rate: u32,
+    shape: LfoShape,
+    held: f32,   // Level of the current sample-and-hold step
+    random: u32, // Xorshift state for the sample-and-hold levels
+}
+
+impl Lfo {
+    pub fn new(rate_hz: f32, sample_rate: u32) -> Self {
+        let mut lfo = Self {
+            phase: 0.0,
+            phase_increment: 2.0 * PI * Real::from(rate_hz) / sample_rate as Real,
+            sample_rate,
+            shape: LfoShape::Sine,
+            held: 0.0,
+            random: rate_hz.to_bits() ^ 0x9E37_79B9, // Never zero, which xorshift can't leave
+        };
+        lfo.held = lfo.next_random();
+        lfo
+    }
+
+    pub fn with_shape(mut self, shape: LfoShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    // Starts the LFO at an offset (in radians), so several LFOs at the same rate can be spread apart
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.set_phase(phase);
+        self
+    }
+
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = Real::from(phase).rem_euclid(2.0 * PI);
+    }
+
+    pub fn set_rate(&mut self, rate_hz: f32) {
+        self.phase_increment = 2.0 * PI * Real::from(rate_hz) / self.sample_rate as Real;
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    // Returns the next value in the range [-1.0, 1.0]
+    pub fn next_value(&mut self) -> f32 {
+        let value = match self.shape {
+            LfoShape::Sine => to_f32(self.phase.sin()),
+            LfoShape::Triangle => to_f32(2.0 * (self.phase / PI - 1.0).abs() - 1.0),
+            LfoShape::Square => if self.phase < PI { 1.0 } else { -1.0 },
+            LfoShape::SampleAndHold => self.held,
+        };
+        self.phase += self.phase_increment;
+        if self.phase > 2.0 * PI {
+            self.phase -= 2.0 * PI;
+            if self.shape == LfoShape::SampleAndHold {
+                self.held = self.next_random();
+            }
+        }
+        value
+    }
+
+    // A uniformly distributed level in [-1.0, 1.0]
+    fn next_random(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,2008 @@
+
+// The synth engine: voices, effects and everything a preset describes, with no
+// audio device or keyboard attached. The `rodio-synth` binary plays it through the
+// sound card; the wasm32 build drives it from a browser's audio worklet.
+
+pub mod ambient;
+pub mod arpeggiator;
+pub mod bank;
+pub mod binaural;
+pub mod blend;
+pub mod chip;
+pub mod chord;
+pub mod clock;
+pub mod controllers;
+pub mod delay_line;
+pub mod diagnostics;
+pub mod dither;
+pub mod effects;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod embed;
+pub mod euclid;
+pub mod filter;
+pub mod flute;
+pub mod fold;
+pub mod formant;
+pub mod glide;
+pub mod harmony;
+pub mod humanize;
+pub mod layer;
+pub mod lfo;
+pub mod live_input;
+pub mod looper;
+pub mod macros;
+pub mod metronome;
+pub mod midi_file;
+pub mod mixer;
+pub mod mono;
+pub mod morph;
+pub mod mouse;
+pub mod notes;
+pub mod organ;
+pub mod oversample;
+pub mod param_bank;
+pub mod param_id;
+pub mod params;
+pub mod parts;
+pub mod pitch_envelope;
+pub mod preset;
+pub mod pressure;
+pub mod pulse;
+pub mod record_tap;
+pub mod reference;
+pub mod scale;
+pub mod scene;
+pub mod scope;
+pub mod score;
+pub mod sends;
+pub mod sequencer;
+pub mod smoothed;
+pub mod snapshot;
+pub mod split;
+pub mod stereo;
+pub mod surround;
+pub mod switch;
+pub mod tempo;
+pub mod undo;
+pub mod velocity_curve;
+pub mod vibrato;
+pub mod vocoder;
+pub mod voice_effects;
+pub mod voice_lfo;
+pub mod voices;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+// The DSP that builds without std, from the core crate, where the modules it had here went
+pub use rodio_synth_core::oscillator::Waveform;
+pub use rodio_synth_core::{biquad, envelope, precision};
+use rodio_synth_core::oscillator::frequency_from_note;
+
+use std::{sync::{mpsc, Arc}, collections::{HashMap, VecDeque}};
+use binaural::{BinauralSettings, BinauralTone};
+use biquad::Biquad;
+use blend::PresetBlend;
+use chip::{ChipChannel, ChipSettings, ChipVoice, TONAL_CHANNELS};
+use chord::ChordSettings;
+use clock::FrameClock;
+use controllers::Controllers;
+use diagnostics::Diagnostic;
+use effects::{crossfeed::{Crossfeed, CrossfeedLevel}, dc_blocker::DcBlocker, eq::Equalizer, width::StereoWidener, Effect, EffectsChain};
+use envelope::{Envelope, EnvelopeSettings, Retrigger};
+use filter::FilterSettings;
+use flute::{Flute, FluteSettings};
+use fold::FoldSettings;
+use formant::{FormantFilter, FormantSettings};
+use glide::{Glides, SmoothingSettings};
+use harmony::HarmonySettings;
+use layer::LayerSettings;
+use lfo::Lfo;
+use live_input::{InputRing, InputSettings};
+use macros::{Macro, MACROS};
+use metronome::Metronome;
+use mixer::{Mixer, MixerSettings};
+use mono::{HeldNotes, MonoSettings};
+use morph::MorphSettings;
+use notes::NoteSet;
+use organ::{OrganSettings, OrganVoice};
+use oversample::{Oversampler, Oversampling};
+use param_bank::ParamBank;
+use param_id::ParamId;
+use parts::{Part, MAX_PARTS};
+use pitch_envelope::PitchEnvelopeSettings;
+use precision::{to_f32, Real};
+use preset::Preset;
+use pressure::{Pressure, VoicePressure};
+use pulse::PulseSettings;
+use record_tap::{RecordTap, RecordedFrame};
+use reference::{ReferenceSettings, ReferenceTone};
+use scale::ScaleSettings;
+use scope::ScopeTap;
+use sends::SendBus;
+use snapshot::{Meter, Snapshot, SNAPSHOTS_PER_SECOND};
+use split::SplitSettings;
+use stereo::{pan_gains, Frame, VoicePanner, LEFT, RIGHT};
+use surround::{Layout, Surround};
+use switch::{BuiltPreset, BuiltSwitch, Outgoing, PresetSwitch, Retiring, MAX_OUTGOING, MAX_RETIRING};
+use velocity_curve::VelocitySettings;
+use vibrato::VibratoSettings;
+use vocoder::Vocoder;
+use voice_effects::VoiceEffects;
+use voice_lfo::VoiceLfoSettings;
+use voices::{Started, StealPolicy, VoicePool};
+
+pub const DEFAULT_POLYPHONY: usize = 16;
+const FILTER_BLOCK: u32 = 32; // Frames between retunes of the voice filters while their LFO moves
+pub const FADE_OUT_SECONDS: f32 = 0.1; // Length of the fade after `SynthCommand::FadeOut`
+pub const MAX_SCHEDULED: usize = 1024; // Commands from `At` that can wait for their frame at once; more are refused
+const MIN_BEND_RANGE: f32 = 2.0; // Semitones a full throw of the pitch-bend wheel can be set to move
+const MAX_BEND_RANGE: f32 = 24.0;
+
+// Notes are MIDI note numbers (60 = C4)
+#[derive(Clone, Debug, PartialEq)]
+pub enum SynthCommand {
+    NoteOn(u8),
+    NoteOff(u8),
+    DetunedNoteOn(u8, f32), // Like `NoteOn`, with that note's voices detuned by the semitones given, as the humanizer plays
+    ScaledNoteOn(u8, f32),  // Like `NoteOn`, at the given fraction (0.0..1.0) of the velocity, which other notes keep
+    VelocityNoteOn(u8, f32), // Like `NoteOn`, at the given velocity (0.0..1.0) in place of the one other notes keep
+    ToggleMetronome,
+    ToggleHold, // While on, released notes keep ringing until hold is turned off or they're played again
+    ToggleGlide, // Turns the mono glide off and back on, keeping its time
+    ToggleLatch, // While on, each press of a key starts its note or stops it again; releases are ignored
+    Panic,      // Fades out every voice within a few milliseconds and forgets all held notes
+    FadeOut,    // Fades the whole output to silence before the program exits
+    SetTempo(f32),
+    SetMacro(usize, f32), // Macro index and its new position, 0.0..1.0
+    SetParam(ParamId, f32), // Parameter path as understood by `Synthesizer::set_param`
+    SetVelocity(f32),      // Loudness of the notes started from now on, 0.0..1.0
+    GlideParam(ParamId, f32), // Like `SetParam`, but smoothed for controllers that move in steps
+    ChannelNoteOn(u8, u8),   // MIDI channel (1-16) and note, for the parts; channel 1 is the same as `NoteOn`
+    ChannelNoteOff(u8, u8),
+    ChannelPressure(f32),    // Aftertouch from the whole keyboard, 0.0..1.0
+    PolyPressure(u8, f32),   // A key's own aftertouch: its note and pressure, 0.0..1.0
+    LoadPreset(Box<Preset>),  // Switches to another sound at once; sounding notes ring out as they were
+    CrossfadePreset(Box<Preset>, f32), // Switches like `LoadPreset`, fading the old effects out under the new over the seconds given
+    QueuePreset(Box<Preset>), // Switches just before the next note starts, so the change lands on the beat
+    Built(BuiltPreset),       // One of the three above with its synth built already, by `switch::build_presets`
+    SetReference(Option<ReferenceSettings>), // Starts a tuning reference tone, or fades it out with None
+    SetBinaural(Option<BinauralSettings>),   // Starts a binaural or isochronic session, or fades it out with None
+    At(u64, Box<SynthCommand>), // Plays the command just before the given frame of `Synthesizer::clock`, or at once if that's passed
+    AtNext(u64), // `At` as `switch::build_presets` passes it on: the frame, with the command sent unboxed right after
+}
+
+impl SynthCommand {
+    // A note starting or ending on a MIDI channel, as the plain command on channel 1
+    pub fn channel_note(channel: u8, note: u8, on: bool) -> Self {
+        match (channel, on) {
+            (1, true) => SynthCommand::NoteOn(note),
+            (1, false) => SynthCommand::NoteOff(note),
+            (_, true) => SynthCommand::ChannelNoteOn(channel, note),
+            (_, false) => SynthCommand::ChannelNoteOff(channel, note),
+        }
+    }
+}
+
+pub struct Synthesizer {
+    oscillators: VoicePool, // Several may play one note, while earlier ones ring out
+    sample_rate: u32,
+    waveform: Waveform,
+    velocity: f32,    // Gain given to new voices, set by `SynthCommand::SetVelocity`
+    velocity_curve: VelocitySettings, // How those velocities become gains
+    bend: f32,        // Pitch bend as a frequency ratio, from the bend and wheel below
+    bend_semitones: f32, // The "pitch.bend" parameter
+    wheel: f32,          // The "pitch.wheel" parameter, -1.0..1.0 across `bend_range`
+    bend_range: f32,     // Semitones of a full wheel throw, from the preset or MIDI RPN 0
+    detune: f32,         // Semitones the voices starting now are detuned by, from `DetunedNoteOn`
+    glides: Glides,   // Parameters on their way to a new value
+    smoothing: SmoothingSettings, // Which edits glide rather than jump, and how
+    param_bank: Option<Arc<ParamBank>>, // Edits from other threads, picked up every frame
+    fade: Option<f32>, // Master gain while fading out for good, None until `FadeOut`
+    envelope: EnvelopeSettings,
+    layer: LayerSettings, // Second oscillator in every voice, off by default
+    morph: MorphSettings, // Crossfading waveform in place of `waveform`, off by default
+    morph_lfo: Lfo,
+    pulse: PulseSettings, // Width of the square wave and its modulation
+    pulse_lfo: Lfo,
+    fold: FoldSettings,   // Wavefolder after the oscillators, off by default
+    flute: FluteSettings, // Blown pipes in place of the oscillators, off by default
+    organ: OrganSettings, // Drawbar tonewheels in place of the oscillators, off by default
+    chip: ChipSettings,   // The NES sound chip in place of the oscillators, off by default
+    chip_steal: usize,    // The tonal channel the next note takes when all three are busy
+    oversampling: Oversampling, // For the wavefolder in every new voice
+    filter: FilterSettings, // Low-pass filter in every voice, off by default
+    filter_lfo: Lfo,
+    filter_octaves: f32,    // Where the filter LFO has moved the cutoff
+    filter_countdown: u32,  // Frames until the voice filters follow the LFO again
+    formant: FormantSettings, // Vowel filters in every voice, off by default
+    formant_lfo: Lfo,
+    formant_countdown: u32,   // Frames until the voices' formants follow the LFO and envelopes again
+    voice_lfo_countdown: u32, // Frames until the voices' own LFOs move them again
+    pitch_envelope: PitchEnvelopeSettings, // Pitch sweep at the start of every note
+    pressure: Pressure,                    // Aftertouch and what it moves
+    controllers: Controllers,              // The mod wheel and expression pedal, and what they move
+    vibrato: VibratoSettings,
+    vibrato_lfo: Lfo,
+    voice_lfo: VoiceLfoSettings,           // Each voice's own LFO, when it moves anything
+    voice_seed: u32,                       // Xorshift state for those LFOs' rates and phases
+    command_receiver: mpsc::Receiver<SynthCommand>,
+    clock: FrameClock,                           // Frames rendered so far
+    scheduled: VecDeque<(u64, SynthCommand)>,    // Commands from `At` waiting for their frame, in frame order
+    next_at: Option<u64>,                        // Frame the next command is for, after an `AtNext`
+    effects: EffectsChain,
+    sends: Vec<SendBus>, // Effect buses the main patch and parts feed, added back after `effects`
+    voice_effects: VoiceEffects, // Run by each voice of the main patch before the mix
+    widener: StereoWidener,
+    eq: Equalizer,
+    dc_blocker: DcBlocker, // Last in the chain, after the EQ
+    tempo: f32,
+    metronome: Metronome,
+    panner: VoicePanner,
+    surround: Surround, // Places the voices round the room instead, when the output has more than two channels
+    mono: MonoSettings,
+    glide_on: bool,              // The mono glide, unless toggled off while playing
+    held_notes: HeldNotes,       // Notes held in mono mode, used for note priority
+    mono_note: Option<u8>,       // The note the single mono voice is currently stored under
+    hold: bool,
+    sustained: Vec<u8>,          // Notes released while hold is on, still sounding
+    latch: bool,
+    latched: Vec<u8>,            // Notes started by a press while latch is on, droning until pressed again
+    scale: ScaleSettings,
+    scaled_notes: HashMap<u8, u8>,      // Played note -> the scale note it was snapped to
+    chord: ChordSettings,
+    chord_voices: HashMap<u8, NoteSet>, // Notes started by each held chord key, harmonies included
+    harmony: HarmonySettings,
+    split: SplitSettings,               // Second patch for the notes below a split point, off by default
+    macros: Vec<Macro>,
+    parts: Vec<Part>, // Extra instruments on their own channels and key ranges
+    mixer_settings: MixerSettings, // The main patch's volume, mute and solo
+    mixer: Mixer,                  // Smoothed gains of the main patch, the parts and the master
+    snapshots: Option<mpsc::SyncSender<Snapshot>>, // State updates for a user interface, if one is running
+    snapshot_countdown: u32,                       // Frames until the next snapshot is due
+    diagnostics: Option<mpsc::SyncSender<Diagnostic>>, // Events for the log, which the audio thread can't write
+    meter: Meter,                                  // Output levels since the last snapshot
+    scope: Option<ScopeTap>,                       // Output samples for an oscilloscope view
+    recordings: Vec<RecordTap>,                    // Output frames and stems for each recording or network stream
+    pending_right: Option<f32>, // Right half of the last rendered frame, not yet handed to rodio
+    pending_preset: Option<SynthCommand>, // A `QueuePreset`, built or not, waiting for the next note
+    outgoing: Vec<Outgoing>,    // Effects of presets switched away from, still fading out
+    retiring: Vec<Retiring>,    // Presets switched away from, playing out their notes
+    preset_switch: PresetSwitch, // What switching preset does with the notes sounding
+    switch_time: f32,           // Seconds a crossfading switch takes
+    input: Option<InputRing>,   // Live audio from an input device, if there is one
+    input_settings: InputSettings,
+    input_filters: [Biquad; 2], // The voice filter's twin for the input, left and right
+    vocoder: Option<Vocoder>,   // Shapes the voices with the input's bands, off by default
+    reference: Option<ReferenceTone>, // Tuning tone, mixed in dry
+    binaural: Option<BinauralTone>,   // Beating tones, mixed in dry
+    crossfeed: Option<Crossfeed>,     // Bleed between the channels for headphones, if asked for
+    blend: Option<PresetBlend>,       // The preset to blend towards with `blend.amount`, if any
+}
+
+impl Synthesizer {
+    pub fn new(sample_rate: u32, preset: &Preset, command_receiver: mpsc::Receiver<SynthCommand>) -> Self {
+        let mut synth = Self {
+            oscillators: VoicePool::new(DEFAULT_POLYPHONY),
+            sample_rate,
+            waveform: Waveform::Sine,
+            velocity: preset.velocity.apply(1.0),
+            velocity_curve: preset.velocity.clone(),
+            bend: 1.0,
+            bend_semitones: 0.0,
+            wheel: 0.0,
+            detune: 0.0,
+            bend_range: preset.bend_range.clamp(MIN_BEND_RANGE, MAX_BEND_RANGE),
+            glides: Glides::new(sample_rate, &preset.smoothing),
+            smoothing: preset.smoothing.clone(),
+            param_bank: None,
+            fade: None,
+            envelope: preset.envelope.clone(),
+            layer: preset.layer.clone(),
+            morph: preset.morph.clone(),
+            morph_lfo: Lfo::new(preset.morph.lfo_rate.hz(preset.tempo), sample_rate).with_shape(preset.morph.lfo_shape),
+            pulse: preset.pulse.clone(),
+            pulse_lfo: Lfo::new(preset.pulse.lfo_rate.hz(preset.tempo), sample_rate).with_shape(preset.pulse.lfo_shape),
+            fold: preset.fold.clone(),
+            flute: preset.flute.clone(),
+            organ: preset.organ.clone(),
+            chip: preset.chip.clone(),
+            chip_steal: 0,
+            oversampling: preset.oversampling,
+            filter: preset.filter.clone(),
+            filter_lfo: Lfo::new(preset.filter.lfo_rate.hz(preset.tempo), sample_rate).with_shape(preset.filter.lfo_shape),
+            filter_octaves: 0.0,
+            filter_countdown: 0,
+            formant: preset.formant.clone(),
+            formant_lfo: Lfo::new(preset.formant.lfo_rate.hz(preset.tempo), sample_rate).with_shape(preset.formant.lfo_shape),
+            formant_countdown: 0,
+            voice_lfo_countdown: 0,
+            pitch_envelope: preset.pitch_envelope.clone(),
+            pressure: Pressure::new(preset.pressure.clone(), preset.tempo, sample_rate),
+            controllers: Controllers::new(&preset.controllers, sample_rate),
+            vibrato: preset.vibrato.clone(),
+            vibrato_lfo: Lfo::new(preset.vibrato.rate.hz(preset.tempo), sample_rate),
+            voice_lfo: preset.voice_lfo.clone(),
+            voice_seed: 0x2545_F491,
+            command_receiver,
+            clock: FrameClock::default(),
+            scheduled: VecDeque::with_capacity(MAX_SCHEDULED),
+            next_at: None,
+            effects: EffectsChain::new(&preset.effects, sample_rate),
+            sends: preset.sends.iter().map(|settings| SendBus::new(settings, sample_rate)).collect(),
+            voice_effects: VoiceEffects::new(&preset.voice_effects, preset.oversampling, sample_rate),
+            widener: StereoWidener::new(&preset.stereo, sample_rate),
+            eq: Equalizer::new(&preset.eq, sample_rate),
+            dc_blocker: DcBlocker::new(sample_rate),
+            tempo: preset.tempo,
+            metronome: Metronome::new(preset.metronome.clone(), preset.tempo, sample_rate),
+            panner: VoicePanner::new(preset.panning.clone()),
+            surround: Surround::new(preset.surround.clone(), preset.tempo, sample_rate),
+            mono: preset.mono.clone(),
+            glide_on: true,
+            held_notes: HeldNotes::new(preset.mono.priority),
+            mono_note: None,
+            hold: false,
+            sustained: Vec::with_capacity(128), // Room for every note, like the maps below
+            latch: false,
+            latched: Vec::with_capacity(128),
+            scale: preset.scale.clone(),
+            scaled_notes: HashMap::with_capacity(128),
+            chord: preset.chord.clone(),
+            chord_voices: HashMap::with_capacity(128),
+            harmony: preset.harmony.clone(),
+            split: preset.split.clone(),
+            macros: preset.macros.iter().take(MACROS).enumerate().map(|(index, settings)| Macro::new(index, settings)).collect(),
+            parts: preset.parts.iter().take(MAX_PARTS).map(|settings| Part::new(settings.clone(), DEFAULT_POLYPHONY)).collect(),
+            mixer_settings: preset.mixer.clone(),
+            mixer: Mixer::new(sample_rate),
+            snapshots: None,
+            snapshot_countdown: 0,
+            diagnostics: None,
+            meter: Meter::default(),
+            scope: None,
+            recordings: Vec::new(),
+            pending_right: None,
+            pending_preset: None,
+            outgoing: Vec::with_capacity(MAX_OUTGOING),
+            retiring: Vec::with_capacity(MAX_RETIRING),
+            preset_switch: PresetSwitch::Cut,
+            switch_time: 0.0,
+            input: None,
+            input_settings: preset.input.clone(),
+            input_filters: [Biquad::identity(), Biquad::identity()],
+            vocoder: preset.vocoder.enabled.then(|| Vocoder::new(&preset.vocoder, sample_rate)),
+            reference: None,
+            binaural: None,
+            crossfeed: None,
+            blend: None,
+        };
+        synth.set_tempo(preset.tempo);
+        synth.mixer.settle(&synth.mixer_settings, &synth.parts);
+        synth.effects.set_oversampling(preset.oversampling);
+        for bus in &mut synth.sends {
+            bus.set_oversampling(preset.oversampling);
+        }
+        for index in 0..synth.macros.len() {
+            synth.load_macro(index);
+        }
+        // Parameters glide from where the preset and its macros put them, whether smoothed
+        // or sent with `GlideParam`
+        for (path, value) in preset.params() {
+            synth.glides.start_at(&path, value);
+        }
+        synth.glides.start_at("pitch.bend", 0.0);
+        synth.glides.start_at("pitch.wheel", 0.0);
+        synth.prepare_voices();
+        synth
+    }
+
+    // Switches to another preset while playing. Sounding notes carry on with the sound
+    // they started with and are released as usual, while the effects, macros and the
+    // rest start afresh from `preset`. The tempo, metronome, waveform, velocity, bend
+    // and voice settings stay as they were, being the player's rather than the sound's.
+    // Voices of parts the new preset doesn't have stop at once. Building the preset
+    // allocates, so a synth on the audio thread is sent presets already built, through
+    // `switch::build_presets`.
+    pub fn load_preset(&mut self, preset: &Preset) {
+        let new = Box::new(Self::new(self.sample_rate, preset, mpsc::channel().1));
+        self.switch_preset(new, self.preset_switch, self.switch_time, None);
+    }
+
+    // Switches preset like `load_preset`, but keeps the old effects running on the same
+    // signal and crossfades the output from them to the new ones over `seconds`, so
+    // reverb and delay tails carry on and a different chain doesn't jump in
+    pub fn crossfade_preset(&mut self, preset: &Preset, seconds: f32) {
+        let new = Box::new(Self::new(self.sample_rate, preset, mpsc::channel().1));
+        self.switch_preset(new, PresetSwitch::Crossfade, seconds, None);
+    }
+
+    // Switches to a preset built off the audio thread, as its command says, and hands the
+    // synth switched away from back for the builder to drop, once it has stopped playing
+    fn switch_built(&mut self, built: &BuiltPreset) {
+        let Some(new) = built.take() else { return };
+        let (switch, seconds) = match built.switch {
+            BuiltSwitch::Crossfade(seconds) => (PresetSwitch::Crossfade, seconds),
+            BuiltSwitch::Load | BuiltSwitch::Queue => (self.preset_switch, self.switch_time),
+        };
+        self.switch_preset(new, switch, seconds, Some(built));
+    }
+
+    // Plays a preset command, either switching now or queueing it for the next note
+    fn switch_command(&mut self, command: SynthCommand) {
+        match command {
+            SynthCommand::LoadPreset(preset) => self.load_preset(&preset),
+            SynthCommand::CrossfadePreset(preset, seconds) => self.crossfade_preset(&preset, seconds),
+            SynthCommand::Built(built) => self.switch_built(&built),
+            _ => {}
+        }
+    }
+
+    // Swaps `new` in. With `Keep`, the old synth is kept whole to play out the notes it
+    // has, taking only their releases from then on, until it falls silent (see `Retiring`);
+    // with a crossfade its effects are kept until they've faded. Otherwise the voices move
+    // across. The old synth ends up with `home`'s builder to drop, or is dropped here when
+    // there's none. The player's state carries over either way. The old presets still
+    // playing have room set aside for them, so when that's used up the switch is a cut.
+    fn switch_preset(&mut self, mut new: Box<Synthesizer>, switch: PresetSwitch, seconds: f32, home: Option<&BuiltPreset>) {
+        use std::mem::swap;
+        let sample_rate = self.sample_rate;
+        let switch = match switch {
+            PresetSwitch::Keep if self.retiring.len() >= MAX_RETIRING => {
+                self.diagnose(Diagnostic::SwitchCut(switch));
+                PresetSwitch::Cut
+            }
+            PresetSwitch::Crossfade if seconds > 0.0 && self.outgoing.len() >= MAX_OUTGOING => {
+                self.diagnose(Diagnostic::SwitchCut(switch));
+                PresetSwitch::Cut
+            }
+            switch => switch,
+        };
+        swap(self, &mut *new);
+        let mut old = new;
+        let priority = self.held_notes.priority(); // The new preset's, before the held notes move across
+        let keep = switch == PresetSwitch::Keep;
+        let (polyphony, policy) = (old.oscillators.polyphony(), old.oscillators.steal_policy());
+        swap(&mut self.command_receiver, &mut old.command_receiver);
+        swap(&mut self.clock, &mut old.clock);
+        swap(&mut self.scheduled, &mut old.scheduled);
+        self.next_at = old.next_at.take();
+        self.preset_switch = old.preset_switch;
+        self.switch_time = old.switch_time;
+        self.waveform = old.waveform;
+        self.velocity = old.velocity;
+        self.bend_semitones = old.bend_semitones;
+        self.wheel = old.wheel;
+        self.update_bend();
+        self.glides.start_at("pitch.bend", old.bend_semitones);
+        self.glides.start_at("pitch.wheel", old.wheel);
+        swap(&mut self.param_bank, &mut old.param_bank);
+        self.fade = old.fade.take();
+        self.set_tempo(old.tempo);
+        swap(&mut self.metronome, &mut old.metronome);
+        self.reference = old.reference.take();
+        self.binaural = old.binaural.take();
+        self.crossfeed = old.crossfeed.take();
+        self.pressure.carry(&old.pressure);
+        self.controllers.carry(&old.controllers);
+        self.surround.set_layout(old.surround.layout());
+        self.blend = old.blend.take();
+        self.hold = old.hold;
+        self.latch = old.latch;
+        if !keep {
+            swap(&mut self.oscillators, &mut old.oscillators);
+            self.oscillators.take_spares(&mut old.oscillators); // This preset's, also for the voices come across
+            swap(&mut self.held_notes, &mut old.held_notes);
+            self.mono_note = old.mono_note;
+            swap(&mut self.sustained, &mut old.sustained);
+            swap(&mut self.latched, &mut old.latched);
+            swap(&mut self.scaled_notes, &mut old.scaled_notes);
+            swap(&mut self.chord_voices, &mut old.chord_voices);
+        }
+        self.held_notes.set_priority(priority);
+        swap(&mut self.mixer, &mut old.mixer); // The master volume stays, the rest glide to the new levels
+        swap(&mut self.snapshots, &mut old.snapshots);
+        self.snapshot_countdown = old.snapshot_countdown;
+        swap(&mut self.diagnostics, &mut old.diagnostics);
+        swap(&mut self.meter, &mut old.meter);
+        swap(&mut self.scope, &mut old.scope);
+        swap(&mut self.recordings, &mut old.recordings);
+        self.pending_right = old.pending_right.take();
+        self.input = old.input.take();
+        swap(&mut self.outgoing, &mut old.outgoing);
+        swap(&mut self.retiring, &mut old.retiring);
+        self.tune_input_filters();
+
+        for (part, old_part) in self.parts.iter_mut().zip(&mut old.parts).filter(|_| !keep) {
+            swap(&mut part.voices, &mut old_part.voices);
+        }
+        for voices in std::iter::once(&mut self.oscillators).chain(self.parts.iter_mut().map(|part| &mut part.voices)) {
+            voices.set_polyphony(polyphony);
+            voices.set_steal_policy(policy);
+        }
+
+        match switch {
+            PresetSwitch::Keep => {
+                old.metronome = Metronome::new(Default::default(), old.tempo, sample_rate); // Off, so only one clicks
+                self.retiring.push(Retiring::new(old, home.cloned(), sample_rate));
+                Retiring::make_room(&mut self.retiring, sample_rate);
+            }
+            PresetSwitch::Crossfade if seconds > 0.0 => {
+                let step = 1.0 / (seconds * sample_rate as f32);
+                self.outgoing.push(Outgoing { synth: old, home: home.cloned(), gain: 1.0, step });
+            }
+            _ => {
+                if let Some(home) = home {
+                    home.give_back(old);
+                }
+            }
+        }
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.tempo = tempo;
+        self.effects.set_tempo(tempo);
+        for bus in &mut self.sends {
+            bus.set_tempo(tempo);
+        }
+        self.voice_effects.set_tempo(tempo);
+        for chain in self.oscillators.effects_mut() {
+            chain.set_tempo(tempo);
+        }
+        self.metronome.set_tempo(tempo);
+        self.surround.set_tempo(tempo);
+        self.pressure.set_tempo(tempo);
+        self.vibrato_lfo.set_rate(self.vibrato.rate.hz(tempo));
+        // Keeps tempo-synced LFOs locked to the beat
+        self.morph_lfo.set_rate(self.morph.lfo_rate.hz(tempo));
+        self.pulse_lfo.set_rate(self.pulse.lfo_rate.hz(tempo));
+        self.filter_lfo.set_rate(self.filter.lfo_rate.hz(tempo));
+        self.formant_lfo.set_rate(self.formant.lfo_rate.hz(tempo));
+    }
+
+    pub fn with_waveform(mut self, waveform: Waveform) -> Self {
+        self.set_waveform(waveform);
+        self
+    }
+
+    // Takes effect for notes started from now on
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    // Which voice a new note takes over when all of them are sounding, for the main
+    // patch and the parts alike
+    pub fn with_steal_policy(mut self, policy: StealPolicy) -> Self {
+        self.oscillators.set_steal_policy(policy);
+        for part in &mut self.parts {
+            part.voices.set_steal_policy(policy);
+        }
+        self
+    }
+
+    pub fn with_polyphony(mut self, polyphony: usize) -> Self {
+        self.oscillators.set_polyphony(polyphony);
+        for part in &mut self.parts {
+            part.voices.set_polyphony(polyphony);
+        }
+        self.prepare_voices();
+        self
+    }
+
+    // Makes the parts needing memory that each voice of the main patch may take as it
+    // starts, for as many as can sound at once
+    fn prepare_voices(&mut self) {
+        let (flute, sample_rate, effects) = (self.flute.enabled, self.sample_rate, &self.voice_effects);
+        self.oscillators.prepare(|| VoiceParts {
+            flute: flute.then(|| Flute::new(sample_rate, 0)),
+            effects: (!effects.is_empty()).then(|| effects.build()),
+        });
+    }
+
+    // Makes the synth follow the parameters set in `bank`, as if each change were a `SetParam`
+    pub fn with_param_bank(mut self, bank: Arc<ParamBank>) -> Self {
+        if let Some(blend) = &mut self.blend {
+            blend.bind(&bank);
+        }
+        self.param_bank = Some(bank);
+        self
+    }
+
+    // What switching preset does with the notes sounding, and for a crossfade, how long it takes
+    pub fn with_preset_switch(mut self, switch: PresetSwitch, seconds: f32) -> Self {
+        self.preset_switch = switch;
+        self.switch_time = seconds.max(0.0);
+        self
+    }
+
+    // Lets the `blend.amount` parameter move the sound between two presets, the one the
+    // synth starts with usually being `blend`'s A
+    pub fn with_blend(mut self, mut blend: PresetBlend) -> Self {
+        if let Some(bank) = &self.param_bank {
+            blend.bind(bank);
+        }
+        self.blend = Some(blend);
+        self
+    }
+
+    // Mixes live audio from `input` in with the voices, ahead of the effects
+    pub fn with_input(mut self, input: InputRing) -> Self {
+        self.input = Some(input);
+        self.tune_input_filters();
+        self
+    }
+
+    // Makes the synth send state snapshots for a user interface to `sender`
+    pub fn with_snapshots(mut self, sender: mpsc::SyncSender<Snapshot>) -> Self {
+        self.snapshots = Some(sender);
+        self
+    }
+
+    // Makes the synth queue what it would log in `sender` (see `Diagnostic`)
+    pub fn with_diagnostics(mut self, sender: mpsc::SyncSender<Diagnostic>) -> Self {
+        self.diagnostics = Some(sender);
+        self
+    }
+
+    // Queues `diagnostic` for the log, or drops it if the log is behind or not kept
+    fn diagnose(&self, diagnostic: Diagnostic) {
+        if let Some(sender) = &self.diagnostics {
+            let _ = sender.try_send(diagnostic);
+        }
+    }
+
+    // Makes the synth copy its (mono-summed) output into `tap` for an oscilloscope
+    pub fn with_scope(mut self, tap: ScopeTap) -> Self {
+        self.scope = Some(tap);
+        self
+    }
+
+    // Adds a tap on the output; a recording and a network stream can run together
+    pub fn with_recording(mut self, tap: RecordTap) -> Self {
+        self.recordings.push(tap);
+        self
+    }
+
+    // Plays through a surround `layout`, taking the frames from `surround_output`. The
+    // stereo mix goes on being rendered for meters, scopes and recordings.
+    pub fn with_surround(mut self, layout: Layout) -> Self {
+        self.surround.set_layout(layout);
+        self
+    }
+
+    // Feeds each channel some of the other, for listening on headphones (see `Crossfeed`)
+    pub fn with_crossfeed(mut self, level: CrossfeedLevel) -> Self {
+        self.crossfeed = Some(Crossfeed::new(level, self.sample_rate));
+        self
+    }
+
+    // The last frame rendered, a sample per channel, when playing in surround
+    pub fn surround_output(&self) -> Option<&[f32]> {
+        self.surround.is_active().then(|| self.surround.output())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn update_snapshot(&mut self, output: Frame) {
+        let Some(sender) = &self.snapshots else { return };
+        self.meter.add(output);
+
+        if self.snapshot_countdown > 0 {
+            self.snapshot_countdown -= 1;
+            return;
+        }
+        self.snapshot_countdown = self.sample_rate / SNAPSHOTS_PER_SECOND;
+
+        let notes = self.oscillators.iter().filter(|osc| !osc.is_releasing()).map(|osc| osc.note).collect();
+        // A full channel just means the UI is behind; this snapshot is skipped
+        let _ = sender.try_send(self.meter.snapshot(notes));
+    }
+
+    // Sets a parameter by its path: "envelope.<name>", "split.<name>" (the lower zone's
+    // envelope), "pitch_envelope.<name>", "voice_lfo.<name>", "pressure.<name>", "vibrato.<name>", "layer.<name>", "morph.<name>", "pulse.<name>",
+    // "mono.<name>", "fold.<name>", "flute.<name>", "organ.<name>", "chip.<name>", "filter.<name>", "formant.<name>", "parts.<index>.<name>", "eq.<name>", "stereo.<name>", "surround.<name>",
+    // "input.<name>", "vocoder.<name>", "mixer.<name>" (the main patch's volume, mute and solo), "master.volume",
+    // "effects.<slot>.<name>", "voice_effects.<slot>.<name>", "sends.<index>.<name>" (see `SendBus::set_param`), "pitch.bend" (in semitones), "pitch.wheel" (-1.0..1.0, as
+    // from a MIDI pitch-bend wheel), "pitch.bend_range" (the wheel's range in semitones)
+    pub fn set_param(&mut self, path: &str, value: f32) -> bool {
+        let mut parts = path.split('.');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("pitch"), Some(name @ ("bend" | "wheel" | "bend_range")), None) => {
+                match name {
+                    "bend" => self.bend_semitones = value.clamp(-24.0, 24.0),
+                    "wheel" => self.wheel = value.clamp(-1.0, 1.0),
+                    _ => self.bend_range = value.clamp(MIN_BEND_RANGE, MAX_BEND_RANGE),
+                }
+                self.update_bend();
+                true
+            }
+            (Some(zone @ ("envelope" | "split")), Some(name), None) => {
+                let lower = zone == "split";
+                let envelope = if lower { &mut self.split.lower.envelope } else { &mut self.envelope };
+                match name {
+                    "attack" => envelope.attack = value,
+                    "release" => envelope.release = value,
+                    _ => return false,
+                }
+                for osc in self.oscillators.iter_mut().filter(|osc| osc.lower_zone == lower) {
+                    osc.set_envelope(envelope);
+                }
+                true
+            }
+            (Some("layer"), Some(name), None) => self.layer.set_param(name, value),
+            (Some("morph"), Some(name), None) => {
+                let known = self.morph.set_param(name, value);
+                self.morph_lfo.set_rate(self.morph.lfo_rate.hz(self.tempo));
+                known
+            }
+            (Some("fold"), Some(name), None) => self.fold.set_param(name, value),
+            (Some("flute"), Some(name), None) => self.flute.set_param(name, value),
+            (Some("organ"), Some(name), None) => self.organ.set_param(name, value),
+            (Some("chip"), Some(name), None) => self.chip.set_param(name, value),
+            (Some("filter"), Some(name), None) => {
+                if !self.filter.set_param(name, value) {
+                    return false;
+                }
+                self.filter_lfo.set_rate(self.filter.lfo_rate.hz(self.tempo));
+                for osc in self.oscillators.iter_mut() {
+                    osc.set_filter(&self.filter, self.filter_octaves);
+                }
+                self.tune_input_filters();
+                true
+            }
+            (Some("formant"), Some(name), None) => {
+                let known = self.formant.set_param(name, value);
+                self.formant_lfo.set_rate(self.formant.lfo_rate.hz(self.tempo));
+                known
+            }
+            (Some("mono"), Some(name), None) => self.mono.set_param(name, value),
+            (Some("pitch_envelope"), Some(name), None) => self.pitch_envelope.set_param(name, value),
+            (Some("voice_lfo"), Some(name), None) => self.voice_lfo.set_param(name, value),
+            (Some("pressure"), Some(name), None) => self.pressure.set_param(name, value, self.tempo),
+            (Some("vibrato"), Some(name), None) => {
+                let known = self.vibrato.set_param(name, value);
+                self.vibrato_lfo.set_rate(self.vibrato.rate.hz(self.tempo));
+                known
+            }
+            (Some("controllers"), Some(name), None) => self.controllers.set(name, value),
+            (Some("pulse"), Some(name), None) => {
+                let known = self.pulse.set_param(name, value);
+                self.pulse_lfo.set_rate(self.pulse.lfo_rate.hz(self.tempo));
+                known
+            }
+            (Some("parts"), Some(index), Some(name)) => match index.parse::<usize>().ok().and_then(|index| self.parts.get_mut(index)) {
+                Some(part) => part.set_param(name, value),
+                None => false,
+            },
+            (Some("mixer"), Some(name), None) => self.mixer_settings.set_param(name, value),
+            (Some("master"), Some("volume"), None) => {
+                self.mixer.set_master(value);
+                true
+            }
+            (Some("master"), Some("expression"), None) => {
+                self.mixer.set_expression(value);
+                true
+            }
+            (Some("eq"), Some(name), None) => self.eq.set_param(name, value),
+            (Some("surround"), Some(name), None) => self.surround.set_param(name, value, self.tempo),
+            (Some("input"), Some(name), None) => self.input_settings.set_param(name, value),
+            (Some("vocoder"), Some(name), None) => self.vocoder.as_mut().is_some_and(|vocoder| vocoder.set_param(name, value)),
+            (Some("stereo"), Some(name), None) => self.widener.set_param(name, value),
+            (Some("effects"), Some(slot), Some(name)) => match slot.parse() {
+                Ok(slot) => self.effects.set_param(slot, name, value),
+                Err(_) => false,
+            },
+            (Some("voice_effects"), Some(slot), Some(name)) => match slot.parse() {
+                Ok(slot) if self.voice_effects.set_param(slot, name, value) => {
+                    for chain in self.oscillators.effects_mut() {
+                        chain.set_param(slot, name, value);
+                    }
+                    true
+                }
+                _ => false,
+            },
+            (Some("sends"), Some(index), Some(name)) => match index.parse::<usize>().ok().and_then(|index| self.sends.get_mut(index)) {
+                Some(bus) => bus.set_param(name, parts.next(), parts.next(), value),
+                None => false,
+            },
+            // Taken out while its parameters are set, which glide like a macro's and are
+            // written back to the bank so the views follow them
+            (Some("blend"), Some("amount"), None) => match self.blend.take() {
+                Some(blend) => {
+                    for (path, slot, value) in blend.at(value) {
+                        self.set_param_smoothed(path, value);
+                        if let (Some(bank), Some(slot)) = (&self.param_bank, slot) {
+                            bank.show(slot, value);
+                        }
+                    }
+                    self.blend = Some(blend);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Every voice, the parts' included, follows the one bend
+    fn update_bend(&mut self) {
+        self.bend = 2.0_f32.powf((self.bend_semitones + self.wheel * self.bend_range) / 12.0);
+    }
+
+    // Sets a parameter like `set_param`, but glides to the value when it's one of those
+    // that zipper audibly when stepped (see `SmoothingSettings::applies_to`)
+    pub fn set_param_smoothed(&mut self, path: &str, value: f32) -> bool {
+        if self.smoothing.applies_to(path) && self.glides.set(path, value) {
+            return true;
+        }
+        self.set_param(path, value)
+    }
+
+    pub fn set_macro(&mut self, index: usize, value: f32) {
+        let Some(settings) = self.macros.get_mut(index) else { return };
+        settings.value = value.clamp(0.0, 1.0);
+        for target in 0..settings.targets.len() {
+            let settings = &self.macros[index];
+            let (id, target) = &settings.targets[target];
+            let (id, value) = (*id, target.value_at(settings.value));
+            self.set_param_smoothed(id.as_str(), value);
+        }
+    }
+
+    // Puts macro `index` where the preset has it. Targets the synth turns out to have no
+    // parameter for are reported and dropped here, as the preset loads, so moving the
+    // macro later never meets them.
+    fn load_macro(&mut self, index: usize) {
+        let mut targets = std::mem::take(&mut self.macros[index].targets);
+        let value = self.macros[index].value;
+        targets.retain(|(id, target)| {
+            let known = self.set_param_smoothed(id.as_str(), target.value_at(value));
+            if !known {
+                macros::report_unknown(index, id.as_str(), &format!("unknown parameter '{}'", id));
+            }
+            known
+        });
+        self.macros[index].targets = targets;
+    }
+
+    pub fn note_on(&mut self, note: u8, waveform: Waveform) {
+        self.parts_note_on(1, note);
+
+        let note = if self.scale.enabled {
+            let snapped = self.scale.quantize(note);
+            self.scaled_notes.insert(note, snapped);
+            snapped
+        } else {
+            note
+        };
+
+        if self.mono.enabled {
+            self.mono_note_on(note, waveform);
+            return;
+        }
+
+        if self.chord.enabled || self.harmony.enabled {
+            let played = if self.chord.enabled { self.chord.expand(note) } else { [note].into_iter().collect() };
+            for chord_note in played.iter() {
+                self.start_voice(chord_note, waveform);
+            }
+            let mut notes = played;
+            if self.harmony.enabled {
+                let velocity = self.velocity;
+                self.velocity *= self.harmony.level.clamp(0.0, 1.0);
+                for harmony_note in self.harmony.expand(played, &self.scale).iter().filter(|&n| !played.contains(n)) {
+                    self.start_voice(harmony_note, waveform);
+                    notes.insert(harmony_note);
+                }
+                self.velocity = velocity;
+            }
+            self.chord_voices.insert(note, notes);
+            return;
+        }
+
+        self.start_voice(note, waveform);
+    }
+
+    // The pitch a voice on `note` starts at, with the detune of a humanized note
+    fn voice_frequency(&self, note: u8) -> f32 {
+        frequency_from_note(note) * 2.0_f32.powf(self.detune / 12.0)
+    }
+
+    fn start_voice(&mut self, note: u8, waveform: Waveform) {
+        let freq = self.voice_frequency(note);
+        let lower = self.split.is_lower(note);
+        let (waveform, envelope) = if lower {
+            (self.split.lower.waveform, &self.split.lower.envelope)
+        } else {
+            (waveform, &self.envelope)
+        };
+        // A voice still held on this note is played again in place. One in its release is
+        // only taken over when the envelope should carry on; on a restart it rings out
+        // under the new voice, so fast repeated notes keep their tails.
+        let existing = self
+            .oscillators
+            .newest(note)
+            .filter(|osc| !osc.is_releasing() || envelope.retrigger != Retrigger::Restart);
+        if let Some(osc) = existing {
+            let releasing = osc.is_releasing();
+            osc.retrigger(freq, envelope.retrigger);
+            osc.set_filter(&self.filter, self.filter_octaves);
+            osc.set_envelope(envelope); // In case it was fading out after a panic
+            osc.velocity = self.velocity;
+            self.diagnose(Diagnostic::VoiceRetriggered { note, releasing });
+        } else {
+            let mut osc = Oscillator::new(freq, waveform, envelope, self.sample_rate);
+            osc.note = note;
+            osc.pan = self.panner.next_pan(freq);
+            osc.velocity = self.velocity;
+            osc.lfo = Some(self.voice_lfo());
+            osc.lower_zone = lower;
+            osc.set_filter(&self.filter, self.filter_octaves);
+            osc.fold_oversampler = Oversampler::new(self.oversampling);
+            self.assign_chip_channel(&mut osc);
+            match self.oscillators.start(osc) {
+                Started::Free => {}
+                Started::Stole { note, releasing } => {
+                    self.diagnose(Diagnostic::VoiceStolen { note, releasing, policy: self.oscillators.steal_policy() })
+                }
+                Started::Refused => {
+                    self.diagnose(Diagnostic::NoteDropped { note, polyphony: self.oscillators.polyphony() });
+                    return;
+                }
+            }
+            self.diagnose(Diagnostic::VoiceStarted { note, voices: self.oscillators.len() });
+        }
+    }
+    
+    // A new voice's own LFO, at a rate and phase of its own. Voices get one whether or not
+    // it moves anything yet, so a depth raised while they play moves them too.
+    fn voice_lfo(&mut self) -> Lfo {
+        let mut random = || {
+            self.voice_seed ^= self.voice_seed << 13;
+            self.voice_seed ^= self.voice_seed >> 17;
+            self.voice_seed ^= self.voice_seed << 5;
+            self.voice_seed as f32 / u32::MAX as f32
+        };
+        let random = [random(), random()];
+        self.voice_lfo.lfo(random, self.tempo, self.sample_rate)
+    }
+
+    // A key's own aftertouch, for the voices it's playing
+    fn poly_pressure(&mut self, note: u8, value: f32) {
+        let note = self.scaled_notes.get(&note).copied().unwrap_or(note);
+        for osc in self.oscillators.iter_mut().filter(|osc| osc.note == note) {
+            osc.pressure = value.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        self.parts_note_off(1, note);
+
+        // Release whatever the note was snapped to when it started, even if the scale changed since
+        let note = self.scaled_notes.remove(&note).unwrap_or(note);
+
+        if self.mono.enabled {
+            self.mono_note_off(note);
+            return;
+        }
+
+        if let Some(notes) = self.chord_voices.remove(&note) {
+            // Leave notes that another held chord is still sounding
+            for chord_note in notes.iter() {
+                if !self.chord_voices.values().any(|held| held.contains(chord_note)) {
+                    self.release_voice(chord_note);
+                }
+            }
+            return;
+        }
+
+        self.release_voice(note);
+    }
+
+    fn parts_note_on(&mut self, channel: u8, note: u8) {
+        for part in self.parts.iter_mut().filter(|part| part.plays(channel, note)) {
+            part.note_on(note, self.velocity, self.sample_rate);
+        }
+    }
+
+    fn parts_note_off(&mut self, channel: u8, note: u8) {
+        for part in self.parts.iter_mut().filter(|part| part.plays(channel, note)) {
+            part.note_off(note);
+        }
+    }
+
+    fn release_voice(&mut self, note: u8) {
+        if let Some(osc) = self.oscillators.newest(note) {
+            osc.start_release();
+        }
+    }
+
+    // In mono mode a single oscillator is moved between notes instead of starting a new one per note
+    fn mono_note_on(&mut self, note: u8, waveform: Waveform) {
+        self.held_notes.press(note);
+        if self.held_notes.winner() != Some(note) {
+            return; // A held note with higher priority keeps sounding
+        }
+        self.mono_play(note, waveform);
+    }
+
+    // Releasing the sounding note goes back to the held note that should sound next, as
+    // on a classic mono synth, so trills and fast runs don't drop out between notes. Only
+    // letting go of the last key releases the voice.
+    fn mono_note_off(&mut self, note: u8) {
+        self.held_notes.release(note);
+        if self.mono_note != Some(note) {
+            return; // Held under the sounding note, so nothing changes
+        }
+        match self.held_notes.winner() {
+            Some(previous) => self.mono_play(previous, self.waveform),
+            None => self.release_voice(note),
+        }
+    }
+
+    // Moves the mono voice to `note`, gliding if legato allows, or starts it
+    fn mono_play(&mut self, note: u8, waveform: Waveform) {
+        let freq = self.voice_frequency(note);
+        let previous = self.mono_note.and_then(|previous| self.oscillators.take_newest(previous));
+        let mut osc = match previous {
+            Some(mut osc) => {
+                let from = osc.frequency();
+                // Legato only applies while the previous note is still held (not releasing)
+                if self.mono.legato && !osc.is_releasing() {
+                    osc.set_frequency(freq);
+                } else {
+                    osc.retrigger(freq, self.envelope.retrigger);
+                    osc.set_envelope(&self.envelope);
+                    osc.velocity = self.velocity; // A legato note keeps the loudness of the one it glides from
+                }
+                // Glide slides there from the previous note's pitch, faster the harder it's played
+                let glide = if self.glide_on { self.mono.glide_time(self.velocity) } else { 0.0 };
+                if glide > 0.0 {
+                    osc.set_frequency(from);
+                    osc.glide_to(freq, glide);
+                }
+                osc
+            }
+            None => {
+                let mut osc = Oscillator::new(freq, waveform, &self.envelope, self.sample_rate);
+                osc.pan = self.panner.next_pan(freq);
+                osc.velocity = self.velocity;
+                osc.lfo = Some(self.voice_lfo());
+                osc.fold_oversampler = Oversampler::new(self.oversampling);
+                osc.note = note;
+                self.assign_chip_channel(&mut osc);
+                osc
+            }
+        };
+        osc.set_filter(&self.filter, self.filter_octaves); // Tracks the new note, even when gliding to it
+        osc.note = note;
+        self.oscillators.start(osc); // Any other voices are releasing, so there's always room
+        self.mono_note = Some(note);
+    }
+
+    // In 2A03 mode, puts a new voice on the noise channel if it's below the split, or else
+    // on the first of the pulses and the triangle with nothing held on it, or when all
+    // three are busy the next in turn. Whatever that channel was playing is cut off, as
+    // each of the chip's channels has only the one voice.
+    fn assign_chip_channel(&mut self, osc: &mut Oscillator) {
+        if !self.chip.enabled {
+            return;
+        }
+        let playing = |osc: &Oscillator, channel| osc.chip.as_ref().is_some_and(|chip| chip.channel() == channel);
+        let channel = if osc.note < self.chip.noise_below {
+            ChipChannel::Noise
+        } else {
+            let free = TONAL_CHANNELS.into_iter().find(|&channel| !self.oscillators.iter().any(|osc| !osc.is_releasing() && playing(osc, channel)));
+            free.unwrap_or_else(|| {
+                self.chip_steal = (self.chip_steal + 1) % TONAL_CHANNELS.len();
+                TONAL_CHANNELS[self.chip_steal]
+            })
+        };
+        for other in self.oscillators.iter_mut().filter(|other| playing(other, channel)) {
+            other.fade_out();
+        }
+        let chip = ChipVoice::new(channel);
+        osc.velocity = chip.quantize_velocity(osc.velocity);
+        osc.chip = Some(chip);
+    }
+
+    // Lets every note go into its release, as if hold, latch and all the keys were let go
+    pub(crate) fn release_all(&mut self) {
+        for osc in self.oscillators.iter_mut().chain(self.parts.iter_mut().flat_map(|part| part.voices.iter_mut())) {
+            if !osc.is_releasing() {
+                osc.start_release();
+            }
+        }
+        (self.hold, self.latch) = (false, false);
+        self.sustained.clear();
+        self.latched.clear();
+        self.chord_voices.clear();
+        self.scaled_notes.clear();
+        self.held_notes.clear();
+        self.mono_note = None;
+    }
+
+    // Silences everything quickly but without a click, for stuck notes
+    pub fn panic(&mut self) {
+        for osc in self.oscillators.iter_mut() {
+            osc.fade_out();
+        }
+        for part in &mut self.parts {
+            part.panic();
+        }
+        self.sustained.clear();
+        self.latched.clear();
+        self.chord_voices.clear();
+        self.scaled_notes.clear();
+        self.held_notes.clear();
+        self.mono_note = None;
+    }
+
+    fn process_commands(&mut self) {
+        while let Ok(command) = self.command_receiver.try_recv() {
+            self.handle_command(command);
+        }
+        let now = self.clock.now();
+        while self.scheduled.front().is_some_and(|&(at, _)| at <= now) {
+            let (_, command) = self.scheduled.pop_front().expect("checked");
+            self.run_command(command);
+        }
+    }
+
+    // Commands for later wait their turn, after any already waiting for the same frame.
+    // The queue has its room from the start, so one that's full refuses the command
+    // rather than growing on the audio thread.
+    fn schedule(&mut self, frame: u64, command: SynthCommand) {
+        if frame <= self.clock.now() {
+            self.run_command(command);
+            return;
+        }
+        if self.scheduled.len() >= MAX_SCHEDULED {
+            self.diagnose(Diagnostic::ScheduleFull { frame });
+            return;
+        }
+        let index = self.scheduled.partition_point(|&(at, _)| at <= frame);
+        self.scheduled.insert(index, (frame, command));
+    }
+
+    // The synth's own timeline, for timing commands with `SynthCommand::At`
+    pub fn clock(&self) -> FrameClock {
+        self.clock.clone()
+    }
+
+    // Applies `command` at once, as if it had come through the command channel, for a
+    // host that plays the synth from its own audio thread (such as the plugin in plugin/)
+    pub fn handle_command(&mut self, command: SynthCommand) {
+        match (command, self.next_at.take()) {
+            (SynthCommand::AtNext(frame), _) => self.next_at = Some(frame),
+            (command, Some(frame)) => self.schedule(frame, command),
+            (command, None) => self.run_command(command),
+        }
+    }
+
+    fn run_command(&mut self, command: SynthCommand) {
+        // Presets switched away from with `Keep` still hear how their notes end, and take
+        // back a note they have latched when its key is pressed again
+        if !self.retiring.is_empty() {
+            if let SynthCommand::NoteOn(note) = command {
+                if let Some(old) = self.retiring.iter_mut().find(|old| old.synth.latched.contains(&note)) {
+                    old.synth.handle_command(command);
+                    return;
+                }
+            }
+            let ending = match &command {
+                SynthCommand::NoteOff(_) | SynthCommand::ChannelNoteOff(..) | SynthCommand::Panic => true,
+                SynthCommand::ToggleHold | SynthCommand::ToggleLatch | SynthCommand::ToggleGlide => true,
+                SynthCommand::SetParam(id, _) | SynthCommand::GlideParam(id, _) => id.as_str().starts_with("pitch."),
+                _ => false,
+            };
+            if ending {
+                for old in &mut self.retiring {
+                    old.synth.handle_command(command.clone());
+                }
+            }
+        }
+        if matches!(command, SynthCommand::NoteOn(_) | SynthCommand::ChannelNoteOn(..)) {
+            match self.pending_preset.take() {
+                Some(SynthCommand::QueuePreset(preset)) => self.load_preset(&preset),
+                Some(command) => self.switch_command(command),
+                None => {}
+            }
+        }
+        match command {
+            SynthCommand::NoteOn(note) | SynthCommand::ChannelNoteOn(1, note) => {
+                self.sustained.retain(|&sustained| sustained != note); // Played again, so it's held by the key now
+                let was_latched = self.latched.contains(&note);
+                self.latched.retain(|&latched| latched != note);
+                if self.latch && was_latched {
+                    self.note_off(note); // The second press stops it
+                } else {
+                    if self.latch {
+                        self.latched.push(note);
+                    }
+                    self.note_on(note, self.waveform);
+                }
+            }
+            SynthCommand::DetunedNoteOn(note, semitones) => {
+                self.detune = semitones.clamp(-1.0, 1.0);
+                self.handle_command(SynthCommand::NoteOn(note));
+                self.detune = 0.0;
+            }
+            SynthCommand::ScaledNoteOn(note, scale) => {
+                let velocity = self.velocity;
+                self.velocity *= scale.clamp(0.0, 1.0);
+                self.handle_command(SynthCommand::NoteOn(note));
+                self.velocity = velocity;
+            }
+            SynthCommand::VelocityNoteOn(note, velocity) => {
+                let kept = self.velocity;
+                self.velocity = velocity.clamp(0.0, 1.0);
+                self.handle_command(SynthCommand::NoteOn(note));
+                self.velocity = kept;
+            }
+            SynthCommand::NoteOff(note) | SynthCommand::ChannelNoteOff(1, note) => {
+                if self.latched.contains(&note) {
+                    // Latched notes only stop on a press
+                } else if self.hold {
+                    if !self.sustained.contains(&note) {
+                        self.sustained.push(note);
+                    }
+                } else {
+                    self.note_off(note);
+                }
+            }
+            SynthCommand::ToggleMetronome => {
+                self.metronome.toggle();
+            }
+            SynthCommand::Panic => {
+                self.panic();
+            }
+            SynthCommand::FadeOut => {
+                self.fade = Some(1.0);
+            }
+            SynthCommand::ToggleGlide => {
+                self.glide_on = !self.glide_on;
+            }
+            SynthCommand::ToggleLatch => {
+                // Latched notes keep droning after latch is turned off, until their key is
+                // played again, which hands the note back to the key
+                self.latch = !self.latch;
+            }
+            SynthCommand::ToggleHold => {
+                self.hold = !self.hold;
+                if !self.hold {
+                    // Taken out while the notes are released, then put back to keep its room
+                    let mut sustained = std::mem::take(&mut self.sustained);
+                    for &note in &sustained {
+                        self.note_off(note);
+                    }
+                    sustained.clear();
+                    self.sustained = sustained;
+                }
+            }
+            SynthCommand::SetTempo(tempo) => {
+                self.set_tempo(tempo);
+            }
+            SynthCommand::SetMacro(index, value) => {
+                self.set_macro(index, value);
+            }
+            SynthCommand::ChannelPressure(value) => {
+                self.pressure.set_channel(value);
+            }
+            SynthCommand::PolyPressure(note, value) => {
+                self.poly_pressure(note, value);
+            }
+            SynthCommand::SetParam(id, value) => {
+                if !self.set_param_smoothed(id.as_str(), value) {
+                    self.diagnose(Diagnostic::UnknownParam(id));
+                }
+            }
+            SynthCommand::SetVelocity(velocity) => {
+                self.velocity = self.velocity_curve.apply(velocity);
+            }
+            SynthCommand::SetReference(Some(settings)) => {
+                self.reference = Some(ReferenceTone::new(settings, self.sample_rate));
+            }
+            SynthCommand::SetReference(None) => {
+                if let Some(tone) = &mut self.reference {
+                    tone.stop();
+                }
+            }
+            // Only reached without `build_presets` in front, as when rendering offline; the
+            // builder sends `AtNext` instead, so the box isn't freed on the audio thread
+            SynthCommand::At(frame, command) => {
+                self.schedule(frame, *command);
+            }
+            SynthCommand::AtNext(frame) => {
+                self.next_at = Some(frame);
+            }
+            SynthCommand::SetBinaural(Some(settings)) => {
+                self.binaural = Some(BinauralTone::new(settings, self.sample_rate));
+            }
+            SynthCommand::SetBinaural(None) => {
+                if let Some(tone) = &mut self.binaural {
+                    tone.stop();
+                }
+            }
+            SynthCommand::GlideParam(id, value) => {
+                if !self.glides.set(id.as_str(), value) && !self.set_param(id.as_str(), value) {
+                    self.diagnose(Diagnostic::UnknownParam(id));
+                }
+            }
+            // Other channels only reach the parts, and hold is for the keyboard's channel
+            SynthCommand::ChannelNoteOn(channel, note) => {
+                self.parts_note_on(channel, note);
+            }
+            SynthCommand::ChannelNoteOff(channel, note) => {
+                self.parts_note_off(channel, note);
+            }
+            SynthCommand::QueuePreset(_) => {
+                self.pending_preset = Some(command);
+            }
+            SynthCommand::Built(ref built) if built.switch == BuiltSwitch::Queue => {
+                self.pending_preset = Some(command);
+            }
+            SynthCommand::LoadPreset(_) | SynthCommand::CrossfadePreset(..) | SynthCommand::Built(_) => {
+                self.pending_preset = None; // Overtaken by this one
+                self.switch_command(command);
+            }
+        }
+    }
+}
+
+struct Oscillator {
+    note: u8, // The note the voice plays, before pitch bend and glides
+    phase: Real,
+    phase_increment: Real,
+    layer_phase: Real, // Phase of the second oscillator, when the synth has one
+    pitch_sweep: f32, // What's left of the pitch envelope, from 1.0 at the start of the note to 0.0
+    filter: Biquad,   // The voice's own low-pass filter, tuned to its note
+    lfo: Option<Lfo>, // Its own drift LFO, which the main patch's voices have
+    drift: Option<(f32, f32, f32)>, // What that last did to it: a pitch ratio, a gain and a pan offset
+    pressure: f32,       // Its key's aftertouch, 0.0..1.0
+    pressure_level: f32, // That, smoothed
+    flute: Option<Flute>,             // Its pipe, given when it starts if the synth has them on
+    organ: Option<OrganVoice>,        // Its tonewheels, likewise
+    chip: Option<ChipVoice>,          // Its sound chip channel, given when it starts in 2A03 mode
+    formant: Option<FormantFilter>, // Its vowel filters, made on the first frame the synth has them on
+    effects: Option<EffectsChain>,  // Its insert effects, likewise (see `VoiceEffects`)
+    fold_oversampler: Oversampler, // Runs the wavefolder at a higher rate, if the preset asks
+    waveform: Waveform,
+    sample_rate: u32,
+    envelope: Envelope,   // Attack and release, from the preset's envelope settings
+    pan: f32,             // Stereo position, -1.0 (left) to 1.0 (right)
+    velocity: f32,        // Gain from how hard the note was played, 0.0 to 1.0
+    lower_zone: bool,     // Playing the split's lower patch, so the main envelope leaves it alone
+    glide_ratio: Real,    // What the phase increment is multiplied by each frame while gliding
+    glide_frames: u32,    // Frames of glide left, 0 when the pitch is steady
+    glide_target: Real,   // The phase increment the glide ends on
+}
+
+// A voice's parts that need memory, made for each voice slot when the preset loads and
+// handed from a voice that has finished to the next to start, so playing never allocates
+#[derive(Default)]
+pub(crate) struct VoiceParts {
+    flute: Option<Flute>,
+    effects: Option<EffectsChain>, // Kept up with edits and tempo like the voices' own
+}
+
+impl Oscillator {
+    // Fits the voice with those of `parts` it hasn't its own of from an earlier note,
+    // afresh for its note. Returns what's left, if anything.
+    pub(crate) fn fit(&mut self, mut parts: VoiceParts) -> Option<VoiceParts> {
+        let seed = self.seed();
+        if self.flute.is_none() {
+            self.flute = parts.flute.take().map(|mut flute| {
+                flute.reset(seed);
+                flute
+            });
+        }
+        if self.effects.is_none() {
+            self.effects = parts.effects.take().map(|mut chain| {
+                chain.reset();
+                chain
+            });
+        }
+        (parts.flute.is_some() || parts.effects.is_some()).then_some(parts)
+    }
+
+    // Takes the voice's parts back, for another voice
+    pub(crate) fn strip(&mut self) -> Option<VoiceParts> {
+        let parts = VoiceParts { flute: self.flute.take(), effects: self.effects.take() };
+        (parts.flute.is_some() || parts.effects.is_some()).then_some(parts)
+    }
+
+    // For its models' noise, the same for every voice on the same note
+    fn seed(&self) -> u32 {
+        (self.note as u32 + 1).wrapping_mul(0x9E37_79B9)
+    }
+
+    pub fn new(frequency: f32, waveform: Waveform, envelope: &EnvelopeSettings, sample_rate: u32) -> Self {
+        Self {
+            note: 0, // Set by the synthesizer
+            phase: 0.0,
+            phase_increment: 2.0 * precision::PI * Real::from(frequency) / sample_rate as Real,
+            layer_phase: 0.0,
+            pitch_sweep: 1.0,
+            filter: Biquad::identity(),
+            lfo: None,
+            drift: None,
+            pressure: 0.0,
+            pressure_level: 0.0,
+            flute: None,
+            organ: None,
+            chip: None,
+            formant: None,
+            effects: None,
+            fold_oversampler: Oversampler::new(Oversampling::Off), // Set by the synthesizer
+            waveform,
+            sample_rate,
+            envelope: Envelope::new(envelope, sample_rate), // Starts its attack from silence
+            pan: 0.0, // Centred until the synthesizer assigns a position
+            velocity: 1.0, // Full volume until the synthesizer says otherwise
+            lower_zone: false,
+            glide_ratio: 1.0,
+            glide_frames: 0,
+            glide_target: 0.0,
+        }
+    }
+
+    // Applies new attack and release times, also to a note that is already sounding
+    pub fn set_envelope(&mut self, envelope: &EnvelopeSettings) {
+        self.envelope.set_rates(envelope);
+    }
+
+    // Tunes the voice's filter to its current note (the one it's gliding to), keeping the filter's state
+    pub fn set_filter(&mut self, settings: &FilterSettings, octaves: f32) {
+        self.filter.set_coefficients(&settings.biquad(self.glide_end(), octaves, self.sample_rate));
+    }
+
+    // This function resets the oscillator phase to ensure smooth transition between notes
+    pub fn reset_phase(&mut self) {
+        self.phase = 0.0;
+        self.layer_phase = 0.0;
+    }
+
+    // Call this when a new note is played on the same key to ensure a smooth transition
+    pub fn restart(&mut self, frequency: f32) {
+        self.set_frequency(frequency);
+        self.reset_phase(); // Reset phase to ensure there's no click
+        self.envelope.restart(); // Start a new envelope from silence
+        self.pitch_sweep = 1.0;
+    }
+
+    // Plays the voice again at `frequency`, restarting its envelope or not as `mode` says
+    pub fn retrigger(&mut self, frequency: f32, mode: Retrigger) {
+        match mode {
+            Retrigger::Restart => self.restart(frequency),
+            Retrigger::Legato if !self.envelope.is_releasing() => self.set_frequency(frequency),
+            Retrigger::Continue | Retrigger::Legato => {
+                self.envelope.resume(); // The attack rises on from the current level
+                self.set_frequency(frequency);
+                self.pitch_sweep = 1.0;
+            }
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.phase_increment = 2.0 * precision::PI * Real::from(frequency) / self.sample_rate as Real;
+        self.glide_frames = 0;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        to_f32(self.phase_increment * self.sample_rate as Real / (2.0 * precision::PI))
+    }
+
+    // The frequency the voice is playing, or will be once its glide is over
+    fn glide_end(&self) -> f32 {
+        match self.glide_frames {
+            0 => self.frequency(),
+            _ => to_f32(self.glide_target * self.sample_rate as Real / (2.0 * precision::PI)),
+        }
+    }
+
+    // Slides from the current pitch to `frequency` over `seconds`, evenly in pitch
+    pub fn glide_to(&mut self, frequency: f32, seconds: f32) {
+        let from = self.phase_increment;
+        self.set_frequency(frequency);
+        self.glide_frames = (seconds * self.sample_rate as f32) as u32;
+        if self.glide_frames > 0 {
+            self.glide_target = self.phase_increment;
+            self.glide_ratio = (self.glide_target / from).powf(1.0 / self.glide_frames as Real);
+            self.phase_increment = from;
+        }
+    }
+
+    // Moves a glide on by a frame
+    fn advance_glide(&mut self) {
+        if self.glide_frames > 0 {
+            self.glide_frames -= 1;
+            self.phase_increment = if self.glide_frames == 0 { self.glide_target } else { self.phase_increment * self.glide_ratio };
+        }
+    }
+
+    pub fn start_release(&mut self) {
+        self.envelope.release(); // From wherever the attack had got to
+    }
+
+    // Releases the note over about 5 ms, whatever its envelope says
+    pub fn fade_out(&mut self) {
+        self.envelope.fade_out();
+    }
+
+    pub fn is_releasing(&self) -> bool {
+        self.envelope.is_releasing()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.envelope.is_finished()
+    }
+
+    // Where the envelope is, 0.0 (silent) to 1.0 (fully open), before this frame is applied
+    pub fn envelope_level(&self) -> f32 {
+        self.envelope.level()
+    }
+
+    pub fn apply_envelope(&mut self, sample: f32) -> f32 {
+        sample * self.envelope.next_level()
+    }
+}
+
+impl Synthesizer {
+    // A synth for offline rendering with `render`, which takes no commands from a channel
+    pub fn offline(sample_rate: u32, preset: &Preset) -> Self {
+        let (_, command_receiver) = mpsc::channel();
+        Self::new(sample_rate, preset, command_receiver)
+    }
+
+    // Renders `frames` stereo frames without an audio device or any threads, applying
+    // each command just before the frame it's timed at (in frames from the start, in
+    // order). The same synth state and commands always give the same output.
+    pub fn render(&mut self, commands: &[(usize, SynthCommand)], frames: usize) -> Vec<Frame> {
+        let mut pending = commands.iter().peekable();
+        (0..frames)
+            .map(|frame| {
+                while let Some((_, command)) = pending.next_if(|&&(at, _)| at <= frame) {
+                    self.handle_command(command.clone());
+                }
+                self.render_frame()
+            })
+            .collect()
+    }
+
+    // Renders one stereo frame: every oscillator is advanced exactly once
+    pub fn render_frame(&mut self) -> Frame {
+        // Process any pending SynthCommands (e.g., NoteOn, NoteOff)
+        self.process_commands();
+
+        // Edits made through the parameter bank since the last frame (through a second
+        // handle, so the blend can write back to the bank while they're applied)
+        if let Some(bank) = self.param_bank.clone() {
+            bank.drain(|path, value| {
+                self.set_param_smoothed(path, value);
+            });
+        }
+
+        // Move gliding parameters along (taken out while they're applied to the synth)
+        let mut glides = std::mem::take(&mut self.glides);
+        glides.advance(|path, value| {
+            self.set_param(path, value);
+        });
+        self.glides = glides;
+
+        // The mod wheel's and expression pedal's targets follow them as they settle
+        let mut controllers = std::mem::take(&mut self.controllers);
+        controllers.advance(|path, value| {
+            self.set_param(path, value);
+        });
+        self.controllers = controllers;
+
+        // Channel pressure's targets follow it as it settles (taken out while they're set)
+        if let Some(amount) = self.pressure.advance() {
+            let targets = std::mem::take(self.pressure.targets());
+            for target in &targets {
+                self.set_param(&target.param, target.value_at(amount));
+            }
+            *self.pressure.targets() = targets;
+        }
+
+        let mut frame_sum = [0.0; 2]; // This will accumulate the panned samples from all oscillators
+
+        // The filter LFO moves the voices' cutoff a block at a time, as retuning is costly
+        if self.filter.enabled && self.filter.lfo_depth > 0.0 {
+            let octaves = self.filter_lfo.next_value() * self.filter.lfo_depth;
+            if self.filter_countdown == 0 {
+                self.filter_countdown = FILTER_BLOCK;
+                self.filter_octaves = octaves;
+                for osc in self.oscillators.iter_mut() {
+                    osc.set_filter(&self.filter, octaves);
+                }
+                self.tune_input_filters();
+            }
+            self.filter_countdown -= 1;
+        }
+
+        // The vowel moves a block at a time too, each voice from where its envelope has it
+        let formant = self.formant.enabled.then(|| {
+            let vowel = self.formant.vowel + self.formant_lfo.next_value() * self.formant.lfo_depth;
+            let retune = self.formant_countdown == 0;
+            if retune {
+                self.formant_countdown = FILTER_BLOCK;
+            }
+            self.formant_countdown -= 1;
+            (vowel, self.formant.envelope, retune)
+        });
+
+        // Each voice's own LFO moves it a block at a time as well, sparing a powf per voice per frame
+        let voice_lfo = self.voice_lfo.is_active().then(|| {
+            let retune = self.voice_lfo_countdown == 0;
+            if retune {
+                self.voice_lfo_countdown = FILTER_BLOCK;
+            }
+            self.voice_lfo_countdown -= 1;
+            (&self.voice_lfo, retune)
+        });
+
+        // Vibrato moves every voice together, on top of the bend
+        let bend = self.bend * self.vibrato.ratio(self.vibrato_lfo.next_value());
+        let shape = VoiceShape {
+            bend,
+            layer: self.layer.enabled.then(|| (self.layer.waveform, self.layer.ratio(), self.layer.mix)),
+            morph: self.morph.enabled.then(|| {
+                let position = self.morph.position + self.morph_lfo.next_value() * self.morph.lfo_depth;
+                (position, self.morph.envelope)
+            }),
+            pulse_width: self.pulse.width + self.pulse_lfo.next_value() * self.pulse.lfo_depth,
+            fold: self.fold.enabled.then_some((self.fold.amount, self.fold.envelope)),
+            flute: self.flute.enabled.then(|| self.flute.clone()),
+            organ: self.organ.enabled.then(|| self.organ.clone()),
+            chip: self.chip.enabled.then_some(self.chip.duty),
+            formant,
+            filter: self.filter.enabled,
+            pitch: (self.pitch_envelope.depth != 0.0).then(|| (self.pitch_envelope.depth, self.pitch_envelope.rate(self.sample_rate))),
+            effects: (!self.voice_effects.is_empty()).then_some(&self.voice_effects),
+            voice_lfo,
+            pressure: self.pressure.voices(),
+        };
+
+        // Counts how many oscillators are contributing to the current frame
+        self.mixer.advance(&self.mixer_settings, &self.parts);
+        let mut surround = self.surround.is_active().then_some(&mut self.surround);
+        let mut active_oscillators = render_voices(&mut self.oscillators, &shape, self.mixer.main(), &mut frame_sum, surround.as_deref_mut());
+        let mut stems = [[0.0; 2]; MAX_PARTS + 1]; // Each channel on its own, for a recording's stems
+        stems[0] = frame_sum;
+        for bus in &mut self.sends {
+            bus.send(frame_sum, bus.amount(None));
+        }
+        // The parts are mixed in at their own volume (muted or soloed away at none), on
+        // the same bus as the main patch, and each sends its own share to the send buses
+        let plain = VoiceShape::plain(bend);
+        for (index, part) in self.parts.iter_mut().enumerate() {
+            let mut part_sum = [0.0; 2];
+            active_oscillators += render_voices(&mut part.voices, &plain, self.mixer.part(index), &mut part_sum, surround.as_deref_mut());
+            for bus in &mut self.sends {
+                bus.send(part_sum, bus.amount(Some(index)));
+            }
+            frame_sum = [frame_sum[LEFT] + part_sum[LEFT], frame_sum[RIGHT] + part_sum[RIGHT]];
+            stems[index + 1] = part_sum;
+        }
+
+        // Normalize the frame sum to prevent clipping; the levels are the mixer's from here
+        // on. If there are no active oscillators, this feeds silence (effect tails keep ringing).
+        let gain = if active_oscillators > 0 { 1.0 / active_oscillators as f32 } else { 0.0 };
+        let normalized_frame = frame_sum.map(|sample| sample * gain);
+
+        // Live input joins after the voices are balanced, so its level doesn't depend on how many play
+        let (normalized_frame, input) = self.mix_input(normalized_frame);
+
+        let mut returns = [0.0; 2]; // The send buses' effects, which surround plays from the front
+        for bus in &mut self.sends {
+            let frame = bus.process(gain);
+            returns = [returns[LEFT] + frame[LEFT], returns[RIGHT] + frame[RIGHT]];
+        }
+        // In surround the voices come from where they were placed, so the master chain only
+        // gets what the effects made of them, with the live input, to play from the front
+        let surround_input = self.surround.is_active().then_some(input);
+        let effected_frame = match surround_input {
+            Some(input) => surround_front(&mut self.effects, normalized_frame, input),
+            None => self.effects.process(normalized_frame),
+        };
+        let effected_frame = [effected_frame[LEFT] + returns[LEFT], effected_frame[RIGHT] + returns[RIGHT]];
+        let mut processed_frame = self.dc_blocker.process(self.eq.process(self.widener.process(effected_frame)));
+        if !self.outgoing.is_empty() {
+            let mut incoming = 1.0;
+            let mut outgoing = [0.0; 2];
+            for chain in &mut self.outgoing {
+                let frame = chain.process(normalized_frame, surround_input);
+                outgoing = [outgoing[LEFT] + frame[LEFT], outgoing[RIGHT] + frame[RIGHT]];
+                incoming -= chain.gain;
+            }
+            let incoming = incoming.max(0.0);
+            processed_frame = [processed_frame[LEFT] * incoming + outgoing[LEFT], processed_frame[RIGHT] * incoming + outgoing[RIGHT]];
+            for done in self.outgoing.extract_if(.., |chain| chain.gain <= 0.0) {
+                if let Some(home) = done.home {
+                    home.give_back(done.synth);
+                }
+            }
+        }
+        // Presets switched away from with `Keep` play out their notes through their own effects
+        if !self.retiring.is_empty() {
+            for old in &mut self.retiring {
+                let frame = old.render_frame();
+                processed_frame = [processed_frame[LEFT] + frame[LEFT], processed_frame[RIGHT] + frame[RIGHT]];
+            }
+            let sample_rate = self.sample_rate;
+            for done in self.retiring.extract_if(.., |old| old.is_done(sample_rate)) {
+                if let Some(home) = done.home {
+                    home.give_back(done.synth);
+                }
+            }
+        }
+
+        // The click is mixed in dry, after all the processing
+        let click = self.metronome.next_sample();
+        let mut dry = [click; 2];
+        if let Some(tone) = &mut self.reference {
+            let sample = tone.next_sample();
+            dry[LEFT] += sample;
+            dry[RIGHT] += sample;
+            if tone.is_finished() {
+                self.reference = None;
+            }
+        }
+        if let Some(tone) = &mut self.binaural {
+            let frame = tone.next_frame();
+            dry[LEFT] += frame[LEFT];
+            dry[RIGHT] += frame[RIGHT];
+            if tone.is_finished() {
+                self.binaural = None;
+            }
+        }
+        processed_frame[LEFT] += dry[LEFT];
+        processed_frame[RIGHT] += dry[RIGHT];
+        if let Some(crossfeed) = &mut self.crossfeed {
+            processed_frame = crossfeed.process(processed_frame);
+        }
+        // The front pair plays the master chain's output round the voices placed in the room.
+        // For meters, scopes and recordings, the stereo frame gets the voices back, dry.
+        if surround_input.is_some() {
+            self.surround.mix(gain, processed_frame, self.mixer.master_gain());
+            processed_frame = [processed_frame[LEFT] + frame_sum[LEFT] * gain, processed_frame[RIGHT] + frame_sum[RIGHT] * gain];
+        }
+
+        // The master volume is last, so it turns down the effects' tails and the click too
+        let processed_frame = processed_frame.map(|sample| sample * self.mixer.master_gain());
+
+        // Enforce soft clipping
+        self.update_snapshot(processed_frame); // Metered before the clamp, so clipping can be counted
+        let mut output = processed_frame.map(|sample| sample.clamp(-1.0, 1.0)); // Clamping the value to the range [-1.0, 1.0]
+        if let Some(fade) = &mut self.fade {
+            *fade = (*fade - 1.0 / (FADE_OUT_SECONDS * self.sample_rate as f32)).max(0.0);
+            output = output.map(|sample| sample * *fade);
+            self.surround.fade(*fade);
+        }
+        if let Some(scope) = &self.scope {
+            scope.push((output[LEFT] + output[RIGHT]) * 0.5);
+        }
+        for tap in &self.recordings {
+            let stems = if tap.stems() { stems.map(|stem| stem.map(|sample| sample * gain)) } else { Default::default() };
+            tap.push(RecordedFrame { mix: output, stems });
+        }
+        self.clock.advance();
+        output
+    }
+
+    fn tune_input_filters(&mut self) {
+        if self.input.is_none() {
+            return;
+        }
+        let biquad = self.filter.untracked_biquad(self.filter_octaves, self.sample_rate);
+        for filter in &mut self.input_filters {
+            filter.set_coefficients(&biquad);
+        }
+    }
+
+    // Adds the next frame of live input to the voices' `frame`, after the vocoder has
+    // shaped them with it, and keys the effects' sidechains with it. Returns the mix and
+    // the input as it went into it.
+    fn mix_input(&mut self, frame: Frame) -> (Frame, Frame) {
+        let Some(ring) = &self.input else { return (frame, [0.0; 2]) };
+        let input = ring.pop().unwrap_or_default(); // Silence until the device catches up
+        let mut mixed = match &mut self.vocoder {
+            Some(vocoder) => vocoder.process(frame, (input[LEFT] + input[RIGHT]) * 0.5),
+            None => frame,
+        };
+        self.effects.sidechain(input);
+        for bus in &mut self.sends {
+            bus.sidechain(input);
+        }
+        let filtered = self.filter.enabled && self.input_settings.filter;
+        let mut added = [0.0; 2];
+        for channel in [LEFT, RIGHT] {
+            let sample = if filtered { self.input_filters[channel].process(input[channel]) } else { input[channel] };
+            added[channel] = sample * self.input_settings.level;
+            mixed[channel] += added[channel];
+        }
+        (mixed, added)
+    }
+}
+
+// What `effects` play from the front in surround, where the voices in `frame` are placed
+// round the room on their own: everything the effects made of `frame`, and the live
+// `input` mixed into it at the share their dry/wet mixes pass through untouched
+fn surround_front(effects: &mut EffectsChain, frame: Frame, input: Frame) -> Frame {
+    let (dry, wet) = effects.process_apart(frame);
+    [wet[LEFT] + input[LEFT] * dry, wet[RIGHT] + input[RIGHT] * dry]
+}
+
+// How the voices are shaped this frame, worked out once for all of them
+struct VoiceShape<'a> {
+    bend: f32,                           // Pitch bend as a frequency ratio
+    layer: Option<(Waveform, f32, f32)>, // Second oscillator's waveform, pitch ratio and mix, if it's on
+    morph: Option<(f32, f32)>,           // Morph position and how far the envelope moves it, if it's on
+    pulse_width: f32,                    // Of the square wave
+    fold: Option<(f32, f32)>,            // Fold amount and how much the envelope adds, if it's on
+    flute: Option<FluteSettings>,        // How the pipes are blown, if they replace the oscillators
+    organ: Option<OrganSettings>,        // The drawbars, if the organ replaces the oscillators
+    chip: Option<[u8; 2]>,               // The pulses' duty cycles, in 2A03 mode
+    formant: Option<(f32, f32, bool)>,   // Vowel, how far the envelope moves it and whether to retune now, if it's on
+    filter: bool,                        // Whether the voices' filters are in use
+    pitch: Option<(f32, f32)>,           // Pitch envelope depth in semitones and its rate, if it has a depth
+    effects: Option<&'a VoiceEffects>,   // What each voice's insert effects are made from, if it has any
+    voice_lfo: Option<(&'a VoiceLfoSettings, bool)>, // What each voice's own LFO moves and whether to move it now, if they have one
+    pressure: Option<VoicePressure>,         // This frame's aftertouch, when it does anything to the voices
+}
+
+impl VoiceShape<'_> {
+    // Just the voice's own waveform, as the parts play
+    fn plain(bend: f32) -> Self {
+        Self {
+            bend,
+            layer: None,
+            morph: None,
+            pulse_width: 0.5,
+            fold: None,
+            flute: None,
+            organ: None,
+            chip: None,
+            formant: None,
+            filter: false,
+            pitch: None,
+            effects: None,
+            voice_lfo: None,
+            pressure: None,
+        }
+    }
+}
+
+// Advances every voice in `voices` by one frame, adding them into `frame_sum` (and placing
+// them in `surround`, when playing in surround) and removing those that have finished
+// their release. Returns how many are still sounding.
+fn render_voices(voices: &mut VoicePool, shape: &VoiceShape, gain: f32, frame_sum: &mut Frame, mut surround: Option<&mut Surround>) -> usize {
+    let mut active_oscillators = 0;
+
+    for osc in voices.iter_mut() {
+        // The pitch envelope bends each voice on top of the pitch bend everyone shares
+        let bend = match shape.pitch {
+            Some((depth, rate)) if osc.pitch_sweep > 0.0 => {
+                let ratio = 2.0_f32.powf(depth * osc.pitch_sweep / 12.0);
+                osc.pitch_sweep = (osc.pitch_sweep - rate).max(0.0);
+                shape.bend * ratio
+            }
+            _ => shape.bend,
+        };
+        // The voice's own drift, if it has some
+        let (ratio, drift_gain, pan_offset) = match (shape.voice_lfo, osc.lfo.as_mut()) {
+            (Some((settings, retune)), Some(lfo)) => {
+                let value = lfo.next_value();
+                if retune || osc.drift.is_none() {
+                    osc.drift = Some(settings.apply(value));
+                }
+                osc.drift.unwrap_or((1.0, 1.0, 0.0))
+            }
+            _ => (1.0, 1.0, 0.0),
+        };
+        // And its aftertouch, the key's own or the keyboard's, whichever is the more
+        let (vibrato, pressure_gain) = match &shape.pressure {
+            Some(pressure) => {
+                osc.pressure_level += (osc.pressure - osc.pressure_level) * pressure.coefficient;
+                pressure.apply(osc.pressure_level.max(pressure.channel))
+            }
+            None => (1.0, 1.0),
+        };
+        let bend = bend * ratio * vibrato;
+        let pan = (osc.pan + pan_offset).clamp(-1.0, 1.0);
+        let frequency = osc.frequency() * bend;
+        let mut osc_sample = if let Some(settings) = &shape.flute {
+            // The envelope is the breath here, as well as shaping the output below. Each
+            // voice is given its pipe as it starts (see `VoiceParts`).
+            let breath = settings.pressure * osc.envelope_level();
+            match &mut osc.flute {
+                Some(flute) => flute.next_sample(frequency, breath, settings, osc.sample_rate),
+                None => 0.0,
+            }
+        } else if let Some(settings) = &shape.organ {
+            let seed = osc.seed();
+            let organ = osc.organ.get_or_insert_with(|| OrganVoice::new(osc.sample_rate, seed));
+            organ.next_sample(frequency, settings, osc.sample_rate)
+        } else if let (Some(duty), Some(chip)) = (shape.chip, &mut osc.chip) {
+            chip.next_sample(osc.note, frequency, duty, osc.sample_rate)
+        } else {
+            match shape.morph {
+                Some((position, envelope)) => morph::sample(position + envelope * osc.envelope_level(), to_f32(osc.phase)),
+                None if matches!(osc.waveform, Waveform::Square) => pulse::sample(to_f32(osc.phase), to_f32(osc.phase_increment) * bend, shape.pulse_width),
+                None => osc.waveform.sample(to_f32(osc.phase)),
+            }
+        };
+        if let Some((waveform, ratio, mix)) = shape.layer {
+            osc_sample = osc_sample * (1.0 - mix) + waveform.sample(to_f32(osc.layer_phase)) * mix;
+            osc.layer_phase = (osc.layer_phase + osc.phase_increment * Real::from(bend * ratio)).rem_euclid(2.0 * precision::PI);
+        }
+        if let Some((amount, envelope)) = shape.fold {
+            let amount = amount + envelope * osc.envelope_level();
+            osc_sample = osc.fold_oversampler.process(osc_sample, |sample| fold::fold(sample, amount));
+        }
+        if let Some((vowel, envelope, retune)) = shape.formant {
+            let vowel = vowel + envelope * osc.envelope_level();
+            let formant = match &mut osc.formant {
+                Some(formant) if retune => {
+                    formant.tune(vowel, osc.sample_rate);
+                    formant
+                }
+                Some(formant) => formant,
+                None => osc.formant.insert(FormantFilter::new(vowel, osc.sample_rate)),
+            };
+            osc_sample = formant.process(osc_sample);
+        }
+        if shape.filter {
+            osc_sample = osc.filter.process(osc_sample);
+        }
+
+        // Envelop the oscillator's sample (handle attack and release)
+        let enveloped_sample = osc.apply_envelope(osc_sample) * osc.velocity * drift_gain * pressure_gain * gain;
+
+        // Oscillators that have completed their release are removed below
+        if !osc.is_finished() {
+            // Otherwise, run it through the voice's own effects, place it in the stereo
+            // field and accumulate it. The effects' tails end with the voice.
+            let frame = match shape.effects {
+                Some(effects) => osc.effects.get_or_insert_with(|| effects.build()).process([enveloped_sample; 2]),
+                None => [enveloped_sample; 2],
+            };
+            if let Some(surround) = surround.as_deref_mut() {
+                surround.place((frame[LEFT] + frame[RIGHT]) * 0.5, pan);
+            }
+            let (left_gain, right_gain) = pan_gains(pan);
+            frame_sum[LEFT] += frame[LEFT] * left_gain;
+            frame_sum[RIGHT] += frame[RIGHT] * right_gain;
+            active_oscillators += 1;
+        }
+
+        // Increment the oscillator's phase, wrapping around at 2π
+        osc.phase += osc.phase_increment * Real::from(bend);
+        if osc.phase > 2.0 * precision::PI {
+            osc.phase -= 2.0 * precision::PI;
+        }
+        osc.advance_glide();
+    }
+
+    // Free the slots of oscillators that have completed their release phase
+    voices.free_finished();
+    active_oscillators
+}
+
+// Iterator implementation for synthesizer, yielding interleaved left/right samples
+impl Iterator for Synthesizer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let [left, right] = self.render_frame();
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use rodio_synth::tempo::{BeatGrid, SharedTempo};
+use rodio_synth::SynthCommand;
+
+const MULTICAST: Ipv4Addr = Ipv4Addr::new(224, 76, 78, 75); // Where Link peers announce themselves
+const PORT: u16 = 20808;
+const MAX_MESSAGE: usize = 512;
+
+// The two protocols' headers: discovery of peers and their sessions, and measuring a
+// session's clock against ours
+const DISCOVERY: &[u8; 8] = b"_asdp_v\x01";
+const MEASUREMENT: &[u8; 8] = b"_link_v\x01";
+const ALIVE: u8 = 1;
+const RESPONSE: u8 = 2;
+const BYE_BYE: u8 = 3;
+const PING: u8 = 1;
+const PONG: u8 = 2;
+
+// Keys of the entries messages carry
+const TIMELINE: [u8; 4] = *b"tmln";
+const SESSION: [u8; 4] = *b"sess";
+const ENDPOINT: [u8; 4] = *b"mep4";
+const HOST_TIME: [u8; 4] = *b"__ht";
+const GHOST_TIME: [u8; 4] = *b"__gt";
+const PREV_GHOST_TIME: [u8; 4] = *b"_pgt";
+
+const TTL: u8 = 5; // Seconds peers keep us without hearing from us again
+const BROADCAST_INTERVAL: Duration = Duration::from_millis(250);
+const TICK: Duration = Duration::from_millis(20); // How soon a tempo set here goes out to the session
+const POINTS: usize = 40; // Offsets a measurement takes the median of
+const PING_TIMEOUT: Duration = Duration::from_millis(50); // Before a ping is taken as lost and sent again
+const MEASUREMENT_TIME: Duration = Duration::from_secs(1); // Before a measurement makes do with what it has
+const REMEASURE_INTERVAL: Duration = Duration::from_secs(30); // For clocks drifting apart
+const SESSION_EPS: i64 = 500_000; // Microseconds a session must be older by to be joined
+
+type NodeId = [u8; 8];
+
+// A session's tempo and where its beats fall, against its ghost time: the microseconds
+// since the session began, which every peer works out from its own clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Timeline {
+    micros_per_beat: i64,
+    beat_origin: i64, // In millionths of a beat, at `time_origin`
+    time_origin: i64,
+}
+
+impl Timeline {
+    fn new(tempo: f32, beat: f64, ghost: i64) -> Self {
+        Self {
+            micros_per_beat: (60e6 / tempo.clamp(20.0, 999.0) as f64).round() as i64,
+            beat_origin: (beat * 1e6).round() as i64,
+            time_origin: ghost,
+        }
+    }
+
+    fn tempo(&self) -> f64 {
+        60e6 / self.micros_per_beat as f64
+    }
+
+    fn beat_at(&self, ghost: i64) -> f64 {
+        (self.beat_origin as f64 + (ghost - self.time_origin) as f64 * 1e6 / self.micros_per_beat as f64) / 1e6
+    }
+}
+
+// What a node tells the others about itself
+#[derive(Clone, Debug, PartialEq)]
+struct NodeState {
+    ident: NodeId,
+    session: NodeId, // The ident of the node that began it
+    timeline: Timeline,
+    endpoint: Option<SocketAddrV4>, // Where it answers pings
+}
+
+#[derive(Debug, PartialEq)]
+enum Discovery {
+    Alive(NodeState),    // Sent to everyone, every so often
+    Response(NodeState), // Sent back to a node newly heard from
+    ByeBye(NodeId),
+}
+
+// A pong: the session of the node pinged and its ghost time as it answered, with our
+// host time as we pinged and the ghost time of the pong before, as the ping carried them
+#[derive(Debug, PartialEq)]
+struct Pong {
+    session: NodeId,
+    ghost: i64,
+    host: i64,
+    prev_ghost: Option<i64>,
+}
+
+// Measures how far a session's ghost time is from our host time by pinging one of its
+// peers: a pong's ghost time falls halfway between the ping leaving and the pong coming
+// back, and the median of many is taken against the network's jitter
+struct Measurement {
+    session: NodeId,
+    peer: SocketAddr,
+    started: Instant,
+    pinged: Instant,
+    offsets: Vec<f64>,
+    prev_ghost: Option<i64>,
+}
+
+impl Measurement {
+    fn add(&mut self, pong: &Pong, received: i64) {
+        if pong.session != self.session {
+            return;
+        }
+        self.offsets.push(pong.ghost as f64 - (pong.host + received) as f64 / 2.0);
+        if let Some(prev_ghost) = pong.prev_ghost {
+            self.offsets.push((pong.ghost + prev_ghost) as f64 / 2.0 - pong.host as f64);
+        }
+        self.prev_ghost = Some(pong.ghost);
+    }
+
+    fn is_done(&self, now: Instant) -> bool {
+        self.offsets.len() >= POINTS || now >= self.started + MEASUREMENT_TIME
+    }
+
+    // Ghost time less host time, if any pongs came back
+    fn offset(&mut self) -> Option<i64> {
+        self.offsets.sort_by(f64::total_cmp);
+        self.offsets.get(self.offsets.len() / 2).map(|&offset| offset.round() as i64)
+    }
+}
+
+// Whether to leave our session for one measured at `theirs` (ghost less host time): the
+// session that has been going longest wins, and between two begun together the lower id
+fn joins(ours: (NodeId, i64), theirs: (NodeId, i64)) -> bool {
+    let ahead = theirs.1 - ours.1;
+    ahead > SESSION_EPS || (ahead.abs() < SESSION_EPS && theirs.0 < ours.0)
+}
+
+// Locks the shared tempo, and with it the clock threads' steps, to an Ableton Link
+// session on the local network, joining the longest-running session found. A tempo set
+// here (tap tempo, a scene, OSC) is passed on to the session's other apps, and theirs
+// is sent to the synth. Link's start/stop sync is not taken part in.
+pub fn spawn(tempo: SharedTempo, output: mpsc::Sender<SynthCommand>) -> io::Result<()> {
+    let interface = local_address();
+    let multicast = multicast_socket(interface)?;
+    let socket = UdpSocket::bind((interface, 0))?;
+    let SocketAddr::V4(endpoint) = socket.local_addr()? else { unreachable!("bound to an IPv4 address") };
+
+    let (packets_tx, packets) = mpsc::channel();
+    for socket in [multicast, socket.try_clone()?] {
+        let packets_tx = packets_tx.clone();
+        thread::spawn(move || {
+            let mut buffer = [0; MAX_MESSAGE];
+            loop {
+                let Ok((length, from)) = socket.recv_from(&mut buffer) else { continue };
+                if packets_tx.send((buffer[..length].to_vec(), from, Instant::now())).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    let mut link = Link::new(tempo, output, socket, endpoint);
+    println!("Looking for Ableton Link peers on UDP port {}", PORT);
+    thread::spawn(move || {
+        let mut next_broadcast = Instant::now();
+        loop {
+            match packets.recv_timeout(TICK) {
+                Ok((bytes, from, at)) => link.receive(&bytes, from, at),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let now = Instant::now();
+            if link.follow_local_tempo(now) || now >= next_broadcast {
+                link.send(ALIVE, (MULTICAST, PORT).into());
+                next_broadcast = now + BROADCAST_INTERVAL;
+            }
+            link.measure(now);
+            link.peers.retain(|_, (_, expires)| now < *expires);
+        }
+    });
+    Ok(())
+}
+
+struct Link {
+    epoch: Instant, // Host time 0, when a session of our own began
+    state: NodeState,
+    offset: i64, // Ghost time less host time in our session
+    peers: HashMap<NodeId, (NodeState, Instant)>, // And when they're forgotten if not heard from
+    measuring: Option<Measurement>,
+    measured: HashMap<NodeId, Instant>, // When sessions were last measured
+    applied: f32, // The tempo last agreed with the session
+    tempo: SharedTempo,
+    output: mpsc::Sender<SynthCommand>,
+    socket: UdpSocket,
+}
+
+impl Link {
+    fn new(tempo: SharedTempo, output: mpsc::Sender<SynthCommand>, socket: UdpSocket, endpoint: SocketAddrV4) -> Self {
+        let ident: NodeId = std::array::from_fn(|_| rand::thread_rng().sample(Alphanumeric));
+        let state = NodeState { ident, session: ident, timeline: Timeline::new(tempo.get(), 0.0, 0), endpoint: Some(endpoint) };
+        let epoch = Instant::now();
+        let mut link = Self {
+            epoch,
+            state,
+            offset: 0,
+            peers: HashMap::new(),
+            measuring: None,
+            measured: HashMap::new(),
+            applied: tempo.get(),
+            tempo,
+            output,
+            socket,
+        };
+        link.publish(epoch);
+        link
+    }
+
+    fn host(&self, instant: Instant) -> i64 {
+        instant.saturating_duration_since(self.epoch).as_micros() as i64
+    }
+
+    fn ghost(&self, instant: Instant) -> i64 {
+        self.host(instant) + self.offset
+    }
+
+    fn send(&self, kind: u8, to: SocketAddr) {
+        let _ = self.socket.send_to(&discovery(kind, &self.state), to); // Peers hear again soon enough
+    }
+
+    fn receive(&mut self, bytes: &[u8], from: SocketAddr, at: Instant) {
+        if bytes.starts_with(MEASUREMENT) {
+            match bytes.get(MEASUREMENT.len()) {
+                Some(&PING) => {
+                    if let Some(pong) = pong(bytes, &self.state.session, self.ghost(at)) {
+                        let _ = self.socket.send_to(&pong, from);
+                    }
+                }
+                Some(&PONG) => {
+                    let (Some(pong), Some(measurement)) = (parse_pong(bytes), &mut self.measuring) else { return };
+                    measurement.add(&pong, at.saturating_duration_since(self.epoch).as_micros() as i64);
+                    let now = Instant::now();
+                    measurement.pinged = now;
+                    let (peer, prev_ghost) = (measurement.peer, measurement.prev_ghost);
+                    let _ = self.socket.send_to(&ping(self.host(now), prev_ghost), peer);
+                }
+                _ => {}
+            }
+            return;
+        }
+        match parse_discovery(bytes) {
+            Some(Discovery::Alive(peer)) => {
+                if peer.ident != self.state.ident && !self.peers.contains_key(&peer.ident) {
+                    self.send(RESPONSE, from); // So it hears of us without waiting
+                }
+                self.saw(peer, at);
+            }
+            Some(Discovery::Response(peer)) => self.saw(peer, at),
+            Some(Discovery::ByeBye(ident)) => {
+                self.peers.remove(&ident);
+            }
+            None => {}
+        }
+    }
+
+    fn saw(&mut self, peer: NodeState, at: Instant) {
+        if peer.ident == self.state.ident {
+            return; // Our own broadcast, looped back
+        }
+        if peer.session == self.state.session {
+            // The newer timeline has its origin further on
+            if peer.timeline.beat_origin > self.state.timeline.beat_origin {
+                self.state.timeline = peer.timeline;
+                self.publish(at);
+            }
+        } else if let Some(endpoint) = peer.endpoint.filter(|_| self.measuring.is_none()) {
+            let recently = self.measured.get(&peer.session).is_some_and(|&measured| at < measured + REMEASURE_INTERVAL);
+            if !recently {
+                self.start_measuring(peer.session, endpoint.into(), at);
+            }
+        }
+        self.peers.insert(peer.ident, (peer, at + Duration::from_secs(TTL as u64)));
+    }
+
+    fn start_measuring(&mut self, session: NodeId, peer: SocketAddr, now: Instant) {
+        let measurement = Measurement { session, peer, started: now, pinged: now, offsets: Vec::new(), prev_ghost: None };
+        let _ = self.socket.send_to(&ping(self.host(now), None), peer);
+        self.measuring = Some(measurement);
+    }
+
+    // Carries a measurement on, and when it's done, joins the session measured if it's
+    // older or keeps up with our own session's clock. Sessions of others are measured
+    // again from time to time, as clocks drift.
+    fn measure(&mut self, now: Instant) {
+        let Some(measurement) = &mut self.measuring else {
+            let ours = self.peers.values().find(|(peer, _)| peer.session == self.state.session && peer.endpoint.is_some());
+            let due = self.measured.get(&self.state.session).is_none_or(|&measured| now >= measured + REMEASURE_INTERVAL);
+            if let Some((peer, _)) = ours.filter(|_| due && self.state.session != self.state.ident) {
+                let (session, endpoint) = (peer.session, peer.endpoint.expect("found with one"));
+                self.start_measuring(session, endpoint.into(), now);
+            }
+            return;
+        };
+        if !measurement.is_done(now) {
+            if now >= measurement.pinged + PING_TIMEOUT {
+                measurement.pinged = now;
+                let (peer, prev_ghost) = (measurement.peer, measurement.prev_ghost);
+                let _ = self.socket.send_to(&ping(self.host(now), prev_ghost), peer);
+            }
+            return;
+        }
+        let mut measurement = self.measuring.take().expect("measuring");
+        self.measured.insert(measurement.session, now);
+        let Some(offset) = measurement.offset() else { return }; // Nobody answered
+        if measurement.session == self.state.session {
+            self.offset = offset;
+            self.publish(now);
+        } else if joins((self.state.session, self.offset), (measurement.session, offset)) {
+            let timelines = self.peers.values().filter(|(peer, _)| peer.session == measurement.session);
+            let Some(timeline) = timelines.map(|(peer, _)| peer.timeline).max_by_key(|timeline| timeline.beat_origin) else { return };
+            (self.state.session, self.state.timeline, self.offset) = (measurement.session, timeline, offset);
+            self.publish(now);
+            println!("Joined an Ableton Link session at {:.1} BPM", timeline.tempo());
+            self.send(ALIVE, (MULTICAST, PORT).into());
+        }
+    }
+
+    // Passes on a tempo set here since the last one agreed, from the beat playing now.
+    // Returns whether it did, so the session hears at once.
+    fn follow_local_tempo(&mut self, now: Instant) -> bool {
+        let tempo = self.tempo.get();
+        if tempo == self.applied {
+            return false;
+        }
+        self.applied = tempo;
+        let ghost = self.ghost(now);
+        self.state.timeline = Timeline::new(tempo, self.state.timeline.beat_at(ghost), ghost);
+        self.publish(now);
+        true
+    }
+
+    // Hands the session's beats to the clock threads, and a new tempo to the synth too
+    fn publish(&mut self, now: Instant) {
+        let timeline = self.state.timeline;
+        self.tempo.set_grid(Some(grid(&timeline, self.ghost(now), now)));
+        let tempo = timeline.tempo() as f32;
+        if tempo != self.applied {
+            self.applied = tempo;
+            self.tempo.set(tempo);
+            let _ = self.output.send(SynthCommand::SetTempo(tempo)); // For tempo-synced effects
+        }
+    }
+}
+
+// The beats of `timeline` as they fall on our clock, `now` being at `ghost`
+fn grid(timeline: &Timeline, ghost: i64, now: Instant) -> BeatGrid {
+    BeatGrid { at: now, beat: timeline.beat_at(ghost), tempo: timeline.tempo() }
+}
+
+// The address of the interface the multicast group is reached through, which peers
+// ping us at
+fn local_address() -> Ipv4Addr {
+    let probe = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).and_then(|socket| {
+        socket.connect((MULTICAST, PORT))?;
+        socket.local_addr()
+    });
+    match probe {
+        Ok(SocketAddr::V4(address)) if !address.ip().is_unspecified() => *address.ip(),
+        _ => Ipv4Addr::LOCALHOST,
+    }
+}
+
+fn multicast_socket(interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?; // Other Link apps on this machine listen on the same port
+    #[cfg(all(unix, not(target_os = "linux")))]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT)).into())?;
+    let socket = UdpSocket::from(socket);
+    socket.join_multicast_v4(&MULTICAST, &interface)?;
+    Ok(socket)
+}
+
+fn entry(message: &mut Vec<u8>, key: [u8; 4], value: &[u8]) {
+    message.extend_from_slice(&key);
+    message.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    message.extend_from_slice(value);
+}
+
+// A message's entries, each a key and its value, or None if one runs past the end
+fn entries(mut bytes: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    let mut entries = Vec::new();
+    while !bytes.is_empty() {
+        let key = bytes.get(..4)?.try_into().ok()?;
+        let size = u32::from_be_bytes(bytes.get(4..8)?.try_into().ok()?) as usize;
+        let end = size.checked_add(8)?;
+        entries.push((key, bytes.get(8..end)?));
+        bytes = &bytes[end..];
+    }
+    Some(entries)
+}
+
+fn find<'a>(entries: &[([u8; 4], &'a [u8])], key: [u8; 4]) -> Option<&'a [u8]> {
+    entries.iter().find(|&&(other, _)| other == key).map(|&(_, value)| value)
+}
+
+fn int(bytes: &[u8], at: usize) -> Option<i64> {
+    Some(i64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn discovery(kind: u8, state: &NodeState) -> Vec<u8> {
+    let mut message = DISCOVERY.to_vec();
+    message.extend_from_slice(&[kind, TTL, 0, 0]); // Then the group, always 0
+    message.extend_from_slice(&state.ident);
+    let Timeline { micros_per_beat, beat_origin, time_origin } = state.timeline;
+    entry(&mut message, TIMELINE, &[micros_per_beat, beat_origin, time_origin].map(i64::to_be_bytes).concat());
+    entry(&mut message, SESSION, &state.session);
+    if let Some(endpoint) = state.endpoint {
+        entry(&mut message, ENDPOINT, &[&endpoint.ip().octets()[..], &endpoint.port().to_be_bytes()].concat());
+    }
+    message
+}
+
+fn parse_discovery(bytes: &[u8]) -> Option<Discovery> {
+    let body = bytes.strip_prefix(DISCOVERY)?;
+    let kind = *body.first()?;
+    let ident: NodeId = body.get(4..12)?.try_into().ok()?;
+    if kind == BYE_BYE {
+        return Some(Discovery::ByeBye(ident));
+    }
+    let entries = entries(&body[12..])?;
+    let timeline = find(&entries, TIMELINE)?;
+    let timeline = Timeline { micros_per_beat: int(timeline, 0)?, beat_origin: int(timeline, 8)?, time_origin: int(timeline, 16)? };
+    if timeline.micros_per_beat <= 0 {
+        return None;
+    }
+    let session = find(&entries, SESSION)?.try_into().ok()?;
+    let endpoint = find(&entries, ENDPOINT).filter(|value| value.len() == 6).map(|value| {
+        SocketAddrV4::new(Ipv4Addr::new(value[0], value[1], value[2], value[3]), u16::from_be_bytes([value[4], value[5]]))
+    });
+    let state = NodeState { ident, session, timeline, endpoint };
+    match kind {
+        ALIVE => Some(Discovery::Alive(state)),
+        RESPONSE => Some(Discovery::Response(state)),
+        _ => None,
+    }
+}
+
+fn ping(host: i64, prev_ghost: Option<i64>) -> Vec<u8> {
+    let mut message = MEASUREMENT.to_vec();
+    message.push(PING);
+    entry(&mut message, HOST_TIME, &host.to_be_bytes());
+    if let Some(prev_ghost) = prev_ghost {
+        entry(&mut message, PREV_GHOST_TIME, &prev_ghost.to_be_bytes());
+    }
+    message
+}
+
+// The answer to a ping: our session and ghost time, then the ping's own entries back
+fn pong(ping: &[u8], session: &NodeId, ghost: i64) -> Option<Vec<u8>> {
+    let payload = ping.strip_prefix(MEASUREMENT)?.strip_prefix(&[PING])?;
+    entries(payload)?;
+    let mut message = MEASUREMENT.to_vec();
+    message.push(PONG);
+    entry(&mut message, SESSION, session);
+    entry(&mut message, GHOST_TIME, &ghost.to_be_bytes());
+    message.extend_from_slice(payload);
+    Some(message)
+}
+
+fn parse_pong(bytes: &[u8]) -> Option<Pong> {
+    let entries = entries(bytes.strip_prefix(MEASUREMENT)?.strip_prefix(&[PONG])?)?;
+    Some(Pong {
+        session: find(&entries, SESSION)?.try_into().ok()?,
+        ghost: int(find(&entries, GHOST_TIME)?, 0)?,
+        host: int(find(&entries, HOST_TIME)?, 0)?,
+        prev_ghost: find(&entries, PREV_GHOST_TIME).and_then(|value| int(value, 0)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> NodeState {
+        NodeState {
+            ident: *b"node0001",
+            session: *b"session1",
+            timeline: Timeline::new(128.0, 16.0, 1_000_000),
+            endpoint: Some(SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 20), 50_123)),
+        }
+    }
+
+    #[test]
+    fn discovery_messages_read_back_as_sent() {
+        let state = state();
+        assert_eq!(parse_discovery(&discovery(ALIVE, &state)), Some(Discovery::Alive(state.clone())));
+        let quiet = NodeState { endpoint: None, ..state.clone() };
+        assert_eq!(parse_discovery(&discovery(RESPONSE, &quiet)), Some(Discovery::Response(quiet)));
+        assert_eq!(parse_discovery(&discovery(BYE_BYE, &state)), Some(Discovery::ByeBye(state.ident)));
+
+        let message = discovery(ALIVE, &state);
+        assert_eq!(&message[..12], b"_asdp_v\x01\x01\x05\x00\x00");
+        assert_eq!(&message[20..28], b"tmln\x00\x00\x00\x18");
+        let endpoint = 14; // The last entry, which may be left out
+        for length in (0..message.len() - endpoint).chain([message.len() - 1]) {
+            assert_eq!(parse_discovery(&message[..length]), None, "Cut off at {}", length);
+        }
+    }
+
+    // Messages as a Link 3 peer (Live, say) sends them, laid out field by field as Link's
+    // own implementation writes them: the header, then for discovery the message type,
+    // TTL, group and node id, and the entries. The Alive carries the start/stop state
+    // Link 3 added, which this side leaves alone.
+    const PEER_ALIVE: &[u8] = b"\
+        _asdp_v\x01\x01\x05\x00\x00q7Zr2LmV\
+        tmln\x00\x00\x00\x18\x00\x00\x00\x00\x00\x07\x27\x0e\x00\x00\x00\x00\x05\xc0y\x20\x00\x00\x00\x09\x98\xcd\xe9P\
+        sess\x00\x00\x00\x08q7Zr2LmV\
+        stst\x00\x00\x00\x11\x01\x00\x00\x00\x00\x03\xd0\x90\x00\x00\x00\x00\x09\x98\xc8\xc4\x80\
+        mep4\x00\x00\x00\x06\xc0\xa8\x01\x2a\xc3\x5b";
+    const PEER_PONG: &[u8] = b"\
+        _link_v\x01\x02\
+        sess\x00\x00\x00\x08q7Zr2LmV\
+        __gt\x00\x00\x00\x08\x00\x00\x00\x09\x98\xce\xdf\x00\
+        __ht\x00\x00\x00\x08\x00\x00\x00\x00\x00\x16\xe3\x60\
+        _pgt\x00\x00\x00\x08\x00\x00\x00\x09\x98\xce\xb7\xf0";
+
+    #[test]
+    fn a_link_peers_messages_read_back_as_sent() {
+        let Some(Discovery::Alive(peer)) = parse_discovery(PEER_ALIVE) else { panic!("The peer's Alive should parse") };
+        assert_eq!((peer.ident, peer.session), (*b"q7Zr2LmV", *b"q7Zr2LmV"));
+        assert_eq!(peer.timeline, Timeline { micros_per_beat: 468_750, beat_origin: 96_500_000, time_origin: 41_218_337_104 });
+        assert_eq!(peer.timeline.tempo(), 128.0);
+        assert_eq!(peer.endpoint, Some(SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 42), 50_011)));
+        // Written out again, it's the same but for the start/stop entry
+        let start_stop = 68..93;
+        assert_eq!(discovery(ALIVE, &peer), [&PEER_ALIVE[..start_stop.start], &PEER_ALIVE[start_stop.end..]].concat());
+
+        let expected = Pong { session: *b"q7Zr2LmV", ghost: 41_218_400_000, host: 1_500_000, prev_ghost: Some(41_218_390_000) };
+        assert_eq!(parse_pong(PEER_PONG), Some(expected));
+        assert_eq!(pong(&ping(1_500_000, Some(41_218_390_000)), b"q7Zr2LmV", 41_218_400_000).as_deref(), Some(PEER_PONG));
+    }
+
+    #[test]
+    fn pongs_carry_the_ping_back() {
+        let answer = pong(&ping(1_000, Some(7_000)), b"session1", 55_000).unwrap();
+        let expected = Pong { session: *b"session1", ghost: 55_000, host: 1_000, prev_ghost: Some(7_000) };
+        assert_eq!(parse_pong(&answer), Some(expected));
+        assert_eq!(pong(&answer, b"session1", 0), None, "A pong isn't a ping");
+        assert_eq!(pong(&ping(1_000, None)[..20], b"session1", 0), None);
+    }
+
+    #[test]
+    fn measuring_finds_the_sessions_clock() {
+        // A peer 2 s ahead, 300 µs away each way
+        let (ahead, trip) = (2_000_000, 300);
+        let now = Instant::now();
+        let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 1));
+        let mut measurement =
+            Measurement { session: *b"session1", peer, started: now, pinged: now, offsets: Vec::new(), prev_ghost: None };
+        let mut host = 10_000;
+        for _ in 0..=POINTS / 2 {
+            let ghost = host + trip + ahead;
+            let pong = parse_pong(&pong(&ping(host, measurement.prev_ghost), b"session1", ghost).unwrap()).unwrap();
+            measurement.add(&pong, host + 2 * trip);
+            host += 2 * trip;
+        }
+        measurement.add(&Pong { session: *b"other001", ghost: 0, host: 0, prev_ghost: None }, 0);
+        assert!(measurement.is_done(now));
+        assert_eq!(measurement.offset(), Some(ahead));
+    }
+
+    #[test]
+    fn the_older_session_is_joined() {
+        let (ours, theirs) = (*b"bbbbbbbb", *b"aaaaaaaa");
+        assert!(joins((ours, 0), (theirs, 2_000_000)));
+        assert!(!joins((ours, 2_000_000), (theirs, 0)));
+        // Begun about together, the lower id wins
+        assert!(joins((ours, 0), (theirs, 100_000)));
+        assert!(!joins((theirs, 100_000), (ours, 0)));
+    }
+
+    #[test]
+    fn the_grid_falls_on_the_sessions_beats() {
+        let timeline = Timeline::new(120.0, 8.0, 5_000_000);
+        assert_eq!(timeline.tempo(), 120.0);
+        assert_eq!(timeline.beat_at(6_000_000), 10.0);
+
+        let now = Instant::now();
+        let grid = grid(&timeline, 6_250_000, now);
+        assert_eq!(grid.beat, 10.5);
+        let tempo = SharedTempo::new(90.0);
+        tempo.set_grid(Some(grid));
+        assert_eq!(tempo.first_step(now, 4.0), now + Duration::from_millis(750)); // Beat 12
+        assert_eq!(tempo.step_after(now + Duration::from_millis(250), 0.25), now + Duration::from_millis(375));
+    }
+}
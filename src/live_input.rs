@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::stereo::Frame;
+
+const CAPACITY: usize = 8192; // Frames; power of two, far more than any capture buffer
+const MAX_BACKLOG: usize = 2048; // Frames the synth may fall behind before it skips ahead
+
+// How audio from an input device is mixed in, with `--input`: through the same filter
+// as the voices, then the effects, so the synth doubles as a simple effects box
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub level: f32,   // Gain of the input in the mix; 0.0 leaves it out
+    pub filter: bool, // Through the voice filter's cutoff and resonance, while the filter is on
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            level: 1.0,
+            filter: true,
+        }
+    }
+}
+
+impl InputSettings {
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "level" => self.level = value.clamp(0.0, 4.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Carries captured frames from the input device's callback to the synth without
+// either side waiting. The two devices' clocks never quite agree, so when the synth
+// finds itself too far behind it drops the backlog to keep the delay short, and when
+// it runs dry it plays silence until more arrives.
+#[derive(Clone)]
+pub struct InputRing {
+    samples: Arc<[AtomicU32]>, // f32 bits, left and right
+    written: Arc<AtomicUsize>, // Frames pushed so far
+    read: Arc<AtomicUsize>,    // Frames taken so far
+}
+
+impl Default for InputRing {
+    fn default() -> Self {
+        Self {
+            samples: (0..CAPACITY * 2).map(|_| AtomicU32::new(0)).collect(),
+            written: Arc::new(AtomicUsize::new(0)),
+            read: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl InputRing {
+    // From the capture callback only
+    pub fn push(&self, frame: Frame) {
+        let position = self.written.load(Ordering::Relaxed);
+        let slot = position % CAPACITY * 2;
+        self.samples[slot].store(frame[0].to_bits(), Ordering::Relaxed);
+        self.samples[slot + 1].store(frame[1].to_bits(), Ordering::Relaxed);
+        self.written.store(position.wrapping_add(1), Ordering::Release);
+    }
+
+    // From the synth only; None when nothing new has arrived
+    pub fn pop(&self) -> Option<Frame> {
+        let written = self.written.load(Ordering::Acquire);
+        let mut position = self.read.load(Ordering::Relaxed);
+        if written == position {
+            return None;
+        }
+        if written.wrapping_sub(position) > MAX_BACKLOG {
+            position = written.wrapping_sub(MAX_BACKLOG / 2);
+        }
+        let slot = position % CAPACITY * 2;
+        let frame = [
+            f32::from_bits(self.samples[slot].load(Ordering::Relaxed)),
+            f32::from_bits(self.samples[slot + 1].load(Ordering::Relaxed)),
+        ];
+        self.read.store(position.wrapping_add(1), Ordering::Relaxed);
+        Some(frame)
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::EnvFilter;
+
+use crate::diagnostics::Diagnostic;
+use crate::xrun::XrunMonitor;
+use crate::SynthCommand;
+
+// Sends the engine's tracing events (commands, voice allocation, unknown parameters,
+// xruns) to `path` as text or JSON lines. Lines are written on a background thread, so
+// the threads logging only queue them. The returned guard flushes the file when dropped.
+pub fn init(path: &Path, json: bool) -> io::Result<WorkerGuard> {
+    let (writer, guard) = tracing_appender::non_blocking(File::create(path)?);
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("debug"));
+    let subscriber = tracing_subscriber::fmt().with_writer(writer).with_env_filter(filter).with_ansi(false);
+    if json {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+    Ok(guard)
+}
+
+// Puts a logger in front of `output`: commands sent to the returned channel are logged
+// and passed on, so the audio thread doesn't format every one it handles
+pub fn tap(output: mpsc::Sender<SynthCommand>) -> mpsc::Sender<SynthCommand> {
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    thread::spawn(move || {
+        for command in rx {
+            tracing::debug!(?command, "command");
+            if output.send(command).is_err() {
+                return;
+            }
+        }
+    });
+    tx
+}
+
+// Logs what the synth queues with `Synthesizer::with_diagnostics`, and the xruns the
+// audio backend counts in `xruns`. Both are looked at every few milliseconds rather than
+// waited on, so the audio thread queuing an event never has to wake this one.
+pub fn drain(diagnostics: mpsc::Receiver<Diagnostic>, xruns: XrunMonitor) {
+    thread::spawn(move || {
+        let mut logged_xruns = 0;
+        loop {
+            let count = xruns.count();
+            if count > logged_xruns {
+                tracing::warn!(count, new = count - logged_xruns, "xrun");
+                logged_xruns = count;
+            }
+            match diagnostics.try_recv() {
+                Ok(diagnostic) => log(diagnostic),
+                Err(TryRecvError::Empty) => thread::sleep(Duration::from_millis(20)),
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+    });
+}
+
+fn log(diagnostic: Diagnostic) {
+    match diagnostic {
+        Diagnostic::VoiceStarted { note, voices } => tracing::debug!(note, voices, "voice started"),
+        Diagnostic::VoiceRetriggered { note, releasing } => tracing::debug!(note, releasing, "voice retriggered"),
+        Diagnostic::VoiceStolen { note, releasing, policy } => tracing::debug!(note, releasing, ?policy, "voice stolen"),
+        Diagnostic::NoteDropped { note, polyphony } => tracing::warn!(note, polyphony, "every voice is held, note dropped"),
+        Diagnostic::ScheduleFull { frame } => tracing::warn!(frame, "too many commands waiting for later frames, one refused"),
+        Diagnostic::SwitchCut(switch) => tracing::warn!(?switch, "too many presets still playing out, switched with a cut"),
+        Diagnostic::UnknownParam(id) => {
+            tracing::warn!(path = %id, "unknown parameter");
+            eprintln!("Unknown parameter '{}'", id);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::param_id::ParamId;
+use crate::SynthCommand;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LooperSettings {
+    pub bars: u32,          // Loop length in bars
+    pub beats_per_bar: u32,
+}
+
+impl Default for LooperSettings {
+    fn default() -> Self {
+        Self {
+            bars: 2,
+            beats_per_bar: 4,
+        }
+    }
+}
+
+pub enum LooperControl {
+    NoteOn(u8),
+    NoteOff(u8),
+    Param(SynthCommand), // A parameter or macro change, kept like the notes so sweeps replay with them
+    ToggleRecord, // Starts the loop if needed; recording on a running loop overdubs
+    TogglePlay,
+    Clear,
+    Report(mpsc::Sender<Vec<LoopStep>>), // Hands back what's been recorded, for autosaving
+    Restore(Vec<LoopStep>),              // Replaces the loop with one saved before, stopped
+}
+
+// One event of a loop as kept in a session file. Of the parameter changes, those the
+// keyboard and MIDI input record (macros and parameter moves) are kept.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LoopStep {
+    NoteOn { at: f32, note: u8 }, // Seconds from the start of the loop
+    NoteOff { at: f32, note: u8 },
+    Macro { at: f32, index: usize, value: f32 },
+    Param { at: f32, path: String, value: f32 },
+}
+
+impl LoopEvent {
+    fn to_step(&self) -> Option<LoopStep> {
+        let at = self.offset.as_secs_f32();
+        Some(match &self.action {
+            LoopAction::Note(note, true) => LoopStep::NoteOn { at, note: *note },
+            LoopAction::Note(note, false) => LoopStep::NoteOff { at, note: *note },
+            LoopAction::Param(SynthCommand::SetMacro(index, value)) => LoopStep::Macro { at, index: *index, value: *value },
+            LoopAction::Param(SynthCommand::SetParam(id, value) | SynthCommand::GlideParam(id, value)) => {
+                LoopStep::Param { at, path: id.to_string(), value: *value }
+            }
+            LoopAction::Param(_) => return None,
+        })
+    }
+
+    // None, once reported, for a parameter step whose path can't be a parameter
+    fn from_step(step: LoopStep) -> Option<Self> {
+        let (at, action) = match step {
+            LoopStep::NoteOn { at, note } => (at, LoopAction::Note(note, true)),
+            LoopStep::NoteOff { at, note } => (at, LoopAction::Note(note, false)),
+            LoopStep::Macro { at, index, value } => (at, LoopAction::Param(SynthCommand::SetMacro(index, value))),
+            LoopStep::Param { at, path, value } => match ParamId::new(&path) {
+                Ok(id) => (at, LoopAction::Param(SynthCommand::GlideParam(id, value))),
+                Err(e) => {
+                    eprintln!("Leaving a step out of the loop: {}", e);
+                    return None;
+                }
+            },
+        };
+        Some(Self { offset: Duration::from_secs_f32(at.max(0.0)), action })
+    }
+}
+
+#[derive(Clone)]
+struct LoopEvent {
+    offset: Duration, // Time from the start of the loop
+    action: LoopAction,
+}
+
+#[derive(Clone)]
+enum LoopAction {
+    Note(u8, bool), // Note and whether it starts or ends
+    Param(SynthCommand),
+}
+
+struct Looper {
+    length: Duration,
+    events: Vec<LoopEvent>, // Sorted by offset
+    playing: bool,
+    recording: bool,
+    loop_start: Instant,           // When the current pass of the loop began
+    next_index: usize,             // Next event to play in the current pass
+    sounding: HashSet<u8>,         // Notes turned on by playback
+    held_while_recording: HashSet<u8>,
+}
+
+impl Looper {
+    fn position(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.loop_start)
+    }
+
+    // Records an event at the current position. It goes in just before the next
+    // event to play, so it isn't replayed until the next pass.
+    fn record(&mut self, now: Instant, action: LoopAction) {
+        if let LoopAction::Note(note, on) = action {
+            if on {
+                self.held_while_recording.insert(note);
+            } else {
+                self.held_while_recording.remove(&note);
+            }
+        }
+        let offset = self.position(now).min(self.length);
+        self.events.insert(self.next_index, LoopEvent { offset, action });
+        self.next_index += 1;
+    }
+
+    // Closes any notes still held when recording stops, so the loop doesn't leave them hanging
+    fn stop_recording(&mut self, now: Instant) {
+        let held: Vec<u8> = self.held_while_recording.drain().collect();
+        for note in held {
+            self.record(now, LoopAction::Note(note, false));
+        }
+        self.recording = false;
+    }
+
+    fn silence(&mut self, output: &mpsc::Sender<SynthCommand>) {
+        for note in self.sounding.drain() {
+            let _ = output.send(SynthCommand::NoteOff(note));
+        }
+    }
+}
+
+// Runs the note looper on its own thread. Keyboard notes are sent to it as
+// `LooperControl::NoteOn/NoteOff` and parameter automation as `LooperControl::Param`
+// (they are only kept while recording), and the loop plays back into `output`.
+pub fn spawn(settings: LooperSettings, tempo: f32, output: mpsc::Sender<SynthCommand>) -> mpsc::Sender<LooperControl> {
+    let (tx, rx) = mpsc::channel::<LooperControl>();
+
+    thread::spawn(move || {
+        let beats = (settings.bars * settings.beats_per_bar).max(1) as f32;
+        let mut looper = Looper {
+            length: Duration::from_secs_f32(beats * 60.0 / tempo),
+            events: Vec::new(),
+            playing: false,
+            recording: false,
+            loop_start: Instant::now(),
+            next_index: 0,
+            sounding: HashSet::new(),
+            held_while_recording: HashSet::new(),
+        };
+
+        loop {
+            let now = Instant::now();
+            let timeout = if looper.playing {
+                let next_event = looper
+                    .events
+                    .get(looper.next_index)
+                    .map_or(looper.length, |event| event.offset);
+                (looper.loop_start + next_event).saturating_duration_since(now)
+            } else {
+                Duration::from_secs(1)
+            };
+
+            let now = match rx.recv_timeout(timeout) {
+                Ok(control) => {
+                    let now = Instant::now();
+                    match control {
+                        LooperControl::NoteOn(note) if looper.recording => looper.record(now, LoopAction::Note(note, true)),
+                        LooperControl::NoteOff(note) if looper.recording => looper.record(now, LoopAction::Note(note, false)),
+                        LooperControl::Param(command) if looper.recording => looper.record(now, LoopAction::Param(command)),
+                        LooperControl::NoteOn(_) | LooperControl::NoteOff(_) | LooperControl::Param(_) => {}
+                        LooperControl::ToggleRecord => {
+                            if looper.recording {
+                                looper.stop_recording(now);
+                            } else {
+                                if !looper.playing {
+                                    looper.playing = true;
+                                    looper.loop_start = now;
+                                    looper.next_index = 0;
+                                }
+                                looper.recording = true;
+                            }
+                        }
+                        LooperControl::TogglePlay => {
+                            if looper.playing {
+                                if looper.recording {
+                                    looper.stop_recording(now);
+                                }
+                                looper.playing = false;
+                                looper.silence(&output);
+                            } else {
+                                looper.playing = true;
+                                looper.loop_start = now;
+                                looper.next_index = 0;
+                            }
+                        }
+                        LooperControl::Clear => {
+                            looper.events.clear();
+                            looper.next_index = 0;
+                            looper.held_while_recording.clear();
+                            looper.silence(&output);
+                        }
+                        LooperControl::Report(reply) => {
+                            let _ = reply.send(looper.events.iter().filter_map(LoopEvent::to_step).collect());
+                        }
+                        LooperControl::Restore(steps) => {
+                            looper.events = steps.into_iter().filter_map(LoopEvent::from_step).collect();
+                            looper.events.sort_by_key(|event| event.offset);
+                            looper.events.retain(|event| event.offset <= looper.length);
+                            (looper.playing, looper.recording) = (false, false);
+                            looper.next_index = 0;
+                            looper.held_while_recording.clear();
+                            looper.silence(&output);
+                        }
+                    }
+                    now
+                }
+                Err(RecvTimeoutError::Timeout) => Instant::now(),
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+
+            // Play everything that is due, wrapping around at the end of the loop
+            while looper.playing {
+                if now >= looper.loop_start + looper.length {
+                    looper.loop_start += looper.length;
+                    looper.next_index = 0;
+                    continue;
+                }
+                let Some(event) = looper.events.get(looper.next_index).cloned() else { break };
+                if looper.loop_start + event.offset > now {
+                    break;
+                }
+                looper.next_index += 1;
+
+                let command = match event.action {
+                    LoopAction::Note(note, true) => {
+                        looper.sounding.insert(note);
+                        SynthCommand::NoteOn(note)
+                    }
+                    LoopAction::Note(note, false) => {
+                        looper.sounding.remove(&note);
+                        SynthCommand::NoteOff(note)
+                    }
+                    LoopAction::Param(command) => command,
+                };
+                if output.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    tx
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::param_id::ParamId;
+
+pub const MACROS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroCurve {
+    Linear,
+    Exponential, // Equal ratios per step, natural for frequencies and times
+}
+
+// One parameter moved by a macro, e.g. `param = "effects.0.mix"` or `param = "eq.high_gain"`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroTarget {
+    pub param: String,
+    pub min: f32, // Value with the macro at 0.0; may be above `max` to move the other way
+    pub max: f32, // Value with the macro at 1.0
+    pub curve: MacroCurve,
+}
+
+impl Default for MacroTarget {
+    fn default() -> Self {
+        Self {
+            param: String::new(),
+            min: 0.0,
+            max: 1.0,
+            curve: MacroCurve::Linear,
+        }
+    }
+}
+
+impl MacroTarget {
+    // The parameter value for a macro position between 0.0 and 1.0
+    pub fn value_at(&self, amount: f32) -> f32 {
+        let amount = amount.clamp(0.0, 1.0);
+        match self.curve {
+            // Exponential needs both ends on the same side of zero, otherwise fall back to linear
+            MacroCurve::Exponential if self.min * self.max > 0.0 => self.min * (self.max / self.min).powf(amount),
+            _ => self.min + (self.max - self.min) * amount,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroSettings {
+    pub name: String,
+    pub value: f32, // Position when the preset loads, 0.0..1.0
+    pub targets: Vec<MacroTarget>,
+}
+
+// A macro as the synth keeps it, its targets' paths looked up when the preset loads so
+// moving it on the audio thread only reads them
+pub struct Macro {
+    pub value: f32,
+    pub targets: Vec<(ParamId, MacroTarget)>,
+}
+
+impl Macro {
+    // Macro `index` of a preset, less (once reported) the targets that can't be parameters
+    pub fn new(index: usize, settings: &MacroSettings) -> Self {
+        let targets = settings
+            .targets
+            .iter()
+            .filter_map(|target| match ParamId::new(&target.param) {
+                Ok(id) => Some((id, target.clone())),
+                Err(e) => {
+                    report_unknown(index, &target.param, &e);
+                    None
+                }
+            })
+            .collect();
+        Self { value: settings.value, targets }
+    }
+}
+
+// Says a target of macro `index` is being left out, and why
+pub fn report_unknown(index: usize, path: &str, reason: &str) {
+    tracing::warn!(path, "unknown parameter in a macro");
+    eprintln!("Macro {}: {}", index + 1, reason);
+}
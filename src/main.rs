@@ -1,281 +1,961 @@
 #![allow(dead_code, unused_variables, clippy::empty_loop)]
 
-use device_query::{DeviceQuery, DeviceState, Keycode};
-use std::{sync::mpsc, collections::HashMap};
+#[cfg(feature = "analyze")]
+mod analyze;
+mod announce;
+mod audio;
+mod capture;
+mod cli;
+mod cpu_meter;
+mod debounce;
+mod denormals;
+mod error;
+#[cfg(feature = "gui")]
+mod gui;
+mod icecast;
+mod jam;
+mod keyboard;
+mod keymap;
+mod latency;
+#[cfg(feature = "link")]
+mod link;
+mod logging;
+#[cfg(feature = "midi")]
+mod midi;
+mod network_output;
+mod osc;
+mod performance;
+mod practice;
+mod quiz;
+mod random_patch;
+mod recording;
+mod reload;
+mod render;
+#[cfg(feature = "scripting")]
+mod script;
+mod session;
+mod shutdown;
+#[cfg(any(feature = "tui", feature = "gui"))]
+mod spectrum;
+mod tap_tempo;
+#[cfg(feature = "tui")]
+mod tui;
+mod velocity;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod xrun;
+
+use device_query::Keycode;
+use std::process::ExitCode;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use rodio::{OutputStream, source::Source};
-use std::f32::consts::PI;
-
-const SAMPLE_RATE: u32 = 44_100;
+use std::time::{Duration, Instant};
+use audio::{Backend, Output};
+use clap::Parser;
+use cli::{Cli, Command};
+use cpu_meter::CpuMeter;
+use debounce::Debounce;
+use error::Error;
+use keyboard::Keyboard;
+use keymap::KeyMap;
+use latency::LatencyProbe;
+use looper::LooperControl;
+use blend::PresetBlend;
+use scene::SceneList;
+use macros::MACROS;
+use envelope::EnvelopeSettings;
+use param_id::ParamId;
+use preset::Preset;
+use sequencer::SequencerControl;
+use shutdown::Shutdown;
+use tap_tempo::TapTempo;
+use tempo::SharedTempo;
+use velocity::VelocityLayer;
+use xrun::XrunMonitor;
+
+// The engine lives in the library; its modules are brought in here so the front
+// ends can keep using `crate::preset`, `crate::SynthCommand` and so on
+use rodio_synth::{ambient, arpeggiator, binaural, blend, diagnostics, dither, effects, envelope, euclid, live_input, looper, macros, midi_file, mono, notes, param_id, params, preset, reference, scene, score, sequencer, stereo, surround, switch, tempo, voices};
+#[cfg(feature = "midi")]
+use rodio_synth::bank;
+#[cfg(any(feature = "tui", feature = "gui"))]
+use rodio_synth::{scope, snapshot};
+use rodio_synth::{SynthCommand, Synthesizer, Waveform, DEFAULT_POLYPHONY, FADE_OUT_SECONDS};
+
+// Number keys 1-8 as down/up pairs for the four macros: 1/2 move macro 1, 3/4 macro 2, ...
+fn macro_step_from_key(key: Keycode) -> Option<(usize, f32)> {
+    const STEP: f32 = 0.1;
+    match key {
+        Keycode::Key1 => Some((0, -STEP)),
+        Keycode::Key2 => Some((0, STEP)),
+        Keycode::Key3 => Some((1, -STEP)),
+        Keycode::Key4 => Some((1, STEP)),
+        Keycode::Key5 => Some((2, -STEP)),
+        Keycode::Key6 => Some((2, STEP)),
+        Keycode::Key7 => Some((3, -STEP)),
+        Keycode::Key8 => Some((3, STEP)),
+        _ => None,
+    }
+}
 
-enum Waveform {
-    Sine,
+// Function keys pick a parameter for Page Up/Down to adjust: F1 the first in the preset's list, ...
+fn param_index_from_key(key: Keycode) -> Option<usize> {
+    let keys = [
+        Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5, Keycode::F6,
+        Keycode::F7, Keycode::F8, Keycode::F9, Keycode::F10, Keycode::F11, Keycode::F12,
+    ];
+    keys.iter().position(|&function| function == key)
 }
 
-enum SynthCommand {
-    NoteOn(Keycode),
-    NoteOff(Keycode),
+// Keys that drive the step sequencer rather than playing notes
+fn sequencer_control_from_key(key: Keycode) -> Option<SequencerControl> {
+    match key {
+        Keycode::Space => Some(SequencerControl::TogglePlay),
+        Keycode::R => Some(SequencerControl::ToggleRecord),
+        Keycode::Right => Some(SequencerControl::Rest),
+        Keycode::Enter => Some(SequencerControl::Save),
+        _ => None
+    }
 }
 
-struct Synthesizer {
-    oscillators: HashMap<Keycode, Oscillator>,
-    sample_rate: u32,
-    command_receiver: mpsc::Receiver<SynthCommand>,
+// Keys that drive the note looper
+fn looper_control_from_key(key: Keycode) -> Option<LooperControl> {
+    match key {
+        Keycode::L => Some(LooperControl::ToggleRecord),
+        Keycode::P => Some(LooperControl::TogglePlay),
+        Keycode::O => Some(LooperControl::Clear),
+        _ => None
+    }
 }
 
-impl Synthesizer {
-    pub fn new(sample_rate: u32, command_receiver: mpsc::Receiver<SynthCommand>) -> Self {
-        Self {
-            oscillators: HashMap::new(),
-            sample_rate,
-            command_receiver
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
         }
     }
+}
 
-    pub fn note_on(&mut self, key: Keycode, waveform: Waveform) {
-        if let Some(freq) = frequency_from_key(key) {
-            // If the key is already playing, reset its phase and envelope
-            if let Some(osc) = self.oscillators.get_mut(&key) {
-                osc.restart(freq);
-            } else {
-                // Create a new oscillator for the new note if not already playing
-                let osc = Oscillator::new(freq, waveform, self.sample_rate);
-                self.oscillators.insert(key, osc);
-            }
+fn run(cli: Cli) -> Result<(), Error> {
+    let _log = match &cli.log {
+        Some(path) => Some(logging::init(path, cli.log_json).map_err(Error::file("open the log file", path))?),
+        None => None,
+    };
+    // A score is read before anything starts so mistakes in it are reported straight away
+    let score = match &cli.command {
+        Some(Command::ListDevices) => {
+            return audio::list_devices();
         }
-    }
-    
-    pub fn note_off(&mut self, key: &Keycode) {
-        if let Some(osc) = self.oscillators.get_mut(key) {
-            osc.start_release();
+        #[cfg(feature = "analyze")]
+        Some(Command::Analyze { file, output }) => {
+            return analyze::analyze(file, &output.clone().unwrap_or_else(|| file.with_extension("png")));
         }
-    }
-
-    fn process_commands(&mut self) {
-        while let Ok(command) = self.command_receiver.try_recv() {
-            match command {
-                SynthCommand::NoteOn(key) => {
-                    self.note_on(key, Waveform::Sine);
-                }
-                SynthCommand::NoteOff(key) => {
-                    self.note_off(&key);
-                }
-            }
+        Some(Command::PlayScore { file } | Command::RenderScore { file, .. }) => Some(score::Score::load(file).map_err(Error::file("load score", file))?),
+        Some(Command::Render { .. } | Command::Practice { .. } | Command::Quiz { .. } | Command::Tune { .. } | Command::Binaural { .. }) | None => None,
+    };
+    // State changes are printed, and spoken too in accessibility mode
+    let announcer = announce::Announcer::new(cli.speak);
+
+    // A set list is read up front too, and its first scene is where playing starts
+    let scenes = match &cli.scenes {
+        Some(path) => Some(SceneList::load(path).map_err(Error::file("load set list", path))?),
+        None => None,
+    };
+
+    // Start from a preset file, a random patch (`--random [seed]`), the first scene or the defaults
+    let mut preset_path = cli.preset.clone();
+    let mut preset = if let Some(scene) = scenes.as_ref().and_then(|scenes| scenes.get(0)) {
+        announcer.say(format!("Scene 1: {}", scene.name));
+        scene.preset.clone()
+    } else if let Some(seed) = cli.random {
+        let seed = seed.unwrap_or_else(rand::random);
+        println!("Random patch, seed {}", seed);
+        preset_path = Some(format!("random-{}.toml", seed).into()); // Saving the pattern also keeps the patch
+        random_patch::random_preset(seed)
+    } else {
+        match &preset_path {
+            Some(path) => Preset::load(path).map_err(Error::file("load preset", path))?,
+            None => Preset::default(),
         }
+    };
+    if cli.ambient {
+        preset.ambient.enabled = true;
     }
-}
-
-struct Oscillator {
-    phase: f32,
-    phase_increment: f32,
-    waveform: Waveform,
-    sample_rate: u32,
-    is_releasing: bool,  // Add this field to indicate if the oscillator is in release phase
-    release_phase: f32,  // A value from 0.0 to 1.0 indicating the progress of the release
-    release_rate: f32,   // The rate at which the release phase progresses
-    attack_phase: f32,    // A value from 0.0 to 1.0 indicating the progress of the attack
-    attack_rate: f32,     // The rate at which the attack phase progresses
-}
-
-impl Oscillator {
-    pub fn new(frequency: f32, waveform: Waveform, sample_rate: u32) -> Self {
-        Self {
-            phase: 0.0,
-            phase_increment: 2.0 * PI * frequency / sample_rate as f32,
-            waveform,
-            sample_rate,
-            is_releasing: false,
-            release_phase: 1.0, // Start at full volume for active notes
-            release_rate: 1.0 / (sample_rate as f32 * 0.5), // This sets a release time of 0.5 seconds
-            attack_phase: 0.0, // Start attack phase at 0 for silence
-            attack_rate: 1.0 / (sample_rate as f32 * 0.01), // This sets a quick attack time of 0.01 seconds
-
-        }
+    if cli.no_envelope {
+        preset.envelope = EnvelopeSettings::gate();
     }
 
-    // This function resets the oscillator phase to ensure smooth transition between notes
-    pub fn reset_phase(&mut self) {
-        self.phase = 0.0;
+    if let (Some(Command::RenderScore { output, dither, .. }), Some(score)) = (&cli.command, &score) {
+        let sample_rate = cli.sample_rate.unwrap_or(render::DEFAULT_SAMPLE_RATE);
+        return render::render_score(score, &preset, sample_rate, *dither, output)
+            .map_err(|source| Error::Render { path: output.clone(), source });
     }
 
-    // Call this when a new note is played on the same key to ensure a smooth transition
-    pub fn restart(&mut self, frequency: f32) {
-        self.set_frequency(frequency);
-        self.reset_phase(); // Reset phase to ensure there's no click
-        self.is_releasing = false; // Stop releasing because a new note is starting
-        self.attack_phase = 0.0; // Reset attack phase to start a new envelope
+    if let Some(Command::Render { file, preset: patch, output, dither }) = &cli.command {
+        let commands = midi_file::read(file).map_err(Error::file("load MIDI file", file))?;
+        let preset = match patch {
+            Some(path) => Preset::load(path).map_err(Error::file("load preset", path))?,
+            None => preset,
+        };
+        let sample_rate = cli.sample_rate.unwrap_or(render::DEFAULT_SAMPLE_RATE);
+        return render::render_midi(commands, &preset, sample_rate, *dither, output)
+            .map_err(|source| Error::Render { path: output.clone(), source });
     }
 
-    pub fn set_frequency(&mut self, frequency: f32) {
-        self.phase_increment = 2.0 * PI * frequency / self.sample_rate as f32;
+    // A session left by a run that crashed takes over from the preset, once it's agreed to
+    let mut session = cli.autosave.as_ref().and_then(|path| session::offer(path, !cli.headless));
+    if let Some(session) = &session {
+        preset = session.preset.clone();
     }
 
-    pub fn start_release(&mut self) {
-        // Only start the release if the note was fully attacked, otherwise set it to the attack_phase
-        if !self.is_releasing && self.attack_phase >= 1.0 {
-            self.is_releasing = true;
-            self.release_phase = 1.0;
-        } else {
-            self.is_releasing = true;
-            self.release_phase = self.attack_phase;
-        }
-    }
+    // So is a file to practise with, once the preset says which channels have parts
+    let practice = match &cli.command {
+        Some(Command::Practice { file, channel }) => Some(practice::Practice::load(file, *channel, &preset).map_err(Error::file("load MIDI file", file))?),
+        _ => None,
+    };
 
-    pub fn apply_envelope(&mut self, sample: f32) -> f32 {
-        if self.attack_phase < 1.0 {
-            self.attack_phase += self.attack_rate;
-            if self.attack_phase > 1.0 {
-                self.attack_phase = 1.0;
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    let tx = if tracing::enabled!(tracing::Level::DEBUG) { logging::tap(tx) } else { tx };
+    // Recording a performance keeps everything on its way to the synth
+    let (tx, performance) = match &cli.record_midi {
+        Some(_) => {
+            let (tx, performance) = performance::tap(tx, preset.tempo);
+            (tx, Some(performance))
+        }
+        None => (tx, None),
+    };
+    let save_performance = {
+        let (performance, path) = (performance.clone(), cli.record_midi.clone());
+        move || {
+            let (Some(performance), Some(path)) = (&performance, &path) else { return };
+            match performance.save(path) {
+                Ok(notes) => println!("Saved {} notes to {}", notes, path.display()),
+                Err(e) => eprintln!("Failed to save the performance to {}: {}", path.display(), e),
             }
-            return sample * self.attack_phase
         }
-    
-        if self.is_releasing {
-            self.release_phase -= self.release_rate;
-            if self.release_phase <= 0.0 {
-                self.release_phase = 0.0;
-                return 0.0; // Oscillator is silent, should be removed.
+    };
+    let shutdown = Shutdown::install();
+
+    // Only cpal's callback knows when its audio will be heard
+    let backend = if cli.measure_latency && cli.backend != Backend::Cpal {
+        eprintln!("Note: measuring latency plays through the cpal backend");
+        Backend::Cpal
+    } else {
+        cli.backend
+    };
+    let output = Output::open(backend, cli.device.as_deref(), cli.sample_rate, cli.buffer_request(), cli.surround)?;
+    let sample_rate = output.sample_rate();
+    let keymap = match (session.as_mut(), &cli.keymap) {
+        (Some(session), _) => std::mem::take(&mut session.keymap),
+        (None, Some(path)) => KeyMap::load(path).map_err(Error::file("load key map", path))?,
+        (None, None) if cli.two_octaves => KeyMap::two_octaves(),
+        (None, None) => KeyMap::default(),
+    };
+    let saved_keymap = Arc::new(Mutex::new(keymap.clone())); // What autosaving keeps, as reloaded
+    let synth = Synthesizer::new(sample_rate, &preset, switch::build_presets(rx, sample_rate, cli.polyphony))
+        .with_waveform(cli.waveform)
+        .with_polyphony(cli.polyphony)
+        .with_steal_policy(cli.voice_stealing)
+        .with_surround(cli.surround)
+        .with_preset_switch(cli.preset_switch, cli.switch_time);
+    let synth = match cli.crossfeed {
+        Some(level) => synth.with_crossfeed(level),
+        None => synth,
+    };
+    // A second preset to blend towards puts its shared parameters on the `blend.amount` control
+    let (synth, blending) = match &cli.blend_with {
+        Some(path) => {
+            let other = Preset::load(path).map_err(Error::file("load preset", path))?;
+            (synth.with_blend(PresetBlend::new(&preset, &other)), true)
+        }
+        None => (synth, false),
+    };
+    let tempo = SharedTempo::new(preset.tempo);
+
+    // Live input is captured at the output's rate and mixed in by the synth
+    let (synth, _input) = match &cli.input {
+        Some(device) => {
+            let ring = live_input::InputRing::default();
+            let stream = audio::open_input(device.as_deref(), sample_rate, ring.clone())?;
+            (synth.with_input(ring), Some(stream))
+        }
+        None => (synth, None),
+    };
+
+    // A recording takes the output as heard, live input included, on a thread of its own;
+    // it's finished after playback stops, being dropped after the stream
+    let (synth, _recording) = match &cli.record {
+        Some(path) => {
+            let parts = preset.parts.len().min(rodio_synth::parts::MAX_PARTS);
+            let (recording, tap) = recording::Recording::start(path, sample_rate, cli.stems, parts).map_err(Error::file("record to", path))?;
+            (synth.with_recording(tap), Some(recording))
+        }
+        None => (synth, None),
+    };
+    // A network stream and an Icecast broadcast take the same output for listeners elsewhere
+    let synth = match cli.stream_port {
+        Some(port) => synth.with_recording(network_output::spawn(port, sample_rate).map_err(Error::start("the audio stream"))?),
+        None => synth,
+    };
+    let synth = match &cli.icecast {
+        Some(mount) => synth.with_recording(icecast::spawn(mount, cli.icecast_format, sample_rate).map_err(Error::start("the Icecast stream"))?),
+        None => synth,
+    };
+    // The last minute or so is always kept, for saving after the fact
+    let (synth, capture) = if cli.capture_seconds > 0.0 {
+        let (capture, tap) = capture::Capture::spawn(cli.capture_seconds, sample_rate);
+        (synth.with_recording(tap), Some(capture))
+    } else {
+        (synth, None)
+    };
+
+    // The sequencer plays straight into the synth; saving writes its pattern back into the preset
+    let sequencer_tx = sequencer::spawn(preset.sequencer.clone(), tempo.clone(), tx.clone(), preset.humanize.clone(), {
+        let preset = preset.clone();
+        let path = preset_path.unwrap_or_else(|| "preset.toml".into());
+        move |pattern| {
+            let mut preset = preset.clone();
+            preset.sequencer = pattern.clone();
+            match preset.save(&path) {
+                Ok(()) => println!("Saved pattern to {}", path.display()),
+                Err(e) => eprintln!("Failed to save pattern to {}: {}", path.display(), e),
             }
-            return sample * self.release_phase;
         }
-    
-        sample // If not in attack or release phase, output the sample as is.
+    });
+
+    // The looper also plays straight into the synth, layering under live playing
+    let looper_tx = looper::spawn(preset.looper.clone(), preset.tempo, tx.clone());
+
+    // Snapshots carry the held notes and output levels to the UI, or to the console without one
+    let (snapshot_tx, snapshot_rx) = mpsc::sync_channel(4);
+    let synth = synth.with_snapshots(snapshot_tx);
+
+    // Shared with the audio backend, which fills them in
+    let xruns = XrunMonitor::new();
+    let cpu = CpuMeter::new();
+
+    // Voice allocation, unknown parameters and xruns go to the log from a thread of its
+    // own, the audio thread only queuing or counting them
+    let (diagnostics_tx, diagnostics) = diagnostics::channel();
+    let synth = synth.with_diagnostics(diagnostics_tx);
+    logging::drain(diagnostics, xruns.clone());
+
+    // Parameter edits from a UI or a remote go straight to the synth, through a bank it
+    // reads every frame rather than through the arp or rhythm generator
+    let mut values = preset.params();
+    values.push(("master.volume".to_string(), 1.0));
+    if blending {
+        values.push(("blend.amount".to_string(), 0.0));
     }
-    
-}
+    let params = params::ParamStore::new(values, tx.clone()).with_preset(&preset);
+    let synth = synth.with_param_bank(params.bank());
 
-// Iterator implementation for synthesizer
-impl Iterator for Synthesizer {
-    type Item = f32;
+    // A restored session puts back the edits and the loop, and autosaving keeps the session from now on
+    if let Some(session) = session {
+        params.set_all(&session.params.into_iter().collect::<Vec<_>>());
+        looper_tx.send(LooperControl::Restore(session.looper))?;
+    }
+    let _autosave = cli.autosave.as_ref().map(|path| {
+        session::Autosave::spawn(path, params.clone(), sequencer_tx.clone(), looper_tx.clone(), saved_keymap.clone())
+    });
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // Process any pending SynthCommands (e.g., NoteOn, NoteOff)
-        self.process_commands();
+    // A UI also gets a scope tap from the synth
+    #[cfg(any(feature = "tui", feature = "gui"))]
+    let scope = scope::ScopeTap::new(sample_rate);
+    #[cfg(any(feature = "tui", feature = "gui"))]
+    let synth = synth.with_scope(scope.clone());
 
-        // Headroom is the amount by which the signal amplitude is reduced to prevent clipping
-        let headroom = 0.8; // Avoids clipping by leaving 20% headroom
-        let mut sample_sum = 0.0; // This will accumulate the samples from all oscillators
-        let mut active_oscillators = 0; // Counts how many oscillators are contributing to the current sample
+    // The synth's own channel, for notes that shouldn't go through the arp or rhythm generator
+    let synth_tx = tx.clone();
 
-        // A list to keep track of oscillators that have finished playing
-        let mut finished_oscillators = Vec::new();
+    if preset.ambient.enabled {
+        ambient::spawn(preset.ambient.clone(), synth_tx.clone());
+        println!("Ambient mode: playing by itself");
+    }
 
-        for (key, osc) in &mut self.oscillators {
-            let osc_sample = match osc.waveform {
-                Waveform::Sine => osc.phase.sin(),
-                // Additional waveforms can be implemented here
+    // With the rhythm generator on, held keys are retriggered in Euclidean patterns
+    let tx = if preset.euclid.enabled {
+        euclid::spawn(preset.euclid.clone(), tempo.clone(), tx)
+    } else {
+        tx
+    };
+
+    // With the arpeggiator on, key presses go to it and it plays the synth (through the rhythm generator, if both are on)
+    let tx = if preset.arp.enabled {
+        arpeggiator::spawn(preset.arp.clone(), preset.humanize.clone(), tempo.clone(), tx).map_err(Error::start("the arpeggiator"))?
+    } else {
+        tx
+    };
+
+    // A script sees the keyboard's notes first and can replace them with its own
+    #[cfg(feature = "scripting")]
+    let tx = match &cli.script {
+        Some(path) => script::spawn(path, tempo.clone(), tx).map_err(Error::file("load script", path))?,
+        None => tx,
+    };
+
+    // In peer mode the keyboard's notes also go to the other player, whose notes come
+    // back straight into the synth
+    let tx = match cli.jam {
+        Some(peer) => {
+            let settings = jam::JamSettings {
+                peer,
+                port: cli.jam_port,
+                delay: cli.jam_delay.map(|ms| Duration::from_secs_f32(ms / 1000.0)),
             };
+            jam::spawn(settings, tx, synth_tx.clone()).map_err(Error::start("peer mode"))?
+        }
+        None => tx,
+    };
+
+    // Practice scores what's played from here on, by the keyboard or a controller
+    let (tx, trainer) = match &practice {
+        Some(practice) => {
+            let (tx, trainer) = practice::tap(practice, tx);
+            (tx, Some(trainer))
+        }
+        None => (tx, None),
+    };
+    // As does a quiz, taking each note played as an answer
+    let (tx, answers) = match &cli.command {
+        Some(Command::Quiz { .. }) => {
+            let (tx, answers) = quiz::tap(tx);
+            (tx, Some(answers))
+        }
+        _ => (tx, None),
+    };
 
-            // Envelop the oscillator's sample (handle attack and release)
-            let enveloped_sample = osc.apply_envelope(osc_sample);
+    // OSC controllers play and tweak the synth the same way the keyboard does
+    if let Some(port) = cli.osc_port {
+        osc::spawn(port, tempo.clone(), tx.clone()).map_err(Error::start("the OSC server"))?;
+    }
 
-            // Check if the oscillator's release phase has completed
-            if osc.is_releasing && osc.release_phase <= 0.0 {
-                finished_oscillators.push(*key); // Mark oscillator for removal
-            } else {
-                // Otherwise, accumulate the sample
-                sample_sum += enveloped_sample;
-                active_oscillators += 1;
-            }
+    // Ableton Link apps on the network share the tempo and where the beats fall
+    #[cfg(feature = "link")]
+    if cli.link {
+        link::spawn(tempo.clone(), tx.clone()).map_err(Error::start("Ableton Link"))?;
+    }
 
-            // Increment the oscillator's phase, wrapping around at 2π
-            osc.phase += osc.phase_increment;
-            if osc.phase > 2.0 * PI {
-                osc.phase -= 2.0 * PI;
-            }
+    // MIDI keyboards and controllers play like the computer keyboard, and program changes
+    // switch between the presets of a bank
+    #[cfg(feature = "midi")]
+    let _midi = match &cli.midi {
+        Some(port) => {
+            let bank = match &cli.bank {
+                Some(dir) => Some(bank::PresetBank::load(dir).map_err(Error::file("load preset bank", dir))?),
+                None => None,
+            };
+            let settings = midi::MidiSettings {
+                port: port.clone(),
+                channel: cli.midi_channel,
+                bank,
+                queue_programs: cli.queue_program_change,
+                thru: cli.midi_thru.clone().map(|port| midi::MidiThru { port, channel: cli.midi_thru_channel }),
+                looper: looper_tx.clone(),
+                params: params.clone(),
+                scenes: scenes.clone(),
+                tempo: tempo.clone(),
+                announcer: announcer.clone(),
+            };
+            Some(midi::connect(settings, tx.clone())?)
         }
+        None => None,
+    };
+
+    // Browser control surfaces and scripts talk JSON over a WebSocket
+    #[cfg(feature = "websocket")]
+    if let Some(port) = cli.websocket_port {
+        let remote = websocket::Remote {
+            preset_name: preset.name.clone(),
+            params: params.clone(),
+            tempo: tempo.clone(),
+            commands: tx.clone(),
+            cpu: cpu.clone(),
+            xruns: xruns.clone(),
+        };
+        websocket::spawn(port, remote).map_err(Error::start("the WebSocket server"))?;
+    }
 
-        // Remove oscillators that have completed their release phase
-        for key in finished_oscillators {
-            self.oscillators.remove(&key);
+    // Audio playback, through rodio or straight from cpal's callback
+    let (probe, latency_report) = if cli.measure_latency {
+        let (probe, report) = LatencyProbe::new();
+        println!("Measuring latency: play single notes with a moment's silence between them");
+        (Some(probe), Some(report))
+    } else {
+        (None, None)
+    };
+    let (_playing, latency) = output.play(synth, xruns.clone(), cpu.clone(), probe.clone())?;
+    // Reported once it's known whether the keyboard adds polling to it
+    let report_latency = |key_poll| {
+        if let Some(latency) = &latency {
+            latency.report(key_poll);
         }
-
-        // Normalize the sample sum to prevent clipping and apply headroom
-        if active_oscillators > 0 {
-            let average_sample = sample_sum / active_oscillators as f32;
-            let normalized_sample = average_sample * headroom;
-
-            // Enforce soft clipping
-            Some(normalized_sample.clamp(-1.0, 1.0)) // Clamping the value to the range [-1.0, 1.0]
-        } else {
-            // If there are no active oscillators, output silence
-            Some(0.0)
+    };
+
+    // A score plays to the end, then waits for the last notes to ring out
+    if let Some(score) = score {
+        report_latency(None);
+        println!("Playing {:.1} s score", score.seconds(tempo.get()));
+        score::perform(&score, &tempo, &tx, || !shutdown.requested());
+        if !shutdown.requested() {
+            thread::sleep(Duration::from_secs_f32(preset.envelope.release + 0.5));
         }
+        fade_out(&synth_tx);
+        save_performance();
+        return Ok(());
     }
-}
 
-impl Source for Synthesizer {
-    fn current_frame_len(&self) -> Option<usize> { None }
-    fn channels(&self) -> u16 { 2 }
-    fn sample_rate(&self) -> u32 { self.sample_rate }
-    fn total_duration(&self) -> Option<Duration> { None }
-}
-
-fn frequency_from_key(key: Keycode) -> Option<f32> {
-    match key {
-        Keycode::A => Some(261.63), // C4
-        Keycode::W => Some(277.18), // C#4/Db4
-        Keycode::S => Some(293.66), // D4
-        Keycode::E => Some(311.13), // D#4/Eb4
-        Keycode::D => Some(329.63), // E4
-        Keycode::F => Some(349.23), // F4
-        Keycode::T => Some(369.99), // F#4/Gb4
-        Keycode::G => Some(392.00), // G4
-        Keycode::Y => Some(415.30), // G#4/Ab4
-        Keycode::H => Some(440.00), // A4
-        Keycode::U => Some(466.16), // A#4/Bb4
-        Keycode::J => Some(493.88), // B4
-        Keycode::K => Some(523.25), // C5
-        _ => None
+    // Macro positions are tracked here so the keys can step them up and down
+    let mut macro_values = [0.0; MACROS];
+    for (value, settings) in macro_values.iter_mut().zip(&preset.macros) {
+        *value = settings.value.clamp(0.0, 1.0);
     }
-}
 
-fn main() {
-    let (tx, rx) = mpsc::channel::<SynthCommand>();
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let synth = Synthesizer::new(SAMPLE_RATE, rx);
+    // Saving the preset or key map file while playing applies it straight away: the preset
+    // as if switched to, so held notes ring on with the sound they started with, and the
+    // key map on the next key change. A file that doesn't load leaves things as they were.
+    let (keymap_tx, keymap_rx) = mpsc::channel::<KeyMap>();
+    if cli.watch && cli.preset.is_none() && cli.keymap.is_none() {
+        eprintln!("--watch needs a --preset or --keymap file to watch");
+    }
+    if cli.watch {
+        let (preset_file, keymap_file) = (cli.preset.clone(), cli.keymap.clone());
+        let (ambient, no_envelope) = (cli.ambient, cli.no_envelope);
+        let (synth, params, announcer) = (synth_tx.clone(), params.clone(), announcer.clone());
+        let saved_keymap = saved_keymap.clone();
+        reload::watch(preset_file.iter().chain(&keymap_file).cloned().collect(), move |path| {
+            if Some(path) == keymap_file.as_deref() {
+                return match KeyMap::load(path) {
+                    Ok(keymap) => {
+                        println!("Reloaded the key map from {}", path.display());
+                        *saved_keymap.lock().unwrap() = keymap.clone();
+                        let _ = keymap_tx.send(keymap); // Nobody to play it without the keyboard thread
+                        true
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to reload the key map from {}: {}", path.display(), e);
+                        true
+                    }
+                };
+            }
+            let mut preset = match Preset::load(path) {
+                Ok(preset) => preset,
+                Err(e) => {
+                    eprintln!("Failed to reload the preset from {}: {}", path.display(), e);
+                    return true;
+                }
+            };
+            preset.ambient.enabled |= ambient;
+            if no_envelope {
+                preset.envelope = EnvelopeSettings::gate();
+            }
+            announcer.say(format!("Reloaded the preset from {}", path.display()));
+            if synth.send(SynthCommand::LoadPreset(Box::new(preset.clone()))).is_err() {
+                return false;
+            }
+            // So the UIs show the new values, and don't put the old ones back on their next edit
+            params.loaded(&preset, false);
+            true
+        });
+    }
 
-    // Input handling thread
-    thread::spawn({
-        move || {
-            let device_state = DeviceState::new();
-            let mut last_pressed_keys = Vec::new();
-            loop {
-                let currently_pressed_keys = device_state.get_keys();
-                let pressed_keys = currently_pressed_keys.iter()
-                                                         .filter(|&&key| !last_pressed_keys.contains(&key)) // Notice the double dereference here
+    let mouse = preset.mouse.clone();
+    // The pointer's two parameters are looked up once, and left unmoved if they can't be
+    let mouse_params = [&mouse.x.param, &mouse.y.param].map(|path| match ParamId::new(path) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            if mouse.enabled {
+                eprintln!("Mouse: {}", e);
+            }
+            None
+        }
+    });
+    let keyboard_backend = cli.keyboard;
+    let show_keys = cli.show_keys && (cli.headless || !cfg!(all(feature = "tui", not(feature = "gui")))); // The terminal UI shows the keys itself
+    #[cfg(all(feature = "tui", not(feature = "gui")))]
+    let ui_keymap = keymap.clone();
+    let debounce_window = Duration::from_secs_f32(cli.debounce.max(0.0) / 1000.0);
+    // Without a UI the function keys and Page Up/Down edit parameters, echoing them on the
+    // console (device_query can't see the scroll wheel, so the page keys stand in for it);
+    // the terminal UI reads the wheel itself and the window has sliders
+    let console_params = (!cfg!(any(feature = "tui", feature = "gui"))).then(|| params.clone());
+    // 9 flips between two edit buffers, however the edits are made, and keypad / and * undo and
+    // redo them (off - and =, which the terminal UI turns the selected parameter with)
+    let (edit_params, mut compare) = (params.clone(), params::Compare::new(&params));
+    // With the arpeggiator on, Left steps through its patterns, the preset's own included
+    let arp_patterns = preset.arp.enabled.then(|| (preset.arp.pattern_names(), preset.arp.selected()));
+
+    // Input handling thread, left out in headless mode where there may be no keyboard or display to poll
+    if cli.headless {
+        report_latency(None);
+        if !cli.has_remote_input() {
+            eprintln!("Headless with no --osc-port, --websocket-port, --midi or --jam: nothing can play the synth");
+        }
+        if show_keys {
+            print!("{}", keymap.diagram(&[])); // No keys are read, so there's nothing to light
+        }
+    } else {
+        // With --show-keys and no terminal UI to show it, the key map is printed once, then redrawn as keys change
+        if show_keys {
+            print!("{}", keymap.diagram(&[]));
+        }
+        // The keyboard is opened on the input thread, which reports back whether it could
+        let (opened_tx, opened) = mpsc::sync_channel(1);
+        let save_performance = save_performance.clone();
+        let announcer = announcer.clone();
+        let probe = probe.clone();
+        thread::spawn({
+            // Sending fails only once the synth has gone, which ends the thread quietly
+            move || -> Result<(), Error> {
+                let mut keyboard = match Keyboard::open(keyboard_backend) {
+                    Ok(keyboard) => keyboard,
+                    Err(e) => return Ok(opened_tx.send(Err(e))?),
+                };
+                opened_tx.send(Ok(keyboard.poll_interval()))?;
+                let mut debounce = Debounce::new(debounce_window);
+                let mut last_pressed_keys = Vec::new();
+                let mut tap_tempo = TapTempo::default();
+                let mut layer = None; // Velocity layer last sent to the synth
+                let mut last_mouse = None;
+                let mut selected_param = 0;
+                let mut hold = false;
+                let mut glide = true;
+                let mut latch = false;
+                let mut keymap = keymap;
+                let mut arp_patterns = arp_patterns;
+                let mut scene: usize = 0; // In the set list
+                let mut sounding: Vec<(Keycode, u8)> = Vec::new(); // Each held key's note, so a new key map can't leave one stuck
+                loop {
+                    let keys = keyboard.next_keys(debounce.wake(std::time::Instant::now())); // Waits for the keys to change
+                    let currently_pressed_keys = debounce.filter(keys, std::time::Instant::now());
+                    let pressed_keys = currently_pressed_keys.iter()
+                                                             .filter(|&&key| !last_pressed_keys.contains(&key)) // Notice the double dereference here
+                                                             .collect::<Vec<_>>();
+                    let released_keys = last_pressed_keys.iter()
+                                                         .filter(|&&key| !currently_pressed_keys.contains(&key)) // Same double dereference here
                                                          .collect::<Vec<_>>();
-                let released_keys = last_pressed_keys.iter()
-                                                     .filter(|&&key| !currently_pressed_keys.contains(&key)) // Same double dereference here
-                                                     .collect::<Vec<_>>();
+                    // Keys the key map plays are notes, whatever else they'd do
+                    let control_keys = pressed_keys.iter().copied().filter(|&&key| keymap.note(key).is_none()).collect::<Vec<_>>();
             
-                // Send NoteOn commands for new keys
-                for &key in pressed_keys.iter() { // Correctly getting a reference to the keycode
-                    tx.send(SynthCommand::NoteOn(*key)).expect("Failed to send NoteOn");
-                }
-                // Send NoteOff commands for released keys
-                for &key in released_keys.iter() { // Same here
-                    tx.send(SynthCommand::NoteOff(*key)).expect("Failed to send NoteOff");
-                }
+                    // Sequencer transport and step-entry keys
+                    for control in control_keys.iter().filter_map(|&&key| sequencer_control_from_key(key)) {
+                        sequencer_tx.send(control)?;
+                    }
+                    // Tap tempo: the clock threads read the shared tempo, the synth gets a command
+                    if control_keys.contains(&&Keycode::Grave) {
+                        if let Some(bpm) = tap_tempo.tap(std::time::Instant::now()) {
+                            tempo.set(bpm);
+                            tx.send(SynthCommand::SetTempo(bpm))?;
+                            announcer.say(format!("Tempo: {:.1} BPM", bpm));
+                        }
+                    }
+                    for (index, step) in control_keys.iter().filter_map(|&&key| macro_step_from_key(key)) {
+                        let value = (macro_values[index] + step).clamp(0.0, 1.0);
+                        macro_values[index] = value;
+                        tx.send(SynthCommand::SetMacro(index, value))?;
+                        looper_tx.send(LooperControl::Param(SynthCommand::SetMacro(index, value)))?;
+                        announcer.say(format!("Macro {}: {:.0}%", index + 1, value * 100.0));
+                    }
+                    if control_keys.contains(&&Keycode::Tab) {
+                        tx.send(SynthCommand::ToggleMetronome)?;
+                    }
+                    // Backspace is the panic button for stuck notes
+                    if control_keys.contains(&&Keycode::Backspace) {
+                        tx.send(SynthCommand::Panic)?;
+                        announcer.say("All notes off");
+                    }
+                    // Caps Lock latches the notes, like a sustain pedal that stays down
+                    if control_keys.contains(&&Keycode::CapsLock) {
+                        hold = !hold;
+                        tx.send(SynthCommand::ToggleHold)?;
+                        announcer.say(format!("Hold {}", if hold { "on" } else { "off" }));
+                    }
+                    // Home switches the mono glide off and on again
+                    if control_keys.contains(&&Keycode::Home) {
+                        glide = !glide;
+                        tx.send(SynthCommand::ToggleGlide)?;
+                        announcer.say(format!("Glide {}", if glide { "on" } else { "off" }));
+                    }
+                    // End latches notes: a press starts one, the next press of its key stops it
+                    if control_keys.contains(&&Keycode::End) {
+                        latch = !latch;
+                        tx.send(SynthCommand::ToggleLatch)?;
+                        announcer.say(format!("Latch {}", if latch { "on" } else { "off" }));
+                    }
+                    // Left moves the arpeggiator on to its next pattern
+                    if let Some((names, selected)) = arp_patterns.as_mut().filter(|_| control_keys.contains(&&Keycode::Left)) {
+                        *selected = (*selected + 1) % names.len();
+                        tx.send(SynthCommand::SetParam(ParamId::known("arp.pattern"), *selected as f32))?;
+                        announcer.say(format!("Arp pattern: {}", names[*selected]));
+                    }
+                    // [ and ] step back and on through the set list
+                    let step = match (control_keys.contains(&&Keycode::LeftBracket), control_keys.contains(&&Keycode::RightBracket)) {
+                        (true, false) => scene.checked_sub(1),
+                        (false, true) => Some(scene + 1),
+                        _ => None,
+                    };
+                    if let Some(next) = step.filter(|_| scenes.is_some()) {
+                        match scenes.as_ref().and_then(|scenes| scenes.get(next)) {
+                            Some(next_scene) => {
+                                scene = next;
+                                announcer.say(format!("Scene {}: {}", scene + 1, next_scene.name));
+                                tempo.set(next_scene.preset.tempo);
+                                for command in next_scene.commands() {
+                                    tx.send(command)?;
+                                }
+                                edit_params.loaded(&next_scene.preset, false);
+                            }
+                            None => announcer.say("No more scenes that way"),
+                        }
+                    }
+                    // Up and Down turn the master volume, keypad 9 and 0 mute and solo the main patch
+                    let current = |path: &str| edit_params.values().into_iter().find(|(param, _)| param == path).map_or(0.0, |(_, value)| value);
+                    const VOLUME_STEP_DB: f32 = 2.0; // Down to -60 dB, then off
+                    let volume_step = match (control_keys.contains(&&Keycode::Up), control_keys.contains(&&Keycode::Down)) {
+                        (true, false) => Some(VOLUME_STEP_DB),
+                        (false, true) => Some(-VOLUME_STEP_DB),
+                        _ => None,
+                    };
+                    if let Some(step) = volume_step {
+                        let volume = match current("master.volume") {
+                            volume if volume <= 0.0 && step > 0.0 => effects::db_to_gain(-60.0),
+                            volume => (volume * effects::db_to_gain(step)).min(1.0),
+                        };
+                        let volume = if volume < effects::db_to_gain(-61.0) { 0.0 } else { volume };
+                        edit_params.set("master.volume", volume);
+                        match volume {
+                            0.0 => announcer.say("Volume: off"),
+                            _ => announcer.say(format!("Volume: {:.0} dB", effects::gain_to_db(volume))),
+                        }
+                    }
+                    for (key, switch) in [(Keycode::Numpad9, "mute"), (Keycode::Numpad0, "solo")] {
+                        if control_keys.contains(&&key) {
+                            let path = format!("mixer.{}", switch);
+                            let on = current(&path) <= 0.5;
+                            edit_params.set(&path, if on { 1.0 } else { 0.0 });
+                            announcer.say(format!("Main patch {} {}", switch, if on { "on" } else { "off" }));
+                        }
+                    }
+                    if control_keys.contains(&&Keycode::Key9) {
+                        let slot = compare.toggle(&edit_params);
+                        announcer.say(format!("Comparing: {}", slot));
+                    }
+                    if control_keys.contains(&&Keycode::NumpadDivide) {
+                        match edit_params.undo() {
+                            Some(edit) => announcer.say(format!("Undo: {}", edit)),
+                            None => announcer.say("Nothing to undo"),
+                        }
+                    }
+                    if control_keys.contains(&&Keycode::NumpadMultiply) {
+                        match edit_params.redo() {
+                            Some(edit) => announcer.say(format!("Redo: {}", edit)),
+                            None => announcer.say("Nothing to redo"),
+                        }
+                    }
+                    // Insert writes out what's been played so far
+                    if control_keys.contains(&&Keycode::Insert) {
+                        save_performance();
+                    }
+                    // Slash saves the last seconds heard, recording or not
+                    if let (true, Some(capture)) = (control_keys.contains(&&Keycode::Slash), &capture) {
+                        capture.save();
+                    }
+                    // Looper transport keys
+                    for control in control_keys.iter().filter_map(|&&key| looper_control_from_key(key)) {
+                        looper_tx.send(control)?;
+                    }
+                    if let Some(reloaded) = keymap_rx.try_iter().last() {
+                        keymap = reloaded;
+                    }
+                    // Held modifiers set how loud the new notes play
+                    let pressed_layer = VelocityLayer::from_keys(&currently_pressed_keys);
+                    if layer != Some(pressed_layer) && pressed_keys.iter().any(|&&key| keymap.note(key).is_some()) {
+                        tx.send(SynthCommand::SetVelocity(pressed_layer.velocity()))?;
+                        sequencer_tx.send(SequencerControl::Velocity(pressed_layer.velocity()))?;
+                        layer = Some(pressed_layer);
+                    }
+                    // Send NoteOn commands for new keys that map to a note (also offered to step entry and the looper)
+                    for (&&key, note) in pressed_keys.iter().filter_map(|key| Some((key, keymap.note(**key)?))) {
+                        sounding.push((key, note));
+                        if let Some(probe) = &probe {
+                            probe.press();
+                        }
+                        tx.send(SynthCommand::NoteOn(note))?;
+                        sequencer_tx.send(SequencerControl::Note(note))?;
+                        looper_tx.send(LooperControl::NoteOn(note))?;
+                    }
+                    // Send NoteOff commands for released keys
+                    for &&key in &released_keys {
+                        let Some(index) = sounding.iter().position(|&(held, _)| held == key) else { continue };
+                        let (_, note) = sounding.swap_remove(index);
+                        tx.send(SynthCommand::NoteOff(note))?;
+                        looper_tx.send(LooperControl::NoteOff(note))?;
+                    }
+                    if show_keys && pressed_keys.iter().chain(&released_keys).any(|&&key| keymap.note(key).is_some()) {
+                        print!("{}", keymap.diagram(&currently_pressed_keys));
+                    }
             
-                // Update the last_pressed_keys list
-                last_pressed_keys = currently_pressed_keys.to_vec();
-
-                // Polling delay
-                thread::sleep(Duration::from_millis(1));
+                    // Parameter selection and adjustment
+                    let param_keys = |&&key: &&Keycode| param_index_from_key(key).is_some() || key == Keycode::PageUp || key == Keycode::PageDown;
+                    if let Some(params) = console_params.as_ref().filter(|_| control_keys.iter().any(param_keys)) {
+                        let paths = params.values();
+                        for index in control_keys.iter().filter_map(|&&key| param_index_from_key(key)) {
+                            if let Some((path, value)) = paths.get(index) {
+                                selected_param = index;
+                                announcer.say(format!("Selected {}: {:.3}", path, value));
+                            }
+                        }
+                        let direction = match (control_keys.contains(&&Keycode::PageUp), control_keys.contains(&&Keycode::PageDown)) {
+                            (true, false) => 1.0,
+                            (false, true) => -1.0,
+                            _ => 0.0,
+                        };
+                        if let Some((path, _)) = paths.get(selected_param).filter(|_| direction != 0.0) {
+                            if let Some(value) = params.nudge(path, direction) {
+                                announcer.say(format!("{}: {:.3}", path, value));
+                            }
+                        }
+                    }
+
+                    // The pointer's position drives two parameters, smoothed by the synth
+                    if let Some(coords) = mouse.enabled.then(|| keyboard.mouse()).flatten() {
+                        if last_mouse != Some(coords) {
+                            for ((_, value), id) in mouse.values(coords).into_iter().zip(mouse_params) {
+                                let Some(id) = id else { continue };
+                                tx.send(SynthCommand::GlideParam(id, value))?;
+                                looper_tx.send(LooperControl::Param(SynthCommand::GlideParam(id, value)))?;
+                            }
+                            last_mouse = Some(coords);
+                        }
+                    }
+
+                    // Update the last_pressed_keys list
+                    last_pressed_keys = currently_pressed_keys.to_vec();
+                }
             }
+        });
+        report_latency(opened.recv().map_err(|_| Error::Disconnected)??);
+    }
+
+    // Practice plays to the end on the console, then reports how it went
+    if let (Some(practice), Some(trainer)) = (&practice, &trainer) {
+        let report = practice::perform(practice, trainer, &synth_tx, || !shutdown.requested());
+        println!("{}", report);
+        fade_out(&synth_tx);
+        save_performance();
+        return Ok(());
+    }
+    if let (Some(Command::Quiz { kind, rounds }), Some(answers)) = (&cli.command, &answers) {
+        let (right, asked) = quiz::run(*kind, *rounds, &synth_tx, answers, || !shutdown.requested());
+        println!("{} of {} right ({:.0}%)", right, asked, 100.0 * right as f32 / asked.max(1) as f32);
+        fade_out(&synth_tx);
+        return Ok(());
+    }
+    // The reference tone sounds over whatever's played until Ctrl-C or SIGTERM
+    if let Some(Command::Tune { note, cents, a4, beat }) = &cli.command {
+        let frequency = reference::reference_frequency(*note, *cents, *a4);
+        match *cents {
+            0.0 => println!("{} at {:.3} Hz (A4 = {} Hz)", notes::note_name(*note), frequency, a4),
+            _ => println!("{} {:+} cents at {:.3} Hz (A4 = {} Hz)", notes::note_name(*note), cents, frequency, a4),
         }
-    });
+        synth_tx.send(SynthCommand::SetReference(Some(reference::ReferenceSettings { frequency, beat: *beat })))?;
+        while !shutdown.requested() {
+            thread::sleep(Duration::from_millis(100));
+        }
+        fade_out(&synth_tx);
+        return Ok(());
+    }
+    // A session ends by itself after its length, or early on Ctrl-C or SIGTERM
+    if let Some(Command::Binaural { carrier, beat, isochronic, minutes, fade }) = &cli.command {
+        let settings = binaural::BinauralSettings { carrier: *carrier, beat: *beat, isochronic: *isochronic, length: minutes.map(|minutes| minutes * 60.0), fade: *fade };
+        let [left, right] = settings.frequencies();
+        if *isochronic {
+            println!("{:.2} Hz pulsing {} times a second", left, beat);
+        } else {
+            println!("{:.2} Hz left, {:.2} Hz right: use headphones", left, right);
+        }
+        if let Some(minutes) = minutes {
+            println!("Session of {} minutes", minutes);
+        }
+        synth_tx.send(SynthCommand::SetBinaural(Some(settings)))?;
+        let end = settings.length.map(|length| Instant::now() + Duration::from_secs_f32(length.max(0.0)));
+        while !shutdown.requested() && end.is_none_or(|end| Instant::now() < end) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        if shutdown.requested() {
+            synth_tx.send(SynthCommand::SetBinaural(None))?;
+            thread::sleep(Duration::from_secs_f32(fade.max(0.0)));
+        }
+        fade_out(&synth_tx);
+        return Ok(());
+    }
 
-    // Audio playback thread
-    thread::spawn(move || {
-        // The synthesizer is now directly used as the audio source
-        stream_handle.play_raw(synth.convert_samples()).expect("Failed to play_raw");
-    });
+    // With the `gui` or `tui` feature the UI takes over the main thread (the window wins
+    // if both are enabled), and closing it ends the program
+    #[allow(unused_mut)] // Only taken when a UI is compiled in
+    let mut snapshot_rx = Some(snapshot_rx);
+    #[cfg(feature = "gui")]
+    if !cli.headless {
+        let snapshots = snapshot_rx.take().unwrap();
+        gui::run(&preset, params, snapshots, scope, xruns.clone(), cpu.clone(), shutdown.clone())
+            .map_err(|e| Error::Ui { what: "window", message: e.to_string() })?;
+    }
+    #[cfg(all(feature = "tui", not(feature = "gui")))]
+    if !cli.headless {
+        let snapshots = snapshot_rx.take().unwrap();
+        tui::run(&preset, params, snapshots, scope, ui_keymap, xruns.clone(), cpu.clone(), shutdown.clone())
+            .map_err(|e| Error::Ui { what: "terminal UI", message: e.to_string() })?;
+    }
 
-    // Keep the main thread alive as long as the audio needs to play.
-    loop {
-        thread::sleep(Duration::from_secs(1));
+    // Without a UI, keep the main thread alive until Ctrl-C or SIGTERM
+    if let Some(snapshots) = snapshot_rx {
+        report_on_console(snapshots, &xruns, &cpu, &shutdown);
+    }
+    fade_out(&synth_tx);
+    save_performance();
+    if let Some(report) = latency_report {
+        print!("{}", report.summary());
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+// Fades the synth out and waits for the fade to reach the speakers, so the program
+// doesn't stop the stream mid-note with a click
+fn fade_out(synth: &mpsc::Sender<SynthCommand>) {
+    if synth.send(SynthCommand::FadeOut).is_ok() {
+        thread::sleep(Duration::from_secs_f32(FADE_OUT_SECONDS) + Duration::from_millis(100)); // Plus the output buffer
+    }
+}
+
+// Reports clipping, underruns and high CPU load on the console, at most once a second,
+// until a shutdown is requested
+fn report_on_console(snapshots: mpsc::Receiver<rodio_synth::snapshot::Snapshot>, xruns: &XrunMonitor, cpu: &CpuMeter, shutdown: &Shutdown) {
+    let mut reported_clips = 0;
+    let mut reported_xruns = 0;
+    let mut peak = 0.0_f32;
+    let mut last_report = std::time::Instant::now();
+    while !shutdown.requested() {
+        // Snapshots keep coming while the audio plays; the timeout notices a shutdown if they don't
+        let Ok(snapshot) = snapshots.recv_timeout(Duration::from_millis(100)) else { continue };
+        peak = peak.max(snapshot.peak[stereo::LEFT]).max(snapshot.peak[stereo::RIGHT]);
+        if last_report.elapsed() < Duration::from_secs(1) {
+            continue;
+        }
+        if snapshot.clips > reported_clips {
+            let db = effects::gain_to_db(peak);
+            println!("Output clipped: {} samples in the last second, peak {:+.1} dBFS", snapshot.clips - reported_clips, db);
+            reported_clips = snapshot.clips;
+        }
+        if xruns.count() > reported_xruns {
+            println!("Audio underrun: {} in the last second ({})", xruns.count() - reported_xruns, xruns.summary());
+            reported_xruns = xruns.count();
+        }
+        if cpu.is_high() {
+            println!("{} of the audio budget, close to underrunning", cpu.summary());
+        }
+        peak = 0.0;
+        last_report = std::time::Instant::now();
+    }
+}
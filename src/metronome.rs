@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+const CLICK_LENGTH: f32 = 0.03; // Seconds for the click to decay to about -60 dB
+const ACCENT_FREQUENCY: f32 = 1500.0;
+const BEAT_FREQUENCY: f32 = 1000.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetronomeSettings {
+    pub enabled: bool,
+    pub volume: f32,
+    pub beats_per_bar: u32, // The first beat of each bar gets the accented click
+}
+
+impl Default for MetronomeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            volume: 0.5,
+            beats_per_bar: 4,
+        }
+    }
+}
+
+// Click generator running in the audio thread, so the clicks are sample-accurate
+pub struct Metronome {
+    settings: MetronomeSettings,
+    sample_rate: u32,
+    samples_per_beat: f32,
+    beat_position: f32, // Samples since the last beat
+    beat: u32,          // Beat within the bar, 0 is the accent
+    click_phase: f32,
+    click_increment: f32,
+    click_level: f32,
+    click_decay: f32,   // Per-sample multiplier for the click envelope
+}
+
+impl Metronome {
+    pub fn new(settings: MetronomeSettings, tempo: f32, sample_rate: u32) -> Self {
+        let mut metronome = Self {
+            settings,
+            sample_rate,
+            samples_per_beat: 0.0,
+            beat_position: 0.0,
+            beat: 0,
+            click_phase: 0.0,
+            click_increment: 0.0,
+            click_level: 0.0,
+            click_decay: (0.001_f32.ln() / (CLICK_LENGTH * sample_rate as f32)).exp(),
+        };
+        metronome.set_tempo(tempo);
+        metronome.restart();
+        metronome
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.samples_per_beat = self.sample_rate as f32 * 60.0 / tempo.max(1.0);
+    }
+
+    pub fn toggle(&mut self) {
+        self.settings.enabled = !self.settings.enabled;
+        if self.settings.enabled {
+            self.restart();
+        }
+    }
+
+    // Starts counting again from an accented first beat
+    fn restart(&mut self) {
+        self.beat = self.settings.beats_per_bar.max(1) - 1; // So the first click is beat 0
+        self.beat_position = self.samples_per_beat;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if !self.settings.enabled {
+            return 0.0;
+        }
+
+        if self.beat_position >= self.samples_per_beat {
+            self.beat_position -= self.samples_per_beat;
+            self.beat = (self.beat + 1) % self.settings.beats_per_bar.max(1);
+
+            let frequency = if self.beat == 0 { ACCENT_FREQUENCY } else { BEAT_FREQUENCY };
+            self.click_increment = 2.0 * PI * frequency / self.sample_rate as f32;
+            self.click_phase = 0.0;
+            self.click_level = 1.0;
+        }
+        self.beat_position += 1.0;
+
+        if self.click_level < 0.001 {
+            return 0.0;
+        }
+        let sample = self.click_phase.sin() * self.click_level * self.settings.volume;
+        self.click_phase += self.click_increment;
+        self.click_level *= self.click_decay;
+        sample
+    }
+}
@@ -0,0 +1,284 @@
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use std::str::FromStr;
+use std::sync::mpsc;
+
+use crate::announce::Announcer;
+use crate::bank::PresetBank;
+use crate::error::Error;
+use crate::looper::LooperControl;
+use crate::param_id::ParamId;
+use crate::params::ParamStore;
+use crate::scene::SceneList;
+use crate::tempo::SharedTempo;
+use crate::SynthCommand;
+
+// The channel the main patch listens on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiChannel {
+    Omni,     // Every channel
+    Only(u8), // 1-16
+}
+
+impl MidiChannel {
+    fn hears(self, channel: u8) -> bool {
+        self == MidiChannel::Omni || self == MidiChannel::Only(channel)
+    }
+}
+
+impl FromStr for MidiChannel {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.eq_ignore_ascii_case("omni") {
+            return Ok(MidiChannel::Omni);
+        }
+        match text.parse() {
+            Ok(channel @ 1..=16) => Ok(MidiChannel::Only(channel)),
+            _ => Err(format!("'{}' is not a MIDI channel (1-16) or omni", text)),
+        }
+    }
+}
+
+// No registered parameter selected, as after RPN 127/127 ("null")
+const NO_RPN: [u8; 2] = [127, 127];
+
+pub struct MidiSettings {
+    pub port: Option<String>,     // Part of the port's name or its number, the first port if None
+    pub channel: MidiChannel,     // Where the main patch's notes and program changes come from
+    pub bank: Option<PresetBank>, // Presets for program changes, which are ignored without one
+    pub queue_programs: bool,     // Switch presets just before the next note rather than at once
+    pub thru: Option<MidiThru>,   // Where to pass everything received on to, if anywhere
+    pub looper: mpsc::Sender<LooperControl>, // Gets the notes and controller moves too, to record them
+    pub params: ParamStore,                  // Told of program changes, so they can be undone
+    pub scenes: Option<SceneList>,           // Picked by program changes ahead of the bank, when given
+    pub tempo: SharedTempo,                  // Follows the scenes' tempos, for the clock threads
+    pub announcer: Announcer,                // Tells of program changes
+}
+
+// Soft thru: every message received is sent on to an output port as well, so the synth
+// can sit in the middle of a chain of hardware
+pub struct MidiThru {
+    pub port: Option<String>, // As for the input port
+    pub channel: Option<u8>,  // Channel (1-16) to move channel messages to, or as they came
+}
+
+// Plays the synth from a MIDI input port. Notes on the main patch's channel play it (and
+// any parts on channel 1, which layer with it) and notes on a part's channel play that
+// part, each with its velocity; channels nothing listens on are left to other gear. On
+// the main channel the pitch-bend wheel bends every voice, RPN 0 sets how far, channel
+// and poly pressure are the preset's aftertouch, the mod wheel (CC 1) and expression
+// pedal (CC 11) move what the preset gives them (vibrato depth and the level, unless it
+// says otherwise), channel volume (CC 7) sets the master volume, general purpose
+// controllers 1-4 (CC 16-19) turn the macros, and a program change switches to that
+// scene of the set list or program of the bank, at once or, when queued, just before
+// the next note starts so a foot switch pressed early still changes sound on the beat.
+// Everything goes to `output`, through the arp and the rest like the keyboard's notes.
+// Input stops when the returned connection is dropped.
+pub fn connect(
+    mut settings: MidiSettings,
+    output: mpsc::Sender<SynthCommand>,
+) -> Result<MidiInputConnection<[[u8; 2]; 16]>, Error> {
+    let mut thru = match settings.thru.take() {
+        Some(MidiThru { port, channel }) => Some((connect_thru(port)?, channel)),
+        None => None,
+    };
+
+    let mut input = MidiInput::new("rodio-synth").map_err(Error::midi("Failed to open MIDI input"))?;
+    if thru.is_none() {
+        input.ignore(Ignore::All); // No sysex, clock or active sensing, which only thru needs
+    }
+    let ports = input.ports();
+    let names: Vec<String> = ports.iter().map(|port| input.port_name(port).unwrap_or_default()).collect();
+    let port = settings.port.take();
+    let Some(index) = pick_port(&names, port.as_deref()) else {
+        return Err(Error::NoMidiPort { direction: "input", wanted: port, available: names });
+    };
+    println!("Reading MIDI from {}", names[index]);
+
+    input
+        .connect(
+            &ports[index],
+            "rodio-synth input",
+            move |_, message, rpn| {
+                if let Some((connection, channel)) = &mut thru {
+                    send_thru(connection, message, *channel);
+                }
+                for command in commands(message, &settings, rpn) {
+                    let recorded = match &command {
+                        SynthCommand::NoteOn(note) => Some(LooperControl::NoteOn(*note)),
+                        SynthCommand::NoteOff(note) => Some(LooperControl::NoteOff(*note)),
+                        SynthCommand::SetParam(..) | SynthCommand::SetMacro(..) => {
+                            Some(LooperControl::Param(command.clone()))
+                        }
+                        _ => None,
+                    };
+                    if let Some(control) = recorded {
+                        let _ = settings.looper.send(control); // Only the synth's channel going away matters
+                    }
+                    match &command {
+                        SynthCommand::LoadPreset(preset) | SynthCommand::CrossfadePreset(preset, _) => {
+                            settings.params.loaded(preset, false)
+                        }
+                        SynthCommand::QueuePreset(preset) => settings.params.loaded(preset, true),
+                        SynthCommand::SetTempo(bpm) => settings.tempo.set(*bpm),
+                        _ => {}
+                    }
+                    if output.send(command).is_err() {
+                        return; // The synth has stopped; the connection goes with the program
+                    }
+                }
+            },
+            [NO_RPN; 16], // The parameter each channel's data entry sets
+        )
+        .map_err(Error::midi("Failed to connect to the MIDI port"))
+}
+
+fn connect_thru(port: Option<String>) -> Result<MidiOutputConnection, Error> {
+    let output = MidiOutput::new("rodio-synth").map_err(Error::midi("Failed to open MIDI output"))?;
+    let ports = output.ports();
+    let names: Vec<String> = ports.iter().map(|port| output.port_name(port).unwrap_or_default()).collect();
+    let Some(index) = pick_port(&names, port.as_deref()) else {
+        return Err(Error::NoMidiPort { direction: "output", wanted: port, available: names });
+    };
+    println!("Passing MIDI through to {}", names[index]);
+    output
+        .connect(&ports[index], "rodio-synth thru")
+        .map_err(Error::midi("Failed to connect to the MIDI thru port"))
+}
+
+// The port whose name contains `wanted`, or whose number it is; the first without one
+fn pick_port(names: &[String], wanted: Option<&str>) -> Option<usize> {
+    match wanted {
+        None => (!names.is_empty()).then_some(0),
+        Some(wanted) => names
+            .iter()
+            .position(|name| name.contains(wanted))
+            .or_else(|| wanted.parse().ok().filter(|&index| index < names.len())),
+    }
+}
+
+// Sends `message` on, moved to `channel` if it's a channel message and one is given
+fn send_thru(connection: &mut MidiOutputConnection, message: &[u8], channel: Option<u8>) {
+    let mut message = message.to_vec();
+    if let (Some(status @ 0x80..=0xef), Some(channel)) = (message.first_mut(), channel) {
+        *status = (*status & 0xf0) | (channel - 1);
+    }
+    // A port that went away isn't worth stopping the synth for
+    if let Err(e) = connection.send(&message) {
+        tracing::warn!(error = %e, "MIDI thru failed");
+    }
+}
+
+// The synth commands for one MIDI message, keeping track of the parameter each channel
+// has selected in `rpn`; messages the synth has no use for are left alone
+fn commands(message: &[u8], settings: &MidiSettings, rpn: &mut [[u8; 2]; 16]) -> Vec<SynthCommand> {
+    let Some((&status, data)) = message.split_first() else { return Vec::new() };
+    let channel = (status & 0x0f) + 1;
+    match (status & 0xf0, data) {
+        (0x90, &[note, velocity, ..]) if velocity > 0 => {
+            let mut commands = notes(channel, note, true, settings);
+            if !commands.is_empty() {
+                commands.insert(0, SynthCommand::SetVelocity(velocity as f32 / 127.0));
+            }
+            commands
+        }
+        // Note on at velocity 0 is a note off
+        (0x80 | 0x90, &[note, ..]) => notes(channel, note, false, settings),
+        (0xe0, &[lsb, msb, ..]) if settings.channel.hears(channel) => {
+            let value = (((msb as i32) << 7) | lsb as i32) - 8192; // Centred on 0, -8192..8191
+            let wheel = value as f32 / if value < 0 { 8192.0 } else { 8191.0 };
+            vec![SynthCommand::SetParam(ParamId::known("pitch.wheel"), wheel)]
+        }
+        (0xd0, &[value, ..]) if settings.channel.hears(channel) => {
+            vec![SynthCommand::ChannelPressure(value as f32 / 127.0)]
+        }
+        (0xa0, &[note, value, ..]) if settings.channel.hears(channel) => {
+            vec![SynthCommand::PolyPressure(note, value as f32 / 127.0)]
+        }
+        (0xb0, &[controller, value, ..]) if settings.channel.hears(channel) => {
+            let selected = &mut rpn[channel as usize - 1];
+            let level = value as f32 / 127.0;
+            let set = |path, value| vec![SynthCommand::SetParam(ParamId::known(path), value)];
+            match controller {
+                101 => selected[0] = value,
+                100 => selected[1] = value,
+                98 | 99 => *selected = NO_RPN, // An NRPN, which data entry now goes to instead
+                16..=19 => return vec![SynthCommand::SetMacro(controller as usize - 16, level)],
+                7 => return set("master.volume", level),
+                1 => return set("controllers.mod_wheel", level),
+                11 => return set("controllers.expression", level),
+                // Data entry for RPN 0, the pitch-bend range in semitones (the cents that
+                // may follow on controller 38 are too fine to matter here)
+                6 if *selected == [0, 0] => return set("pitch.bend_range", value as f32),
+                _ => {}
+            }
+            Vec::new()
+        }
+        (0xc0, &[program, ..]) if settings.channel.hears(channel) && settings.scenes.is_some() => {
+            let Some(scene) = settings.scenes.as_ref().and_then(|scenes| scenes.get(program as usize)) else {
+                eprintln!("No scene {}", program as usize + 1);
+                return Vec::new();
+            };
+            settings.announcer.say(format!("Scene {}: {}", program as usize + 1, scene.name));
+            scene.commands()
+        }
+        (0xc0, &[program, ..]) if settings.channel.hears(channel) => {
+            let Some(bank) = &settings.bank else { return Vec::new() };
+            let (Some(preset), Some(name)) = (bank.get(program), bank.name(program)) else {
+                eprintln!("No preset for program {}", program);
+                return Vec::new();
+            };
+            settings.announcer.say(format!("Program {}: {}", program, name));
+            let preset = Box::new(preset.clone());
+            if settings.queue_programs {
+                vec![SynthCommand::QueuePreset(preset)]
+            } else {
+                vec![SynthCommand::LoadPreset(preset)]
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+// A note on `channel` for the main patch, if it listens there, and for any parts that
+// do. The synth sends channel 1 to the main patch, so that one only goes through when
+// the main patch hears it; the parts on the others are picked by the synth, which
+// also keeps up with the parts of presets loaded by program changes.
+fn notes(channel: u8, note: u8, on: bool, settings: &MidiSettings) -> Vec<SynthCommand> {
+    let mut commands = Vec::new();
+    if settings.channel.hears(channel) {
+        commands.push(SynthCommand::channel_note(1, note, on));
+    }
+    if channel != 1 {
+        commands.push(SynthCommand::channel_note(channel, note, on));
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MidiChannel;
+
+    #[test]
+    fn channels_parse_from_one_to_sixteen_or_omni() {
+        assert_eq!("omni".parse(), Ok(MidiChannel::Omni));
+        assert_eq!("OMNI".parse(), Ok(MidiChannel::Omni));
+        assert_eq!("1".parse(), Ok(MidiChannel::Only(1)));
+        assert_eq!("16".parse(), Ok(MidiChannel::Only(16)));
+    }
+
+    #[test]
+    fn malformed_channels_are_rejected() {
+        for text in ["0", "17", "-1", "", "x", "1.5", "omni2"] {
+            let error = text.parse::<MidiChannel>().unwrap_err();
+            assert_eq!(error, format!("'{}' is not a MIDI channel (1-16) or omni", text));
+        }
+    }
+
+    #[test]
+    fn omni_hears_every_channel() {
+        assert!((1..=16).all(|channel| MidiChannel::Omni.hears(channel)));
+        assert!(MidiChannel::Only(3).hears(3));
+        assert!(!MidiChannel::Only(3).hears(4));
+    }
+}
@@ -0,0 +1,128 @@
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, PitchBend, Smf, Timing, TrackEvent, TrackEventKind};
+use std::{io, path::Path};
+
+use crate::macros::MACROS;
+use crate::param_id::ParamId;
+use crate::SynthCommand;
+
+const TICKS_PER_BEAT: u16 = 480;
+const MACRO_CONTROLLER: u8 = 16; // Macros 1-4 are general purpose controllers 1-4 (CC 16-19), as on MIDI input
+
+// Writes timed synth commands (seconds from the start, as `Score::commands` makes them)
+// to a single-track Standard MIDI file, starting at `tempo` so a DAW's grid lines up.
+// Notes keep their channel and the velocity last set before them, macro moves become
+// controllers 16-19, the pitch wheel becomes pitch bend and tempo changes are kept;
+// everything else is left out.
+pub fn write(commands: &[(f32, SynthCommand)], mut tempo: f32, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut track = vec![TrackEvent { delta: u28::new(0), kind: tempo_event(tempo) }];
+    let mut velocity = 1.0;
+    let (mut seconds, mut beats, mut tick) = (0.0, 0.0, 0);
+    let midi = |channel: u8, message| TrackEventKind::Midi { channel: u4::new(channel.clamp(1, 16) - 1), message };
+    for (at, command) in commands {
+        // Tempo changes stretch the time between ticks from where they happen
+        beats += (at - seconds).max(0.0) as f64 * tempo as f64 / 60.0;
+        seconds = seconds.max(*at);
+        let kind = match *command {
+            // A file has no room for a humanized note's detune, so it's played in tune
+            SynthCommand::NoteOn(note) | SynthCommand::DetunedNoteOn(note, _) | SynthCommand::ChannelNoteOn(1, note) => midi(1, note_on(note, velocity)),
+            SynthCommand::ScaledNoteOn(note, scale) => midi(1, note_on(note, velocity * scale)),
+            SynthCommand::VelocityNoteOn(note, velocity) => midi(1, note_on(note, velocity)),
+            SynthCommand::ChannelNoteOn(channel, note) => midi(channel, note_on(note, velocity)),
+            SynthCommand::NoteOff(note) => midi(1, MidiMessage::NoteOff { key: u7::new(note), vel: u7::new(64) }),
+            SynthCommand::ChannelNoteOff(channel, note) => midi(channel, MidiMessage::NoteOff { key: u7::new(note), vel: u7::new(64) }),
+            SynthCommand::SetVelocity(value) => {
+                velocity = value;
+                continue;
+            }
+            SynthCommand::SetMacro(index, value) => midi(1, MidiMessage::Controller {
+                controller: u7::new(MACRO_CONTROLLER + index as u8),
+                value: u7::new((value.clamp(0.0, 1.0) * 127.0).round() as u8),
+            }),
+            SynthCommand::SetParam(id, value) | SynthCommand::GlideParam(id, value) if id.as_str() == "pitch.wheel" => {
+                midi(1, MidiMessage::PitchBend { bend: PitchBend::from_f32(value.clamp(-1.0, 1.0)) })
+            }
+            SynthCommand::SetTempo(bpm) => {
+                tempo = bpm;
+                tempo_event(bpm)
+            }
+            _ => continue,
+        };
+        let at_tick = (beats * TICKS_PER_BEAT as f64).round() as u32;
+        track.push(TrackEvent { delta: u28::new(at_tick - tick), kind });
+        tick = at_tick;
+    }
+    track.push(TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
+
+    let mut smf = Smf::new(Header::new(Format::SingleTrack, Timing::Metrical(u15::new(TICKS_PER_BEAT))));
+    smf.tracks.push(track);
+    smf.save(path)
+}
+
+fn note_on(note: u8, velocity: f32) -> MidiMessage {
+    // Velocity 0 would read as a note off
+    MidiMessage::NoteOn { key: u7::new(note), vel: u7::new((velocity.clamp(0.0, 1.0) * 127.0).round().max(1.0) as u8) }
+}
+
+fn tempo_event(bpm: f32) -> TrackEventKind<'static> {
+    // Microseconds per beat
+    TrackEventKind::Meta(MetaMessage::Tempo(u24::new((60_000_000.0 / bpm.max(1.0)) as u32)))
+}
+
+// Reads a Standard MIDI file as timed synth commands, the other way round from `write`:
+// notes on every channel with their velocities, controllers 16-19 as the macros, pitch
+// bend as the pitch wheel and tempo changes, all tracks merged
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<(f32, SynthCommand)>> {
+    let bytes = std::fs::read(path)?;
+    let smf = Smf::parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    // Every event with its absolute tick, in order; a stable sort keeps each track's own order
+    let mut events: Vec<(u64, TrackEventKind)> = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            events.push((tick, event.kind));
+        }
+    }
+    events.sort_by_key(|&(tick, _)| tick);
+
+    let mut commands = Vec::new();
+    let mut micros_per_beat = 500_000.0; // 120 BPM until the file says otherwise
+    let (mut seconds, mut last_tick) = (0.0_f64, 0);
+    for (tick, kind) in events {
+        seconds += match smf.header.timing {
+            Timing::Metrical(ticks_per_beat) => (tick - last_tick) as f64 * micros_per_beat / 1_000_000.0 / ticks_per_beat.as_int().max(1) as f64,
+            Timing::Timecode(fps, subframes) => (tick - last_tick) as f64 / (fps.as_f32() as f64 * subframes.max(1) as f64),
+        };
+        last_tick = tick;
+        let at = seconds as f32;
+        match kind {
+            // A tempo of no time per beat can't be played, so it's ignored
+            TrackEventKind::Meta(MetaMessage::Tempo(micros)) if micros.as_int() > 0 => {
+                micros_per_beat = micros.as_int() as f64;
+                commands.push((at, SynthCommand::SetTempo((60_000_000.0 / micros_per_beat) as f32)));
+            }
+            TrackEventKind::Midi { channel, message } => {
+                let channel = channel.as_int() + 1;
+                match message {
+                    MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                        commands.push((at, SynthCommand::SetVelocity(vel.as_int() as f32 / 127.0)));
+                        commands.push((at, SynthCommand::channel_note(channel, key.as_int(), true)));
+                    }
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        commands.push((at, SynthCommand::channel_note(channel, key.as_int(), false)));
+                    }
+                    MidiMessage::Controller { controller, value } if (MACRO_CONTROLLER..MACRO_CONTROLLER + MACROS as u8).contains(&controller.as_int()) => {
+                        let index = (controller.as_int() - MACRO_CONTROLLER) as usize;
+                        commands.push((at, SynthCommand::SetMacro(index, value.as_int() as f32 / 127.0)));
+                    }
+                    MidiMessage::PitchBend { bend } => commands.push((at, SynthCommand::SetParam(ParamId::known("pitch.wheel"), bend.as_f32()))),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(commands)
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::parts::{Part, MAX_PARTS};
+
+const SMOOTHING: f32 = 0.005; // Seconds a gain takes to get most of the way to a new level: quick, but no click
+
+// The main patch's channel on the mixer, beside the parts' (each part has the same
+// three settings in `PartSettings`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MixerSettings {
+    pub volume: f32, // 0.0..1.0
+    pub mute: bool,
+    pub solo: bool, // While any channel is soloed, only the soloed ones sound
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+impl MixerSettings {
+    // The settings the synth's "mixer.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("volume", self.volume), ("mute", if self.mute { 1.0 } else { 0.0 }), ("solo", if self.solo { 1.0 } else { 0.0 })]
+    }
+
+    // Mute and solo are switches, on above 0.5, so a macro or pedal can throw them
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "volume" => self.volume = value.clamp(0.0, 1.0),
+            "mute" => self.mute = value > 0.5,
+            "solo" => self.solo = value > 0.5,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// The gains the mixer settings come to, each moving smoothly to where the volumes,
+// mutes and solos put it, so muting or turning a part down doesn't click
+pub(crate) struct Mixer {
+    master: f32,                 // The "master.volume" parameter, 0.0..1.0 on the final output
+    expression: f32,             // The "master.expression" parameter, the expression pedal's share of it
+    master_gain: f32,            // Smoothed
+    gains: [f32; MAX_PARTS + 1], // Smoothed, of the main patch and then each part
+    coefficient: f32,            // Fraction of the remaining distance covered each frame
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            master: 1.0,
+            expression: 1.0,
+            master_gain: 1.0,
+            gains: [1.0; MAX_PARTS + 1],
+            coefficient: 1.0 - (-1.0 / (SMOOTHING * sample_rate as f32)).exp(),
+        }
+    }
+
+    pub fn set_master(&mut self, volume: f32) {
+        self.master = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn set_expression(&mut self, expression: f32) {
+        self.expression = expression.clamp(0.0, 1.0);
+    }
+
+    // Moves every gain a frame's step on
+    pub fn advance(&mut self, main: &MixerSettings, parts: &[Part]) {
+        self.step(main, parts, self.coefficient);
+    }
+
+    // Puts every gain where it's headed at once, for a preset that's just loaded
+    pub fn settle(&mut self, main: &MixerSettings, parts: &[Part]) {
+        self.step(main, parts, 1.0);
+    }
+
+    fn step(&mut self, main: &MixerSettings, parts: &[Part], coefficient: f32) {
+        let soloing = main.solo || parts.iter().any(|part| part.settings.solo);
+        let target = |volume: f32, mute: bool, solo: bool| if mute || (soloing && !solo) { 0.0 } else { volume };
+        let targets = std::iter::once(target(main.volume, main.mute, main.solo))
+            .chain(parts.iter().map(|part| target(part.settings.volume, part.settings.mute, part.settings.solo)));
+        for (gain, target) in self.gains.iter_mut().zip(targets) {
+            *gain += (target - *gain) * coefficient;
+        }
+        self.master_gain += (self.master * self.expression - self.master_gain) * coefficient;
+    }
+
+    // The main patch's gain
+    pub fn main(&self) -> f32 {
+        self.gains[0]
+    }
+
+    pub fn part(&self, index: usize) -> f32 {
+        self.gains[index + 1]
+    }
+
+    pub fn master_gain(&self) -> f32 {
+        self.master_gain
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotePriority {
+    Last,    // The most recently pressed note wins
+    Lowest,  // The lowest held note wins, handy for basslines
+    Highest, // The highest held note wins, handy for leads
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonoSettings {
+    pub enabled: bool,          // Only one note sounds at a time
+    pub priority: NotePriority, // Which held note sounds when several are down
+    pub legato: bool,           // Overlapping notes change pitch without restarting the envelope
+    pub glide: f32,             // Seconds to slide from one note's pitch to the next, 0.0 to jump
+    pub glide_velocity: f32,    // 0.0..1.0, how much shorter a hard-played note makes the slide
+}
+
+impl Default for MonoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            priority: NotePriority::Last,
+            legato: true,
+            glide: 0.0,
+            glide_velocity: 0.0,
+        }
+    }
+}
+
+impl MonoSettings {
+    // The settings the synth's "mono.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("glide", self.glide), ("glide_velocity", self.glide_velocity)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "glide" => self.glide = value.clamp(0.0, 5.0),
+            "glide_velocity" => self.glide_velocity = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+
+    // How long the slide to a note played at `velocity` takes
+    pub fn glide_time(&self, velocity: f32) -> f32 {
+        self.glide * (1.0 - self.glide_velocity * velocity.clamp(0.0, 1.0))
+    }
+}
+
+// The notes currently held down in mono mode, in the order they were pressed
+pub struct HeldNotes {
+    notes: Vec<u8>,
+    priority: NotePriority,
+}
+
+impl HeldNotes {
+    pub fn new(priority: NotePriority) -> Self {
+        Self {
+            notes: Vec::with_capacity(128), // Room for every note, so pressing one never allocates
+            priority,
+        }
+    }
+
+    pub fn priority(&self) -> NotePriority {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: NotePriority) {
+        self.priority = priority;
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+    }
+
+    pub fn press(&mut self, note: u8) {
+        self.notes.retain(|&held| held != note);
+        self.notes.push(note);
+    }
+
+    pub fn release(&mut self, note: u8) {
+        self.notes.retain(|&held| held != note);
+    }
+
+    // The held note that should be sounding according to the note priority
+    pub fn winner(&self) -> Option<u8> {
+        match self.priority {
+            NotePriority::Last => self.notes.last().copied(),
+            NotePriority::Lowest => self.notes.iter().min().copied(),
+            NotePriority::Highest => self.notes.iter().max().copied(),
+        }
+    }
+}
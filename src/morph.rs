@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use crate::lfo::LfoShape;
+use crate::tempo::Rate;
+use crate::Waveform;
+
+// The waveforms a morph passes through, in order of brightness
+const ORDER: [Waveform; 4] = [Waveform::Sine, Waveform::Triangle, Waveform::Saw, Waveform::Square];
+
+// Replaces the fixed waveform with a position that crossfades between neighbouring
+// shapes, sine → triangle → saw → square, so the timbre can be swept while notes play.
+// An LFO can sweep it continuously and each voice's envelope can open it as the note starts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MorphSettings {
+    pub enabled: bool,
+    pub position: f32,  // 0.0 sine, 1.0 triangle, 2.0 saw, 3.0 square, mixtures in between
+    pub lfo_rate: Rate, // In Hz or as a tempo division
+    pub lfo_depth: f32, // How far the LFO moves the position either way, 0.0 for no sweep
+    pub lfo_shape: LfoShape,
+    pub envelope: f32,  // How far the voice's envelope moves the position at full level; negative moves it down
+}
+
+impl Default for MorphSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            position: 0.0,
+            lfo_rate: Rate::Hz(0.5),
+            lfo_depth: 0.0,
+            lfo_shape: LfoShape::Sine,
+            envelope: 0.0,
+        }
+    }
+}
+
+impl MorphSettings {
+    // The settings the synth's "morph.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params = vec![("position", self.position)];
+        params.extend(self.lfo_rate.editable_param("lfo_rate"));
+        params.extend([("lfo_depth", self.lfo_depth), ("envelope", self.envelope)]);
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "position" => self.position = value.clamp(0.0, 3.0),
+            "lfo_rate" => self.lfo_rate = Rate::Hz(value.max(0.0)),
+            "lfo_depth" => self.lfo_depth = value.clamp(0.0, 3.0),
+            "envelope" => self.envelope = value.clamp(-3.0, 3.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// The morphed waveform's value at `phase` (0 to 2π) for a position between 0.0 and 3.0
+pub fn sample(position: f32, phase: f32) -> f32 {
+    let position = position.clamp(0.0, 3.0);
+    let index = (position as usize).min(ORDER.len() - 2);
+    let amount = position - index as f32;
+    ORDER[index].sample(phase) * (1.0 - amount) + ORDER[index + 1].sample(phase) * amount
+}
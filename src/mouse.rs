@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::macros::{MacroCurve, MacroTarget};
+
+// Turns the mouse pointer into two controllers while playing: left to right moves
+// one parameter and bottom to top another, each over its own range
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseSettings {
+    pub enabled: bool,
+    pub width: u32,     // Screen size in pixels, the range the pointer moves over
+    pub height: u32,
+    pub x: MacroTarget, // Parameter moved by the horizontal position, "pitch.bend" for pitch bend
+    pub y: MacroTarget, // Parameter moved by the vertical position, top is `max`
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 1920,
+            height: 1080,
+            x: MacroTarget {
+                param: "pitch.bend".to_string(),
+                min: -2.0, // Semitones
+                max: 2.0,
+                curve: MacroCurve::Linear,
+            },
+            y: MacroTarget {
+                param: "eq.high_gain".to_string(),
+                min: -24.0, // Darker towards the bottom, like closing a low-pass filter
+                max: 0.0,
+                curve: MacroCurve::Linear,
+            },
+        }
+    }
+}
+
+impl MouseSettings {
+    // The two parameter values for a pointer position in screen pixels
+    pub fn values(&self, (x, y): (i32, i32)) -> [(&str, f32); 2] {
+        let across = x as f32 / self.width.max(1) as f32;
+        let up = 1.0 - y as f32 / self.height.max(1) as f32; // Screen rows count down from the top
+        [(&self.x.param, self.x.value_at(across)), (&self.y.param, self.y.value_at(up))]
+    }
+}
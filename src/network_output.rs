@@ -0,0 +1,88 @@
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rodio_synth::record_tap::{RecordTap, RecordedFrame};
+use rodio_synth::stereo::Frame;
+
+const PACKET_FRAMES: usize = 256;                         // About 5 ms a packet
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500); // A listener that stalls this long is dropped, so it can't hold up the others
+const MAGIC: &[u8; 4] = b"PCM1";
+
+// Streams what the synth plays over TCP to anyone who connects to `port`, so a headless
+// box running the synth can be listened to from another machine. Each listener first
+// gets a header, then packets until it hangs up:
+//
+//     header: "PCM1", sample rate (u32), channels (u16, 2), bits (u16, 16)
+//     packet: length in bytes (u32), then that many bytes of interleaved i16 samples
+//
+// all little-endian. Listeners join and leave at any time; with none the sound is thrown away.
+// Returns the tap for `Synthesizer::with_recording`.
+pub fn spawn(port: u16, sample_rate: u32) -> io::Result<RecordTap> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Streaming audio to TCP listeners on port {}", port);
+    let listeners = Arc::new(Mutex::new(Vec::new()));
+
+    thread::spawn({
+        let listeners = listeners.clone();
+        move || {
+            for stream in listener.incoming().flatten() {
+                match greet(stream, sample_rate) {
+                    Ok(stream) => listeners.lock().unwrap().push(stream),
+                    Err(e) => eprintln!("Failed to start streaming to a listener: {}", e),
+                }
+            }
+        }
+    });
+
+    let (tap, frames) = RecordTap::new(sample_rate, false);
+    thread::spawn(move || send(frames, &listeners));
+    Ok(tap)
+}
+
+fn greet(mut stream: TcpStream, sample_rate: u32) -> io::Result<TcpStream> {
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut header = MAGIC.to_vec();
+    header.extend(sample_rate.to_le_bytes());
+    header.extend(2_u16.to_le_bytes());
+    header.extend(16_u16.to_le_bytes());
+    stream.write_all(&header)?;
+    if let Ok(address) = stream.peer_addr() {
+        println!("Streaming audio to {}", address);
+    }
+    Ok(stream)
+}
+
+// A frame as interleaved little-endian 16-bit samples
+pub fn pcm16(frame: Frame) -> [u8; 4] {
+    let [left, right] = frame.map(|sample| ((sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16).to_le_bytes());
+    [left[0], left[1], right[0], right[1]]
+}
+
+// Gathers frames into packets and sends each to every listener, until the synth goes away
+fn send(frames: mpsc::Receiver<RecordedFrame>, listeners: &Mutex<Vec<TcpStream>>) {
+    let mut packet = Vec::with_capacity(4 + PACKET_FRAMES * 4);
+    let mut count = 0;
+    for frame in frames {
+        if count == 0 {
+            packet.clear();
+            packet.extend(((PACKET_FRAMES * 4) as u32).to_le_bytes());
+        }
+        packet.extend(pcm16(frame.mix));
+        count += 1;
+        if count == PACKET_FRAMES {
+            count = 0;
+            listeners.lock().unwrap().retain_mut(|stream| match stream.write_all(&packet) {
+                Ok(()) => true,
+                Err(e) => {
+                    let address = stream.peer_addr().map_or("a listener".to_string(), |address| address.to_string());
+                    println!("Stopped streaming audio to {}: {}", address, e);
+                    false
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,73 @@
+// Conversions between MIDI note numbers and note names such as "C4", "F#3" or "Bb5"
+
+const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+// A set of MIDI notes, one bit each, so it can be built and passed on without allocating
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoteSet(u128);
+
+impl NoteSet {
+    pub fn insert(&mut self, note: u8) {
+        self.0 |= 1 << (note & 127);
+    }
+
+    pub fn contains(self, note: u8) -> bool {
+        note < 128 && self.0 & (1 << note) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    // The notes in ascending order
+    pub fn iter(self) -> impl Iterator<Item = u8> {
+        (0..128).filter(move |&note| self.contains(note))
+    }
+}
+
+impl FromIterator<u8> for NoteSet {
+    fn from_iter<I: IntoIterator<Item = u8>>(notes: I) -> Self {
+        let mut set = NoteSet::default();
+        for note in notes {
+            set.insert(note);
+        }
+        set
+    }
+}
+
+pub fn note_name(note: u8) -> String {
+    let octave = note as i32 / 12 - 1; // MIDI 60 is C4
+    format!("{}{}", NAMES[note as usize % 12], octave)
+}
+
+pub fn parse_note_name(name: &str) -> Option<u8> {
+    let name = name.trim();
+    let mut chars = name.chars();
+    let letter = chars.next()?.to_ascii_uppercase();
+    let base: i32 = match letter {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let (accidental, octave) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        _ => (0, rest),
+    };
+    let octave: i32 = octave.parse().ok()?;
+
+    let note = (octave + 1) * 12 + base + accidental;
+    u8::try_from(note).ok().filter(|&note| note <= 127)
+}
+
+// Pitch class (0 = C .. 11 = B) of a note name without an octave, such as "F#" or "Bb"
+pub fn parse_pitch_class(name: &str) -> Option<u8> {
+    parse_note_name(&format!("{}4", name.trim())).map(|note| note % 12)
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+// The drawbars' pitches as harmonics of the 16' sub-octave, from 16' down to 1':
+// 16', 5 1/3', 8', 4', 2 2/3', 2', 1 3/5', 1 1/3' and 1'
+const HARMONICS: [f32; 9] = [1.0, 3.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 16.0];
+const DRAWBAR_NAMES: [&str; 9] = [
+    "drawbar_1", "drawbar_2", "drawbar_3", "drawbar_4", "drawbar_5", "drawbar_6", "drawbar_7", "drawbar_8", "drawbar_9",
+];
+const PERCUSSION_HARMONIC: f32 = 6.0; // 2 2/3', the third harmonic of the note
+const CLICK_MS: f32 = 3.0; // Length of the key click
+const HEADROOM: f32 = 3.0; // Three drawbars all the way out play at about full scale
+
+// A tonewheel organ in place of the oscillators: every voice adds up sine waves at the
+// pitches of nine drawbars, each pulled out from 0 (silent) to 8 (loudest) as on the
+// real thing, so "888000000" is the classic full gospel sound. Percussion adds a ping
+// at the third harmonic that dies away, and the key click the contacts make. For the
+// swirl, put a rotary effect in the chain.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrganSettings {
+    pub enabled: bool,
+    pub drawbars: [f32; 9],    // 0.0..8.0 each, from 16' down to 1'
+    pub percussion: f32,       // Level of the percussion ping, 0.0 for none
+    pub percussion_decay: f32, // Seconds for the ping to die away
+    pub click: f32,            // Level of the key click, 0.0 for none
+}
+
+impl Default for OrganSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            drawbars: [8.0, 8.0, 8.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            percussion: 0.0,
+            percussion_decay: 0.3,
+            click: 0.2,
+        }
+    }
+}
+
+impl OrganSettings {
+    // The settings the synth's "organ.<name>" parameters change, with their current values;
+    // the drawbars are "drawbar_1" (16') to "drawbar_9" (1')
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params: Vec<_> = DRAWBAR_NAMES.into_iter().zip(self.drawbars).collect();
+        params.extend([("percussion", self.percussion), ("percussion_decay", self.percussion_decay), ("click", self.click)]);
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if let Some(index) = DRAWBAR_NAMES.iter().position(|&drawbar| drawbar == name) {
+            self.drawbars[index] = value.clamp(0.0, 8.0);
+            return true;
+        }
+        match name {
+            "percussion" => self.percussion = value.clamp(0.0, 1.0),
+            "percussion_decay" => self.percussion_decay = value.clamp(0.01, 5.0),
+            "click" => self.click = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// One voice's tonewheels
+pub struct OrganVoice {
+    phase: f32,      // Of the 16' sub-octave, which every drawbar is a whole harmonic of
+    percussion: f32, // What's left of the ping, from 1.0 at the start of the note
+    click: f32,      // Samples of key click still to come
+    random: u32,     // Xorshift state for the click
+}
+
+impl OrganVoice {
+    pub fn new(sample_rate: u32, seed: u32) -> Self {
+        Self {
+            phase: 0.0,
+            percussion: 1.0,
+            click: click_length(sample_rate),
+            random: seed | 1, // Never zero, which xorshift can't leave
+        }
+    }
+
+    // The next sample for a voice playing `frequency`
+    pub fn next_sample(&mut self, frequency: f32, settings: &OrganSettings, sample_rate: u32) -> f32 {
+        let mut sample: f32 = HARMONICS
+            .iter()
+            .zip(&settings.drawbars)
+            .filter(|(_, &level)| level > 0.0)
+            .map(|(harmonic, level)| (self.phase * harmonic).sin() * level / 8.0)
+            .sum();
+        if settings.percussion > 0.0 {
+            sample += (self.phase * PERCUSSION_HARMONIC).sin() * settings.percussion * self.percussion;
+            self.percussion *= 1.0 - 1.0 / (settings.percussion_decay.max(0.01) * sample_rate as f32);
+        }
+        if self.click > 0.0 {
+            sample += self.next_noise() * settings.click * self.click / click_length(sample_rate);
+            self.click -= 1.0;
+        }
+
+        self.phase = (self.phase + PI * frequency / sample_rate as f32).rem_euclid(2.0 * PI);
+        sample / HEADROOM
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+fn click_length(sample_rate: u32) -> f32 {
+    CLICK_MS * 0.001 * sample_rate as f32
+}
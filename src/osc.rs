@@ -0,0 +1,269 @@
+use std::io;
+use std::net::UdpSocket;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::macros::MACROS;
+use crate::param_id::ParamId;
+use crate::tempo::SharedTempo;
+use crate::SynthCommand;
+
+const MAX_PACKET: usize = 65_536;
+
+// One argument of an OSC message; integers and floats are both accepted wherever a
+// number is expected, since controllers differ in what they send
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl OscArg {
+    fn as_f32(&self) -> Option<f32> {
+        match *self {
+            OscArg::Int(value) => Some(value as f32),
+            OscArg::Float(value) => Some(value as f32),
+            OscArg::Bool(value) => Some(if value { 1.0 } else { 0.0 }),
+            OscArg::Str(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+// Listens for OSC over UDP on `port` and turns the messages into synth commands:
+//
+//   /note/on <note> [velocity] [channel]  MIDI note number; a velocity of 0 means note off.
+//   /note/off <note> [channel]            The channel (1-16, default 1) picks the parts that play
+//   /param/<path> <value>       e.g. /param/eq/low_gain or /param/effects/0/mix
+//   /macro/<1-4> <0.0..1.0>
+//   /tempo <bpm>
+//   /metronome                  Toggles the click
+//   /hold                       Toggles hold, which keeps released notes ringing
+//   /glide                      Toggles the mono glide
+//   /latch                      Toggles latch, where each press starts or stops a note
+//   /panic                      Silences every voice, for stuck notes
+//
+// Bundles are unpacked and their messages applied at once, ignoring the time tag.
+pub fn spawn(port: u16, tempo: SharedTempo, output: mpsc::Sender<SynthCommand>) -> io::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    println!("Listening for OSC on UDP port {}", port);
+
+    thread::spawn(move || {
+        let mut buffer = vec![0; MAX_PACKET];
+        loop {
+            let Ok((length, sender)) = socket.recv_from(&mut buffer) else { continue };
+            let messages = match decode_packet(&buffer[..length]) {
+                Some(messages) => messages,
+                None => {
+                    eprintln!("Ignoring malformed OSC packet from {}", sender);
+                    continue;
+                }
+            };
+            for message in messages {
+                let Some(command) = to_command(&message) else {
+                    eprintln!("Ignoring OSC message {} {:?}", message.address, message.args);
+                    continue;
+                };
+                if let SynthCommand::SetTempo(bpm) = command {
+                    tempo.set(bpm); // The clock threads read the shared tempo, like with tap tempo
+                }
+                if output.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+fn to_command(message: &OscMessage) -> Option<SynthCommand> {
+    let number = |index: usize| message.args.get(index).and_then(OscArg::as_f32);
+    let note = || number(0).map(|note| note.clamp(0.0, 127.0) as u8);
+    let channel = |index: usize| number(index).map_or(1, |channel| channel.clamp(1.0, 16.0) as u8);
+
+    let mut parts = message.address.trim_start_matches('/').splitn(2, '/');
+    match (parts.next()?, parts.next()) {
+        ("note", Some("on")) => match number(1) {
+            Some(velocity) if velocity <= 0.0 => Some(SynthCommand::channel_note(channel(2), note()?, false)),
+            _ => Some(SynthCommand::channel_note(channel(2), note()?, true)),
+        },
+        ("note", Some("off")) => Some(SynthCommand::channel_note(channel(1), note()?, false)),
+        // Paths that can't be a parameter are ignored here like any other bad address
+        ("param", Some(path)) => Some(SynthCommand::SetParam(ParamId::new(&path.replace('/', ".")).ok()?, number(0)?)),
+        ("macro", Some(index)) => {
+            let index = index.parse::<usize>().ok().filter(|index| (1..=MACROS).contains(index))?;
+            Some(SynthCommand::SetMacro(index - 1, number(0)?.clamp(0.0, 1.0)))
+        }
+        ("tempo", None) => Some(SynthCommand::SetTempo(number(0)?.clamp(20.0, 400.0))),
+        ("metronome", None) => Some(SynthCommand::ToggleMetronome),
+        ("hold", None) => Some(SynthCommand::ToggleHold),
+        ("glide", None) => Some(SynthCommand::ToggleGlide),
+        ("latch", None) => Some(SynthCommand::ToggleLatch),
+        ("panic", None) => Some(SynthCommand::Panic),
+        _ => None,
+    }
+}
+
+// A packet is either one message or a bundle of packets, each prefixed with its size
+pub fn decode_packet(data: &[u8]) -> Option<Vec<OscMessage>> {
+    if !data.starts_with(b"#bundle\0") {
+        return Some(vec![decode_message(data)?]);
+    }
+    let mut messages = Vec::new();
+    let mut rest = data.get(16..)?; // "#bundle\0" and the time tag
+    while !rest.is_empty() {
+        let size = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        messages.extend(decode_packet(rest.get(4..4 + size)?)?);
+        rest = &rest[4 + size..];
+    }
+    Some(messages)
+}
+
+fn decode_message(data: &[u8]) -> Option<OscMessage> {
+    let (address, mut rest) = read_string(data)?;
+    if !address.starts_with('/') {
+        return None;
+    }
+    // Very old senders leave out the type tags; treat that as no arguments
+    let (tags, after_tags) = if rest.is_empty() { (",".to_string(), rest) } else { read_string(rest)? };
+    rest = after_tags;
+
+    let mut args = Vec::new();
+    for tag in tags.strip_prefix(',')?.chars() {
+        let arg = match tag {
+            'i' => OscArg::Int(i32::from_be_bytes(take(&mut rest, 4)?.try_into().ok()?) as i64),
+            'h' => OscArg::Int(i64::from_be_bytes(take(&mut rest, 8)?.try_into().ok()?)),
+            'f' => OscArg::Float(f32::from_be_bytes(take(&mut rest, 4)?.try_into().ok()?) as f64),
+            'd' => OscArg::Float(f64::from_be_bytes(take(&mut rest, 8)?.try_into().ok()?)),
+            's' | 'S' => {
+                let (text, after) = read_string(rest)?;
+                rest = after;
+                OscArg::Str(text)
+            }
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            'N' | 'I' => continue, // Nil and impulse carry no data
+            _ => return None,      // Blobs and the rarer types aren't used by anything here
+        };
+        args.push(arg);
+    }
+    Some(OscMessage { address, args })
+}
+
+// A null-terminated string padded with nulls to a multiple of four bytes, and what follows it
+fn read_string(data: &[u8]) -> Option<(String, &[u8])> {
+    let end = data.iter().position(|&byte| byte == 0)?;
+    let text = std::str::from_utf8(&data[..end]).ok()?.to_string();
+    let padded = (end + 4) & !3;
+    if data.get(end..padded)?.iter().any(|&byte| byte != 0) {
+        return None;
+    }
+    Some((text, &data[padded..]))
+}
+
+fn take<'a>(data: &mut &'a [u8], count: usize) -> Option<&'a [u8]> {
+    let taken = data.get(..count)?;
+    *data = &data[count..];
+    Some(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `text` null-terminated and padded to four bytes, as OSC writes strings
+    fn string(text: &str) -> Vec<u8> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize((text.len() + 4) & !3, 0);
+        bytes
+    }
+
+    fn message(address: &str, tags: &str, args: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = string(address);
+        bytes.extend(string(tags));
+        for arg in args {
+            bytes.extend_from_slice(arg);
+        }
+        bytes
+    }
+
+    fn bundle(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"#bundle\0".to_vec();
+        bytes.extend(1u64.to_be_bytes()); // "Immediately"
+        for packet in packets {
+            bytes.extend((packet.len() as u32).to_be_bytes());
+            bytes.extend(packet);
+        }
+        bytes
+    }
+
+    #[test]
+    fn message_with_int_and_float_arguments() {
+        let packet = message("/note/on", ",if", &[&60i32.to_be_bytes(), &0.5f32.to_be_bytes()]);
+        let messages = decode_packet(&packet).unwrap();
+        assert_eq!(messages, vec![OscMessage { address: "/note/on".to_string(), args: vec![OscArg::Int(60), OscArg::Float(0.5)] }]);
+        assert_eq!(to_command(&messages[0]), Some(SynthCommand::channel_note(1, 60, true)));
+    }
+
+    #[test]
+    fn message_with_strings_and_flags() {
+        let packet = message("/x", ",sTFN", &[&string("abcd")]);
+        let args = decode_packet(&packet).unwrap().remove(0).args;
+        assert_eq!(args, vec![OscArg::Str("abcd".to_string()), OscArg::Bool(true), OscArg::Bool(false)]);
+    }
+
+    #[test]
+    fn message_without_type_tags_has_no_arguments() {
+        let messages = decode_packet(&string("/panic")).unwrap();
+        assert_eq!(messages[0].args, Vec::new());
+        assert_eq!(to_command(&messages[0]), Some(SynthCommand::Panic));
+    }
+
+    #[test]
+    fn bundles_unpack_in_order_including_nested_ones() {
+        let tempo = message("/tempo", ",d", &[&140f64.to_be_bytes()]);
+        let hold = message("/hold", ",", &[]);
+        let macro_ = message("/macro/2", ",h", &[&1i64.to_be_bytes()]);
+        let packet = bundle(&[tempo, bundle(&[hold, macro_])]);
+
+        let addresses: Vec<_> = decode_packet(&packet).unwrap().into_iter().map(|message| message.address).collect();
+        assert_eq!(addresses, ["/tempo", "/hold", "/macro/2"]);
+        assert_eq!(decode_packet(&bundle(&[])), Some(Vec::new()));
+    }
+
+    #[test]
+    fn bad_padding_is_rejected() {
+        // The address's padding must be nulls, and must be there at all
+        let mut packet = message("/hold", ",", &[]);
+        packet[6] = b'x';
+        assert_eq!(decode_packet(&packet), None);
+        assert_eq!(decode_packet(b"/hold\0"), None);
+        assert_eq!(decode_packet(b"/ab\0,i\0"), None);
+    }
+
+    #[test]
+    fn truncated_packets_are_rejected() {
+        let packet = message("/note/on", ",ii", &[&60i32.to_be_bytes(), &100i32.to_be_bytes()]);
+        assert_eq!(decode_packet(&packet[..packet.len() - 2]), None);
+
+        let packet = bundle(&[message("/hold", ",", &[])]);
+        assert_eq!(decode_packet(&packet[..packet.len() - 1]), None);
+        assert_eq!(decode_packet(&packet[..12]), None); // Cut inside the time tag
+        assert_eq!(decode_packet(&packet[..18]), None); // Cut inside an element's size
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        assert_eq!(decode_packet(&message("hold", ",", &[])), None); // No leading slash
+        assert_eq!(decode_packet(&message("/x", "i", &[&1i32.to_be_bytes()])), None); // Tags without a comma
+        assert_eq!(decode_packet(&message("/x", ",b", &[&0i32.to_be_bytes()])), None); // Blobs aren't read
+        assert_eq!(decode_packet(&[]), None);
+    }
+}
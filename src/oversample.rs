@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+const TAPS: usize = 31; // Length of each half-band low-pass, odd so it has a centre tap
+const PHASE_TAPS: usize = TAPS.div_ceil(2); // Taps in the longer of its two polyphase branches
+
+// How many times over the nonlinear stages (distortion, the wavefolder) run. Shaping a
+// wave adds harmonics far above the original, and those past Nyquist fold back down as
+// inharmonic aliasing; running the shaper at a higher rate and filtering before coming
+// back down leaves most of them out. Each step costs more CPU per sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Oversampling {
+    #[default]
+    Off,
+    X2,
+    X4,
+}
+
+impl Oversampling {
+    // Number of 2x steps up and back down
+    fn stages(self) -> usize {
+        match self {
+            Oversampling::Off => 0,
+            Oversampling::X2 => 1,
+            Oversampling::X4 => 2,
+        }
+    }
+}
+
+// Runs a shaping function at a multiple of the sample rate, one sample in and one out.
+// The stages are kept in place rather than on the heap, so every voice can have one
+// made as it starts without allocating.
+#[derive(Clone)]
+pub struct Oversampler {
+    stages: [Option<Stage>; 2], // The first `oversampling.stages()` are used
+}
+
+impl Oversampler {
+    pub fn new(oversampling: Oversampling) -> Self {
+        Self {
+            stages: std::array::from_fn(|index| (index < oversampling.stages()).then(Stage::new)),
+        }
+    }
+
+    // Forgets the samples the filters hold
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut().flatten() {
+            stage.input = [0.0; PHASE_TAPS];
+            stage.output = [0.0; TAPS];
+        }
+    }
+
+    pub fn process(&mut self, input: f32, mut shaper: impl FnMut(f32) -> f32) -> f32 {
+        let mut samples = [0.0; 4]; // Enough for 4x
+        samples[0] = input;
+        let mut len = 1;
+        for stage in self.stages.iter_mut().flatten() {
+            let lower = samples;
+            for (index, &sample) in lower[..len].iter().enumerate() {
+                [samples[2 * index], samples[2 * index + 1]] = stage.upsample(sample);
+            }
+            len *= 2;
+        }
+        for sample in &mut samples[..len] {
+            *sample = shaper(*sample);
+        }
+        for stage in self.stages.iter_mut().flatten().rev() {
+            len /= 2;
+            for index in 0..len {
+                samples[index] = stage.downsample(samples[2 * index], samples[2 * index + 1]);
+            }
+        }
+        samples[0]
+    }
+}
+
+// One 2x step with a half-band windowed-sinc low-pass. Upsampling runs its even and odd
+// taps as two polyphase branches over the input, skipping the zeros stuffed between
+// samples; downsampling only works out the samples that are kept.
+#[derive(Clone)]
+struct Stage {
+    taps: [f32; TAPS],
+    input: [f32; PHASE_TAPS], // Low-rate history for upsampling, newest first
+    output: [f32; TAPS],      // High-rate history for downsampling, newest first
+}
+
+impl Stage {
+    fn new() -> Self {
+        let middle = (TAPS / 2) as f32;
+        let mut taps = [0.0; TAPS];
+        for (n, tap) in taps.iter_mut().enumerate() {
+            let t = n as f32 - middle;
+            let sinc = if t == 0.0 { 0.5 } else { (PI * 0.5 * t).sin() / (PI * t) }; // Cutoff at a quarter of the high rate
+            let x = 2.0 * PI * n as f32 / (TAPS - 1) as f32;
+            *tap = sinc * (0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos()); // Blackman window
+        }
+        let sum: f32 = taps.iter().sum();
+        Self {
+            taps: taps.map(|tap| tap / sum),
+            input: [0.0; PHASE_TAPS],
+            output: [0.0; TAPS],
+        }
+    }
+
+    fn upsample(&mut self, sample: f32) -> [f32; 2] {
+        push(&mut self.input, sample);
+        // Doubled to make up for the zeros in between
+        let branch = |offset: usize| 2.0 * self.taps.iter().skip(offset).step_by(2).zip(&self.input).map(|(tap, x)| tap * x).sum::<f32>();
+        [branch(0), branch(1)]
+    }
+
+    fn downsample(&mut self, first: f32, second: f32) -> f32 {
+        push(&mut self.output, first);
+        push(&mut self.output, second);
+        self.taps.iter().zip(&self.output).map(|(tap, x)| tap * x).sum()
+    }
+}
+
+fn push(history: &mut [f32], sample: f32) {
+    history.copy_within(..history.len() - 1, 1);
+    history[0] = sample;
+}
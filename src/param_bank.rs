@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+// Live parameter values shared by the threads that edit them and the audio thread,
+// without locks and without a command per change. Each parameter has a slot holding
+// its latest value; writers store into it and flag it, and the synth applies the
+// flagged slots once per frame. A slider dragged through a hundred values between
+// two frames costs the audio thread one update, and the UI never waits on it.
+//
+// The set of parameters is fixed when the bank is made; anything else still has to
+// go as a `SetParam` command.
+pub struct ParamBank {
+    slots: Vec<Slot>,
+    changed: AtomicBool, // Raised with any slot's flag, so an idle bank costs one load a frame
+}
+
+struct Slot {
+    path: String,
+    value: AtomicU32, // The f32's bits
+    changed: AtomicBool,
+}
+
+impl ParamBank {
+    pub fn new(params: Vec<(String, f32)>) -> Self {
+        Self {
+            slots: params
+                .into_iter()
+                .map(|(path, value)| Slot { path, value: AtomicU32::new(value.to_bits()), changed: AtomicBool::new(false) })
+                .collect(),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn index(&self, path: &str) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.path == path)
+    }
+
+    pub fn get(&self, index: usize) -> f32 {
+        f32::from_bits(self.slots[index].value.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, index: usize, value: f32) {
+        let slot = &self.slots[index];
+        slot.value.store(value.to_bits(), Ordering::Relaxed);
+        // The flags are released after the value, so whoever sees them sees the value too
+        slot.changed.store(true, Ordering::Release);
+        self.changed.store(true, Ordering::Release);
+    }
+
+    // Stores a value the synth itself has set, for the views to show, without flagging it
+    // back to the synth. Lock-free like `set`, so the audio thread can call it.
+    pub fn show(&self, index: usize, value: f32) {
+        self.slots[index].value.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    // Every parameter and its latest value, in the order the bank was made with
+    pub fn values(&self) -> Vec<(String, f32)> {
+        self.slots.iter().enumerate().map(|(index, slot)| (slot.path.clone(), self.get(index))).collect()
+    }
+
+    // Hands `apply` each parameter set since the last call, with its latest value. Runs
+    // on the audio thread, so it neither locks nor allocates.
+    pub fn drain(&self, mut apply: impl FnMut(&str, f32)) {
+        if !self.changed.load(Ordering::Relaxed) || !self.changed.swap(false, Ordering::Acquire) {
+            return;
+        }
+        for slot in &self.slots {
+            if slot.changed.swap(false, Ordering::Acquire) {
+                apply(&slot.path, f32::from_bits(slot.value.load(Ordering::Relaxed)));
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+// The longest parameter path a `ParamId` holds
+pub const MAX_PATH: usize = 47;
+
+// The sections of a parameter path the synth (or, for "arp", the arpeggiator in front
+// of it) sets parameters in; see `Synthesizer::set_param`
+const SECTIONS: &[&str] = &[
+    "pitch", "envelope", "split", "layer", "morph", "fold", "flute", "organ", "chip", "filter", "formant", "mono",
+    "pitch_envelope", "voice_lfo", "pressure", "vibrato", "controllers", "pulse", "parts", "mixer", "master", "eq",
+    "surround", "input", "vocoder", "stereo", "effects", "voice_effects", "sends", "blend", "arp",
+];
+
+// A parameter path, like "filter.cutoff", checked and copied into the id itself. Commands
+// carry these rather than Strings, so setting a parameter on the audio thread neither
+// frees a path nor finds out there that it was mistyped: a malformed path or an unknown
+// section is refused by `new`, on the thread sending it. Only whether the section has a
+// parameter by that name is left for the synth to decide.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParamId {
+    len: u8,
+    bytes: [u8; MAX_PATH],
+}
+
+impl ParamId {
+    pub fn new(path: &str) -> Result<Self, String> {
+        let mut parts = path.split('.');
+        let section = parts.next().unwrap_or_default();
+        if !SECTIONS.contains(&section) || parts.clone().next().is_none() || parts.any(str::is_empty) {
+            return Err(format!("unknown parameter '{}'", path));
+        }
+        if path.len() > MAX_PATH {
+            return Err(format!("parameter path '{}' is longer than {} bytes", path, MAX_PATH));
+        }
+        let mut bytes = [0; MAX_PATH];
+        bytes[..path.len()].copy_from_slice(path.as_bytes());
+        Ok(Self { len: path.len() as u8, bytes })
+    }
+
+    // For a path written into the program, which is a mistake to get wrong
+    pub fn known(path: &str) -> Self {
+        Self::new(path).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Copied whole from a &str in `new`, so it's still UTF-8
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ParamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for ParamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ParamId({:?})", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::Preset;
+
+    // Everything with parameters of its own switched on, so each section shows up
+    const PRESET: &str = r#"
+        [filter]
+        enabled = true
+        [flute]
+        enabled = true
+        [organ]
+        enabled = true
+        [chip]
+        enabled = true
+        [mono]
+        enabled = true
+        [formant]
+        enabled = true
+        [fold]
+        enabled = true
+        [morph]
+        enabled = true
+        [vocoder]
+        enabled = true
+        [split]
+        enabled = true
+
+        [[effects]]
+        type = "chorus"
+        [[voice_effects]]
+        type = "distortion"
+        [[sends]]
+        effects = [{ type = "phaser" }]
+        [[parts]]
+        channel = 2
+    "#;
+
+    #[test]
+    fn every_editable_parameter_has_an_id() {
+        let preset = Preset::parse(PRESET).expect("Test preset should parse");
+        for (path, _) in preset.params() {
+            assert_eq!(ParamId::new(&path).map(|id| id.to_string()), Ok(path.clone()), "{}", path);
+        }
+    }
+
+    #[test]
+    fn paths_that_cant_be_parameters_are_refused() {
+        for path in ["", "filter", "filter.", ".cutoff", "filter..cutoff", "filtre.cutoff", &format!("eq.{}", "x".repeat(MAX_PATH))] {
+            assert!(ParamId::new(path).is_err(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::param_bank::ParamBank;
+use crate::param_id::ParamId;
+use crate::preset::Preset;
+use crate::undo::{Edit, History};
+use crate::SynthCommand;
+
+// The live-editable parameters and their current values, shared by the user
+// interfaces. Changes go into a `ParamBank` the synth reads every frame, so all
+// views agree on what the synth is playing and a fast slider doesn't flood it with
+// commands.
+#[derive(Clone)]
+pub struct ParamStore {
+    bank: Arc<ParamBank>,
+    commands: mpsc::Sender<SynthCommand>, // For paths the bank wasn't made with, and presets to undo
+    history: Arc<Mutex<History>>,         // Of the edits to the bank's parameters and the preset loads
+}
+
+impl ParamStore {
+    pub fn new(params: Vec<(String, f32)>, commands: mpsc::Sender<SynthCommand>) -> Self {
+        Self {
+            bank: Arc::new(ParamBank::new(params)),
+            commands,
+            history: Arc::default(),
+        }
+    }
+
+    // Lets the first preset load be undone, back to `preset`
+    pub fn with_preset(self, preset: &Preset) -> Self {
+        *self.history() = History::new(preset);
+        self
+    }
+
+    fn history(&self) -> MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The bank to hand to `Synthesizer::with_param_bank`
+    pub fn bank(&self) -> Arc<ParamBank> {
+        self.bank.clone()
+    }
+
+    pub fn values(&self) -> Vec<(String, f32)> {
+        self.bank.values()
+    }
+
+    // The preset last loaded, as it loaded; `values` has the edits made since
+    pub fn preset(&self) -> Option<Preset> {
+        self.history().current().cloned()
+    }
+
+    pub fn set(&self, path: &str, value: f32) {
+        self.set_all(&[(path.to_string(), value)]);
+    }
+
+    // Sets several parameters as one edit, to undo together
+    pub fn set_all(&self, values: &[(String, f32)]) {
+        let changes = values
+            .iter()
+            .filter_map(|(path, value)| {
+                let from = self.bank.get(self.bank.index(path)?);
+                let to = self.apply(path, *value);
+                (from != to).then(|| (path.clone(), from, to))
+            })
+            .collect();
+        for (path, value) in values.iter().filter(|(path, _)| self.bank.index(path).is_none()) {
+            self.apply(path, *value);
+        }
+        self.history().params(changes);
+    }
+
+    // Notes that `preset` has been sent to the synth (for undo), and unless it's only
+    // queued, brings the values in line with it
+    pub fn loaded(&self, preset: &Preset, queued: bool) {
+        self.history().preset(preset, self.values());
+        if !queued {
+            self.restore(preset.params());
+        }
+    }
+
+    // Takes back the last edit or preset load, returning what it was
+    pub fn undo(&self) -> Option<String> {
+        let edit = self.history().undo()?;
+        Some(match edit {
+            Edit::Params(changes) => self.restore(changes.into_iter().map(|(path, from, _)| (path, from))),
+            Edit::Preset { from, from_values, .. } => {
+                let _ = self.commands.send(SynthCommand::LoadPreset(from.clone()));
+                self.restore(from_values);
+                preset_label(&from)
+            }
+        })
+    }
+
+    pub fn redo(&self) -> Option<String> {
+        let edit = self.history().redo()?;
+        Some(match edit {
+            Edit::Params(changes) => self.restore(changes.into_iter().map(|(path, _, to)| (path, to))),
+            Edit::Preset { to, .. } => {
+                let _ = self.commands.send(SynthCommand::LoadPreset(to.clone()));
+                self.restore(to.params());
+                preset_label(&to)
+            }
+        })
+    }
+
+    // Sets those of `values` the bank has, without recording them, returning what was set
+    fn restore(&self, values: impl IntoIterator<Item = (String, f32)>) -> String {
+        let values: Vec<String> = values
+            .into_iter()
+            .filter(|(path, _)| self.bank.index(path).is_some())
+            .map(|(path, value)| format!("{} {:.3}", path, self.apply(&path, value)))
+            .collect();
+        values.join(", ")
+    }
+
+    // Sets one parameter, clamped to its range, and returns the value it got
+    fn apply(&self, path: &str, value: f32) -> f32 {
+        let range = ParamRange::of(path);
+        let value = value.clamp(range.min, range.max);
+        match self.bank.index(path) {
+            Some(index) => self.bank.set(index, value),
+            // The synth only stops listening on the way out, when the edit no longer matters
+            None => match ParamId::new(path) {
+                Ok(id) => {
+                    let _ = self.commands.send(SynthCommand::SetParam(id, value));
+                }
+                Err(e) => eprintln!("Couldn't set a parameter: {}", e),
+            },
+        }
+        value
+    }
+
+    // Moves a parameter by 5% of its value (at least 0.01) in `direction` and
+    // returns the new value, or None for an unknown path
+    pub fn nudge(&self, path: &str, direction: f32) -> Option<f32> {
+        let value = self.bank.get(self.bank.index(path)?);
+        let range = ParamRange::of(path);
+        let value = (value + direction * (value.abs() * 0.05).max(0.01)).clamp(range.min, range.max);
+        self.set(path, value);
+        Some(value)
+    }
+}
+
+fn preset_label(preset: &Preset) -> String {
+    match preset.name.as_str() {
+        "" => "unnamed preset".to_string(),
+        name => format!("preset {}", name),
+    }
+}
+
+// A/B compare for sound design: two edit buffers over one `ParamStore`. Editing starts
+// in B, with A keeping the patch as it loaded, and each toggle puts away the values of
+// the slot being left and brings back the other's, so an edit can be heard against the
+// original (or against an earlier idea, edited in A) without losing either.
+pub struct Compare {
+    other: Vec<(String, f32)>, // The slot not being heard
+    on_b: bool,
+}
+
+impl Compare {
+    pub fn new(store: &ParamStore) -> Self {
+        Self { other: store.values(), on_b: true }
+    }
+
+    // Switches to the other slot and returns its name
+    pub fn toggle(&mut self, store: &ParamStore) -> char {
+        let leaving = store.values();
+        store.restore(std::mem::replace(&mut self.other, leaving)); // Not an edit, so not for undo
+        self.on_b = !self.on_b;
+        if self.on_b { 'B' } else { 'A' }
+    }
+}
+
+// The sensible range of a parameter, for sliders and for clamping edits
+#[derive(Clone, Copy, Debug)]
+pub struct ParamRange {
+    pub min: f32,
+    pub max: f32,
+    pub logarithmic: bool, // Frequencies and times feel even on a log scale
+}
+
+impl ParamRange {
+    pub fn of(path: &str) -> Self {
+        let linear = |min, max| ParamRange { min, max, logarithmic: false };
+        let log = |min, max| ParamRange { min, max, logarithmic: true };
+        let name = path.rsplit('.').next().unwrap_or(path);
+        match (path.starts_with("envelope."), name) {
+            (true, _) => log(0.001, 5.0), // Seconds
+            (_, "mix" | "tone" | "haas_amount") => linear(0.0, 1.0),
+            (_, "feedback") => linear(-0.95, 0.95),
+            (_, "rate") => log(0.01, 20.0),
+            (_, "depth" | "delay") => linear(0.0, 10.0),
+            (_, "drive") => linear(0.0, 20.0),
+            (_, "level" | "width") => linear(0.0, 2.0),
+            (_, "bits") => linear(1.0, 24.0),
+            (_, "sample_rate") => log(100.0, 44_100.0),
+            (_, "threshold") => linear(-60.0, 0.0),
+            (_, "ratio") => log(1.0, 20.0),
+            (_, "attack") => log(0.1, 200.0),    // Milliseconds
+            (_, "release") => log(5.0, 2000.0),  // Milliseconds
+            (_, "makeup") => linear(0.0, 24.0),
+            (_, "bend_range") => linear(2.0, 24.0), // Semitones
+            (_, "pressure") => linear(0.0, 1.5),
+            (_, "percussion_decay") => log(0.01, 5.0), // Seconds
+            (_, name) if name.starts_with("drawbar_") => linear(0.0, 8.0),
+            (_, name) if name.starts_with("duty_") => linear(0.0, 3.0),
+            (_, "glide") => log(0.001, 5.0), // Seconds
+            (_, "vowel") => linear(0.0, 4.0),  // A, E, I, O, U
+            (_, "formant_shift" | "semitones") => linear(-12.0, 12.0), // Semitones
+            (_, "haas_delay") => linear(1.0, 40.0),
+            (_, "mid_q") => log(0.1, 10.0),
+            (_, name) if name.ends_with("_gain") => linear(-24.0, 24.0),
+            (_, name) if name.ends_with("_frequency") => log(20.0, 20_000.0),
+            _ => linear(0.0, 1.0),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::EnvelopeSettings;
+use crate::envelope::Retrigger;
+use crate::voices::VoicePool;
+use crate::{frequency_from_note, Oscillator, Waveform};
+
+pub const MAX_PARTS: usize = 4;
+
+// An extra instrument played alongside the main patch, like one part of a multitimbral
+// module: it answers notes on its own MIDI channel within its key range, so one
+// controller can play a bass on channel 2 while another plays pads on channel 3, or a
+// part on channel 1 can layer with the main patch or cover a range of its own
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartSettings {
+    pub name: String,
+    pub channel: u8, // MIDI channel, 1-16; the computer keyboard plays channel 1
+    pub low: u8,     // Lowest note the part plays
+    pub high: u8,    // Highest note the part plays
+    pub waveform: Waveform,
+    pub envelope: EnvelopeSettings,
+    pub volume: f32, // 0.0..1.0
+    pub pan: f32,    // -1.0 (left) to 1.0 (right)
+    pub mute: bool,
+    pub solo: bool, // See `MixerSettings::solo`
+}
+
+impl Default for PartSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            channel: 2,
+            low: 0,
+            high: 127,
+            waveform: Waveform::Sine,
+            envelope: EnvelopeSettings::default(),
+            volume: 0.8,
+            pan: 0.0,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+impl PartSettings {
+    // The settings the synth's "parts.<index>.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("volume", self.volume),
+            ("pan", self.pan),
+            ("attack", self.envelope.attack),
+            ("release", self.envelope.release),
+            ("mute", if self.mute { 1.0 } else { 0.0 }),
+            ("solo", if self.solo { 1.0 } else { 0.0 }),
+        ]
+    }
+}
+
+// A part and the voices it has sounding
+pub(crate) struct Part {
+    pub settings: PartSettings,
+    pub voices: VoicePool,
+}
+
+impl Part {
+    pub fn new(settings: PartSettings, polyphony: usize) -> Self {
+        Self {
+            settings,
+            voices: VoicePool::new(polyphony),
+        }
+    }
+
+    pub fn plays(&self, channel: u8, note: u8) -> bool {
+        self.settings.channel == channel && (self.settings.low..=self.settings.high).contains(&note)
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: f32, sample_rate: u32) {
+        let freq = frequency_from_note(note);
+        // Like the main patch, a releasing voice rings out under the new one on a restart
+        let retrigger = self.settings.envelope.retrigger;
+        let existing = self.voices.newest(note).filter(|osc| !osc.is_releasing() || retrigger != Retrigger::Restart);
+        if let Some(osc) = existing {
+            osc.retrigger(freq, retrigger);
+            osc.set_envelope(&self.settings.envelope);
+            osc.velocity = velocity;
+            return;
+        }
+        let mut osc = Oscillator::new(freq, self.settings.waveform, &self.settings.envelope, sample_rate);
+        osc.note = note;
+        osc.pan = self.settings.pan;
+        osc.velocity = velocity;
+        self.voices.start(osc);
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        if let Some(osc) = self.voices.newest(note) {
+            osc.start_release();
+        }
+    }
+
+    pub fn panic(&mut self) {
+        for osc in self.voices.iter_mut() {
+            osc.fade_out();
+        }
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "volume" => self.settings.volume = value.clamp(0.0, 1.0),
+            "mute" => self.settings.mute = value > 0.5,
+            "solo" => self.settings.solo = value > 0.5,
+            "pan" => {
+                self.settings.pan = value.clamp(-1.0, 1.0);
+                for osc in self.voices.iter_mut() {
+                    osc.pan = self.settings.pan;
+                }
+            }
+            "attack" | "release" => {
+                if name == "attack" {
+                    self.settings.envelope.attack = value;
+                } else {
+                    self.settings.envelope.release = value;
+                }
+                for osc in self.voices.iter_mut() {
+                    osc.set_envelope(&self.settings.envelope);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Instant;
+use std::{io, path::Path, thread};
+
+use crate::midi_file;
+use crate::SynthCommand;
+
+// Everything played, kept on its way to the synth so it can be written out as a MIDI
+// file and edited in a DAW: notes from every source (keys, MIDI, arp, sequencer,
+// looper), their velocities, macro moves, the pitch wheel and tempo changes
+#[derive(Clone)]
+pub struct Performance {
+    commands: Arc<Mutex<Vec<(Instant, SynthCommand)>>>,
+    tempo: f32, // Tempo at the start, before any recorded changes
+}
+
+impl Performance {
+    // Writes what's been played so far to `path`, timed from the first thing played,
+    // and returns how many notes it has
+    pub fn save(&self, path: &Path) -> io::Result<usize> {
+        let commands = self.commands.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(&(start, _)) = commands.first() else { return Ok(0) };
+        let timed: Vec<(f32, SynthCommand)> = commands
+            .iter()
+            .map(|(at, command)| (at.duration_since(start).as_secs_f32(), command.clone()))
+            .collect();
+        midi_file::write(&timed, self.tempo, path)?;
+        Ok(timed.iter().filter(|(_, command)| matches!(command, SynthCommand::NoteOn(_) | SynthCommand::DetunedNoteOn(..) | SynthCommand::ScaledNoteOn(..) | SynthCommand::VelocityNoteOn(..) | SynthCommand::ChannelNoteOn(..))).count())
+    }
+}
+
+// Puts a recorder in front of `output`: commands sent to the returned channel reach
+// `output` unchanged, and those a MIDI file can hold are kept with when they passed
+pub fn tap(output: mpsc::Sender<SynthCommand>, tempo: f32) -> (mpsc::Sender<SynthCommand>, Performance) {
+    let performance = Performance { commands: Arc::new(Mutex::new(Vec::new())), tempo };
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    let commands = performance.commands.clone();
+    thread::spawn(move || {
+        for command in rx {
+            let kept = match &command {
+                SynthCommand::SetParam(id, _) | SynthCommand::GlideParam(id, _) => id.as_str() == "pitch.wheel",
+                SynthCommand::NoteOn(_)
+                | SynthCommand::DetunedNoteOn(..)
+                | SynthCommand::ScaledNoteOn(..)
+                | SynthCommand::VelocityNoteOn(..)
+                | SynthCommand::NoteOff(_)
+                | SynthCommand::ChannelNoteOn(..)
+                | SynthCommand::ChannelNoteOff(..)
+                | SynthCommand::SetVelocity(_)
+                | SynthCommand::SetMacro(..)
+                | SynthCommand::SetTempo(_) => true,
+                _ => false,
+            };
+            if kept {
+                commands.lock().unwrap_or_else(|e| e.into_inner()).push((Instant::now(), command.clone()));
+            }
+            if output.send(command).is_err() {
+                return;
+            }
+        }
+    });
+    (tx, performance)
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+// Bends each note from `depth` semitones away back to its own pitch over `time`, apart
+// from the amplitude envelope: a fast downward sweep gives drums and plucks their snap,
+// a slow rise from below (a negative depth) makes a swoop for effects
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PitchEnvelopeSettings {
+    pub depth: f32, // Semitones above (negative: below) the note that it starts at, 0.0 for none
+    pub time: f32,  // Seconds to sweep back to the note
+}
+
+impl Default for PitchEnvelopeSettings {
+    fn default() -> Self {
+        Self { depth: 0.0, time: 0.05 }
+    }
+}
+
+impl PitchEnvelopeSettings {
+    // The settings the synth's "pitch_envelope.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("depth", self.depth), ("time", self.time)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "depth" => self.depth = value.clamp(-48.0, 48.0),
+            "time" => self.time = value.max(0.001),
+            _ => return false,
+        }
+        true
+    }
+
+    // How much of the sweep is left after each sample
+    pub fn rate(&self, sample_rate: u32) -> f32 {
+        1.0 / (sample_rate as f32 * self.time.max(0.001))
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{io, path::Path};
+
+use crate::midi_file;
+use crate::notes::note_name;
+use crate::preset::Preset;
+use crate::SynthCommand;
+
+const WINDOW: f32 = 0.3; // Seconds either side of a note's time a key press still counts for it
+const LEAD: f32 = 0.5; // Seconds ahead of time each note is shown
+const LEAD_IN: f32 = 2.0; // Seconds to get ready before the file starts
+
+// Play-along practice: one channel of a MIDI file is the part to play, shown note by
+// note just ahead of time, and the rest is the backing, played by the synth. Backing
+// notes go to the preset's parts where it has one on their channel and to the main
+// patch otherwise, so a file plays with any preset. Key presses are scored as they come
+// against the part: right note near enough its time, wrong note, or one nothing was due.
+pub struct Practice {
+    part: Vec<(f32, u8)>, // When each note is due, in seconds from the start
+    backing: Vec<(f32, SynthCommand)>,
+}
+
+impl Practice {
+    pub fn load(path: impl AsRef<Path>, channel: u8, preset: &Preset) -> io::Result<Self> {
+        let mut part = Vec::new();
+        let mut backing = Vec::new();
+        let has_part = |channel: u8| preset.parts.iter().any(|part| part.channel == channel);
+        for (at, command) in midi_file::read(path)? {
+            let note = match command {
+                SynthCommand::NoteOn(note) => Some((1, note, true)),
+                SynthCommand::NoteOff(note) => Some((1, note, false)),
+                SynthCommand::ChannelNoteOn(channel, note) => Some((channel, note, true)),
+                SynthCommand::ChannelNoteOff(channel, note) => Some((channel, note, false)),
+                _ => None,
+            };
+            match note {
+                Some((from, note, true)) if from == channel => part.push((at, note)),
+                Some((from, _, false)) if from == channel => {}
+                Some((from, note, on)) => {
+                    let to = if has_part(from) { from } else { 1 };
+                    backing.push((at, SynthCommand::channel_note(to, note, on)));
+                }
+                None => backing.push((at, command)),
+            }
+        }
+        if part.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("no notes on channel {} to practise", channel)));
+        }
+        Ok(Self { part, backing })
+    }
+}
+
+// How a run through went
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub notes: usize, // In the part
+    pub hits: usize,  // Right note, in time
+    pub wrong: usize, // A different note where one was due
+    pub extra: usize, // Played where nothing was due
+    pub offset: f32,  // Total seconds the hits were late (early counts against it)
+    pub error: f32,   // Total seconds the hits were off, either way
+}
+
+impl Report {
+    pub fn missed(&self) -> usize {
+        self.notes - self.hits - self.wrong
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = 100.0 * self.hits as f32 / self.notes.max(1) as f32;
+        write!(f, "{} of {} notes right ({:.0}%), {} wrong, {} missed, {} extra", self.hits, self.notes, percent, self.wrong, self.missed(), self.extra)?;
+        if self.hits > 0 {
+            let hits = self.hits as f32;
+            let offset = self.offset / hits * 1000.0;
+            write!(f, "; {:.0} ms off on average, {:.0} ms {}", self.error / hits * 1000.0, offset.abs(), if offset < 0.0 { "early" } else { "late" })?;
+        }
+        Ok(())
+    }
+}
+
+// The part and what's been played against it so far
+struct Judge {
+    part: Vec<(f32, u8)>,
+    taken: Vec<bool>, // Notes of the part already played, right or wrong
+    start: Option<Instant>,
+    report: Report,
+}
+
+impl Judge {
+    // Scores a key press, printing how it went
+    fn play(&mut self, note: u8, at: Instant) {
+        let Some(start) = self.start else { return };
+        let time = if at >= start { at.duration_since(start).as_secs_f32() } else { -start.duration_since(at).as_secs_f32() };
+        // The nearest due note of the same pitch, or failing that the nearest due note
+        let due = |same: bool| {
+            (0..self.part.len())
+                .filter(|&i| !self.taken[i] && (self.part[i].0 - time).abs() <= WINDOW && (!same || self.part[i].1 == note))
+                .min_by(|&a, &b| (self.part[a].0 - time).abs().total_cmp(&(self.part[b].0 - time).abs()))
+        };
+        if let Some(i) = due(true) {
+            self.taken[i] = true;
+            let offset = time - self.part[i].0;
+            self.report.hits += 1;
+            self.report.offset += offset;
+            self.report.error += offset.abs();
+            println!("  {} right, {:+.0} ms", note_name(note), offset * 1000.0);
+        } else if let Some(i) = due(false) {
+            self.taken[i] = true;
+            self.report.wrong += 1;
+            println!("  {} wrong, wanted {}", note_name(note), note_name(self.part[i].1));
+        } else if time >= -WINDOW {
+            self.report.extra += 1;
+            println!("  {} extra", note_name(note));
+        }
+    }
+}
+
+// Keeps the score of a run through; see `tap`
+#[derive(Clone)]
+pub struct Trainer {
+    judge: Arc<Mutex<Judge>>,
+}
+
+// Puts a judge in front of `output`: notes sent to the returned channel reach `output`
+// unchanged and are scored against the part once the practice starts
+pub fn tap(practice: &Practice, output: mpsc::Sender<SynthCommand>) -> (mpsc::Sender<SynthCommand>, Trainer) {
+    let judge = Judge { part: practice.part.clone(), taken: vec![false; practice.part.len()], start: None, report: Report::default() };
+    let trainer = Trainer { judge: Arc::new(Mutex::new(judge)) };
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    let judge = trainer.judge.clone();
+    thread::spawn(move || {
+        for command in rx {
+            if let SynthCommand::NoteOn(note) = command {
+                judge.lock().unwrap_or_else(|e| e.into_inner()).play(note, Instant::now());
+            }
+            if output.send(command).is_err() {
+                return;
+            }
+        }
+    });
+    (tx, trainer)
+}
+
+// Plays the backing into `output` in real time, showing the part's notes as they come
+// up, and returns how it went once the last note's time has passed or, between events,
+// when `keep_going` says to stop
+pub fn perform(practice: &Practice, trainer: &Trainer, output: &mpsc::Sender<SynthCommand>, keep_going: impl Fn() -> bool) -> Report {
+    // The part's notes, chords together, each shown LEAD ahead of its time
+    let mut cues: Vec<(f32, Vec<u8>)> = Vec::new();
+    for &(at, note) in &practice.part {
+        match cues.last_mut() {
+            Some((time, notes)) if at - *time < 0.02 => notes.push(note),
+            _ => cues.push((at, vec![note])),
+        }
+    }
+
+    println!("Starting in {:.0} s: play each note as it's shown", LEAD_IN);
+    let start = Instant::now() + Duration::from_secs_f32(LEAD_IN);
+    trainer.judge.lock().unwrap_or_else(|e| e.into_inner()).start = Some(start);
+    // Cues for the first notes fall before the start, so they're shown during the lead-in
+    let sleep_until = |at: f32| {
+        let instant = if at >= 0.0 { start + Duration::from_secs_f32(at) } else { start - Duration::from_secs_f32(-at) };
+        thread::sleep(instant.saturating_duration_since(Instant::now()));
+    };
+
+    let (mut backing, mut cues) = (practice.backing.iter().peekable(), cues.iter().peekable());
+    loop {
+        let cue_due = cues.peek().map(|(at, _)| at - LEAD);
+        let backing_due = backing.peek().map(|(at, _)| *at);
+        let (due, cue) = match (cue_due, backing_due) {
+            (Some(cue), Some(backing)) if cue <= backing => (cue, true),
+            (_, Some(backing)) => (backing, false),
+            (Some(cue), None) => (cue, true),
+            (None, None) => break,
+        };
+        sleep_until(due);
+        if !keep_going() {
+            break;
+        }
+        if cue {
+            if let Some((_, notes)) = cues.next() {
+                let names: Vec<String> = notes.iter().map(|&note| note_name(note)).collect();
+                println!("{}", names.join(" "));
+            }
+        } else if let Some((_, command)) = backing.next() {
+            if output.send(command.clone()).is_err() {
+                break; // The synth is gone, so there's nobody to play to
+            }
+        }
+    }
+    let last = practice.part.last().map_or(0.0, |&(at, _)| at);
+    sleep_until(last + WINDOW); // Time for the last note to be played
+
+    let judge = trainer.judge.lock().unwrap_or_else(|e| e.into_inner());
+    Report { notes: judge.part.len(), ..judge.report.clone() }
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+use crate::ambient::AmbientSettings;
+use crate::arpeggiator::ArpSettings;
+use crate::chip::ChipSettings;
+use crate::chord::ChordSettings;
+use crate::controllers::ControllerSettings;
+use crate::effects::eq::EqSettings;
+use crate::effects::width::WidthSettings;
+use crate::effects::EffectConfig;
+use crate::envelope::EnvelopeSettings;
+use crate::euclid::EuclidSettings;
+use crate::filter::FilterSettings;
+use crate::flute::FluteSettings;
+use crate::fold::FoldSettings;
+use crate::formant::FormantSettings;
+use crate::glide::SmoothingSettings;
+use crate::harmony::HarmonySettings;
+use crate::humanize::HumanizeSettings;
+use crate::layer::LayerSettings;
+use crate::live_input::InputSettings;
+use crate::looper::LooperSettings;
+use crate::macros::MacroSettings;
+use crate::metronome::MetronomeSettings;
+use crate::mixer::MixerSettings;
+use crate::mono::MonoSettings;
+use crate::morph::MorphSettings;
+use crate::mouse::MouseSettings;
+use crate::organ::OrganSettings;
+use crate::oversample::Oversampling;
+use crate::parts::PartSettings;
+use crate::pitch_envelope::PitchEnvelopeSettings;
+use crate::pressure::PressureSettings;
+use crate::pulse::PulseSettings;
+use crate::scale::ScaleSettings;
+use crate::sends::SendSettings;
+use crate::sequencer::SequencerSettings;
+use crate::split::SplitSettings;
+use crate::stereo::PanSettings;
+use crate::surround::SurroundSettings;
+use crate::tempo::DEFAULT_TEMPO;
+use crate::velocity_curve::VelocitySettings;
+use crate::vibrato::VibratoSettings;
+use crate::vocoder::VocoderSettings;
+use crate::voice_lfo::VoiceLfoSettings;
+
+pub const DEFAULT_BEND_RANGE: f32 = 2.0; // The General MIDI default
+
+// Everything needed to recreate a sound, stored on disk as TOML
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    pub name: String,
+    pub tempo: f32,                   // Beats per minute, drives tempo-synced rates
+    pub envelope: EnvelopeSettings,   // Attack and release of every voice
+    pub pitch_envelope: PitchEnvelopeSettings, // Pitch sweep at the start of every note, none by default
+    pub voice_lfo: VoiceLfoSettings,  // An LFO of its own in every voice for drift between notes, off by default
+    pub pressure: PressureSettings,   // What aftertouch does, nothing by default
+    pub vibrato: VibratoSettings,     // Every voice's vibrato, which the mod wheel brings in by default
+    pub controllers: ControllerSettings, // What the mod wheel and expression pedal move
+    pub bend_range: f32,              // Semitones a full throw of the pitch-bend wheel moves, 2 to 24
+    pub layer: LayerSettings,         // Second oscillator in every voice, off by default
+    pub morph: MorphSettings,         // Continuously variable waveform, off by default
+    pub pulse: PulseSettings,         // Pulse width of the square wave and its LFO
+    pub fold: FoldSettings,           // Wavefolder after the oscillators, off by default
+    pub flute: FluteSettings,         // Blown-pipe model in place of the oscillators, off by default
+    pub organ: OrganSettings,         // Drawbar organ in place of the oscillators, off by default
+    pub chip: ChipSettings,           // NES sound chip in place of the oscillators ("2A03 mode"), off by default
+    pub formant: FormantSettings,     // Vowel filters in every voice, ahead of the low-pass; off by default
+    pub filter: FilterSettings,       // Low-pass filter in every voice with key tracking, off by default
+    pub oversampling: Oversampling,   // Quality of the distortion and wavefolder, off by default
+    pub mono: MonoSettings,           // Monophonic voice mode, off by default
+    pub split: SplitSettings,         // A second patch below a split point, off by default
+    pub scale: ScaleSettings,         // Snaps played notes into a scale, off by default
+    pub chord: ChordSettings,         // One key plays a whole chord, off by default
+    pub harmony: HarmonySettings,     // Voices added at fixed intervals from every note, off by default
+    pub arp: ArpSettings,             // Arpeggiator, off by default
+    pub euclid: EuclidSettings,       // Euclidean rhythm generator, off by default
+    pub sequencer: SequencerSettings, // Step sequencer pattern
+    pub ambient: AmbientSettings,     // The synth playing itself, off by default
+    pub humanize: HumanizeSettings,   // Random timing, velocity and pitch in the arpeggiator's and sequencer's notes
+    pub looper: LooperSettings,       // Loop length of the note looper
+    pub metronome: MetronomeSettings, // Click track at the preset tempo
+    pub effects: Vec<EffectConfig>,   // Applied in order to the mixed output
+    pub voice_effects: Vec<EffectConfig>, // Applied in order inside every voice of the main patch, before the mix
+    pub sends: Vec<SendSettings>,     // Effect buses the main patch and parts send to, added back after `effects`
+    pub stereo: WidthSettings,        // Master stereo width, after the effects
+    pub eq: EqSettings,               // Master EQ, always last in the chain
+    pub panning: PanSettings,         // Where new voices are placed in the stereo field
+    pub surround: SurroundSettings,   // How far round the room they go and how it turns, with --surround
+    pub macros: Vec<MacroSettings>,   // Up to four knobs that each move several parameters
+    pub smoothing: SmoothingSettings, // How edits of cutoffs, volumes, pans, bend and detune glide
+    pub mouse: MouseSettings,         // Pointer position as two more knobs, off by default
+    pub parts: Vec<PartSettings>,     // Up to four more instruments on their own MIDI channels
+    pub mixer: MixerSettings,         // The main patch's volume, mute and solo beside the parts'
+    pub input: InputSettings,         // How live audio is mixed in, when there is any (`--input`)
+    pub velocity: VelocitySettings,   // How hard notes are played turns into how loud they are
+    pub vocoder: VocoderSettings,     // Live input speaking through the voices, off by default
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            tempo: DEFAULT_TEMPO,
+            envelope: EnvelopeSettings::default(),
+            pitch_envelope: PitchEnvelopeSettings::default(),
+            voice_lfo: VoiceLfoSettings::default(),
+            pressure: PressureSettings::default(),
+            vibrato: VibratoSettings::default(),
+            controllers: ControllerSettings::default(),
+            bend_range: DEFAULT_BEND_RANGE,
+            layer: LayerSettings::default(),
+            morph: MorphSettings::default(),
+            pulse: PulseSettings::default(),
+            fold: FoldSettings::default(),
+            flute: FluteSettings::default(),
+            organ: OrganSettings::default(),
+            chip: ChipSettings::default(),
+            formant: FormantSettings::default(),
+            filter: FilterSettings::default(),
+            oversampling: Oversampling::Off,
+            mono: MonoSettings::default(),
+            split: SplitSettings::default(),
+            scale: ScaleSettings::default(),
+            chord: ChordSettings::default(),
+            harmony: HarmonySettings::default(),
+            arp: ArpSettings::default(),
+            ambient: AmbientSettings::default(),
+            humanize: HumanizeSettings::default(),
+            euclid: EuclidSettings::default(),
+            sequencer: SequencerSettings::default(),
+            looper: LooperSettings::default(),
+            metronome: MetronomeSettings::default(),
+            effects: Vec::new(),
+            voice_effects: Vec::new(),
+            sends: Vec::new(),
+            stereo: WidthSettings::default(),
+            eq: EqSettings::default(),
+            panning: PanSettings::default(),
+            surround: SurroundSettings::default(),
+            macros: Vec::new(),
+            smoothing: SmoothingSettings::default(),
+            mouse: MouseSettings::default(),
+            parts: Vec::new(),
+            mixer: MixerSettings::default(),
+            input: InputSettings::default(),
+            velocity: VelocitySettings::default(),
+            vocoder: VocoderSettings::default(),
+        }
+    }
+}
+
+impl Preset {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let preset: Self = toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        preset.arp.check().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(preset)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text = toml::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    // Every parameter that can be changed while playing, as the paths the synth's
+    // `set_param` understands ("eq.low_gain", "effects.0.mix", ...) and their values
+    pub fn params(&self) -> Vec<(String, f32)> {
+        let mut params: Vec<(String, f32)> = self
+            .envelope
+            .params()
+            .into_iter()
+            .map(|(name, value)| (format!("envelope.{}", name), value))
+            .collect();
+        if self.layer.enabled {
+            params.extend(self.layer.params().into_iter().map(|(name, value)| (format!("layer.{}", name), value)));
+        }
+        params.push(("pitch.bend_range".to_string(), self.bend_range));
+        params.extend(self.pitch_envelope.params().into_iter().map(|(name, value)| (format!("pitch_envelope.{}", name), value)));
+        params.extend(self.vibrato.params().into_iter().map(|(name, value)| (format!("vibrato.{}", name), value)));
+        params.extend(self.pressure.params().into_iter().map(|(name, value)| (format!("pressure.{}", name), value)));
+        params.extend(self.pulse.params().into_iter().map(|(name, value)| (format!("pulse.{}", name), value)));
+        params.extend(self.voice_lfo.params().into_iter().map(|(name, value)| (format!("voice_lfo.{}", name), value)));
+        if self.filter.enabled {
+            params.extend(self.filter.params().into_iter().map(|(name, value)| (format!("filter.{}", name), value)));
+        }
+        if self.flute.enabled {
+            params.extend(self.flute.params().into_iter().map(|(name, value)| (format!("flute.{}", name), value)));
+        }
+        if self.organ.enabled {
+            params.extend(self.organ.params().into_iter().map(|(name, value)| (format!("organ.{}", name), value)));
+        }
+        if self.chip.enabled {
+            params.extend(self.chip.params().into_iter().map(|(name, value)| (format!("chip.{}", name), value)));
+        }
+        if self.mono.enabled {
+            params.extend(self.mono.params().into_iter().map(|(name, value)| (format!("mono.{}", name), value)));
+        }
+        if self.formant.enabled {
+            params.extend(self.formant.params().into_iter().map(|(name, value)| (format!("formant.{}", name), value)));
+        }
+        if self.fold.enabled {
+            params.extend(self.fold.params().into_iter().map(|(name, value)| (format!("fold.{}", name), value)));
+        }
+        if self.morph.enabled {
+            params.extend(self.morph.params().into_iter().map(|(name, value)| (format!("morph.{}", name), value)));
+        }
+        if self.vocoder.enabled {
+            params.extend(self.vocoder.params().into_iter().map(|(name, value)| (format!("vocoder.{}", name), value)));
+        }
+        if self.split.enabled {
+            params.extend(self.split.lower.envelope.params().into_iter().map(|(name, value)| (format!("split.{}", name), value)));
+        }
+        params.extend(self.mixer.params().into_iter().map(|(name, value)| (format!("mixer.{}", name), value)));
+        for (index, part) in self.parts.iter().take(crate::parts::MAX_PARTS).enumerate() {
+            params.extend(part.params().into_iter().map(|(name, value)| (format!("parts.{}.{}", index, name), value)));
+        }
+        for (slot, effect) in self.effects.iter().enumerate() {
+            params.extend(effect.params().into_iter().map(|(name, value)| (format!("effects.{}.{}", slot, name), value)));
+        }
+        for (slot, effect) in self.voice_effects.iter().filter(|effect| effect.per_voice()).enumerate() {
+            params.extend(effect.params().into_iter().map(|(name, value)| (format!("voice_effects.{}.{}", slot, name), value)));
+        }
+        for (index, send) in self.sends.iter().enumerate() {
+            params.extend(send.params().into_iter().map(|(name, value)| (format!("sends.{}.{}", index, name), value)));
+        }
+        params.extend(self.stereo.params().into_iter().map(|(name, value)| (format!("stereo.{}", name), value)));
+        params.extend(self.eq.params().into_iter().map(|(name, value)| (format!("eq.{}", name), value)));
+        params
+    }
+}
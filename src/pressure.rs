@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::lfo::Lfo;
+use crate::macros::MacroTarget;
+use crate::smoothed::SmoothedControl;
+use crate::tempo::Rate;
+
+// What pressing harder into the keys does, from a keyboard with aftertouch. Channel
+// pressure is the whole keyboard's, poly pressure each key's own; a voice goes by
+// whichever is the more. Pressure deepens each voice's vibrato and raises its level,
+// and channel pressure moves synth parameters as a macro does, say a filter cutoff:
+//
+//     [[pressure.targets]]
+//     param = "filter.cutoff"
+//     min = 800.0
+//     max = 6000.0
+//     curve = "exponential"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PressureSettings {
+    pub smoothing: f32,            // Milliseconds pressure takes to settle on a new value, since it arrives in steps
+    pub vibrato: f32,              // Cents of vibrato either way at full pressure
+    pub vibrato_rate: Rate,
+    pub level: f32,                // How much louder full pressure makes a voice, as a fraction of its level
+    pub targets: Vec<MacroTarget>, // Moved by channel pressure, from `min` with none to `max` at full
+}
+
+impl Default for PressureSettings {
+    fn default() -> Self {
+        Self {
+            smoothing: 30.0,
+            vibrato: 0.0,
+            vibrato_rate: Rate::Hz(5.5),
+            level: 0.0,
+            targets: Vec::new(),
+        }
+    }
+}
+
+impl PressureSettings {
+    // The settings the synth's "pressure.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params = vec![("smoothing", self.smoothing), ("vibrato", self.vibrato)];
+        params.extend(self.vibrato_rate.editable_param("vibrato_rate"));
+        params.push(("level", self.level));
+        params
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "smoothing" => self.smoothing = value.max(0.0),
+            "vibrato" => self.vibrato = value.clamp(0.0, 100.0),
+            "vibrato_rate" => self.vibrato_rate = Rate::Hz(value.max(0.0)),
+            "level" => self.level = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// This frame's pressure, for each voice to take its own share of
+#[derive(Clone, Copy, Debug)]
+pub struct VoicePressure {
+    pub channel: f32,     // The keyboard's pressure, smoothed, 0.0..1.0
+    pub coefficient: f32, // Fraction of the way each voice's own pressure moves to its key's each frame
+    pub vibrato: f32,     // Cents of vibrato at full pressure, at this frame's point of the LFO
+    pub level: f32,       // As `PressureSettings::level`
+}
+
+impl VoicePressure {
+    // A voice's pitch ratio and gain at `pressure` (0.0..1.0)
+    pub fn apply(&self, pressure: f32) -> (f32, f32) {
+        (2.0_f32.powf(self.vibrato * pressure / 1200.0), 1.0 + self.level * pressure)
+    }
+}
+
+pub struct Pressure {
+    settings: PressureSettings,
+    channel: SmoothedControl, // The keyboard's pressure, which the targets follow
+    vibrato: Lfo,
+    sample_rate: u32,
+}
+
+impl Pressure {
+    pub fn new(settings: PressureSettings, tempo: f32, sample_rate: u32) -> Self {
+        Self {
+            vibrato: Lfo::new(settings.vibrato_rate.hz(tempo), sample_rate),
+            channel: SmoothedControl::new(0.0, settings.smoothing, sample_rate).with_rest_applied(),
+            settings,
+            sample_rate,
+        }
+    }
+
+    pub fn set_channel(&mut self, value: f32) {
+        self.channel.set(value.clamp(0.0, 1.0));
+    }
+
+    // Carries the pressure held at the moment over from another preset's
+    pub fn carry(&mut self, other: &Pressure) {
+        self.channel.set(other.channel.value());
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32, tempo: f32) -> bool {
+        let known = self.settings.set_param(name, value);
+        self.channel.set_smoothing(self.settings.smoothing, self.sample_rate);
+        self.set_tempo(tempo);
+        known
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.vibrato.set_rate(self.settings.vibrato_rate.hz(tempo));
+    }
+
+    // Moves the channel pressure along by a frame; see `SmoothedControl::advance`
+    pub fn advance(&mut self) -> Option<f32> {
+        self.channel.advance()
+    }
+
+    pub fn targets(&mut self) -> &mut Vec<MacroTarget> {
+        &mut self.settings.targets
+    }
+
+    // What the voices need for this frame, unless pressure does nothing to them
+    pub fn voices(&mut self) -> Option<VoicePressure> {
+        if self.settings.vibrato == 0.0 && self.settings.level == 0.0 {
+            return None;
+        }
+        Some(VoicePressure {
+            channel: self.channel.smoothed(),
+            coefficient: self.channel.coefficient(),
+            vibrato: self.settings.vibrato * self.vibrato.next_value(),
+            level: self.settings.level,
+        })
+    }
+}
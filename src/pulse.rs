@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use crate::lfo::LfoShape;
+use crate::tempo::Rate;
+
+// The square wave as a pulse of variable width, which an LFO can sweep (PWM) for the
+// moving, chorus-like sound of analog strings and pads
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PulseSettings {
+    pub width: f32,     // Fraction of each cycle spent high, 0.5 for a square wave
+    pub lfo_rate: Rate, // In Hz or as a tempo division
+    pub lfo_depth: f32, // How far the LFO moves the width either way, 0.0 for no PWM
+    pub lfo_shape: LfoShape,
+}
+
+impl Default for PulseSettings {
+    fn default() -> Self {
+        Self {
+            width: 0.5,
+            lfo_rate: Rate::Hz(0.3),
+            lfo_depth: 0.0,
+            lfo_shape: LfoShape::Sine,
+        }
+    }
+}
+
+impl PulseSettings {
+    // The settings the synth's "pulse.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params = vec![("width", self.width)];
+        params.extend(self.lfo_rate.editable_param("lfo_rate"));
+        params.push(("lfo_depth", self.lfo_depth));
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "width" => self.width = value.clamp(MIN_WIDTH, 1.0 - MIN_WIDTH),
+            "lfo_rate" => self.lfo_rate = Rate::Hz(value.max(0.0)),
+            "lfo_depth" => self.lfo_depth = value.clamp(0.0, 0.5),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Narrower pulses thin out to nothing and alias badly, so the width stops short of the ends
+pub const MIN_WIDTH: f32 = 0.02;
+
+// A band-limited pulse at `phase` (0 to 2π) advancing by `increment` per sample. The
+// jumps of a naive pulse alias into the audible range, so each one is smoothed with a
+// polynomial band-limited step (PolyBLEP) over the samples either side of it.
+pub fn sample(phase: f32, increment: f32, width: f32) -> f32 {
+    let width = width.clamp(MIN_WIDTH, 1.0 - MIN_WIDTH);
+    let t = phase / (2.0 * PI);
+    let dt = (increment / (2.0 * PI)).min(0.5);
+    let naive = if t < width { 1.0 } else { -1.0 };
+    naive + poly_blep(t, dt) - poly_blep((t - width).rem_euclid(1.0), dt)
+}
+
+// Correction for a unit step at t = 0 of a signal with phase `t` in 0..1
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = t / dt;
+        2.0 * t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,156 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::notes::note_name;
+use crate::SynthCommand;
+
+const ANSWER_FROM: u8 = 60; // C4: answers are played as the note that far above it
+const NOTE_SECONDS: f32 = 0.7; // Each note of a broken interval or chord, and the gap after the quiz plays it
+const POLL: Duration = Duration::from_millis(100); // How often a wait for an answer checks for a shutdown
+
+const INTERVALS: [&str; 12] = [
+    "minor second", "major second", "minor third", "major third", "perfect fourth", "tritone",
+    "perfect fifth", "minor sixth", "major sixth", "minor seventh", "major seventh", "octave",
+];
+
+// Chords, each answered by the note that sets it apart from the others, counted from the root
+const CHORDS: [(&str, &[u8], u8); 4] = [
+    ("major", &[0, 4, 7], 4),      // Its major third
+    ("minor", &[0, 3, 7], 3),      // Its minor third
+    ("diminished", &[0, 3, 6], 6), // Its flat fifth
+    ("augmented", &[0, 4, 8], 8),  // Its sharp fifth
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum QuizKind {
+    Intervals, // Two notes, up to an octave apart
+    Chords,    // Major, minor, diminished or augmented triads
+}
+
+// One question: the notes to play and the answer, as semitones above the lowest
+struct Question {
+    notes: Vec<u8>,
+    answer: u8,
+    name: &'static str,
+}
+
+impl QuizKind {
+    fn question(self, rng: &mut impl Rng) -> Question {
+        let root = rng.gen_range(48..=66); // C3 to F#4, so the top stays in a comfortable range
+        match self {
+            QuizKind::Intervals => {
+                let interval = rng.gen_range(1..=12);
+                Question { notes: vec![root, root + interval], answer: interval, name: INTERVALS[interval as usize - 1] }
+            }
+            QuizKind::Chords => {
+                let &(name, shape, answer) = CHORDS.choose(rng).unwrap_or(&CHORDS[0]);
+                Question { notes: shape.iter().map(|&interval| root + interval).collect(), answer, name }
+            }
+        }
+    }
+
+    fn prompt(self) -> String {
+        let from = |semitones: u8| note_name(ANSWER_FROM + semitones);
+        match self {
+            QuizKind::Intervals => format!("Which interval? Play it up from {}; {} plays it again", from(0), from(0)),
+            QuizKind::Chords => format!(
+                "Which chord? {} major, {} minor, {} diminished, {} augmented; {} plays it again",
+                from(4), from(3), from(6), from(8), from(0)
+            ),
+        }
+    }
+
+    // What an answer means, for saying what was played instead
+    fn answer_name(self, answer: u8) -> Option<&'static str> {
+        match self {
+            QuizKind::Intervals => INTERVALS.get((answer as usize).checked_sub(1)?).copied(),
+            QuizKind::Chords => CHORDS.iter().find(|&&(_, _, chord)| chord == answer).map(|&(name, _, _)| name),
+        }
+    }
+}
+
+// Puts a listener in front of `output`: notes sent to the returned channel reach
+// `output` unchanged, and each note played also arrives on the receiver as an answer
+pub fn tap(output: mpsc::Sender<SynthCommand>) -> (mpsc::Sender<SynthCommand>, mpsc::Receiver<u8>) {
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    let (answers_tx, answers) = mpsc::channel();
+    thread::spawn(move || {
+        for command in rx {
+            if let SynthCommand::NoteOn(note) = command {
+                let _ = answers_tx.send(note); // Nobody listening just means the quiz is over
+            }
+            if output.send(command).is_err() {
+                return;
+            }
+        }
+    });
+    (tx, answers)
+}
+
+// Ear training: plays `rounds` questions into `output`, each broken and then together,
+// and waits for each answer from `answers`, played on the keyboard as the note that
+// far above C4, before saying whether it was right. Stops early when `keep_going` says
+// to. Returns how many were answered right, out of how many asked.
+pub fn run(kind: QuizKind, rounds: usize, output: &mpsc::Sender<SynthCommand>, answers: &mpsc::Receiver<u8>, keep_going: impl Fn() -> bool) -> (usize, usize) {
+    let mut rng = rand::thread_rng();
+    let (mut right, mut asked) = (0, 0);
+    println!("{}", kind.prompt());
+    'rounds: for round in 1..=rounds {
+        let question = kind.question(&mut rng);
+        println!("Question {} of {}", round, rounds);
+        let answer = loop {
+            if play(&question.notes, output).is_err() {
+                break 'rounds;
+            }
+            while answers.try_recv().is_ok() {} // Anything played while the question sounded
+            let answer = loop {
+                if !keep_going() {
+                    break 'rounds;
+                }
+                match answers.recv_timeout(POLL) {
+                    Ok(note) => break note,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break 'rounds,
+                }
+            };
+            if answer != ANSWER_FROM {
+                break answer;
+            }
+        };
+        asked += 1;
+        let semitones = answer.checked_sub(ANSWER_FROM);
+        if semitones == Some(question.answer) {
+            right += 1;
+            println!("Right: {}", question.name);
+        } else {
+            match semitones.and_then(|semitones| kind.answer_name(semitones)) {
+                Some(played) => println!("No, that's {}: it was {}", played, question.name),
+                None => println!("No: it was {}", question.name),
+            }
+        }
+        thread::sleep(Duration::from_secs_f32(NOTE_SECONDS));
+    }
+    (right, asked)
+}
+
+// The notes one by one, then all together
+fn play(notes: &[u8], output: &mpsc::Sender<SynthCommand>) -> Result<(), mpsc::SendError<SynthCommand>> {
+    let hold = |seconds: f32| thread::sleep(Duration::from_secs_f32(seconds));
+    for &note in notes {
+        output.send(SynthCommand::NoteOn(note))?;
+        hold(NOTE_SECONDS);
+        output.send(SynthCommand::NoteOff(note))?;
+    }
+    for &note in notes {
+        output.send(SynthCommand::NoteOn(note))?;
+    }
+    hold(NOTE_SECONDS * 2.0);
+    for &note in notes {
+        output.send(SynthCommand::NoteOff(note))?;
+    }
+    hold(NOTE_SECONDS);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::effects::bitcrusher::BitcrusherSettings;
+use crate::effects::chorus::ChorusSettings;
+use crate::effects::compressor::CompressorSettings;
+use crate::effects::distortion::{DistortionCurve, DistortionSettings};
+use crate::effects::eq::EqSettings;
+use crate::effects::flanger::FlangerSettings;
+use crate::effects::phaser::PhaserSettings;
+use crate::effects::width::WidthSettings;
+use crate::effects::EffectConfig;
+use crate::mono::{MonoSettings, NotePriority};
+use crate::preset::Preset;
+use crate::stereo::{AutoPan, PanSettings};
+use crate::tempo::{Feel, NoteDivision, Rate};
+
+const MAX_EFFECTS: usize = 3; // Longer random chains mostly turn into mush
+
+// Builds a preset with every sound parameter picked at random inside a range that
+// still sounds musical. The same seed always gives the same patch, so a lucky
+// result can be recreated. Performance settings (arp, sequencer, looper, ...) keep
+// their defaults.
+pub fn random_preset(seed: u64) -> Preset {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let count = rng.gen_range(0..=MAX_EFFECTS);
+    let effects = (0..count).map(|_| random_effect(&mut rng)).collect();
+
+    Preset {
+        name: format!("Random {}", seed),
+        mono: MonoSettings {
+            enabled: rng.gen_bool(0.25),
+            priority: *[NotePriority::Last, NotePriority::Lowest, NotePriority::Highest].choose(&mut rng).unwrap(),
+            legato: rng.gen_bool(0.5),
+            ..MonoSettings::default()
+        },
+        effects,
+        stereo: WidthSettings {
+            width: rng.gen_range(0.5..1.6),
+            haas_delay: rng.gen_range(5.0..30.0),
+            haas_amount: if rng.gen_bool(0.3) { rng.gen_range(0.1..0.6) } else { 0.0 },
+        },
+        eq: EqSettings {
+            low_gain: rng.gen_range(-6.0..6.0),
+            low_frequency: rng.gen_range(80.0..400.0),
+            mid_gain: rng.gen_range(-6.0..6.0),
+            mid_frequency: rng.gen_range(400.0..3000.0),
+            mid_q: rng.gen_range(0.5..2.0),
+            high_gain: rng.gen_range(-6.0..6.0),
+            high_frequency: rng.gen_range(3000.0..10_000.0),
+        },
+        panning: PanSettings {
+            pan: rng.gen_range(-0.2..0.2),
+            mode: *[AutoPan::Off, AutoPan::RoundRobin, AutoPan::Key].choose(&mut rng).unwrap(),
+            spread: rng.gen_range(0.2..0.8),
+        },
+        ..Preset::default()
+    }
+}
+
+fn random_effect(rng: &mut StdRng) -> EffectConfig {
+    match rng.gen_range(0..6) {
+        0 => EffectConfig::Chorus(ChorusSettings {
+            rate: Rate::Hz(rng.gen_range(0.1..3.0)),
+            depth: rng.gen_range(1.0..8.0),
+            voices: rng.gen_range(1..=4),
+            mix: rng.gen_range(0.2..0.7),
+        }),
+        1 => EffectConfig::Flanger(FlangerSettings {
+            rate: random_rate(rng),
+            delay: rng.gen_range(0.5..3.0),
+            depth: rng.gen_range(1.0..5.0),
+            feedback: rng.gen_range(-0.8..0.8),
+            mix: rng.gen_range(0.3..0.7),
+        }),
+        2 => {
+            let min_frequency = rng.gen_range(100.0..600.0);
+            EffectConfig::Phaser(PhaserSettings {
+                rate: random_rate(rng),
+                stages: *[4, 6, 8].choose(rng).unwrap(),
+                min_frequency,
+                max_frequency: min_frequency * rng.gen_range(3.0..12.0),
+                feedback: rng.gen_range(-0.7..0.7),
+                mix: 0.5,
+            })
+        }
+        3 => EffectConfig::Distortion(DistortionSettings {
+            curve: *[DistortionCurve::SoftClip, DistortionCurve::HardClip, DistortionCurve::Foldback, DistortionCurve::Asymmetric]
+                .choose(rng)
+                .unwrap(),
+            drive: rng.gen_range(1.0..10.0),
+            tone: rng.gen_range(0.3..1.0),
+            level: rng.gen_range(0.3..0.7),
+            mix: rng.gen_range(0.3..1.0),
+        }),
+        4 => EffectConfig::Bitcrusher(BitcrusherSettings {
+            bits: rng.gen_range(4.0..12.0),
+            sample_rate: rng.gen_range(4000.0..22_050.0),
+            mix: rng.gen_range(0.3..1.0),
+        }),
+        _ => EffectConfig::Compressor(CompressorSettings {
+            threshold: rng.gen_range(-30.0..-6.0),
+            ratio: rng.gen_range(1.5..8.0),
+            attack: rng.gen_range(1.0..30.0),
+            release: rng.gen_range(50.0..400.0),
+            makeup: rng.gen_range(0.0..9.0),
+            ..CompressorSettings::default()
+        }),
+    }
+}
+
+// Either a free rate in Hz or one of the common tempo divisions
+fn random_rate(rng: &mut StdRng) -> Rate {
+    if rng.gen_bool(0.5) {
+        Rate::Hz(rng.gen_range(0.05..2.0))
+    } else {
+        Rate::Synced(NoteDivision {
+            numerator: *[1, 2].choose(rng).unwrap(),
+            denominator: *[1, 2, 4, 8].choose(rng).unwrap(),
+            feel: *[Feel::Straight, Feel::Triplet, Feel::Dotted].choose(rng).unwrap(),
+        })
+    }
+}
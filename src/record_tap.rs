@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+
+use crate::parts::MAX_PARTS;
+use crate::stereo::Frame;
+
+const BUFFER_SECONDS: u32 = 2; // Frames held for the writer, which can fall this far behind before any are lost
+
+// One frame of a recording: the output as heard and, for stems, each channel's own
+// sound. The stems are the main patch and then the parts, dry: balanced against each
+// other like in the mix, but before the master effects and send buses.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RecordedFrame {
+    pub mix: Frame,
+    pub stems: [Frame; MAX_PARTS + 1],
+}
+
+// Hands every frame the synth plays to a thread that writes them to disk. The audio
+// thread never waits on it: a frame that finds the buffer full is dropped and counted.
+#[derive(Clone)]
+pub struct RecordTap {
+    frames: mpsc::SyncSender<RecordedFrame>,
+    dropped: Arc<AtomicUsize>,
+    stems: bool,
+}
+
+impl RecordTap {
+    // The tap for `Synthesizer::with_recording` and the receiving end for the writer
+    pub fn new(sample_rate: u32, stems: bool) -> (Self, mpsc::Receiver<RecordedFrame>) {
+        let (frames, receiver) = mpsc::sync_channel((sample_rate * BUFFER_SECONDS) as usize);
+        (Self { frames, dropped: Arc::default(), stems }, receiver)
+    }
+
+    // Whether the stems are wanted; without them the synth leaves them silent
+    pub fn stems(&self) -> bool {
+        self.stems
+    }
+
+    pub fn push(&self, frame: RecordedFrame) {
+        if self.frames.try_send(frame).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Frames lost because the writer fell behind
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
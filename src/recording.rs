@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use rodio_synth::record_tap::{RecordTap, RecordedFrame};
+
+pub type Writer = hound::WavWriter<BufWriter<File>>;
+
+// A recording of what the synth plays, written as 32-bit float stereo WAV files by a
+// thread of its own: the mix as heard, live input and all, and with stems the main
+// patch and each part dry beside it ("take.wav" gets "take-main.wav", "take-part1.wav",
+// ...). The files are finished when this is dropped.
+pub struct Recording {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Recording {
+    // Starts writing to `path`, and to files for `parts` parts besides the main patch
+    // when stems are wanted. Returns the tap for `Synthesizer::with_recording`.
+    pub fn start(path: &Path, sample_rate: u32, stems: bool, parts: usize) -> io::Result<(Self, RecordTap)> {
+        let (tap, frames) = RecordTap::new(sample_rate, stems);
+        let mut writers = vec![(path.to_path_buf(), create(path, sample_rate)?)];
+        if stems {
+            let names = std::iter::once("main".to_string()).chain((1..=parts).map(|part| format!("part{}", part)));
+            for name in names {
+                let stem = stem_path(path, &name);
+                writers.push((stem.clone(), create(&stem, sample_rate)?));
+            }
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let (stop, tap) = (stop.clone(), tap.clone());
+            move || write(frames, writers, sample_rate, &stop, &tap)
+        });
+        Ok((Self { stop, thread: Some(thread) }, tap))
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// A 32-bit float stereo WAV file
+pub fn create(path: &Path, sample_rate: u32) -> io::Result<Writer> {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    Writer::create(path, spec).map_err(|e| match e {
+        hound::Error::IoError(e) => e,
+        e => io::Error::other(e),
+    })
+}
+
+// "take.wav" and "main" -> "take-main.wav"
+fn stem_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map_or("wav".into(), |extension| extension.to_string_lossy());
+    path.with_file_name(format!("{}-{}.{}", stem, name, extension))
+}
+
+// Writes frames until told to stop (or the synth goes away), then whatever is left
+fn write(frames: mpsc::Receiver<RecordedFrame>, mut writers: Vec<(PathBuf, Writer)>, sample_rate: u32, stop: &AtomicBool, tap: &RecordTap) {
+    let mut written = 0_usize;
+    let mut failed = false;
+    let mut write_frame = |frame: RecordedFrame| {
+        let channels = std::iter::once(frame.mix).chain(frame.stems);
+        for ((path, writer), [left, right]) in writers.iter_mut().zip(channels) {
+            if let Err(e) = writer.write_sample(left).and_then(|_| writer.write_sample(right)) {
+                if !failed {
+                    eprintln!("Failed to write the recording to {}: {}", path.display(), e);
+                    failed = true;
+                }
+            }
+        }
+        written += 1;
+    };
+    while !stop.load(Ordering::Relaxed) {
+        match frames.recv_timeout(Duration::from_millis(100)) {
+            Ok(frame) => write_frame(frame),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    for frame in frames.try_iter() {
+        write_frame(frame);
+    }
+    let stems = writers.len() - 1;
+    let recorded = writers.first().map(|(path, _)| path.clone());
+    for (path, writer) in writers {
+        if let Err(e) = writer.finalize() {
+            eprintln!("Failed to finish the recording {}: {}", path.display(), e);
+        }
+    }
+    if let Some(path) = recorded {
+        let seconds = written as f32 / sample_rate as f32;
+        match stems {
+            0 => println!("Recorded {:.1} s to {}", seconds, path.display()),
+            stems => println!("Recorded {:.1} s to {}, with {} stems beside it", seconds, path.display(), stems),
+        }
+    }
+    if tap.dropped() > 0 {
+        eprintln!("The recording lost {} frames: the disk couldn't keep up", tap.dropped());
+    }
+}
@@ -0,0 +1,63 @@
+use std::f64::consts::TAU;
+
+const LEVEL: f32 = 0.25; // About -12 dBFS, loud enough to tune against without swamping the instrument
+const HARMONIC: f32 = 0.5; // Of the second harmonic the beating octave beats against
+const FADE_SECONDS: f32 = 0.02; // Starting and stopping without a click
+
+// A steady sine at an exact pitch, for tuning instruments against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferenceSettings {
+    pub frequency: f64,
+    pub beat: Option<f32>, // Hz an octave above is played out of tune by, to hear what beating sounds like
+}
+
+// The frequency of `note` moved by `cents`, with A4 at `a4` Hz
+pub fn reference_frequency(note: u8, cents: f32, a4: f32) -> f64 {
+    a4 as f64 * 2.0_f64.powf((note as f64 - 69.0 + cents as f64 / 100.0) / 12.0)
+}
+
+// The reference tone as the synth plays it, dry like the click. Phases are kept in
+// double precision so the pitch stays exact over a long tuning session. With a beat,
+// the tone gets a second harmonic and a second sine comes in an octave up and `beat` Hz
+// sharp, which beats against that harmonic at `beat` times a second, as an octave
+// slightly out of tune does.
+pub struct ReferenceTone {
+    settings: ReferenceSettings,
+    phases: [f64; 3], // Of the tone, its harmonic and the octave, in cycles
+    gain: f32,        // Fading in, or out once stopped
+    stopping: bool,
+    sample_rate: u32,
+}
+
+impl ReferenceTone {
+    pub fn new(settings: ReferenceSettings, sample_rate: u32) -> Self {
+        Self { settings, phases: [0.0; 3], gain: 0.0, stopping: false, sample_rate }
+    }
+
+    // Fades out, after which `is_finished` says so
+    pub fn stop(&mut self) {
+        self.stopping = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.stopping && self.gain == 0.0
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let step = 1.0 / (FADE_SECONDS * self.sample_rate as f32);
+        self.gain = if self.stopping { (self.gain - step).max(0.0) } else { (self.gain + step).min(1.0) };
+
+        let frequency = self.settings.frequency;
+        let frequencies = [frequency, frequency * 2.0, frequency * 2.0 + self.settings.beat.unwrap_or(0.0) as f64];
+        let mut cycle = [0.0; 3];
+        for ((phase, frequency), cycle) in self.phases.iter_mut().zip(frequencies).zip(&mut cycle) {
+            *cycle = (TAU * *phase).sin() as f32;
+            *phase = (*phase + frequency / self.sample_rate as f64).fract();
+        }
+        let sample = match self.settings.beat {
+            Some(_) => (cycle[0] + (cycle[1] + cycle[2]) * HARMONIC) / (1.0 + 2.0 * HARMONIC),
+            None => cycle[0],
+        };
+        sample * LEVEL * self.gain
+    }
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL: Duration = Duration::from_millis(500); // How often the files are checked; quick enough to feel live
+
+// Watches `paths` on a thread of its own, calling `changed` with a path each time the
+// file there is written. It polls the modification times rather than asking the OS to
+// report changes, which works the same everywhere and also catches editors that save
+// by replacing the file. A file that's missing or being written is just tried again on
+// the next poll. The thread ends once `changed` returns false.
+pub fn watch(paths: Vec<PathBuf>, mut changed: impl FnMut(&Path) -> bool + Send + 'static) {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    thread::spawn(move || {
+        let mut times: Vec<Option<SystemTime>> = paths.iter().map(|path| modified(path)).collect();
+        loop {
+            thread::sleep(POLL);
+            for (path, time) in paths.iter().zip(&mut times) {
+                let now = modified(path);
+                if now.is_some() && now != *time {
+                    *time = now;
+                    if !changed(path) {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use crate::dither::{Dither, Quantizer};
+use crate::preset::Preset;
+use crate::score::Score;
+use crate::{SynthCommand, Synthesizer};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100; // Without --sample-rate, as there's no device to ask
+const TAIL_SECONDS: f32 = 1.0; // Rendered after the last release, for effect tails
+
+// Renders the score with the preset as fast as the CPU allows, without a sound card,
+// and writes it to `path` as a 16-bit stereo WAV file
+pub fn render_score(score: &Score, preset: &Preset, sample_rate: u32, dither: Dither, path: &Path) -> Result<(), hound::Error> {
+    render(score.commands(preset.tempo), score.seconds(preset.tempo), preset, sample_rate, dither, path)
+}
+
+// Renders a MIDI file (see `midi_file::read`) with the preset, like `render_score`
+pub fn render_midi(commands: Vec<(f32, SynthCommand)>, preset: &Preset, sample_rate: u32, dither: Dither, path: &Path) -> Result<(), hound::Error> {
+    let seconds = commands.iter().map(|&(at, _)| at).fold(0.0, f32::max);
+    render(commands, seconds, preset, sample_rate, dither, path)
+}
+
+// Renders timed commands lasting `seconds`, plus the release and a tail
+fn render(commands: Vec<(f32, SynthCommand)>, seconds: f32, preset: &Preset, sample_rate: u32, dither: Dither, path: &Path) -> Result<(), hound::Error> {
+    let commands: Vec<_> = commands
+        .into_iter()
+        .map(|(at, command)| ((at * sample_rate as f32) as usize, command))
+        .collect();
+    let seconds = seconds + preset.envelope.release + TAIL_SECONDS;
+    let frames = Synthesizer::offline(sample_rate, preset).render(&commands, (seconds * sample_rate as f32) as usize);
+
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    let mut quantizer = Quantizer::new(dither);
+    for frame in frames {
+        for sample in quantizer.quantize(frame) {
+            writer.write_sample(sample)?;
+        }
+    }
+    writer.finalize()
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::notes::{note_name, parse_pitch_class};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleKind {
+    Major,
+    Minor, // Natural minor
+    MajorPentatonic,
+    MinorPentatonic,
+    Custom, // Uses `degrees` from the settings
+}
+
+// The tonic of a scale, 0 = C .. 11 = B. Written as "C", "F#" or "Bb" in presets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PitchClass(pub u8);
+
+impl TryFrom<String> for PitchClass {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_pitch_class(&value).map(PitchClass).ok_or_else(|| format!("'{}' is not a pitch class", value.trim()))
+    }
+}
+
+impl From<PitchClass> for String {
+    fn from(class: PitchClass) -> Self {
+        class.to_string()
+    }
+}
+
+impl fmt::Display for PitchClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = note_name(self.0 % 12 + 60);
+        write!(f, "{}", name.trim_end_matches('4'))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScaleSettings {
+    pub enabled: bool,
+    pub root: PitchClass, // Tonic of the scale
+    pub scale: ScaleKind,
+    pub degrees: Vec<u8>, // Semitones above the root (0..11) for the custom scale
+}
+
+impl Default for ScaleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            root: PitchClass(0),
+            scale: ScaleKind::Major,
+            degrees: vec![0, 2, 4, 7, 9],
+        }
+    }
+}
+
+impl ScaleSettings {
+    fn degrees(&self) -> &[u8] {
+        match self.scale {
+            ScaleKind::Major => &[0, 2, 4, 5, 7, 9, 11],
+            ScaleKind::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            ScaleKind::MajorPentatonic => &[0, 2, 4, 7, 9],
+            ScaleKind::MinorPentatonic => &[0, 3, 5, 7, 10],
+            ScaleKind::Custom => &self.degrees,
+        }
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        let root = self.root.0 % 12;
+        let degree = (note + 12 - root) % 12;
+        self.degrees().iter().any(|&d| d % 12 == degree)
+    }
+
+    // Moves `note` to the nearest note in the scale; when two are equally close the
+    // lower one wins. Notes already in the scale (or an empty custom scale) pass through.
+    pub fn quantize(&self, note: u8) -> u8 {
+        if self.degrees().is_empty() || self.contains(note) {
+            return note;
+        }
+        for distance in 1..=6u8 {
+            if let Some(below) = note.checked_sub(distance).filter(|&n| self.contains(n)) {
+                return below;
+            }
+            if let Some(above) = note.checked_add(distance).filter(|&n| n <= 127 && self.contains(n)) {
+                return above;
+            }
+        }
+        note
+    }
+}
@@ -0,0 +1,108 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::preset::Preset;
+use crate::SynthCommand;
+
+// A set list file: the scenes of a live set, in order
+//
+//     crossfade = 0.5          # Seconds, for every scene that doesn't give its own
+//
+//     [[scene]]
+//     name = "Intro"
+//     preset = "pad.toml"      # Relative to the set list
+//
+//     [[scene]]
+//     name = "Verse"
+//     preset = "bass.toml"
+//     tempo = 96               # Instead of the preset's
+//     crossfade = 0.0          # Switch at once
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SetListFile {
+    crossfade: f32,
+    scene: Vec<SceneFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SceneFile {
+    name: String,
+    preset: PathBuf,
+    tempo: Option<f32>,
+    crossfade: Option<f32>,
+}
+
+// One scene: everything a preset holds (the patch, its effects, tempo, split and layer)
+// under a name, and how long switching to it takes
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub name: String,
+    pub preset: Preset,
+    pub crossfade: f32, // Seconds the old effects fade out under the new, 0.0 for at once
+}
+
+impl Scene {
+    // What switching to the scene sends the synth. Its tempo goes too, which a plain
+    // preset switch leaves alone. Without a crossfade of its own, the switch goes as
+    // the synth's `--preset-switch` says.
+    pub fn commands(&self) -> Vec<SynthCommand> {
+        let preset = Box::new(self.preset.clone());
+        let switch = if self.crossfade > 0.0 {
+            SynthCommand::CrossfadePreset(preset, self.crossfade)
+        } else {
+            SynthCommand::LoadPreset(preset)
+        };
+        vec![SynthCommand::SetTempo(self.preset.tempo), switch]
+    }
+}
+
+// The scenes of a set list, switched between by key or MIDI program change. Every
+// preset is read up front, so a broken one is reported at start, not mid-set.
+#[derive(Clone, Debug, Default)]
+pub struct SceneList {
+    scenes: Vec<Scene>,
+}
+
+impl SceneList {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let file: SetListFile = toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if file.scene.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the set list has no scenes"));
+        }
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let scenes = file
+            .scene
+            .into_iter()
+            .map(|scene| {
+                let preset_path = dir.join(&scene.preset);
+                let mut preset = Preset::load(&preset_path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", preset_path.display(), e)))?;
+                if let Some(tempo) = scene.tempo {
+                    preset.tempo = tempo;
+                }
+                let name = if scene.name.is_empty() {
+                    scene.preset.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+                } else {
+                    scene.name
+                };
+                Ok(Scene { name, preset, crossfade: scene.crossfade.unwrap_or(file.crossfade).max(0.0) })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { scenes })
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Scene> {
+        self.scenes.get(index)
+    }
+}
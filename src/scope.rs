@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const CAPACITY: usize = 4096; // Power of two, comfortably more than two screens of samples
+pub const SCOPE_WIDTH: usize = 1024; // Samples shown at once, about 23 ms at 44.1 kHz
+
+// Lock-free tap on the synth's output for the oscilloscope. The audio thread
+// overwrites a ring of samples without ever waiting; the UI copies out the most
+// recent ones whenever it redraws. A read racing a write can see a sample or two
+// from the previous pass, which is invisible on a scope.
+#[derive(Clone)]
+pub struct ScopeTap {
+    samples: Arc<[AtomicU32]>, // f32 bits
+    written: Arc<AtomicUsize>, // Total samples pushed, the ring position is this modulo CAPACITY
+    sample_rate: u32,
+}
+
+impl ScopeTap {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            samples: (0..CAPACITY).map(|_| AtomicU32::new(0)).collect(),
+            written: Arc::new(AtomicUsize::new(0)),
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn push(&self, sample: f32) {
+        let position = self.written.load(Ordering::Relaxed);
+        self.samples[position % CAPACITY].store(sample.to_bits(), Ordering::Relaxed);
+        self.written.store(position.wrapping_add(1), Ordering::Release);
+    }
+
+    // The last `count` samples, oldest first
+    pub fn latest(&self, count: usize) -> Vec<f32> {
+        let count = count.min(CAPACITY);
+        let end = self.written.load(Ordering::Acquire);
+        (end.wrapping_sub(count)..end)
+            .map(|position| f32::from_bits(self.samples[position % CAPACITY].load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    // A screen of samples starting at a rising zero crossing, so a steady waveform
+    // is drawn at the same place every frame instead of scrolling
+    pub fn triggered(&self) -> Vec<f32> {
+        let samples = self.latest(2 * SCOPE_WIDTH);
+        let start = (1..SCOPE_WIDTH)
+            .find(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0)
+            .unwrap_or(SCOPE_WIDTH); // Free-run on the newest samples when nothing crosses zero
+        samples[start..start + SCOPE_WIDTH].to_vec()
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{fs, io, path::Path};
+
+use crate::notes::parse_note_name;
+use crate::tempo::{NoteDivision, SharedTempo};
+use crate::SynthCommand;
+
+const GATE: f32 = 0.9; // Fraction of its length each note is held, so repeated notes are heard apart
+
+// A small text score, one or more items per line:
+//
+//   # Comments run to the end of the line
+//   tempo 96          # Beats per minute from here on
+//   length 1/8        # Length of the items that don't give their own
+//   C4 E4 G4:1/4      # Notes, with an optional length after a colon
+//   r r:1/2           # Rests
+//   C4+E4+G4:1/2      # Chords, notes joined with +
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScoreEvent {
+    Notes(Vec<u8>, f32), // Notes to play together and their length in beats
+    Rest(f32),
+    Tempo(f32),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Score {
+    pub events: Vec<ScoreEvent>,
+}
+
+impl Score {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        let mut length = NoteDivision::QUARTER;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let at = |e: String| format!("line {}: {}", number + 1, e);
+            while let Some(word) = words.next() {
+                match word {
+                    "tempo" => {
+                        let bpm = words.next().and_then(|bpm| bpm.parse::<f32>().ok()).filter(|bpm| (20.0..=400.0).contains(bpm));
+                        events.push(ScoreEvent::Tempo(bpm.ok_or_else(|| at("tempo needs a number of beats per minute from 20 to 400".into()))?));
+                    }
+                    "length" => {
+                        length = words.next().ok_or_else(|| at("length needs a note division such as 1/8".into()))?.parse().map_err(at)?;
+                    }
+                    item => events.push(parse_item(item, length).map_err(at)?),
+                }
+            }
+        }
+        Ok(Self { events })
+    }
+
+    // Length of the whole score in seconds, starting from `tempo`
+    pub fn seconds(&self, mut tempo: f32) -> f32 {
+        let mut seconds = 0.0;
+        for event in &self.events {
+            match *event {
+                ScoreEvent::Notes(_, beats) | ScoreEvent::Rest(beats) => seconds += beats * 60.0 / tempo,
+                ScoreEvent::Tempo(bpm) => tempo = bpm,
+            }
+        }
+        seconds
+    }
+
+    // The score as synth commands, each with the seconds from the start it's due at,
+    // starting from `tempo`
+    pub fn commands(&self, mut tempo: f32) -> Vec<(f32, SynthCommand)> {
+        let mut commands = Vec::new();
+        let mut at = 0.0;
+        for event in &self.events {
+            match event {
+                ScoreEvent::Tempo(bpm) => {
+                    tempo = *bpm;
+                    commands.push((at, SynthCommand::SetTempo(tempo)));
+                }
+                ScoreEvent::Rest(beats) => at += beats * 60.0 / tempo,
+                ScoreEvent::Notes(notes, beats) => {
+                    let length = beats * 60.0 / tempo;
+                    commands.extend(notes.iter().map(|&note| (at, SynthCommand::NoteOn(note))));
+                    commands.extend(notes.iter().map(|&note| (at + length * GATE, SynthCommand::NoteOff(note))));
+                    at += length;
+                }
+            }
+        }
+        commands
+    }
+}
+
+// One note, chord or rest with its optional ":length"
+fn parse_item(item: &str, length: NoteDivision) -> Result<ScoreEvent, String> {
+    let (body, length) = match item.split_once(':') {
+        Some((body, length)) => (body, length.parse::<NoteDivision>()?),
+        None => (item, length),
+    };
+    if body == "r" {
+        return Ok(ScoreEvent::Rest(length.beats()));
+    }
+    let notes = body
+        .split('+')
+        .map(|name| parse_note_name(name).ok_or_else(|| format!("'{}' is not a note name or rest", name)))
+        .collect::<Result<Vec<u8>, String>>()?;
+    Ok(ScoreEvent::Notes(notes, length.beats()))
+}
+
+// Plays the score into `output` in real time, returning when the last note has been
+// released or, between events, when `keep_going` says to stop. Tempo changes also go
+// to `tempo` so tempo-synced effects follow the score.
+pub fn perform(score: &Score, tempo: &SharedTempo, output: &mpsc::Sender<SynthCommand>, keep_going: impl Fn() -> bool) {
+    let bpm = tempo.get();
+    let start = Instant::now(); // Every command is timed from here, so timing doesn't drift
+    for (at, command) in score.commands(bpm) {
+        thread::sleep((start + Duration::from_secs_f32(at)).saturating_duration_since(Instant::now()));
+        if !keep_going() {
+            return;
+        }
+        if let SynthCommand::SetTempo(bpm) = command {
+            tempo.set(bpm);
+        }
+        if output.send(command).is_err() {
+            return; // The synth is gone, so there's nobody to play to
+        }
+    }
+    thread::sleep((start + Duration::from_secs_f32(score.seconds(bpm))).saturating_duration_since(Instant::now())); // Through any closing rest
+}
@@ -0,0 +1,203 @@
+use rand::Rng;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use std::{fs, io, path::Path, thread};
+
+use crate::param_id::ParamId;
+use crate::tempo::SharedTempo;
+use crate::SynthCommand;
+
+const DEFAULT_TICK: f64 = 0.25; // Beats between `on_tick` calls when the script doesn't set `tick`
+const MAX_BEATS: f64 = 1024.0;  // Longest `tick` or `play` note, so huge lengths can't overflow a Duration
+
+// What a script asked for while one of its functions ran
+enum Action {
+    Command(SynthCommand),
+    Play(u8, f64), // Note on now, note off after this many beats
+}
+
+// Runs a Rhai script on its own thread, between the keyboard and the rest of the chain.
+// The script can define any of these, and each one it leaves out keeps the usual behavior:
+//
+//   let tick = 0.5;                 // Beats between ticks, a sixteenth note by default
+//   fn on_tick(step) { ... }        // Called on every tick, counting from 0
+//   fn on_note_on(note) { ... }     // Replaces playing the key's note
+//   fn on_note_off(note) { ... }    // Replaces releasing it
+//
+// and call `note_on(note)`, `note_off(note)`, `play(note, beats)`, `param(path, value)`,
+// `set_macro(index, value)`, `set_tempo(bpm)`, `tempo()`, `random()` and
+// `random_int(low, high)`. Everything the script sends goes to `output`.
+pub fn spawn(path: impl AsRef<Path>, tempo: SharedTempo, output: mpsc::Sender<SynthCommand>) -> io::Result<mpsc::Sender<SynthCommand>> {
+    let text = fs::read_to_string(path)?;
+    // Compile here so a broken script is reported before anything starts
+    Engine::new().compile(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let (tx, rx) = mpsc::channel::<SynthCommand>();
+    thread::spawn(move || {
+        // The engine isn't Send, so the thread builds its own
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        let engine = engine(actions.clone(), tempo.clone());
+        let ast = engine.compile(&text).expect("Script compiled once already");
+        let mut scope = Scope::new();
+        if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+            eprintln!("Script failed: {}", e);
+        }
+        let tick = scope.get_value::<f64>("tick").filter(|tick| !tick.is_nan()).unwrap_or(DEFAULT_TICK).clamp(0.01, MAX_BEATS);
+        let has = |name: &str| ast.iter_functions().any(|function| function.name == name);
+        let (has_tick, has_note_on, has_note_off) = (has("on_tick"), has("on_note_on"), has("on_note_off"));
+
+        let mut step: i64 = 0;
+        let mut next_tick = Instant::now();
+        let mut releases: Vec<(u8, Instant)> = Vec::new(); // Notes started by `play` and when they end
+
+        loop {
+            let next_release = releases.iter().map(|&(_, at)| at).min();
+            let wake = match (has_tick, next_release) {
+                (true, Some(at)) => Some(at.min(next_tick)),
+                (true, None) => Some(next_tick),
+                (false, at) => at,
+            };
+            let timeout = wake.map_or(Duration::from_secs(1), |at| at.saturating_duration_since(Instant::now()));
+
+            match rx.recv_timeout(timeout) {
+                Ok(SynthCommand::NoteOn(note)) if has_note_on => call(&engine, &mut scope, &ast, "on_note_on", note as i64),
+                Ok(SynthCommand::NoteOff(note)) if has_note_off => call(&engine, &mut scope, &ast, "on_note_off", note as i64),
+                Ok(command) => actions.borrow_mut().push(Action::Command(command)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            if has_tick && now >= next_tick {
+                call(&engine, &mut scope, &ast, "on_tick", step);
+                step += 1;
+                let beat = Duration::from_secs_f32(60.0 / tempo.get());
+                next_tick += beat.mul_f64(tick);
+                if next_tick < now {
+                    next_tick = now + beat.mul_f64(tick); // Fell behind, don't rush to catch up
+                }
+            }
+
+            let mut commands = Vec::new();
+            releases.retain(|&(note, at)| {
+                if at <= now {
+                    commands.push(SynthCommand::NoteOff(note));
+                }
+                at > now
+            });
+            for action in actions.borrow_mut().drain(..) {
+                match action {
+                    Action::Command(command) => commands.push(command),
+                    Action::Play(note, beats) => {
+                        let beats = if beats.is_nan() { 0.0 } else { beats.clamp(0.0, MAX_BEATS) };
+                        let length = Duration::from_secs_f32(60.0 / tempo.get()).mul_f64(beats);
+                        commands.push(SynthCommand::NoteOn(note));
+                        releases.push((note, now + length));
+                    }
+                }
+            }
+            for command in commands {
+                if output.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(tx)
+}
+
+// Calls a script function with one argument; errors are reported and otherwise ignored
+// so one bad call doesn't stop the music
+fn call(engine: &Engine, scope: &mut Scope, ast: &AST, name: &str, argument: i64) {
+    if let Err(e) = engine.call_fn::<Dynamic>(scope, ast, name, (argument,)) {
+        eprintln!("Script error in {}: {}", name, e);
+    }
+}
+
+// An engine with the synth's functions registered, each pushing to `actions`
+fn engine(actions: Rc<RefCell<Vec<Action>>>, tempo: SharedTempo) -> Engine {
+    let mut engine = Engine::new();
+    let note = |note: i64| note.clamp(0, 127) as u8;
+
+    let queue = actions.clone();
+    engine.register_fn("note_on", move |n: i64| queue.borrow_mut().push(Action::Command(SynthCommand::NoteOn(note(n)))));
+    let queue = actions.clone();
+    engine.register_fn("note_off", move |n: i64| queue.borrow_mut().push(Action::Command(SynthCommand::NoteOff(note(n)))));
+    let queue = actions.clone();
+    engine.register_fn("play", move |n: i64, beats: f64| queue.borrow_mut().push(Action::Play(note(n), beats)));
+    let queue = actions.clone();
+    // A path that can't be a parameter stops the script with an error, like a bad call
+    engine.register_fn("param", move |path: &str, value: f64| -> Result<(), Box<EvalAltResult>> {
+        queue.borrow_mut().push(Action::Command(SynthCommand::SetParam(ParamId::new(path)?, value as f32)));
+        Ok(())
+    });
+    let queue = actions.clone();
+    engine.register_fn("set_macro", move |index: i64, value: f64| {
+        // Macros are numbered from 1 in scripts, as on the keys and over OSC
+        if (1..=crate::macros::MACROS as i64).contains(&index) {
+            queue.borrow_mut().push(Action::Command(SynthCommand::SetMacro(index as usize - 1, value as f32)));
+        }
+    });
+    let (queue, shared) = (actions, tempo.clone());
+    engine.register_fn("set_tempo", move |bpm: f64| {
+        if bpm.is_nan() {
+            return;
+        }
+        // As over OSC; the clocks divide by the tempo, so it can't reach zero
+        let bpm = (bpm as f32).clamp(20.0, 400.0);
+        shared.set(bpm);
+        queue.borrow_mut().push(Action::Command(SynthCommand::SetTempo(bpm)));
+    });
+    engine.register_fn("tempo", move || tempo.get() as f64);
+    engine.register_fn("random", || rand::thread_rng().gen::<f64>());
+    engine.register_fn("random_int", |low: i64, high: i64| rand::thread_rng().gen_range(low..=high.max(low)));
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `script` and returns what it sent once `probe` has come through after it
+    fn run(name: &str, script: &str, tempo: &SharedTempo) -> Vec<SynthCommand> {
+        let path = std::env::temp_dir().join(format!("rodio-synth-{}-{}.rhai", name, std::process::id()));
+        fs::write(&path, script).unwrap();
+        let (output, commands) = mpsc::channel();
+        let input = spawn(&path, tempo.clone(), output).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let probe = SynthCommand::SetMacro(0, 0.5);
+        input.send(probe.clone()).unwrap();
+        let mut received = Vec::new();
+        loop {
+            let command = commands.recv_timeout(Duration::from_secs(5)).expect("The script thread stopped");
+            if command == probe {
+                return received;
+            }
+            received.push(command);
+        }
+    }
+
+    #[test]
+    fn tempo_is_clamped_when_set() {
+        let tempo = SharedTempo::new(120.0);
+        let sent = run("slow", "set_tempo(0.0); set_tempo(-5.0);", &tempo);
+        assert_eq!(sent, [SynthCommand::SetTempo(20.0), SynthCommand::SetTempo(20.0)]);
+        assert_eq!(tempo.get(), 20.0);
+
+        let sent = run("fast", "set_tempo(1e9);", &tempo);
+        assert_eq!(sent, [SynthCommand::SetTempo(400.0)]);
+        assert_eq!(tempo.get(), 400.0);
+    }
+
+    #[test]
+    fn huge_beat_counts_are_capped() {
+        let tempo = SharedTempo::new(120.0);
+        let sent = run("long", "let tick = 1e300; play(60, 1e300); play(62, -1.0); play(64, 0.0 / 0.0);", &tempo);
+        assert_eq!(sent, [SynthCommand::NoteOn(60), SynthCommand::NoteOn(62), SynthCommand::NoteOn(64)]);
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+use crate::effects::{EffectConfig, EffectsChain};
+use crate::oversample::Oversampling;
+use crate::stereo::Frame;
+
+// A send bus, like an aux send on a mixing desk: the main patch and each part send
+// some of their sound into its effects, and what comes out is added back after the
+// master effects. The dry sound still goes through the master effects as ever, so a
+// reverb here can drench a pad on one part while the bass on another stays dry.
+//
+//     [[sends]]
+//     name = "hall"
+//     main = 0.2               # A little of the main patch
+//     parts = [0.0, 0.7]       # None of part 0, a lot of part 1
+//     [[sends.effects]]
+//     type = "chorus"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SendSettings {
+    pub name: String,
+    pub effects: Vec<EffectConfig>, // Applied in order to what's sent
+    pub main: f32,                  // How much of the main patch is sent, 0.0..1.0
+    pub parts: Vec<f32>,            // How much of each part is sent, in the preset's order; parts left out send none
+    pub level: f32,                 // Of the bus's output, 0.0..2.0
+}
+
+impl Default for SendSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            effects: Vec::new(),
+            main: 0.0,
+            parts: Vec::new(),
+            level: 1.0,
+        }
+    }
+}
+
+impl SendSettings {
+    // The settings the synth's "sends.<index>.<name>" parameters change, with their
+    // current values; each part's send is "part_<index>"
+    pub fn params(&self) -> Vec<(String, f32)> {
+        let mut params = vec![("main".to_string(), self.main), ("level".to_string(), self.level)];
+        params.extend(self.parts.iter().enumerate().map(|(index, &amount)| (format!("part_{}", index), amount)));
+        for (slot, effect) in self.effects.iter().enumerate() {
+            params.extend(effect.params().into_iter().map(|(name, value)| (format!("effects.{}.{}", slot, name), value)));
+        }
+        params
+    }
+}
+
+// A send bus and the sound sent to it this frame
+pub(crate) struct SendBus {
+    settings: SendSettings,
+    effects: EffectsChain,
+    input: Frame,
+}
+
+impl SendBus {
+    pub fn new(settings: &SendSettings, sample_rate: u32) -> Self {
+        Self {
+            effects: EffectsChain::new(&settings.effects, sample_rate),
+            settings: settings.clone(),
+            input: [0.0; 2],
+        }
+    }
+
+    // How much of the main patch (`None`) or a part is sent
+    pub fn amount(&self, part: Option<usize>) -> f32 {
+        match part {
+            Some(index) => self.settings.parts.get(index).copied().unwrap_or(0.0),
+            None => self.settings.main,
+        }
+    }
+
+    pub fn send(&mut self, frame: Frame, amount: f32) {
+        self.input = [self.input[0] + frame[0] * amount, self.input[1] + frame[1] * amount];
+    }
+
+    // Runs what was sent this frame, times `gain` (what balances the voices), through the
+    // effects, which then wait for the next frame's
+    pub fn process(&mut self, gain: f32) -> Frame {
+        let input = std::mem::take(&mut self.input).map(|sample| sample * gain);
+        self.effects.process(input).map(|sample| sample * self.settings.level)
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.effects.set_tempo(tempo);
+    }
+
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.effects.set_oversampling(oversampling);
+    }
+
+    pub fn sidechain(&mut self, key: Frame) {
+        self.effects.sidechain(key);
+    }
+
+    // `name` is "main", "level", "part_<index>" or "effects", which is followed by the
+    // slot and the effect's own parameter
+    pub fn set_param(&mut self, name: &str, slot: Option<&str>, effect_param: Option<&str>, value: f32) -> bool {
+        match (name, slot, effect_param) {
+            ("main", None, None) => self.settings.main = value.clamp(0.0, 1.0),
+            ("level", None, None) => self.settings.level = value.clamp(0.0, 2.0),
+            ("effects", Some(slot), Some(param)) => {
+                return slot.parse().is_ok_and(|slot| self.effects.set_param(slot, param, value));
+            }
+            (name, None, None) => match name.strip_prefix("part_").and_then(|index| index.parse::<usize>().ok()) {
+                Some(index) if index < self.settings.parts.len() => self.settings.parts[index] = value.clamp(0.0, 1.0),
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::humanize::{HumanizeSettings, Humanizer};
+use crate::notes::{note_name, parse_note_name};
+use crate::tempo::{NoteDivision, SharedTempo};
+use crate::SynthCommand;
+
+pub const STEPS: usize = 16;
+
+// One step of a pattern: a note, or a rest. Written as "C4", "F#3" or "-" in presets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Step(pub Option<u8>);
+
+impl TryFrom<String> for Step {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.trim() {
+            "-" | "" => Ok(Step(None)),
+            name => parse_note_name(name).map(|note| Step(Some(note))).ok_or_else(|| format!("'{}' is not a note name", name)),
+        }
+    }
+}
+
+impl From<Step> for String {
+    fn from(step: Step) -> Self {
+        step.to_string()
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(note) => write!(f, "{}", note_name(note)),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SequencerSettings {
+    pub steps: Vec<Step>,   // The pattern, padded or truncated to 16 steps
+    pub rate: NoteDivision, // Length of each step
+    pub gate: f32,          // Fraction of the step each note is held for
+}
+
+impl Default for SequencerSettings {
+    fn default() -> Self {
+        Self {
+            steps: vec![Step::default(); STEPS],
+            rate: NoteDivision::SIXTEENTH,
+            gate: 0.5,
+        }
+    }
+}
+
+pub enum SequencerControl {
+    TogglePlay,
+    ToggleRecord,    // Step entry: each played note fills the next step
+    Note(u8),        // A note played on the keyboard, recorded while step entry is on
+    Rest,            // Records a rest while step entry is on
+    Save,            // Hands the current pattern to the save callback
+    Velocity(f32),   // The keyboard's velocity, which humanized notes vary and put back after them
+    Report(mpsc::Sender<SequencerSettings>), // Hands the current pattern back, for autosaving
+}
+
+// Runs the 16-step sequencer on its own clock thread, playing into `output`.
+// `on_save` receives the pattern whenever a `Save` control arrives, so it can be
+// written back into the preset file.
+pub fn spawn(
+    mut settings: SequencerSettings,
+    tempo: SharedTempo,
+    output: mpsc::Sender<SynthCommand>,
+    humanize: HumanizeSettings,
+    on_save: impl Fn(&SequencerSettings) + Send + 'static,
+) -> mpsc::Sender<SequencerControl> {
+    let (tx, rx) = mpsc::channel::<SequencerControl>();
+    settings.steps.resize(STEPS, Step::default());
+
+    thread::spawn(move || {
+        let gate = settings.gate.clamp(0.05, 1.0);
+        let mut humanizer = Humanizer::new(humanize);
+        let mut delay = Duration::ZERO; // How far behind the step its note comes in
+        let mut velocity = 1.0;
+
+        let mut playing = false;
+        let mut recording: Option<usize> = None; // Next step to be written while recording
+        let mut position = 0;
+        let mut next_step = Instant::now();
+        let mut sounding: Option<(u8, Instant)> = None;
+
+        loop {
+            let now = Instant::now();
+            let timeout = match (playing, sounding) {
+                (true, Some((_, gate_off))) => (next_step + delay).min(gate_off).saturating_duration_since(now),
+                (true, None) => (next_step + delay).saturating_duration_since(now),
+                (false, Some((_, gate_off))) => gate_off.saturating_duration_since(now),
+                (false, None) => Duration::from_secs(1),
+            };
+
+            match rx.recv_timeout(timeout) {
+                Ok(SequencerControl::TogglePlay) => {
+                    playing = !playing;
+                    position = 0;
+                    // On a Link grid, patterns start on a multiple of their length, so peers' line up
+                    next_step = tempo.first_step(Instant::now(), settings.rate.beats() * STEPS as f32);
+                }
+                Ok(SequencerControl::ToggleRecord) => {
+                    recording = match recording {
+                        Some(_) => None,
+                        None => Some(0),
+                    };
+                }
+                Ok(SequencerControl::Note(note)) => {
+                    if let Some(index) = recording {
+                        settings.steps[index] = Step(Some(note));
+                        recording = Some((index + 1) % STEPS);
+                    }
+                }
+                Ok(SequencerControl::Rest) => {
+                    if let Some(index) = recording {
+                        settings.steps[index] = Step(None);
+                        recording = Some((index + 1) % STEPS);
+                    }
+                }
+                Ok(SequencerControl::Save) => on_save(&settings),
+                Ok(SequencerControl::Velocity(value)) => velocity = value,
+                Ok(SequencerControl::Report(reply)) => {
+                    let _ = reply.send(settings.clone());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            if let Some((note, gate_off)) = sounding {
+                if now >= gate_off || !playing {
+                    if output.send(SynthCommand::NoteOff(note)).is_err() {
+                        return;
+                    }
+                    sounding = None;
+                }
+            }
+
+            if playing && now >= next_step + delay {
+                let step_length = Duration::from_secs_f32(settings.rate.seconds(tempo.get()));
+                if let Step(Some(note)) = settings.steps[position] {
+                    if let Some((previous, _)) = sounding.take() {
+                        let _ = output.send(SynthCommand::NoteOff(previous));
+                    }
+                    let commands = humanizer.before_note(velocity).into_iter().chain([humanizer.note_on(note)]);
+                    for command in commands.chain(humanizer.after_note(velocity)) {
+                        if output.send(command).is_err() {
+                            return;
+                        }
+                    }
+                    sounding = Some((note, now + step_length.mul_f32(gate)));
+                }
+                position = (position + 1) % STEPS;
+                next_step = tempo.step_after(next_step, settings.rate.beats());
+                if next_step < now {
+                    next_step = tempo.step_after(now, settings.rate.beats());
+                }
+                delay = humanizer.delay();
+            }
+        }
+    });
+
+    tx
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rodio_synth::looper::{LoopStep, LooperControl};
+use rodio_synth::params::ParamStore;
+use rodio_synth::preset::Preset;
+use rodio_synth::sequencer::SequencerControl;
+
+use crate::keymap::KeyMap;
+
+const INTERVAL: Duration = Duration::from_secs(30);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1); // For the sequencer and looper threads to hand over what they hold
+
+// Everything played into being since launch that would be lost in a crash: the preset
+// playing with the sequencer's pattern, every live parameter as edited, the loop, and
+// the key map as last loaded.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub preset: Preset,
+    pub params: BTreeMap<String, f32>,
+    pub looper: Vec<LoopStep>,
+    #[serde(default)] // Sessions saved before key maps were kept play the default one
+    pub keymap: KeyMap,
+}
+
+impl Session {
+    fn load(path: &Path) -> io::Result<Self> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Asks the sequencer and looper threads for their part; None if either has gone
+    fn capture(
+        params: &ParamStore,
+        sequencer: &mpsc::Sender<SequencerControl>,
+        looper: &mpsc::Sender<LooperControl>,
+        keymap: &Mutex<KeyMap>,
+    ) -> Option<Self> {
+        let mut preset = params.preset()?;
+        let (reply, pattern) = mpsc::channel();
+        sequencer.send(SequencerControl::Report(reply)).ok()?;
+        preset.sequencer = pattern.recv_timeout(REPLY_TIMEOUT).ok()?;
+        let (reply, steps) = mpsc::channel();
+        looper.send(LooperControl::Report(reply)).ok()?;
+        let looper = steps.recv_timeout(REPLY_TIMEOUT).ok()?;
+        let keymap = keymap.lock().unwrap().clone();
+        Some(Self { preset, params: params.values().into_iter().collect(), looper, keymap })
+    }
+}
+
+// A session file is only left behind by a run that didn't end cleanly, so finding one
+// means there's work to get back. Asks whether to restore it, or without a terminal to
+// ask on (headless), restores it, so a box that lost power comes back as it was.
+pub fn offer(path: &Path, ask: bool) -> Option<Session> {
+    if !path.exists() {
+        return None;
+    }
+    let session = match Session::load(path) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Failed to read the autosaved session {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    if ask {
+        print!("The last run didn't end cleanly. Restore the session autosaved to {}? [Y/n] ", path.display());
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        let _ = io::stdin().lock().read_line(&mut answer);
+        if answer.trim().to_lowercase().starts_with('n') {
+            return None;
+        }
+    }
+    println!("Restored the autosaved session");
+    Some(session)
+}
+
+// Saves the session every INTERVAL while playing, when anything has changed, and
+// removes the file on a clean exit. A panic leaves it behind like a crash does.
+pub struct Autosave {
+    path: PathBuf,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Autosave {
+    // `keymap` is the one playing, kept up to date as it's reloaded
+    pub fn spawn(
+        path: &Path,
+        params: ParamStore,
+        sequencer: mpsc::Sender<SequencerControl>,
+        looper: mpsc::Sender<LooperControl>,
+        keymap: Arc<Mutex<KeyMap>>,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn({
+            let path = path.to_path_buf();
+            move || {
+                let mut saved = String::new();
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(INTERVAL) {
+                    let Some(session) = Session::capture(&params, &sequencer, &looper, &keymap) else { continue };
+                    match toml::to_string_pretty(&session) {
+                        Ok(text) if text != saved => match write(&path, &text) {
+                            Ok(()) => saved = text,
+                            Err(e) => eprintln!("Failed to autosave the session to {}: {}", path.display(), e),
+                        },
+                        Ok(_) => {}
+                        Err(e) => eprintln!("Failed to autosave the session: {}", e),
+                    }
+                }
+            }
+        });
+        Self { path: path.to_path_buf(), stop: Some(stop), thread: Some(thread) }
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if !thread::panicking() && self.path.exists() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                eprintln!("Failed to remove the autosaved session {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+// Writes beside the file and renames over it, so power lost mid-write leaves the last save whole
+fn write(path: &Path, text: &str) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    fs::write(&partial, text)?;
+    fs::rename(&partial, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(keymap: KeyMap) -> Session {
+        let params = BTreeMap::from([("filter.cutoff".to_string(), 800.0)]);
+        Session { preset: Preset::default(), params, looper: Vec::new(), keymap }
+    }
+
+    #[test]
+    fn the_key_map_survives_a_save_and_restore() {
+        let file = std::env::temp_dir().join(format!("rodio-synth-keymap-{}.toml", std::process::id()));
+        fs::write(&file, "layout = \"azerty\"\n[notes]\nA = \"C4\"\nSemicolon = \"D#5\"\nF1 = \"C2\"\n").unwrap();
+        let keymap = KeyMap::load(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+
+        let saved = session(keymap);
+        let text = toml::to_string_pretty(&saved).unwrap();
+        assert!(text.contains("[keymap.notes]"), "{}", text);
+        assert_eq!(toml::from_str::<Session>(&text).unwrap(), saved);
+    }
+
+    #[test]
+    fn sessions_saved_without_a_key_map_play_the_default_one() {
+        let mut text = toml::to_string_pretty(&session(KeyMap::default())).unwrap();
+        let start = text.find("[keymap").unwrap();
+        text.truncate(start);
+        assert_eq!(toml::from_str::<Session>(&text).unwrap().keymap, KeyMap::default());
+    }
+}
@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Set when Ctrl-C or SIGTERM asks the program to stop. The UIs and the console loop
+// check it and return, so the audio can fade out before the program exits. A second
+// signal exits straight away, in case something is stuck.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn install() -> Self {
+        let requested = Arc::new(AtomicBool::new(false));
+        let flag = requested.clone();
+        ctrlc::set_handler(move || {
+            if flag.swap(true, Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+        })
+        .expect("Failed to install the Ctrl-C handler");
+        Self { requested }
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,79 @@
+const BLOCK: u32 = 32; // Frames between moves of what a control sets, as for gliding parameters
+
+// A control played in steps, such as a MIDI controller or aftertouch, glided between them
+// with a one-pole filter so what it moves doesn't zipper. What it sets is moved a block at
+// a time, and only once it has moved.
+#[derive(Default)]
+pub struct SmoothedControl {
+    value: f32, // Where it was last put
+    smoothed: f32,
+    applied: f32,     // Where what it sets was last set from
+    coefficient: f32, // Fraction of the way it moves to `value` each frame
+    countdown: u32,
+}
+
+impl SmoothedControl {
+    // Until it moves from `rest`, what it sets keeps the values it has
+    pub fn new(rest: f32, smoothing: f32, sample_rate: u32) -> Self {
+        Self {
+            value: rest,
+            smoothed: rest,
+            applied: rest,
+            coefficient: coefficient(smoothing, sample_rate),
+            countdown: 0,
+        }
+    }
+
+    // Sets what it moves from `rest` on the first block as well
+    pub fn with_rest_applied(mut self) -> Self {
+        self.applied = f32::NAN;
+        self
+    }
+
+    pub fn set(&mut self, value: f32) {
+        self.value = value;
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn smoothed(&self) -> f32 {
+        self.smoothed
+    }
+
+    pub fn coefficient(&self) -> f32 {
+        self.coefficient
+    }
+
+    // Milliseconds it takes to settle on a new value
+    pub fn set_smoothing(&mut self, smoothing: f32, sample_rate: u32) {
+        self.coefficient = coefficient(smoothing, sample_rate);
+    }
+
+    // Moves it along by a frame. Every `BLOCK` frames in which it has moved, returns it
+    // for what it sets to be set from.
+    pub fn advance(&mut self) -> Option<f32> {
+        self.smoothed += (self.value - self.smoothed) * self.coefficient;
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return None;
+        }
+        self.countdown = BLOCK - 1;
+        if (self.smoothed - self.applied).abs() < 1e-4 {
+            return None;
+        }
+        self.applied = self.smoothed;
+        Some(self.applied)
+    }
+}
+
+// One-pole coefficient for settling in about `ms` at `sample_rate`
+fn coefficient(ms: f32, sample_rate: u32) -> f32 {
+    let frames = ms.max(0.0) / 1000.0 * sample_rate as f32;
+    if frames <= 1.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / frames).exp()
+    }
+}
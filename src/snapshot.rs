@@ -0,0 +1,54 @@
+use crate::notes::NoteSet;
+use crate::stereo::{Frame, LEFT, RIGHT};
+
+pub const SNAPSHOTS_PER_SECOND: u32 = 30;
+
+// What a user interface needs to know about the running synth. The audio thread
+// sends one of these a few dozen times a second; if the UI falls behind, snapshots
+// are dropped rather than blocking the audio.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub notes: NoteSet, // Notes currently held (not releasing)
+    pub peak: Frame,    // Highest absolute output sample per channel since the last snapshot
+    pub rms: Frame,     // RMS level per channel since the last snapshot
+    pub clips: u64,     // Samples that hit the output limiter since the synth started
+}
+
+// Collects the output levels between snapshots
+#[derive(Default)]
+pub struct Meter {
+    peak: Frame,
+    sum_squares: Frame,
+    frames: u32,
+    clips: u64,
+}
+
+impl Meter {
+    // `frame` is the output before the final clamp, so overs show up and get counted
+    pub fn add(&mut self, frame: Frame) {
+        for channel in [LEFT, RIGHT] {
+            let level = frame[channel].abs();
+            self.peak[channel] = self.peak[channel].max(level);
+            self.sum_squares[channel] += level * level;
+        }
+        if frame[LEFT].abs() > 1.0 || frame[RIGHT].abs() > 1.0 {
+            self.clips += 1;
+        }
+        self.frames += 1;
+    }
+
+    // Packs the levels since the last call into a snapshot and starts a new measurement
+    pub fn snapshot(&mut self, notes: NoteSet) -> Snapshot {
+        let frames = self.frames.max(1) as f32;
+        let snapshot = Snapshot {
+            notes,
+            peak: self.peak,
+            rms: self.sum_squares.map(|sum| (sum / frames).sqrt()),
+            clips: self.clips,
+        };
+        self.peak = [0.0; 2];
+        self.sum_squares = [0.0; 2];
+        self.frames = 0;
+        snapshot
+    }
+}
@@ -0,0 +1,83 @@
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::effects::gain_to_db;
+use crate::scope::ScopeTap;
+
+const FFT_SIZE: usize = 4096; // About 11 Hz per bin at 44.1 kHz, enough to separate low notes
+pub const BANDS: usize = 96;
+pub const MIN_FREQUENCY: f32 = 20.0;
+pub const MAX_FREQUENCY: f32 = 20_000.0;
+pub const FLOOR_DB: f32 = -90.0;
+const UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+
+// FFT analyzer for the spectrum view. It reads the newest samples from the scope
+// tap, applies a Hann window and reduces the bins to log-spaced bands in dB.
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    window_gain: f32, // Sum of the window, to scale a full-scale sine to 0 dB
+    sample_rate: u32,
+    bands: Vec<f32>,
+    buffer: Vec<Complex<f32>>, // Scratch for the transform
+    magnitudes: Vec<f32>,      // Of the bins up to half the sample rate
+    last_update: Option<Instant>,
+}
+
+impl Spectrum {
+    pub fn new(sample_rate: u32) -> Self {
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window_gain: window.iter().sum(),
+            window,
+            sample_rate,
+            bands: vec![FLOOR_DB; BANDS],
+            buffer: vec![Complex::new(0.0, 0.0); FFT_SIZE],
+            magnitudes: vec![0.0; FFT_SIZE / 2],
+            last_update: None,
+        }
+    }
+
+    // Band levels in dB from MIN_FREQUENCY to MAX_FREQUENCY, recomputed a few times a second
+    pub fn bands(&mut self, scope: &ScopeTap) -> &[f32] {
+        if self.last_update.is_some_and(|last| last.elapsed() < UPDATE_INTERVAL) {
+            return &self.bands;
+        }
+        self.last_update = Some(Instant::now());
+
+        let latest = scope.latest(FFT_SIZE);
+        self.buffer.fill(Complex::new(0.0, 0.0));
+        for ((bin, &sample), &window) in self.buffer.iter_mut().zip(&latest).zip(&self.window) {
+            *bin = Complex::new(sample * window, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+
+        let bin_width = self.sample_rate as f32 / FFT_SIZE as f32;
+        for (magnitude, bin) in self.magnitudes.iter_mut().zip(&self.buffer) {
+            *magnitude = 2.0 * bin.norm() / self.window_gain;
+        }
+        let magnitudes = &self.magnitudes;
+
+        for (band, level) in self.bands.iter_mut().enumerate() {
+            // Each band takes the loudest bin between its edges, or the nearest bin if it's narrower than one
+            let low = band_frequency(band as f32) / bin_width;
+            let high = band_frequency(band as f32 + 1.0) / bin_width;
+            let first = (low.round() as usize).min(magnitudes.len() - 1);
+            let last = (high.round() as usize).clamp(first + 1, magnitudes.len());
+            let peak = magnitudes[first..last].iter().copied().fold(0.0, f32::max);
+            *level = gain_to_db(peak).max(FLOOR_DB);
+        }
+        &self.bands
+    }
+}
+
+// Lower edge of a (possibly fractional) band index on the log frequency axis
+pub fn band_frequency(band: f32) -> f32 {
+    MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(band / BANDS as f32)
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::EnvelopeSettings;
+use crate::Waveform;
+
+// Splits the keyboard in two: notes below `point` play the `lower` patch (a bass, say)
+// and the rest play the main one, so each hand has its own sound
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SplitSettings {
+    pub enabled: bool,
+    pub point: u8,        // Lowest note of the upper zone, C4 by default
+    pub lower: ZonePatch, // Sound of the notes below the split point
+}
+
+impl Default for SplitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            point: 60,
+            lower: ZonePatch::default(),
+        }
+    }
+}
+
+impl SplitSettings {
+    pub fn is_lower(&self, note: u8) -> bool {
+        self.enabled && note < self.point
+    }
+}
+
+// What a zone sounds like, in place of the synth's own waveform and envelope
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZonePatch {
+    pub waveform: Waveform,
+    pub envelope: EnvelopeSettings,
+}
+
+impl Default for ZonePatch {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Saw,
+            envelope: EnvelopeSettings::default(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_4;
+
+// One stereo sample pair, [left, right]
+pub type Frame = [f32; 2];
+
+pub const LEFT: usize = 0;
+pub const RIGHT: usize = 1;
+
+// Constant-power pan law: -1.0 is hard left, 0.0 centre, 1.0 hard right.
+// Returns the (left, right) gains.
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4; // 0..π/2
+    (angle.cos(), angle.sin())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoPan {
+    Off,        // Every voice sits at `pan`
+    RoundRobin, // Each new voice takes the next position in a left/right pattern
+    Key,        // Low notes to the left, high notes to the right, like sitting at a piano
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanSettings {
+    pub pan: f32,    // Base position of every voice, -1.0..1.0
+    pub mode: AutoPan,
+    pub spread: f32, // How far auto-pan moves voices away from `pan`, 0.0..1.0
+}
+
+impl Default for PanSettings {
+    fn default() -> Self {
+        Self {
+            pan: 0.0,
+            mode: AutoPan::Off,
+            spread: 0.5,
+        }
+    }
+}
+
+// Positions cycled through by round-robin panning
+const ROUND_ROBIN_POSITIONS: [f32; 6] = [-1.0, 1.0, -0.5, 0.5, -0.25, 0.25];
+// Frequency that sits in the middle of the stereo field for key panning (F#4)
+const KEY_PAN_CENTRE: f32 = 369.99;
+
+// Hands out pan positions to new voices according to the preset's panning settings
+pub struct VoicePanner {
+    settings: PanSettings,
+    round_robin_index: usize,
+}
+
+impl VoicePanner {
+    pub fn new(settings: PanSettings) -> Self {
+        Self {
+            settings,
+            round_robin_index: 0,
+        }
+    }
+
+    pub fn next_pan(&mut self, frequency: f32) -> f32 {
+        let offset = match self.settings.mode {
+            AutoPan::Off => 0.0,
+            AutoPan::RoundRobin => {
+                let position = ROUND_ROBIN_POSITIONS[self.round_robin_index];
+                self.round_robin_index = (self.round_robin_index + 1) % ROUND_ROBIN_POSITIONS.len();
+                position
+            }
+            // Half an octave either side of the centre reaches the edge, so the C4..C5 row spans the field
+            AutoPan::Key => (frequency / KEY_PAN_CENTRE).log2() * 2.0,
+        };
+        (self.settings.pan + offset.clamp(-1.0, 1.0) * self.settings.spread).clamp(-1.0, 1.0)
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+use crate::biquad::Biquad;
+use crate::stereo::{Frame, LEFT, RIGHT};
+use crate::tempo::Rate;
+
+pub const MAX_CHANNELS: usize = 6;
+const LFE_CUTOFF: f32 = 120.0; // Hz, what the subwoofer is given of the whole mix
+
+// One sample for each speaker, in the layout's channel order; unused channels stay silent
+pub type SurroundFrame = [f32; MAX_CHANNELS];
+
+// Speaker layouts beyond a stereo pair, for interfaces with more outputs. Channels come
+// in the order WAV files and most interfaces use: front left and right first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    #[default]
+    Stereo,
+    Quad, // Front left, front right, rear left, rear right
+    #[value(name = "5.1")]
+    FivePointOne, // Front left, front right, centre, LFE, surround left, surround right
+}
+
+impl Layout {
+    pub fn channels(self) -> usize {
+        match self {
+            Layout::Stereo => 2,
+            Layout::Quad => 4,
+            Layout::FivePointOne => 6,
+        }
+    }
+
+    // The speakers voices are placed between, as (channel, azimuth) in turns clockwise
+    // from straight ahead, sorted by azimuth. The LFE channel isn't one of them.
+    fn speakers(self) -> &'static [(usize, f32)] {
+        match self {
+            Layout::Stereo => &[(RIGHT, 0.125), (LEFT, 0.875)],
+            Layout::Quad => &[(1, 0.125), (3, 0.375), (2, 0.625), (0, 0.875)],
+            Layout::FivePointOne => &[(2, 0.0), (1, 30.0 / 360.0), (5, 110.0 / 360.0), (4, 250.0 / 360.0), (0, 330.0 / 360.0)],
+        }
+    }
+
+    fn lfe(self) -> Option<usize> {
+        match self {
+            Layout::FivePointOne => Some(3),
+            _ => None,
+        }
+    }
+}
+
+// How voices are spread round the room when playing in surround (`--surround`). Each
+// voice goes where its stereo pan would put it, carried further round by `spread`, and
+// the whole field can turn slowly for sound that circles the listener.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SurroundSettings {
+    pub spread: f32,    // 0.25 keeps hard-panned voices at the front corners, 1.0 takes them right round behind
+    pub rotation: Rate, // How often the field turns once round the room, clockwise; 0 Hz holds it still
+}
+
+impl Default for SurroundSettings {
+    fn default() -> Self {
+        Self {
+            spread: 0.25,
+            rotation: Rate::Hz(0.0),
+        }
+    }
+}
+
+impl SurroundSettings {
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "spread" => self.spread = value.clamp(0.0, 1.0),
+            "rotation" => self.rotation = Rate::Hz(value.clamp(-10.0, 10.0)),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Places each voice between the pair of speakers either side of it with a constant-power
+// law, the way a stereo pan places it between two. Whatever the master chain adds to the
+// voices comes from the front pair, and the LFE channel gets the low end of it all.
+pub struct Surround {
+    layout: Layout,
+    settings: SurroundSettings,
+    rotation: f32, // The field's turn so far, 0.0..1.0
+    rotation_step: f32,
+    lfe: Biquad,
+    voices: SurroundFrame, // This frame's voices, as they're placed
+    output: SurroundFrame,
+    sample_rate: u32,
+}
+
+impl Surround {
+    pub fn new(settings: SurroundSettings, tempo: f32, sample_rate: u32) -> Self {
+        let mut surround = Self {
+            layout: Layout::Stereo,
+            settings,
+            rotation: 0.0,
+            rotation_step: 0.0,
+            lfe: Biquad::low_pass(LFE_CUTOFF, std::f32::consts::FRAC_1_SQRT_2, sample_rate),
+            voices: [0.0; MAX_CHANNELS],
+            output: [0.0; MAX_CHANNELS],
+            sample_rate,
+        };
+        surround.set_tempo(tempo);
+        surround
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    // Whether voices are being placed at all; in stereo the synth's own mix is the output
+    pub fn is_active(&self) -> bool {
+        self.layout != Layout::Stereo
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32, tempo: f32) -> bool {
+        let known = self.settings.set_param(name, value);
+        self.set_tempo(tempo);
+        known
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.rotation_step = self.settings.rotation.hz(tempo) / self.sample_rate as f32;
+    }
+
+    // Adds a voice's `sample` to this frame, at its stereo `pan` (-1.0..1.0)
+    pub fn place(&mut self, sample: f32, pan: f32) {
+        let azimuth = (pan.clamp(-1.0, 1.0) * self.settings.spread * 0.5 + self.rotation).rem_euclid(1.0);
+        let speakers = self.layout.speakers();
+        let next = speakers.iter().position(|&(_, at)| at > azimuth).unwrap_or(0);
+        let (from, at) = speakers[(next + speakers.len() - 1) % speakers.len()];
+        let (to, until) = speakers[next];
+        let angle = (azimuth - at).rem_euclid(1.0) / (until - at).rem_euclid(1.0) * FRAC_PI_2;
+        self.voices[from] += sample * angle.cos();
+        self.voices[to] += sample * angle.sin();
+    }
+
+    // Finishes the frame: the voices placed so far at `gain`, with `front` on the front
+    // pair, then `master` and the clamp to [-1.0, 1.0] the stereo output gets
+    pub fn mix(&mut self, gain: f32, front: Frame, master: f32) {
+        let mut output = self.voices.map(|sample| sample * gain);
+        output[LEFT] += front[LEFT];
+        output[RIGHT] += front[RIGHT];
+        if let Some(lfe) = self.layout.lfe() {
+            output[lfe] = self.lfe.process(output.iter().sum::<f32>() * 0.5);
+        }
+        self.output = output.map(|sample| (sample * master).clamp(-1.0, 1.0));
+        self.voices = [0.0; MAX_CHANNELS];
+        self.rotation = (self.rotation + self.rotation_step).rem_euclid(1.0);
+    }
+
+    // Fades the last frame by `gain`, for the fade-out on quitting
+    pub fn fade(&mut self, gain: f32) {
+        self.output = self.output.map(|sample| sample * gain);
+    }
+
+    // The frame last mixed, a sample per channel of the layout
+    pub fn output(&self) -> &[f32] {
+        &self.output[..self.layout.channels()]
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::effects::Effect;
+use crate::preset::Preset;
+use crate::stereo::Frame;
+use crate::{surround_front, SynthCommand, Synthesizer, FADE_OUT_SECONDS};
+
+pub const KEEP_SECONDS: f32 = 10.0; // Longest a preset switched away from with `Keep` holds its notes before releasing them
+const KEPT_PRESETS: usize = 2; // Presets switched away from that play on at once; older ones fade out
+pub(crate) const MAX_RETIRING: usize = KEPT_PRESETS + 6; // Room for those and the ones still fading out, set aside up front
+pub(crate) const MAX_OUTGOING: usize = 8; // Crossfades that can overlap, set aside up front the same way
+const RETIRED_LEVEL: f32 = 1e-4; // -80 dB, below which a preset switched away from counts as silent
+
+// How a preset switch treats the notes still sounding
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PresetSwitch {
+    #[default]
+    Cut,       // Held notes ring on through the new preset's shaping and effects at once
+    Keep,      // Held notes finish on the old preset, effects and all, for up to `KEEP_SECONDS`; new notes play the new one
+    Crossfade, // Held notes carry on, and the output fades from the old effects to the new
+}
+
+// The effects of a preset being switched away from, fading out (see `crossfade_preset`).
+// Only the old synth's effects, send buses and master chain still run.
+pub(crate) struct Outgoing {
+    pub synth: Box<Synthesizer>,
+    pub home: Option<BuiltPreset>, // Where it's left to be dropped once faded (see `BuiltPreset::give_back`)
+    pub gain: f32, // 1.0 down to 0.0
+    pub step: f32, // Per frame
+}
+
+impl Outgoing {
+    // The chain's output at its current gain, which then moves down a step. In surround,
+    // given the live input in `frame`, it's only what goes to the front (see `surround_front`).
+    pub fn process(&mut self, frame: Frame, surround: Option<Frame>) -> Frame {
+        let synth = &mut *self.synth;
+        let mut effected = match surround {
+            Some(input) => surround_front(&mut synth.effects, frame, input),
+            None => synth.effects.process(frame),
+        };
+        for bus in &mut synth.sends {
+            let frame = bus.process(0.0); // Sent nothing more, their tails ring out under the fade
+            effected = [effected[0] + frame[0], effected[1] + frame[1]];
+        }
+        let output = synth.dc_blocker.process(synth.eq.process(synth.widener.process(effected)));
+        let gain = self.gain;
+        self.gain = (self.gain - self.step).max(0.0);
+        output.map(|sample| sample * gain)
+    }
+}
+
+// A preset switched away from with `Keep`, playing out its notes through its own effects.
+// Notes still held after `KEEP_SECONDS` are released, so a drone can't keep a whole synth
+// running, and it's dropped once it has been silent for a second.
+pub(crate) struct Retiring {
+    pub synth: Box<Synthesizer>,
+    pub home: Option<BuiltPreset>, // Where it's left to be dropped once done (see `BuiltPreset::give_back`)
+    held: u32,   // Frames left before its notes are released
+    silent: u32, // Frames it has been silent for
+    gain: f32,
+    step: f32, // Per frame, once it's fading out to make room
+}
+
+impl Retiring {
+    pub fn new(synth: Box<Synthesizer>, home: Option<BuiltPreset>, sample_rate: u32) -> Self {
+        let held = (KEEP_SECONDS * sample_rate as f32) as u32;
+        Self { synth, home, held, silent: 0, gain: 1.0, step: 0.0 }
+    }
+
+    // Keeps the latest `KEPT_PRESETS` of `retiring` playing and fades out the rest
+    pub fn make_room(retiring: &mut [Retiring], sample_rate: u32) {
+        let kept = retiring.iter().filter(|old| old.step == 0.0).count();
+        for old in retiring.iter_mut().filter(|old| old.step == 0.0).take(kept.saturating_sub(KEPT_PRESETS)) {
+            old.step = 1.0 / (FADE_OUT_SECONDS * sample_rate as f32);
+        }
+    }
+
+    pub fn render_frame(&mut self) -> Frame {
+        if self.held > 0 {
+            self.held -= 1;
+            if self.held == 0 {
+                self.synth.release_all();
+            }
+        }
+        let frame = self.synth.render_frame();
+        self.silent = if frame.iter().any(|sample| sample.abs() > RETIRED_LEVEL) { 0 } else { self.silent + 1 };
+        let gain = self.gain;
+        self.gain = (self.gain - self.step).max(0.0);
+        frame.map(|sample| sample * gain)
+    }
+
+    pub fn is_done(&self, sample_rate: u32) -> bool {
+        self.gain == 0.0 || self.silent >= sample_rate
+    }
+}
+
+// Which preset command a `BuiltPreset` was built for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuiltSwitch {
+    Load,
+    Crossfade(f32), // Seconds
+    Queue,
+}
+
+type Slot = Arc<Mutex<Option<Box<Synthesizer>>>>;
+
+// A preset already built into the synth that plays it, so switching to it on the audio
+// thread is only a swap. Clones share the one synth, which goes to whichever switches
+// first; the synth switched away from is left in its place to be dropped by the builder.
+#[derive(Clone)]
+pub struct BuiltPreset {
+    pub switch: BuiltSwitch,
+    synth: Slot,
+}
+
+impl BuiltPreset {
+    // Made with the `polyphony` of the synth it's for, so switching doesn't resize the voices
+    pub fn new(sample_rate: u32, polyphony: usize, preset: &Preset, switch: BuiltSwitch) -> Self {
+        let synth = Synthesizer::new(sample_rate, preset, mpsc::channel().1).with_polyphony(polyphony);
+        Self { switch, synth: Arc::new(Mutex::new(Some(Box::new(synth)))) }
+    }
+
+    // The synth to switch to, unless it has been taken already
+    pub(crate) fn take(&self) -> Option<Box<Synthesizer>> {
+        self.synth.try_lock().ok()?.take()
+    }
+
+    // Leaves the synth switched away from for the builder to drop, so what it holds (a
+    // plugin, say) is torn down off the audio thread
+    pub(crate) fn give_back(&self, old: Box<Synthesizer>) {
+        if let Ok(mut slot) = self.synth.try_lock() {
+            *slot = Some(old);
+        }
+    }
+}
+
+impl std::fmt::Debug for BuiltPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("BuiltPreset").field("switch", &self.switch).finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for BuiltPreset {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.synth, &other.synth)
+    }
+}
+
+// Passes `commands` on to the synth through a thread that builds the presets they switch
+// to on the way, so the audio thread never has to. It also drops the synths switched
+// away from, once the audio thread has let go of them.
+pub fn build_presets(commands: mpsc::Receiver<SynthCommand>, sample_rate: u32, polyphony: usize) -> mpsc::Receiver<SynthCommand> {
+    let (output, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut sent: Vec<Slot> = Vec::new();
+        loop {
+            let command = match commands.recv_timeout(Duration::from_millis(500)) {
+                Ok(command) => Some(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            };
+            sent.retain(|slot| Arc::strong_count(slot) > 1); // The rest hold nothing or an old synth
+            if let Some(command) = command {
+                // A timed command goes as its frame and then the command itself, so the
+                // synth has no box to free when it takes the command out
+                let (frame, command) = build(command, sample_rate, polyphony, &mut sent);
+                if frame.is_some_and(|frame| output.send(SynthCommand::AtNext(frame)).is_err()) || output.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+// The command with its presets built, and the frame it's timed at if it came in an `At`
+fn build(command: SynthCommand, sample_rate: u32, polyphony: usize, sent: &mut Vec<Slot>) -> (Option<u64>, SynthCommand) {
+    let (preset, switch) = match command {
+        SynthCommand::LoadPreset(preset) => (preset, BuiltSwitch::Load),
+        SynthCommand::CrossfadePreset(preset, seconds) => (preset, BuiltSwitch::Crossfade(seconds)),
+        SynthCommand::QueuePreset(preset) => (preset, BuiltSwitch::Queue),
+        SynthCommand::At(frame, command) => {
+            // An `At` inside another waits for whichever frame is later, as the synth would
+            let (inner, command) = build(*command, sample_rate, polyphony, sent);
+            return (Some(inner.map_or(frame, |inner| inner.max(frame))), command);
+        }
+        command => return (None, command),
+    };
+    let built = BuiltPreset::new(sample_rate, polyphony, &preset, switch);
+    sent.push(built.synth.clone());
+    (None, SynthCommand::Built(built))
+}
@@ -0,0 +1,35 @@
+use std::time::{Duration, Instant};
+
+const MAX_TAPS: usize = 5; // Average over the last four intervals
+const RESET_AFTER: Duration = Duration::from_secs(2); // A long pause starts a new measurement
+const MIN_TEMPO: f32 = 30.0;
+const MAX_TEMPO: f32 = 300.0;
+
+// Measures the tempo from the spacing of repeated presses of the tap key
+#[derive(Default)]
+pub struct TapTempo {
+    taps: Vec<Instant>,
+}
+
+impl TapTempo {
+    // Registers a tap and returns the new tempo once there are at least two taps
+    pub fn tap(&mut self, now: Instant) -> Option<f32> {
+        if let Some(&last) = self.taps.last() {
+            if now.duration_since(last) > RESET_AFTER {
+                self.taps.clear();
+            }
+        }
+        if self.taps.len() == MAX_TAPS {
+            self.taps.remove(0);
+        }
+        self.taps.push(now);
+
+        let (first, last) = (self.taps.first()?, self.taps.last()?);
+        let intervals = self.taps.len() as f32 - 1.0;
+        if intervals < 1.0 {
+            return None;
+        }
+        let average = last.duration_since(*first).as_secs_f32() / intervals;
+        Some((60.0 / average).clamp(MIN_TEMPO, MAX_TEMPO))
+    }
+}
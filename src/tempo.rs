@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TEMPO: f32 = 120.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Feel {
+    Straight,
+    Triplet,
+    Dotted,
+}
+
+// A musical note length such as "1/4", "1/8T" (triplet) or "1/16D" (dotted).
+// Whole multiples like "2/1" are allowed for slow, multi-bar sweeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NoteDivision {
+    pub numerator: u32,
+    pub denominator: u32,
+    pub feel: Feel,
+}
+
+impl NoteDivision {
+    pub const QUARTER: NoteDivision = NoteDivision { numerator: 1, denominator: 4, feel: Feel::Straight };
+    pub const SIXTEENTH: NoteDivision = NoteDivision { numerator: 1, denominator: 16, feel: Feel::Straight };
+
+    // Length of the division measured in quarter-note beats
+    pub fn beats(&self) -> f32 {
+        let straight = 4.0 * self.numerator as f32 / self.denominator as f32;
+        match self.feel {
+            Feel::Straight => straight,
+            Feel::Triplet => straight * 2.0 / 3.0,
+            Feel::Dotted => straight * 1.5,
+        }
+    }
+
+    pub fn seconds(&self, tempo: f32) -> f32 {
+        self.beats() * 60.0 / tempo
+    }
+
+    // How many times per second this division repeats at the given tempo
+    pub fn hz(&self, tempo: f32) -> f32 {
+        1.0 / self.seconds(tempo)
+    }
+}
+
+impl FromStr for NoteDivision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (body, feel) = match s.chars().last() {
+            Some('T') | Some('t') => (&s[..s.len() - 1], Feel::Triplet),
+            Some('D') | Some('d') | Some('.') => (&s[..s.len() - 1], Feel::Dotted),
+            _ => (s, Feel::Straight),
+        };
+        let (numerator, denominator) = body
+            .split_once('/')
+            .ok_or_else(|| format!("note division '{}' should look like 1/4, 1/8T or 1/16D", s))?;
+        let numerator: u32 = numerator.trim().parse().map_err(|_| format!("bad numerator in '{}'", s))?;
+        let denominator: u32 = denominator.trim().parse().map_err(|_| format!("bad denominator in '{}'", s))?;
+        if numerator == 0 || denominator == 0 {
+            return Err(format!("note division '{}' must be non-zero", s));
+        }
+        Ok(NoteDivision { numerator, denominator, feel })
+    }
+}
+
+impl TryFrom<String> for NoteDivision {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<NoteDivision> for String {
+    fn from(division: NoteDivision) -> Self {
+        division.to_string()
+    }
+}
+
+impl fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffix = match self.feel {
+            Feel::Straight => "",
+            Feel::Triplet => "T",
+            Feel::Dotted => "D",
+        };
+        write!(f, "{}/{}{}", self.numerator, self.denominator, suffix)
+    }
+}
+
+// A modulation rate given either in Hz or as a note division locked to the tempo.
+// In presets this is written as `rate = 0.5` or `rate = "1/8T"`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Rate {
+    Hz(f32),
+    Synced(NoteDivision),
+}
+
+impl Rate {
+    pub fn hz(&self, tempo: f32) -> f32 {
+        match self {
+            Rate::Hz(hz) => *hz,
+            Rate::Synced(division) => division.hz(tempo),
+        }
+    }
+
+    // The rate as a parameter called `name`, for the settings' `params`. A tempo-synced
+    // rate isn't a plain number, so it isn't offered for editing.
+    pub fn editable_param(&self, name: &'static str) -> Option<(&'static str, f32)> {
+        match self {
+            Rate::Hz(hz) => Some((name, *hz)),
+            Rate::Synced(_) => None,
+        }
+    }
+}
+
+// The global tempo, readable from any thread without locking. Clock threads
+// (arpeggiator, sequencer) read it every step, so changes apply on the next step.
+// While something outside sets where the beats fall (an Ableton Link session), they
+// also take their steps on its beats.
+#[derive(Clone)]
+pub struct SharedTempo {
+    bpm: Arc<AtomicU32>,
+    grid: Arc<Mutex<Option<BeatGrid>>>,
+}
+
+// Where the beats fall: beat `beat` at `at`, and on at `tempo`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatGrid {
+    pub at: Instant,
+    pub beat: f64,
+    pub tempo: f64,
+}
+
+impl BeatGrid {
+    pub fn beat_at(&self, instant: Instant) -> f64 {
+        let seconds = match instant.checked_duration_since(self.at) {
+            Some(after) => after.as_secs_f64(),
+            None => -self.at.duration_since(instant).as_secs_f64(),
+        };
+        self.beat + seconds * self.tempo / 60.0
+    }
+
+    pub fn instant_of(&self, beat: f64) -> Instant {
+        let seconds = (beat - self.beat) * 60.0 / self.tempo;
+        if seconds >= 0.0 {
+            self.at + Duration::from_secs_f64(seconds)
+        } else {
+            self.at - Duration::from_secs_f64(-seconds)
+        }
+    }
+
+    // The first multiple of `beats` after `instant`, as a step that's a hair early
+    // still counts as on its beat
+    fn next(&self, instant: Instant, beats: f32) -> Instant {
+        let beats = beats as f64;
+        self.instant_of(((self.beat_at(instant) / beats + 1e-3).floor() + 1.0) * beats)
+    }
+}
+
+impl SharedTempo {
+    pub fn new(tempo: f32) -> Self {
+        Self { bpm: Arc::new(AtomicU32::new(tempo.to_bits())), grid: Arc::default() }
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.bpm.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, tempo: f32) {
+        self.bpm.store(tempo.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_grid(&self, grid: Option<BeatGrid>) {
+        *self.grid.lock().unwrap() = grid;
+    }
+
+    // When a clock that starts at `now` takes its first step: at once, or with a grid, on
+    // its next multiple of `beats`, the length of a step or of a pattern
+    pub fn first_step(&self, now: Instant, beats: f32) -> Instant {
+        match *self.grid.lock().unwrap() {
+            Some(grid) => grid.next(now, beats),
+            None => now,
+        }
+    }
+
+    // When the step after one taken at `step` falls, `beats` on
+    pub fn step_after(&self, step: Instant, beats: f32) -> Instant {
+        match *self.grid.lock().unwrap() {
+            Some(grid) => grid.next(step, beats),
+            None => step + Duration::from_secs_f32(beats * 60.0 / self.get()),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use device_query::Keycode;
+use ratatui::crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, Gauge, GraphType, List, ListItem, ListState, Paragraph};
+use ratatui::DefaultTerminal;
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::cpu_meter::CpuMeter;
+use crate::effects::gain_to_db;
+use crate::keymap::{key_label, KeyMap, CELL};
+use crate::notes::{note_name, NoteSet};
+use crate::params::ParamStore;
+use crate::preset::Preset;
+use crate::scope::{ScopeTap, SCOPE_WIDTH};
+use crate::shutdown::Shutdown;
+use crate::snapshot::{Snapshot, SNAPSHOTS_PER_SECOND};
+use crate::spectrum::{Spectrum, BANDS, FLOOR_DB};
+use crate::stereo::{Frame, LEFT, RIGHT};
+use crate::xrun::XrunMonitor;
+
+const METER_FALLOFF: f32 = 0.85; // Per-redraw decay of the level meters, so peaks stay readable
+
+// Terminal front end: held notes, the key map with the keys of sounding notes lit, output meters, the preset name and a list of
+// parameters that can be edited while playing. Notes are still played with the
+// keyboard as before; the TUI only takes its own keys (arrows, F1-F12, -, =, Esc)
+// and the scroll wheel, which adjusts the selected parameter.
+struct App {
+    preset_name: String,
+    params: ParamStore,
+    values: Vec<(String, f32)>, // Copy of the store's values for drawing
+    selected: ListState,
+    notes: NoteSet,
+    key_rows: Vec<Vec<(usize, Keycode, u8)>>, // The key map as laid out on the keyboard
+    levels: Frame, // Peak meters with a slow falloff
+    rms: Frame,
+    clips: u64,
+    xruns: XrunMonitor,
+    cpu: CpuMeter,
+    scope: ScopeTap,
+    spectrum: Spectrum,
+    shutdown: Shutdown,
+}
+
+// Runs the UI on the calling thread until Esc is pressed or a shutdown is requested
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    preset: &Preset,
+    params: ParamStore,
+    snapshots: mpsc::Receiver<Snapshot>,
+    scope: ScopeTap,
+    keymap: KeyMap,
+    xruns: XrunMonitor,
+    cpu: CpuMeter,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    let mut app = App {
+        preset_name: if preset.name.is_empty() { "(unnamed)".to_string() } else { preset.name.clone() },
+        values: params.values(),
+        params,
+        selected: ListState::default().with_selected(Some(0)),
+        notes: NoteSet::default(),
+        key_rows: keymap.rows(),
+        levels: [0.0; 2],
+        rms: [0.0; 2],
+        clips: 0,
+        xruns,
+        cpu,
+        spectrum: Spectrum::new(scope.sample_rate()),
+        scope,
+        shutdown,
+    };
+
+    let mut terminal = ratatui::init();
+    execute!(io::stdout(), EnableMouseCapture)?;
+    let result = app.run(&mut terminal, &snapshots);
+    execute!(io::stdout(), DisableMouseCapture)?;
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal, snapshots: &mpsc::Receiver<Snapshot>) -> io::Result<()> {
+        let frame_time = Duration::from_millis(1000 / SNAPSHOTS_PER_SECOND as u64);
+        while !self.shutdown.requested() {
+            self.levels = self.levels.map(|level| level * METER_FALLOFF);
+            while let Ok(snapshot) = snapshots.try_recv() {
+                self.notes = snapshot.notes;
+                self.levels[LEFT] = self.levels[LEFT].max(snapshot.peak[LEFT]);
+                self.levels[RIGHT] = self.levels[RIGHT].max(snapshot.peak[RIGHT]);
+                self.rms = snapshot.rms;
+                self.clips = snapshot.clips;
+            }
+            self.values = self.params.values();
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(frame_time)? {
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Mouse(mouse) => {
+                    match mouse.kind {
+                        MouseEventKind::ScrollUp => self.adjust(1.0),
+                        MouseEventKind::ScrollDown => self.adjust(-1.0),
+                        _ => {}
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Up => self.selected.select_previous(),
+                KeyCode::Down => self.selected.select_next(),
+                KeyCode::F(number) if (number as usize) <= self.values.len() => self.selected.select(Some(number as usize - 1)),
+                KeyCode::Char('-') => self.adjust(-1.0),
+                KeyCode::Char('=') | KeyCode::Char('+') => self.adjust(1.0),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()), // Raw mode swallows the signal
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Nudges the selected parameter up or down
+    fn adjust(&mut self, direction: f32) {
+        let Some(index) = self.selected.selected() else { return };
+        let Some((path, _)) = self.values.get(index) else { return };
+        self.params.nudge(path, direction);
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame) {
+        let [header, notes, keys, left, right, scope, spectrum, params, help] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(self.key_rows.len() as u16 * 2 + 2),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let clips = if self.clips > 0 {
+            format!("CLIPPED {} samples", self.clips)
+        } else {
+            "No clipping".to_string()
+        };
+        let cpu_style = if self.cpu.is_high() { Style::new().fg(Color::Red) } else { Style::new() };
+        let title = Block::bordered()
+            .title("Preset")
+            .title_bottom(clips)
+            .title_bottom(self.xruns.summary())
+            .title_bottom(Line::styled(self.cpu.summary(), cpu_style));
+        frame.render_widget(Paragraph::new(self.preset_name.as_str()).block(title), header);
+
+        let held: Vec<String> = self.notes.iter().map(note_name).collect();
+        frame.render_widget(Paragraph::new(held.join(" ")).block(Block::bordered().title("Notes")), notes);
+
+        // Each row of keys over the notes they play, lit while the note sounds
+        let mut lines = Vec::new();
+        for row in &self.key_rows {
+            let (mut keys_line, mut notes_line, mut width) = (Vec::new(), Vec::new(), 0);
+            for &(column, key, note) in row {
+                let style = if self.notes.contains(note) { Style::new().add_modifier(Modifier::REVERSED) } else { Style::new() };
+                let padding = " ".repeat(column.saturating_sub(width));
+                keys_line.extend([Span::raw(padding.clone()), Span::styled(format!("{:<3}", key_label(key)), style), Span::raw(" ")]);
+                notes_line.extend([Span::raw(padding), Span::styled(format!("{:<3}", note_name(note)), style), Span::raw(" ")]);
+                width = column.max(width) + CELL;
+            }
+            lines.extend([Line::from(keys_line), Line::from(notes_line)]);
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title("Keys")), keys);
+
+        frame.render_widget(meter("L", self.levels[LEFT], self.rms[LEFT]), left);
+        frame.render_widget(meter("R", self.levels[RIGHT], self.rms[RIGHT]), right);
+
+        let points: Vec<(f64, f64)> = self
+            .scope
+            .triggered()
+            .iter()
+            .enumerate()
+            .map(|(i, &sample)| (i as f64, sample as f64))
+            .collect();
+        let trace = Dataset::default().marker(Marker::Braille).graph_type(GraphType::Line).data(&points);
+        let chart = Chart::new(vec![trace])
+            .block(Block::bordered().title("Scope"))
+            .x_axis(Axis::default().bounds([0.0, SCOPE_WIDTH as f64]))
+            .y_axis(Axis::default().bounds([-1.0, 1.0]));
+        frame.render_widget(chart, scope);
+
+        let points: Vec<(f64, f64)> = self
+            .spectrum
+            .bands(&self.scope)
+            .iter()
+            .enumerate()
+            .map(|(band, &level)| (band as f64, level as f64))
+            .collect();
+        let trace = Dataset::default().marker(Marker::Braille).graph_type(GraphType::Line).data(&points);
+        let chart = Chart::new(vec![trace])
+            .block(Block::bordered().title("Spectrum"))
+            .x_axis(Axis::default().bounds([0.0, (BANDS - 1) as f64]).labels(["20 Hz", "200 Hz", "2 kHz", "20 kHz"]))
+            .y_axis(Axis::default().bounds([FLOOR_DB as f64, 0.0]).labels(["-90 dB", "-45 dB", "0 dB"]));
+        frame.render_widget(chart, spectrum);
+
+        let items: Vec<ListItem> = self
+            .values
+            .iter()
+            .map(|(path, value)| ListItem::new(format!("{:<32}{:>10.3}", path, value)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Parameters"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, params, &mut self.selected);
+
+        frame.render_widget(Paragraph::new("Up/Down/F1-F12 select   - / = / wheel adjust   Esc quit"), help);
+    }
+}
+
+fn meter(label: &str, peak: f32, rms: f32) -> Gauge<'static> {
+    let color = if peak >= 1.0 { Color::Red } else { Color::Green };
+    Gauge::default()
+        .ratio(peak.clamp(0.0, 1.0) as f64)
+        .label(format!("{} peak {:6.1} dB  rms {:6.1} dB", label, gain_to_db(peak), gain_to_db(rms)))
+        .gauge_style(Style::new().fg(color))
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::preset::Preset;
+
+const DEPTH: usize = 100; // Edits kept to undo; the oldest go first
+const MERGE: Duration = Duration::from_secs(1); // Moves of one parameter closer together than this undo as one
+
+// One step of the history
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Params(Vec<(String, f32, f32)>), // Each parameter's path, value before and value after
+    Preset {
+        from: Box<Preset>,
+        from_values: Vec<(String, f32)>, // The edited values of `from` when it was left
+        to: Box<Preset>,
+    },
+}
+
+// Undo and redo stacks of parameter edits and preset loads. A slider drag or a held
+// key moves a parameter many times a second, so moves of the same parameter that
+// follow each other closely are merged into one edit.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    last: Option<Instant>, // When the newest edit was last added to
+    preset: Option<Box<Preset>>, // The preset playing, for undoing the next load
+}
+
+impl History {
+    pub fn new(preset: &Preset) -> Self {
+        Self { preset: Some(Box::new(preset.clone())), ..Self::default() }
+    }
+
+    // The preset playing (or queued), as last loaded
+    pub fn current(&self) -> Option<&Preset> {
+        self.preset.as_deref()
+    }
+
+    pub fn params(&mut self, changes: Vec<(String, f32, f32)>) {
+        let now = Instant::now();
+        let recent = self.last.is_some_and(|last| now.duration_since(last) < MERGE);
+        self.last = Some(now);
+        match (self.undo.back_mut(), changes.as_slice()) {
+            (Some(Edit::Params(edit)), [(path, _, to)]) if recent && edit.len() == 1 && edit[0].0 == *path => {
+                edit[0].2 = *to;
+                self.redo.clear();
+            }
+            _ if !changes.is_empty() => self.push(Edit::Params(changes)),
+            _ => {}
+        }
+    }
+
+    // Records a switch to `preset`, from whatever was playing with `values`; the first
+    // switch can't be undone when the history wasn't made with the starting preset
+    pub fn preset(&mut self, preset: &Preset, values: Vec<(String, f32)>) {
+        let to = Box::new(preset.clone());
+        if let Some(from) = self.preset.replace(to.clone()) {
+            self.push(Edit::Preset { from, from_values: values, to });
+        }
+        self.last = None;
+    }
+
+    fn push(&mut self, edit: Edit) {
+        if self.undo.len() == DEPTH {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+        self.redo.clear();
+    }
+
+    // The edit to take back, which then waits to be redone
+    pub fn undo(&mut self) -> Option<Edit> {
+        let edit = self.undo.pop_back()?;
+        if let Edit::Preset { from, .. } = &edit {
+            self.preset = Some(from.clone());
+        }
+        self.redo.push(edit.clone());
+        self.last = None;
+        Some(edit)
+    }
+
+    pub fn redo(&mut self) -> Option<Edit> {
+        let edit = self.redo.pop()?;
+        if let Edit::Preset { to, .. } = &edit {
+            self.preset = Some(to.clone());
+        }
+        self.undo.push_back(edit.clone());
+        self.last = None;
+        Some(edit)
+    }
+}
@@ -0,0 +1,31 @@
+use device_query::Keycode;
+
+// The keyboard has no velocity, so the modifier keys pick one of three loudness layers
+// for the notes pressed while they are held: Ctrl plays soft, Shift plays loud
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VelocityLayer {
+    Soft,
+    Medium,
+    Loud,
+}
+
+impl VelocityLayer {
+    pub fn from_keys(keys: &[Keycode]) -> Self {
+        let held = |a, b| keys.contains(&a) || keys.contains(&b);
+        if held(Keycode::LShift, Keycode::RShift) {
+            VelocityLayer::Loud
+        } else if held(Keycode::LControl, Keycode::RControl) {
+            VelocityLayer::Soft
+        } else {
+            VelocityLayer::Medium
+        }
+    }
+
+    pub fn velocity(self) -> f32 {
+        match self {
+            VelocityLayer::Soft => 0.4,
+            VelocityLayer::Medium => 0.7, // Leaves room above for accents
+            VelocityLayer::Loud => 1.0,
+        }
+    }
+}
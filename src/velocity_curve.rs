@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VelocityCurve {
+    Linear, // As played
+    Soft,   // Light playing comes out louder, for stiff controllers
+    Hard,   // It takes a firm touch to play loud, for light ones
+    Fixed,  // Every note at the same level, whatever the touch
+    Custom, // Follows the preset's own table
+}
+
+// How hard a note is played turns into how loud it sounds. Applies to every velocity the
+// synth is given, from MIDI, the keyboard's layers, scores and the rest, before it sets
+// the voices' levels.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VelocitySettings {
+    pub curve: VelocityCurve,
+    pub fixed: f32,      // The level of every note with the fixed curve
+    pub table: Vec<f32>, // Levels for velocities spread evenly from 0.0 to 1.0, joined by straight lines, for the custom curve
+}
+
+impl Default for VelocitySettings {
+    fn default() -> Self {
+        Self {
+            curve: VelocityCurve::Linear,
+            fixed: 0.8,
+            table: vec![0.0, 0.5, 0.75, 0.9, 1.0],
+        }
+    }
+}
+
+impl VelocitySettings {
+    // The level for a note played at `velocity` (0.0..1.0)
+    pub fn apply(&self, velocity: f32) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+        let level = match self.curve {
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Soft => velocity.sqrt(),
+            VelocityCurve::Hard => velocity * velocity,
+            VelocityCurve::Fixed => self.fixed,
+            VelocityCurve::Custom => match self.table.len() {
+                0 => velocity,
+                1 => self.table[0],
+                points => {
+                    let position = velocity * (points - 1) as f32;
+                    let index = (position as usize).min(points - 2);
+                    let amount = position - index as f32;
+                    self.table[index] + (self.table[index + 1] - self.table[index]) * amount
+                }
+            },
+        };
+        level.clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tempo::Rate;
+
+// Vibrato on every voice, all moving together as a player's finger would move them. It
+// is off until something sets its depth, the mod wheel by default (see `ControllerSettings`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VibratoSettings {
+    pub depth: f32, // Cents either way, 0.0 for none
+    pub rate: Rate, // In Hz or as a tempo division
+}
+
+impl Default for VibratoSettings {
+    fn default() -> Self {
+        Self {
+            depth: 0.0,
+            rate: Rate::Hz(5.5),
+        }
+    }
+}
+
+impl VibratoSettings {
+    // The settings the synth's "vibrato.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params = vec![("depth", self.depth)];
+        params.extend(self.rate.editable_param("rate"));
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "depth" => self.depth = value.clamp(0.0, 100.0),
+            "rate" => self.rate = Rate::Hz(value.max(0.0)),
+            _ => return false,
+        }
+        true
+    }
+
+    // The pitch ratio with the vibrato's LFO at `value` (-1.0..1.0)
+    pub fn ratio(&self, value: f32) -> f32 {
+        2.0_f32.powf(self.depth * value / 1200.0)
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::biquad::Biquad;
+use crate::stereo::Frame;
+
+const LOWEST_BAND: f32 = 120.0; // Hz, centre of the first band
+const HIGHEST_BAND: f32 = 7000.0; // Centre of the last, below which the consonants still come through
+const ATTACK_MS: f32 = 2.0; // How fast a band opens, quick enough for consonants
+const MAKEUP: f32 = 24.0; // Gain that brings a vocoded voice back to about the level of a plain one
+
+// A channel vocoder that lets live input (`--input`) speak through the voices. The input
+// is split into bands spaced evenly in pitch, and how loud each band is sets the level
+// of the same band of the synth. The formant shift moves the synth's bands against the
+// input's, up for a smaller sounding voice and down for a larger one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VocoderSettings {
+    pub enabled: bool,
+    pub bands: usize,       // 4 to 32; more sound clearer, fewer more robotic
+    pub formant_shift: f32, // Semitones, -12.0..12.0
+    pub release: f32,       // Milliseconds a band takes to close after the input goes quiet
+    pub mix: f32,           // 0.0 is the plain synth, 1.0 only the vocoded sound
+}
+
+impl Default for VocoderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bands: 16,
+            formant_shift: 0.0,
+            release: 30.0,
+            mix: 1.0,
+        }
+    }
+}
+
+impl VocoderSettings {
+    // The settings the synth's "vocoder.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        vec![("formant_shift", self.formant_shift), ("release", self.release), ("mix", self.mix)]
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "formant_shift" => self.formant_shift = value.clamp(-12.0, 12.0),
+            "release" => self.release = value.clamp(1.0, 1000.0),
+            "mix" => self.mix = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub struct Vocoder {
+    settings: VocoderSettings,
+    sample_rate: u32,
+    analysis: Vec<Biquad>,       // The input's bands
+    synthesis: Vec<[Biquad; 2]>, // The synth's bands, left and right
+    levels: Vec<f32>,            // How loud each of the input's bands is
+    attack: f32,                 // Per-sample smoothing of a rising level
+    release: f32,                // And of a falling one
+}
+
+impl Vocoder {
+    pub fn new(settings: &VocoderSettings, sample_rate: u32) -> Self {
+        let bands = settings.bands.clamp(4, 32);
+        let mut vocoder = Self {
+            settings: settings.clone(),
+            sample_rate,
+            analysis: vec![Biquad::identity(); bands],
+            synthesis: vec![[Biquad::identity(); 2]; bands],
+            levels: vec![0.0; bands],
+            attack: smoothing(ATTACK_MS, sample_rate),
+            release: 0.0,
+        };
+        vocoder.tune();
+        vocoder
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let known = self.settings.set_param(name, value);
+        self.tune();
+        known
+    }
+
+    // Places the bands and sets the release from the settings; the band count stays
+    // as it was made, so this never allocates
+    fn tune(&mut self) {
+        let bands = self.analysis.len();
+        let octaves = (HIGHEST_BAND / LOWEST_BAND).log2();
+        let spacing = octaves / (bands - 1) as f32; // Octaves from one band to the next
+        let q = 2.0_f32.powf(spacing).sqrt() / (2.0_f32.powf(spacing) - 1.0); // Neighbours meet at their -3 dB points
+        let shift = 2.0_f32.powf(self.settings.formant_shift / 12.0);
+        for band in 0..bands {
+            let frequency = LOWEST_BAND * 2.0_f32.powf(band as f32 * spacing);
+            self.analysis[band].set_coefficients(&Biquad::band_pass(frequency, q, self.sample_rate));
+            let shifted = Biquad::band_pass(frequency * shift, q, self.sample_rate);
+            for filter in &mut self.synthesis[band] {
+                filter.set_coefficients(&shifted);
+            }
+        }
+        self.release = smoothing(self.settings.release, self.sample_rate);
+    }
+
+    // The synth's `carrier` frame shaped by the input's `modulator` sample
+    pub fn process(&mut self, carrier: Frame, modulator: f32) -> Frame {
+        let mut vocoded = [0.0; 2];
+        for ((analysis, synthesis), level) in self.analysis.iter_mut().zip(&mut self.synthesis).zip(&mut self.levels) {
+            let input = analysis.process(modulator).abs();
+            let speed = if input > *level { self.attack } else { self.release };
+            *level += (input - *level) * speed;
+            for (output, (filter, &sample)) in vocoded.iter_mut().zip(synthesis.iter_mut().zip(&carrier)) {
+                *output += filter.process(sample) * *level;
+            }
+        }
+        let mix = self.settings.mix;
+        [0, 1].map(|channel| carrier[channel] * (1.0 - mix) + vocoded[channel] * MAKEUP * mix)
+    }
+}
+
+// One-pole smoothing that covers most of the way in `ms`
+fn smoothing(ms: f32, sample_rate: u32) -> f32 {
+    1.0 - (-1000.0 / (ms.max(0.1) * sample_rate as f32)).exp()
+}
@@ -0,0 +1,68 @@
+use crate::effects::{EffectConfig, EffectsChain};
+use crate::oversample::Oversampling;
+
+// The effects every voice of the main patch runs on its own sound before the voices are
+// mixed, from the preset's `voice_effects`. Distortion in each voice leaves a chord clean
+// where the same drive on the mix would turn it to mud, and a chorus per voice widens
+// each note differently. Only effects that are cheap and hold little can go there (see
+// `EffectConfig::per_voice`); the rest belong on the master bus, so they're left out
+// with a warning and the slots are counted without them. A chain for each voice slot is
+// made when the preset loads, from the settings here with the edits made since, and
+// handed from note to note afresh (see `VoiceParts`); the edits reach the chains waiting
+// too, so a note played after a macro moves hears the move.
+pub struct VoiceEffects {
+    configs: Vec<EffectConfig>,
+    edits: Vec<(usize, String, f32)>, // Slot, parameter and value set since the preset loaded
+    tempo: f32,
+    oversampling: Oversampling,
+    sample_rate: u32,
+}
+
+impl VoiceEffects {
+    pub fn new(configs: &[EffectConfig], oversampling: Oversampling, sample_rate: u32) -> Self {
+        let configs = configs
+            .iter()
+            .filter(|config| {
+                if !config.per_voice() {
+                    tracing::warn!(effect = config.name(), "effect can't run in every voice, left out");
+                }
+                config.per_voice()
+            })
+            .cloned()
+            .collect();
+        Self { configs, edits: Vec::new(), tempo: 0.0, oversampling, sample_rate }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.configs.is_empty()
+    }
+
+    // A chain for a voice starting now
+    pub fn build(&self) -> EffectsChain {
+        let mut chain = EffectsChain::new(&self.configs, self.sample_rate);
+        chain.set_tempo(self.tempo);
+        chain.set_oversampling(self.oversampling);
+        for (slot, name, value) in &self.edits {
+            chain.set_param(*slot, name, *value);
+        }
+        chain
+    }
+
+    // For the chains of voices started from now on; the synth passes it to those sounding
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.tempo = tempo;
+    }
+
+    // Remembers an edit for the chains of voices started from now on, if the effect in
+    // `slot` has the parameter; the synth makes it in those sounding
+    pub fn set_param(&mut self, slot: usize, name: &str, value: f32) -> bool {
+        let known = self.configs.get(slot).is_some_and(|config| config.params().iter().any(|&(param, _)| param == name));
+        if known {
+            match self.edits.iter_mut().find(|(edited, param, _)| *edited == slot && param == name) {
+                Some(edit) => edit.2 = value,
+                None => self.edits.push((slot, name.to_string(), value)),
+            }
+        }
+        known
+    }
+}
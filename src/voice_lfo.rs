@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use crate::lfo::{Lfo, LfoShape};
+use crate::tempo::Rate;
+
+// An LFO of its own in every voice, started at a random phase and a slightly different
+// rate for each note, so a chord's notes waver apart instead of in lockstep the way the
+// synth-wide LFOs move them: the slow drift between players that makes an ensemble. A
+// voice keeps the rate it started with; the depths apply to every voice as they change.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceLfoSettings {
+    pub rate: Rate,       // In Hz or as a tempo division
+    pub rate_spread: f32, // How far each voice's rate strays from `rate`, as a fraction of it, 0.0..1.0
+    pub shape: LfoShape,
+    pub pitch_depth: f32, // Cents the LFO detunes each voice either way, 0.0 for none
+    pub amp_depth: f32,   // How far it dips each voice's level, 0.0..1.0
+    pub pan_depth: f32,   // How far it moves each voice from its place in the stereo field, 0.0..1.0
+}
+
+impl Default for VoiceLfoSettings {
+    fn default() -> Self {
+        Self {
+            rate: Rate::Hz(0.3),
+            rate_spread: 0.3,
+            shape: LfoShape::Sine,
+            pitch_depth: 0.0,
+            amp_depth: 0.0,
+            pan_depth: 0.0,
+        }
+    }
+}
+
+impl VoiceLfoSettings {
+    // Off until it moves something
+    pub fn is_active(&self) -> bool {
+        self.pitch_depth != 0.0 || self.amp_depth != 0.0 || self.pan_depth != 0.0
+    }
+
+    // The settings the synth's "voice_lfo.<name>" parameters change, with their current values
+    pub fn params(&self) -> Vec<(&'static str, f32)> {
+        let mut params: Vec<_> = self.rate.editable_param("rate").into_iter().collect();
+        params.extend([
+            ("rate_spread", self.rate_spread),
+            ("pitch_depth", self.pitch_depth),
+            ("amp_depth", self.amp_depth),
+            ("pan_depth", self.pan_depth),
+        ]);
+        params
+    }
+
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate" => self.rate = Rate::Hz(value.max(0.0)),
+            "rate_spread" => self.rate_spread = value.clamp(0.0, 1.0),
+            "pitch_depth" => self.pitch_depth = value.clamp(-100.0, 100.0),
+            "amp_depth" => self.amp_depth = value.clamp(0.0, 1.0),
+            "pan_depth" => self.pan_depth = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+
+    // A new voice's LFO; `random` is two uniform draws in 0.0..1.0, for its rate and phase
+    pub fn lfo(&self, random: [f32; 2], tempo: f32, sample_rate: u32) -> Lfo {
+        let rate = self.rate.hz(tempo) * (1.0 + self.rate_spread * (random[0] * 2.0 - 1.0));
+        Lfo::new(rate.max(0.0), sample_rate).with_shape(self.shape).with_phase(random[1] * 2.0 * PI)
+    }
+
+    // What a voice's LFO at `value` (-1.0..1.0) does to it: a pitch ratio, a gain and a pan offset
+    pub fn apply(&self, value: f32) -> (f32, f32, f32) {
+        let ratio = 2.0_f32.powf(self.pitch_depth * value / 1200.0);
+        let gain = 1.0 - self.amp_depth * (0.5 - 0.5 * value);
+        (ratio, gain, self.pan_depth * value)
+    }
+}
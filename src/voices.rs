@@ -0,0 +1,203 @@
+use crate::effects::EffectsChain;
+use crate::{Oscillator, VoiceParts};
+
+const NOTES: usize = 128;
+
+// Which voice makes way for a new note when every voice is sounding. Voices in their
+// release always go first, the quietest of them; the policy decides between held ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StealPolicy {
+    #[default]
+    Oldest,    // The voice that started longest ago
+    Quietest,  // The one with the lowest envelope level times velocity
+    Lowest,    // The one playing the lowest note, keeping melodies on top
+    RefuseNew, // None; the new note isn't played
+}
+
+// How `VoicePool::start` found room for a voice
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Started {
+    Free,                                // In a slot nothing was playing in
+    Stole { note: u8, releasing: bool }, // In place of a voice on `note`, cut off
+    Refused,                             // Nowhere; every voice is held and none may be stolen
+}
+
+// The voices a synth (or one of its parts) has sounding. There's a slot for each voice
+// of polyphony, made when the pool is, so starting, stealing and finishing notes on
+// the audio thread never allocates. Notes find their voice through the slot they last
+// started in rather than by searching.
+pub(crate) struct VoicePool {
+    slots: Vec<Option<Oscillator>>, // Free when None; never grows while playing
+    started: Vec<u64>,              // When each slot's voice started, counting voices
+    voices_started: u64,
+    newest: [Option<usize>; NOTES], // Slot of the last voice started on each note
+    policy: StealPolicy,
+    spares: Vec<VoiceParts>,        // Parts for voices to start with, a set for each slot
+}
+
+impl VoicePool {
+    pub fn new(polyphony: usize) -> Self {
+        Self {
+            slots: (0..polyphony.max(1)).map(|_| None).collect(),
+            started: vec![0; polyphony.max(1)],
+            voices_started: 0,
+            newest: [None; NOTES],
+            policy: StealPolicy::default(),
+            spares: Vec::new(),
+        }
+    }
+
+    // Makes a set of parts for every slot with `make`, replacing any there were. This
+    // allocates, so it's for when the preset loads.
+    pub fn prepare(&mut self, make: impl Fn() -> VoiceParts) {
+        self.spares = Vec::with_capacity(self.slots.len());
+        self.spares.extend((0..self.slots.len()).map(|_| make()));
+    }
+
+    // Takes the spare parts of `other`, made for a preset being switched to, and fits
+    // them to the voices sounding that have none. The voices' insert effects become the
+    // new preset's too, their old chains going to `other` with its old spares.
+    pub fn take_spares(&mut self, other: &mut VoicePool) {
+        std::mem::swap(&mut self.spares, &mut other.spares);
+        for osc in self.slots.iter_mut().flatten() {
+            if let Some(chain) = osc.effects.take() {
+                other.spares.push(VoiceParts { effects: Some(chain), ..VoiceParts::default() });
+            }
+            if let Some(parts) = self.spares.pop() {
+                if let Some(parts) = osc.fit(parts) {
+                    self.spares.push(parts);
+                }
+            }
+        }
+    }
+
+    // The insert effects of every voice, sounding or spare, for edits and tempo to reach
+    pub fn effects_mut(&mut self) -> impl Iterator<Item = &mut EffectsChain> {
+        let sounding = self.slots.iter_mut().flatten().filter_map(|osc| osc.effects.as_mut());
+        sounding.chain(self.spares.iter_mut().filter_map(|parts| parts.effects.as_mut()))
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn steal_policy(&self) -> StealPolicy {
+        self.policy
+    }
+
+    pub fn polyphony(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Makes room for `polyphony` voices; those in slots that go away stop at once.
+    // This allocates, so it's for setting up rather than for the audio thread.
+    pub fn set_polyphony(&mut self, polyphony: usize) {
+        self.slots.resize_with(polyphony.max(1), || None);
+        self.started.resize(self.slots.len(), 0);
+        let slots = self.slots.len();
+        for newest in &mut self.newest {
+            *newest = newest.filter(|&slot| slot < slots);
+        }
+    }
+
+    // How many voices are sounding
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Oscillator> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Oscillator> {
+        self.slots.iter_mut().flatten()
+    }
+
+    // The last voice started on `note`, if it's still sounding. Any earlier ones are in
+    // their release, since a held voice is always played again in place.
+    pub fn newest(&mut self, note: u8) -> Option<&mut Oscillator> {
+        let slot = self.newest[note as usize % NOTES]?;
+        self.slots[slot].as_mut()
+    }
+
+    // Takes the last voice started on `note` out of the pool, to be started again elsewhere
+    pub fn take_newest(&mut self, note: u8) -> Option<Oscillator> {
+        let slot = self.newest[note as usize % NOTES]?;
+        self.free(slot)
+    }
+
+    // Puts `osc` in a free slot, stealing one by the policy when there's none, and says
+    // which it did. `osc` is left out if no voice may be stolen.
+    pub fn start(&mut self, mut osc: Oscillator) -> Started {
+        let (slot, started) = match self.slots.iter().position(Option::is_none) {
+            Some(slot) => (slot, Started::Free),
+            None => {
+                let Some(slot) = self.victim() else { return Started::Refused };
+                match self.free(slot) {
+                    Some(stolen) => {
+                        let started = Started::Stole { note: stolen.note, releasing: stolen.is_releasing() };
+                        self.recycle(stolen);
+                        (slot, started)
+                    }
+                    None => (slot, Started::Free),
+                }
+            }
+        };
+        if let Some(parts) = self.spares.pop() {
+            if let Some(parts) = osc.fit(parts) {
+                self.spares.push(parts);
+            }
+        }
+        self.voices_started += 1;
+        self.started[slot] = self.voices_started;
+        self.newest[osc.note as usize % NOTES] = Some(slot);
+        self.slots[slot] = Some(osc);
+        started
+    }
+
+    // The slot to steal from a full pool, if any may be
+    fn victim(&self) -> Option<usize> {
+        let sounding = || self.slots.iter().enumerate().filter_map(|(slot, osc)| Some((slot, osc.as_ref()?)));
+        let loudness = |osc: &Oscillator| osc.envelope_level() * osc.velocity;
+        let quietest = |(_, a): &(usize, &Oscillator), (_, b): &(usize, &Oscillator)| loudness(a).total_cmp(&loudness(b));
+
+        let releasing = sounding().filter(|(_, osc)| osc.is_releasing()).min_by(quietest);
+        let held = match self.policy {
+            StealPolicy::Oldest => sounding().min_by_key(|&(slot, _)| self.started[slot]),
+            StealPolicy::Quietest => sounding().min_by(quietest),
+            StealPolicy::Lowest => sounding().min_by_key(|(_, osc)| osc.note),
+            StealPolicy::RefuseNew => None,
+        };
+        releasing.or(held).map(|(slot, _)| slot)
+    }
+
+    // Frees the slots of the voices that have finished their release
+    pub fn free_finished(&mut self) {
+        for slot in 0..self.slots.len() {
+            if self.slots[slot].as_ref().is_some_and(|osc| osc.is_finished()) {
+                if let Some(osc) = self.free(slot) {
+                    self.recycle(osc);
+                }
+            }
+        }
+    }
+
+    // Keeps the parts of a voice that's done for the next, unless there are enough spare
+    // already (from a voice that came across from another preset)
+    fn recycle(&mut self, mut osc: Oscillator) {
+        if self.spares.len() < self.spares.capacity() {
+            if let Some(parts) = osc.strip() {
+                self.spares.push(parts);
+            }
+        }
+    }
+
+    fn free(&mut self, slot: usize) -> Option<Oscillator> {
+        let osc = self.slots[slot].take()?;
+        let newest = &mut self.newest[osc.note as usize % NOTES];
+        if *newest == Some(slot) {
+            *newest = None;
+        }
+        Some(osc)
+    }
+}
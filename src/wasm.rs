@@ -0,0 +1,114 @@
+use std::sync::mpsc;
+
+use crate::preset::Preset;
+use crate::{SynthCommand, Synthesizer};
+
+// Exports for the browser demo in web/. The module is instantiated without any
+// JavaScript glue: the audio worklet calls these functions directly and copies each
+// rendered block out of the module's memory. Every `synth` pointer is one returned
+// by `synth_new`, and every `ptr`/`len` pair a UTF-8 string written into memory from
+// `synth_alloc`.
+//
+// Build with `cargo build --lib --release --target wasm32-unknown-unknown` and copy
+// target/wasm32-unknown-unknown/release/rodio_synth.wasm into web/.
+
+pub const BLOCK: usize = 128; // Frames per render call, one Web Audio render quantum
+
+pub struct WebSynth {
+    synth: Synthesizer,
+    commands: mpsc::Sender<SynthCommand>,
+    left: [f32; BLOCK],
+    right: [f32; BLOCK],
+}
+
+impl WebSynth {
+    fn new(sample_rate: u32, preset: &Preset) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        Self {
+            synth: Synthesizer::new(sample_rate, preset, receiver),
+            commands,
+            left: [0.0; BLOCK],
+            right: [0.0; BLOCK],
+        }
+    }
+}
+
+// Only the arpeggiator's random pattern asks for randomness, and its clock thread
+// can't run in the browser, so nothing ever needs entropy here
+fn no_entropy(_: &mut [u8]) -> Result<(), getrandom::Error> {
+    Err(getrandom::Error::UNSUPPORTED)
+}
+getrandom::register_custom_getrandom!(no_entropy);
+
+#[no_mangle]
+extern "C" fn synth_new(sample_rate: u32) -> *mut WebSynth {
+    Box::into_raw(Box::new(WebSynth::new(sample_rate, &Preset::default())))
+}
+
+// Room in the module's memory for JavaScript to write a string into
+#[no_mangle]
+extern "C" fn synth_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+// `ptr` and `len` must come from one `synth_alloc` call
+#[no_mangle]
+unsafe extern "C" fn synth_free(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+unsafe fn text<'a>(ptr: *const u8, len: usize) -> Option<&'a str> {
+    std::str::from_utf8(std::slice::from_raw_parts(ptr, len)).ok()
+}
+
+// Replaces the sound with a TOML preset; returns false, keeping the current sound,
+// if it doesn't parse
+#[no_mangle]
+unsafe extern "C" fn synth_load_preset(synth: *mut WebSynth, ptr: *const u8, len: usize) -> bool {
+    let synth = &mut *synth;
+    match text(ptr, len).and_then(|text| Preset::parse(text).ok()) {
+        Some(preset) => {
+            *synth = WebSynth::new(synth.synth.sample_rate(), &preset);
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn synth_note_on(synth: *mut WebSynth, note: u8) {
+    let _ = (*synth).commands.send(SynthCommand::NoteOn(note));
+}
+
+#[no_mangle]
+unsafe extern "C" fn synth_note_off(synth: *mut WebSynth, note: u8) {
+    let _ = (*synth).commands.send(SynthCommand::NoteOff(note));
+}
+
+// Sets a parameter by its path, such as "eq.low_gain", smoothed like any other edit
+#[no_mangle]
+unsafe extern "C" fn synth_set_param(synth: *mut WebSynth, ptr: *const u8, len: usize, value: f32) -> bool {
+    match text(ptr, len) {
+        Some(path) => (*synth).synth.set_param_smoothed(path, value),
+        None => false,
+    }
+}
+
+// Renders the next `BLOCK` frames; read them through `synth_left` and `synth_right`
+#[no_mangle]
+unsafe extern "C" fn synth_render(synth: *mut WebSynth) {
+    let synth = &mut *synth;
+    for frame in 0..BLOCK {
+        [synth.left[frame], synth.right[frame]] = synth.synth.render_frame();
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn synth_left(synth: *const WebSynth) -> *const f32 {
+    (*synth).left.as_ptr()
+}
+
+#[no_mangle]
+unsafe extern "C" fn synth_right(synth: *const WebSynth) -> *const f32 {
+    (*synth).right.as_ptr()
+}
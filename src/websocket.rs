@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use tungstenite::{Message, WebSocket};
+
+use crate::cpu_meter::CpuMeter;
+use crate::macros::MACROS;
+use crate::params::ParamStore;
+use crate::tempo::SharedTempo;
+use crate::xrun::XrunMonitor;
+use crate::SynthCommand;
+
+// What a client can send, one JSON object per text message:
+//
+//   {"type": "note_on", "note": 60}                 Optionally with "channel": 1-16, for the parts
+//   {"type": "note_off", "note": 60}
+//   {"type": "param", "path": "eq.low_gain", "value": 3.0}
+//   {"type": "macro", "index": 0, "value": 0.5}     Index 0-3
+//   {"type": "tempo", "bpm": 128}
+//   {"type": "metronome"}                           Toggles the click
+//   {"type": "hold"}                                Toggles hold, which keeps released notes ringing
+//   {"type": "latch"}                               Toggles latch, where each press starts or stops a note
+//   {"type": "panic"}                               Silences every voice, for stuck notes
+//   {"type": "get_state"}                           Answered with a "state" message
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    NoteOn { note: u8, channel: Option<u8> },
+    NoteOff { note: u8, channel: Option<u8> },
+    Param { path: String, value: f32 },
+    Macro { index: usize, value: f32 },
+    Tempo { bpm: f32 },
+    Metronome,
+    Hold,
+    Latch,
+    Panic,
+    GetState,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    State {
+        preset: String,
+        tempo: f32,
+        params: Vec<Param>, // In the same order as the UIs list them
+        cpu: f32,           // Fraction of the real-time budget
+        xruns: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Param {
+    path: String,
+    value: f32,
+}
+
+// Everything a connection needs to play the synth and describe it
+#[derive(Clone)]
+pub struct Remote {
+    pub preset_name: String,
+    pub params: ParamStore,
+    pub tempo: SharedTempo,
+    pub commands: mpsc::Sender<SynthCommand>, // Notes go through the arp and rhythm generator like the keyboard's
+    pub cpu: CpuMeter,
+    pub xruns: XrunMonitor,
+}
+
+// Accepts WebSocket connections on `port`, each served on its own thread
+pub fn spawn(port: u16, remote: Remote) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Listening for WebSocket connections on port {}", port);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let remote = remote.clone();
+            thread::spawn(move || match tungstenite::accept(stream) {
+                Ok(socket) => serve(socket, remote),
+                Err(e) => eprintln!("WebSocket handshake failed: {}", e),
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve(mut socket: WebSocket<TcpStream>, remote: Remote) {
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) | Err(_) => return,
+            Ok(_) => continue, // Pings are answered by tungstenite itself
+        };
+        let reply = match serde_json::from_str::<Request>(&text) {
+            Ok(request) => handle(request, &remote),
+            Err(e) => Some(Reply::Error { message: e.to_string() }),
+        };
+        if let Some(reply) = reply {
+            let json = serde_json::to_string(&reply).expect("Failed to serialize reply");
+            if socket.send(Message::Text(json)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn handle(request: Request, remote: &Remote) -> Option<Reply> {
+    let command = match request {
+        Request::NoteOn { note, channel } => SynthCommand::channel_note(channel.unwrap_or(1).clamp(1, 16), note.min(127), true),
+        Request::NoteOff { note, channel } => SynthCommand::channel_note(channel.unwrap_or(1).clamp(1, 16), note.min(127), false),
+        Request::Param { path, value } => {
+            if !remote.params.values().iter().any(|(known, _)| *known == path) {
+                return Some(Reply::Error { message: format!("Unknown parameter '{}'", path) });
+            }
+            remote.params.set(&path, value);
+            return None;
+        }
+        Request::Macro { index, value } if index < MACROS => SynthCommand::SetMacro(index, value.clamp(0.0, 1.0)),
+        Request::Macro { index, .. } => return Some(Reply::Error { message: format!("No macro {}", index) }),
+        Request::Tempo { bpm } => {
+            let bpm = bpm.clamp(20.0, 400.0);
+            remote.tempo.set(bpm); // The clock threads read the shared tempo, like with tap tempo
+            SynthCommand::SetTempo(bpm)
+        }
+        Request::Metronome => SynthCommand::ToggleMetronome,
+        Request::Hold => SynthCommand::ToggleHold,
+        Request::Latch => SynthCommand::ToggleLatch,
+        Request::Panic => SynthCommand::Panic,
+        Request::GetState => {
+            return Some(Reply::State {
+                preset: remote.preset_name.clone(),
+                tempo: remote.tempo.get(),
+                params: remote.params.values().into_iter().map(|(path, value)| Param { path, value }).collect(),
+                cpu: remote.cpu.load(),
+                xruns: remote.xruns.count(),
+            })
+        }
+    };
+    match remote.commands.send(command) {
+        Ok(()) => None,
+        Err(_) => Some(Reply::Error { message: "The synth has stopped".to_string() }),
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Counts audio underruns (xruns) as the backend detects them. The audio thread only
+// bumps atomics; the console, UI or log reads the count and when the last one
+// happened, as time since startup, to line clicks up with what else was going on.
+#[derive(Clone)]
+pub struct XrunMonitor {
+    start: Instant,
+    count: Arc<AtomicU64>,
+    last: Arc<AtomicU64>, // Microseconds from `start` to the most recent xrun
+}
+
+impl XrunMonitor {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            count: Arc::new(AtomicU64::new(0)),
+            last: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn record(&self) {
+        self.last.store(self.start.elapsed().as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Acquire)
+    }
+
+    // Time since startup of the most recent xrun, if there has been one
+    pub fn last(&self) -> Option<Duration> {
+        (self.count() > 0).then(|| Duration::from_micros(self.last.load(Ordering::Relaxed)))
+    }
+
+    // "3 xruns, last at 12.345 s", or "No xruns"
+    pub fn summary(&self) -> String {
+        match self.last() {
+            Some(last) => format!("{} xruns, last at {:.3} s", self.count(), last.as_secs_f32()),
+            None => "No xruns".to_string(),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// Ambient mode's notes, which play quieter than the keys without changing how loud they are
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use rodio_synth::ambient::{self, AmbientSettings};
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+#[test]
+fn notes_are_scaled_in_the_range() {
+    let (tx, rx) = mpsc::channel();
+    ambient::spawn(AmbientSettings { enabled: true, seed: Some(3), ..AmbientSettings::default() }, tx);
+    match rx.recv_timeout(Duration::from_secs(1)).expect("The first note starts at once") {
+        SynthCommand::ScaledNoteOn(note, scale) => {
+            assert!((48..=72).contains(&note), "{}", note);
+            assert!((0.3..0.7).contains(&scale), "{}", scale);
+        }
+        command => panic!("{:?}", command),
+    }
+}
+
+#[test]
+fn a_scaled_note_leaves_the_velocity_as_it_was() {
+    let peak = |commands: &[(usize, SynthCommand)]| {
+        let frames = Synthesizer::offline(SAMPLE_RATE, &Preset::default()).render(commands, 16_000);
+        frames[12_000..].iter().map(|frame| frame[0].abs()).fold(0.0, f32::max)
+    };
+    let alone = peak(&[(12_000, SynthCommand::NoteOn(60))]);
+    let after = peak(&[(0, SynthCommand::ScaledNoteOn(69, 0.3)), (100, SynthCommand::NoteOff(69)), (12_000, SynthCommand::NoteOn(60))]);
+    assert!((after - alone).abs() < 1e-3, "{} against {}", after, alone);
+
+    let scaled = peak(&[(12_000, SynthCommand::ScaledNoteOn(60, 0.5))]);
+    assert!((scaled - alone * 0.5).abs() < 0.01, "{} against {}", scaled, alone);
+}
@@ -0,0 +1,88 @@
+// Custom arpeggiator patterns: how their steps are written, and how they play over the
+// held notes
+
+use rodio_synth::arpeggiator::{ArpSettings, ArpStep, ArpSteps, Arpeggiator, CustomPattern};
+use rodio_synth::preset::Preset;
+
+fn steps(text: &str) -> Vec<ArpStep> {
+    text.parse::<ArpSteps>().unwrap_or_else(|e| panic!("'{}' should parse: {}", text, e)).0
+}
+
+fn arp(text: &str) -> Arpeggiator {
+    let settings = ArpSettings {
+        custom: "test".to_string(),
+        patterns: vec![CustomPattern { name: "test".to_string(), steps: text.parse().unwrap() }],
+        ..ArpSettings::default()
+    };
+    let mut arp = Arpeggiator::new(settings).expect("The pattern is there");
+    for note in [60, 64, 67] {
+        arp.press(note);
+    }
+    arp
+}
+
+fn play(arp: &mut Arpeggiator, steps: usize) -> Vec<Option<(u8, u32)>> {
+    (0..steps).map(|_| arp.next_note()).collect()
+}
+
+#[test]
+fn rests_octave_jumps_and_ties_parse() {
+    assert_eq!(steps(". 1 ."), [ArpStep::Rest, ArpStep::Note { index: 0, octave: 0 }, ArpStep::Rest]);
+    assert_eq!(
+        steps("2+ 3-- 1+-"),
+        [ArpStep::Note { index: 1, octave: 1 }, ArpStep::Note { index: 2, octave: -2 }, ArpStep::Note { index: 0, octave: 0 }]
+    );
+    assert_eq!(steps("1 _ _"), [ArpStep::Note { index: 0, octave: 0 }, ArpStep::Tie, ArpStep::Tie]);
+    assert_eq!(steps("  1   2+\n. _ ").len(), 4);
+
+    let text = "1 3 2+ . 1 _ 3- _";
+    assert_eq!(text.parse::<ArpSteps>().unwrap().to_string(), text);
+}
+
+#[test]
+fn malformed_steps_are_refused() {
+    for text in ["", "   ", "0", "x", "1 +", "1+x", "-1", "1.5"] {
+        assert!(text.parse::<ArpSteps>().is_err(), "'{}' should be refused", text);
+    }
+    let error = "1 0".parse::<ArpSteps>().unwrap_err();
+    assert!(error.contains("count from 1"), "{}", error);
+}
+
+#[test]
+fn octave_shifts_past_the_midi_range_are_refused() {
+    assert_eq!(steps(&format!("1{}", "+".repeat(10))), [ArpStep::Note { index: 0, octave: 10 }]);
+    assert_eq!(steps(&format!("1{}+", "+-".repeat(200))), [ArpStep::Note { index: 0, octave: 1 }]);
+    for shift in ["+".repeat(11), "-".repeat(11), "+".repeat(200)] {
+        let error = format!("1{}", shift).parse::<ArpSteps>().unwrap_err();
+        assert!(error.contains("octaves"), "{}", error);
+    }
+}
+
+#[test]
+fn rests_jumps_and_ties_play() {
+    assert_eq!(play(&mut arp("1 . 3"), 4), [Some((60, 1)), None, Some((67, 1)), Some((60, 1))]);
+    assert_eq!(play(&mut arp("2+ 1-- 4"), 3), [Some((76, 1)), Some((36, 1)), Some((60, 1))]); // Round again past the top
+    assert_eq!(play(&mut arp("1 _ _ 2"), 4), [Some((60, 3)), None, None, Some((64, 1))]);
+    // A tie with no note before it rests, and ties at the end hold on into the start
+    assert_eq!(play(&mut arp("_ 1 2 _"), 4), [None, Some((60, 1)), Some((64, 3)), None]);
+    assert_eq!(play(&mut arp("1 _ ."), 1), [Some((60, 2))]);
+}
+
+#[test]
+fn a_missing_custom_pattern_is_an_error() {
+    let settings = ArpSettings { custom: "climb".to_string(), ..ArpSettings::default() };
+    assert!(settings.check().is_err());
+    assert!(Arpeggiator::new(settings).is_err());
+
+    let preset = "
+        [arp]
+        custom = \"climb\"
+
+        [[arp.patterns]]
+        name = \"fall\"
+        steps = \"3 2 1\"
+    ";
+    let error = Preset::parse(preset).unwrap_err();
+    assert!(error.to_string().contains("climb"), "{}", error);
+    assert!(Preset::parse(&preset.replace("fall", "climb")).is_ok());
+}
@@ -0,0 +1,52 @@
+// Blending between two presets with `blend.amount`, and the values it writes back for the views
+
+use std::sync::Arc;
+
+use rodio_synth::blend::PresetBlend;
+use rodio_synth::param_bank::ParamBank;
+use rodio_synth::param_id::ParamId;
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+fn presets() -> (Preset, Preset) {
+    let (mut a, mut b) = (Preset::default(), Preset::default());
+    (a.envelope.attack, b.envelope.attack) = (0.01, 1.0);
+    (a.bend_range, b.bend_range) = (2.0, 12.0);
+    (a, b)
+}
+
+fn value(blend: &PresetBlend, path: &str, amount: f32) -> f32 {
+    blend.at(amount).find(|&(other, _, _)| other == path).map(|(_, _, value)| value).unwrap()
+}
+
+#[test]
+fn times_blend_on_a_log_scale_and_the_rest_linearly() {
+    let (a, b) = presets();
+    let blend = PresetBlend::new(&a, &b);
+    assert!((value(&blend, "envelope.attack", 0.5) - 0.1).abs() < 1e-6);
+    assert_eq!(value(&blend, "pitch.bend_range", 0.5), 7.0);
+    assert_eq!(value(&blend, "pitch.bend_range", 2.0), 12.0); // The amount is clamped
+    assert!(blend.at(0.5).all(|(_, slot, _)| slot.is_none()), "No bank is bound yet");
+}
+
+#[test]
+fn blended_values_are_written_back_to_the_bank() {
+    let (a, b) = presets();
+    let mut values = a.params();
+    values.push(("blend.amount".to_string(), 0.0));
+    let bank = Arc::new(ParamBank::new(values));
+    let mut synth = Synthesizer::offline(8_000, &a).with_blend(PresetBlend::new(&a, &b)).with_param_bank(bank.clone());
+
+    bank.set(bank.index("blend.amount").unwrap(), 1.0);
+    synth.render(&[], 1);
+    assert_eq!(bank.get(bank.index("pitch.bend_range").unwrap()), 12.0);
+    assert_eq!(bank.get(bank.index("envelope.attack").unwrap()), 1.0);
+
+    // Written back without flagging, so the synth isn't handed its own values again
+    let mut drained = Vec::new();
+    bank.drain(|path, _| drained.push(path.to_string()));
+    assert!(drained.is_empty(), "{:?}", drained);
+
+    synth.render(&[(0, SynthCommand::SetParam(ParamId::known("blend.amount"), 0.5))], 1);
+    assert_eq!(bank.get(bank.index("pitch.bend_range").unwrap()), 7.0);
+}
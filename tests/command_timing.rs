@@ -0,0 +1,70 @@
+// Timestamped commands: everything sent ahead through the command channel at once, as a
+// sequencer or MIDI file player sending a block's events does, plays on its own frame.
+
+use std::sync::mpsc;
+
+use rodio_synth::diagnostics::{self, Diagnostic};
+use rodio_synth::preset::Preset;
+use rodio_synth::switch::build_presets;
+use rodio_synth::{SynthCommand, Synthesizer, DEFAULT_POLYPHONY, MAX_SCHEDULED};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+// The first frame with any sound, after sending `commands` at frame 0
+fn first_sound(commands: Vec<SynthCommand>) -> Option<usize> {
+    let (tx, rx) = mpsc::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), rx);
+    for command in commands {
+        tx.send(command).expect("The synth should be listening");
+    }
+    (0..SAMPLE_RATE as usize).find(|_| synth.render_frame() != [0.0; 2])
+}
+
+fn at(frame: u64, command: SynthCommand) -> SynthCommand {
+    SynthCommand::At(frame, Box::new(command))
+}
+
+#[test]
+fn note_starts_on_its_frame() {
+    // A sine starts at zero, so the note's first frame is silent and the next isn't
+    assert_eq!(first_sound(vec![at(1234, SynthCommand::NoteOn(69))]), Some(1235));
+}
+
+#[test]
+fn same_frame_keeps_the_order_sent() {
+    let commands = vec![at(100, SynthCommand::NoteOn(69)), at(100, SynthCommand::NoteOff(69)), at(50, SynthCommand::SetVelocity(0.5))];
+    let (tx, rx) = mpsc::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), rx);
+    for command in commands {
+        tx.send(command).expect("The synth should be listening");
+    }
+    let frames: Vec<_> = (0..200).map(|_| synth.render_frame()).collect();
+    // Released as soon as it started, the note only has its release to sound through
+    assert!(frames[..101].iter().all(|&frame| frame == [0.0; 2]));
+    assert!(frames[101..].iter().all(|frame| frame[0].abs() < 0.01));
+}
+
+#[test]
+fn note_through_the_preset_builder_starts_on_its_frame() {
+    let (tx, rx) = mpsc::channel();
+    let built = build_presets(rx, SAMPLE_RATE, DEFAULT_POLYPHONY);
+    tx.send(at(1234, SynthCommand::NoteOn(69))).expect("The builder should be listening");
+    // The frame and the note come out of the builder one after the other
+    let commands = vec![built.recv().unwrap(), built.recv().unwrap()];
+    assert_eq!(commands[0], SynthCommand::AtNext(1234));
+    assert_eq!(first_sound(commands), Some(1235));
+}
+
+#[test]
+fn a_full_queue_refuses_commands_for_later() {
+    let (tx, rx) = mpsc::channel();
+    let (diagnostics_tx, diagnostics) = diagnostics::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), rx).with_diagnostics(diagnostics_tx);
+    for frame in 0..=MAX_SCHEDULED as u64 {
+        tx.send(at(1_000 + frame, SynthCommand::SetVelocity(0.5))).expect("The synth should be listening");
+    }
+    synth.render_frame();
+
+    let refused: Vec<_> = diagnostics.try_iter().collect();
+    assert_eq!(refused, [Diagnostic::ScheduleFull { frame: 1_000 + MAX_SCHEDULED as u64 }]);
+}
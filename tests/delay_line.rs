@@ -0,0 +1,35 @@
+// The delay line under the chorus, flanger and the rest: an impulse written into it
+// comes back out exactly as many samples later as the delay read asks for, and a
+// fractional delay falls between the two samples either side of it.
+
+use rodio_synth::delay_line::DelayLine;
+
+// Writes an impulse and then silence, reading `delay` after each write
+fn impulse_response(delay: f32, length: usize) -> Vec<f32> {
+    let mut line = DelayLine::new(64);
+    (0..length)
+        .map(|at| {
+            line.write(if at == 0 { 1.0 } else { 0.0 });
+            line.read(delay)
+        })
+        .collect()
+}
+
+#[test]
+fn whole_delay_returns_the_impulse_that_many_samples_later() {
+    for delay in [0, 1, 2, 17, 64] {
+        let response = impulse_response(delay as f32, 80);
+        let at = response.iter().position(|&sample| sample != 0.0);
+        assert_eq!(at, Some(delay), "delay of {} samples", delay);
+        assert_eq!(response[delay], 1.0, "delay of {} samples", delay);
+        assert_eq!(response.iter().filter(|&&sample| sample != 0.0).count(), 1, "delay of {} samples", delay);
+    }
+}
+
+#[test]
+fn fractional_delay_splits_the_impulse_between_neighbours() {
+    let response = impulse_response(5.25, 16);
+    assert_eq!(response[5], 0.75);
+    assert_eq!(response[6], 0.25);
+    assert_eq!(response.iter().sum::<f32>(), 1.0);
+}
@@ -0,0 +1,94 @@
+// Playing an embedded synth through its handle: scheduled events sound on their frame,
+// counted from the first frame the source renders
+
+use std::time::{Duration, Instant};
+
+use rodio::Source;
+use rodio_synth::embed::{embed, SynthEvent, SynthSource};
+use rodio_synth::preset::Preset;
+use rodio_synth::SynthCommand;
+
+const SAMPLE_RATE: u32 = 8_000;
+
+// The left channel of the next `frames` frames
+fn left(source: &mut SynthSource, frames: usize) -> Vec<f32> {
+    source.by_ref().take(frames * 2).step_by(2).collect()
+}
+
+fn first_sounding(samples: &[f32]) -> Option<usize> {
+    samples.iter().position(|&sample| sample != 0.0)
+}
+
+// Plays `events` as the handle would schedule them, straight on the source's synth rather
+// than through the preset builder's thread, and gives the left channel of `frames` frames
+fn play_scheduled(source: &mut SynthSource, events: Vec<(u64, SynthEvent)>, frames: usize) -> Vec<f32> {
+    let commands: Vec<_> = events.into_iter().map(|(frame, event)| (0, SynthCommand::At(frame, Box::new(event.command())))).collect();
+    source.synth().render(&commands, frames).iter().map(|frame| frame[0]).collect()
+}
+
+fn scheduled_note(frame: u64) -> Option<usize> {
+    let (_synth, mut source) = embed(SAMPLE_RATE, &Preset::default());
+    first_sounding(&play_scheduled(&mut source, vec![(frame, SynthEvent::NoteOn { note: 69, velocity: 100 })], 4_000))
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().map(|sample| sample.abs()).fold(0.0, f32::max)
+}
+
+#[test]
+fn scheduled_notes_start_on_their_frame() {
+    let early = scheduled_note(1_000).expect("The note should sound");
+    let late = scheduled_note(1_500).expect("The note should sound");
+    assert!((1_000..1_010).contains(&early), "{}", early);
+    assert_eq!(late - early, 500);
+}
+
+#[test]
+fn the_clock_counts_rendered_frames_and_late_events_play_at_once() {
+    let (synth, mut source) = embed(SAMPLE_RATE, &Preset::default());
+    assert_eq!((source.channels(), source.sample_rate()), (2, SAMPLE_RATE));
+    assert_eq!(first_sounding(&left(&mut source, 300)), None);
+    assert_eq!(synth.now(), 300);
+
+    let samples = play_scheduled(&mut source, vec![(100, SynthEvent::NoteOn { note: 69, velocity: 100 })], 100);
+    let start = first_sounding(&samples).expect("A passed frame should play at once");
+    assert!(start < 10, "{}", start);
+}
+
+#[test]
+fn events_for_one_frame_play_in_the_order_scheduled() {
+    let (_synth, mut source) = embed(SAMPLE_RATE, &Preset::default());
+    let events = vec![
+        (500, SynthEvent::NoteOn { note: 69, velocity: 100 }),
+        (500, SynthEvent::NoteOff(69)),
+        (800, SynthEvent::NoteOff(72)),
+        (800, SynthEvent::NoteOn { note: 72, velocity: 100 }),
+    ];
+    let samples = play_scheduled(&mut source, events, 2_000);
+    assert!(samples[..800].iter().all(|&sample| sample.abs() < 1e-3), "The first note ends as it starts");
+    assert!(first_sounding(&samples[800..]).is_some_and(|start| start < 10), "The second note is left on");
+}
+
+#[test]
+fn a_note_keeps_its_velocity_to_itself() {
+    let later_note = |events: Vec<(u64, SynthEvent)>| {
+        let (_synth, mut source) = embed(SAMPLE_RATE, &Preset::default());
+        let mut commands: Vec<_> = events.into_iter().map(|(frame, event)| (frame as usize, event.command())).collect();
+        commands.push((12_000, SynthCommand::NoteOn(60)));
+        peak(&source.synth().render(&commands, 16_000).iter().map(|frame| frame[0]).collect::<Vec<_>>()[12_000..])
+    };
+    let alone = later_note(vec![]);
+    let after = later_note(vec![(0, SynthEvent::NoteOn { note: 69, velocity: 20 }), (100, SynthEvent::NoteOff(69))]);
+    assert!((after - alone).abs() < 1e-3, "{} against {}", after, alone);
+}
+
+#[test]
+fn notes_from_the_handle_reach_the_synth() {
+    let (synth, mut source) = embed(SAMPLE_RATE, &Preset::default());
+    synth.note_on(69, 100);
+    // It passes through the preset builder's thread first, so may take a few buffers
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while first_sounding(&left(&mut source, 100)).is_none() {
+        assert!(Instant::now() < deadline, "The note should have reached the synth");
+    }
+}
@@ -0,0 +1,82 @@
+// Property tests for the voice envelope: whatever the attack and release times and
+// however the note is played, released and played again, the level stays in range,
+// only rises during the attack, only falls during the release and stays silent once
+// the release is over.
+
+use proptest::prelude::*;
+
+use rodio_synth::envelope::{Envelope, EnvelopeSettings};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+#[derive(Clone, Copy, Debug)]
+enum Event {
+    Restart,  // Played again from silence
+    Resume,   // Played again from the current level
+    Release,  // Key released
+    FadeOut,  // Panic
+}
+
+fn event() -> impl Strategy<Value = Event> {
+    prop_oneof![Just(Event::Restart), Just(Event::Resume), Just(Event::Release), Just(Event::FadeOut)]
+}
+
+proptest! {
+    #[test]
+    fn envelope_invariants(
+        attack in 0.0f32..0.2,
+        release in 0.0f32..0.2,
+        events in prop::collection::vec((event(), 0usize..1000), 0..12),
+    ) {
+        let mut envelope = Envelope::new(&EnvelopeSettings { attack, release, ..EnvelopeSettings::default() }, SAMPLE_RATE);
+        let mut previous = envelope.level();
+        prop_assert_eq!(previous, 0.0);
+
+        for (event, samples) in events {
+            let before = envelope.level();
+            match event {
+                Event::Restart => envelope.restart(),
+                Event::Resume => envelope.resume(),
+                Event::Release => envelope.release(),
+                Event::FadeOut => envelope.fade_out(),
+            }
+            match event {
+                Event::Restart => prop_assert_eq!(envelope.level(), 0.0),
+                // None of the others may jump
+                _ => prop_assert_eq!(envelope.level(), before, "{:?} moved the level", event),
+            }
+            previous = envelope.level();
+
+            for _ in 0..samples {
+                let level = envelope.next_level();
+                prop_assert!((0.0..=1.0).contains(&level), "level {} out of range", level);
+                if envelope.is_releasing() {
+                    prop_assert!(level <= previous, "release rose from {} to {}", previous, level);
+                } else {
+                    prop_assert!(level >= previous, "attack fell from {} to {}", previous, level);
+                }
+                if envelope.is_finished() {
+                    prop_assert_eq!(level, 0.0);
+                }
+                previous = level;
+            }
+        }
+    }
+
+    #[test]
+    fn release_finishes_in_time(attack in 0.0f32..0.2, release in 0.0f32..0.2, held in 0usize..2000) {
+        let settings = EnvelopeSettings { attack, release, ..EnvelopeSettings::default() };
+        let mut envelope = Envelope::new(&settings, SAMPLE_RATE);
+        for _ in 0..held {
+            envelope.next_level();
+        }
+        envelope.release();
+        // A full-level release takes `release` seconds; from partway up the attack it's shorter
+        let limit = (release.max(0.001) * SAMPLE_RATE as f32).ceil() as usize + 1;
+        for _ in 0..limit {
+            envelope.next_level();
+        }
+        prop_assert!(envelope.is_finished());
+        prop_assert_eq!(envelope.next_level(), 0.0);
+    }
+}
@@ -0,0 +1,62 @@
+// Euclidean tracks as written in presets, and the patterns they play
+
+use rodio_synth::euclid::{EuclidSettings, EuclidTrack};
+use rodio_synth::sequencer::Step;
+
+fn pattern(track: &EuclidTrack) -> String {
+    (0..track.steps as usize).map(|index| if track.is_hit(index) { 'x' } else { '.' }).collect()
+}
+
+fn track(hits: u32, steps: u32, rotation: u32) -> EuclidTrack {
+    EuclidTrack { hits, steps, rotation, note: Step(None) }
+}
+
+#[test]
+fn hits_spread_evenly_over_the_steps() {
+    assert_eq!(pattern(&track(3, 8, 0)), "x..x..x.");
+    assert_eq!(pattern(&track(5, 8, 0)), "x.x.xx.x"); // Bjorklund's x.xx.xx., rotated
+    assert_eq!(pattern(&track(4, 16, 0)), "x...x...x...x...");
+    assert_eq!(pattern(&track(0, 4, 0)), "....");
+}
+
+#[test]
+fn rotation_shifts_the_pattern_later() {
+    assert_eq!(pattern(&track(3, 8, 1)), ".x..x..x");
+    assert_eq!(pattern(&track(3, 8, 9)), ".x..x..x");
+}
+
+#[test]
+fn out_of_range_counts_are_clamped() {
+    assert_eq!(pattern(&track(12, 8, 0)), "xxxxxxxx");
+    let empty = track(1, 0, 0);
+    assert!(empty.is_hit(0) && empty.is_hit(5));
+}
+
+#[test]
+fn tracks_parse_from_a_preset() {
+    let settings: EuclidSettings = toml::from_str(
+        r#"
+        enabled = true
+        [[tracks]]
+        hits = 5
+        steps = 13
+        rotation = 2
+        note = "F#2"
+        [[tracks]]
+        note = "-"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(settings.tracks.len(), 2);
+    assert_eq!(settings.tracks[0], EuclidTrack { hits: 5, steps: 13, rotation: 2, note: Step(Some(42)) });
+    assert_eq!(settings.tracks[1], EuclidTrack { note: Step(None), ..EuclidTrack::default() });
+}
+
+#[test]
+fn malformed_track_notes_fail_to_load() {
+    for note in ["H2", "C", "C99", "x"] {
+        let toml = format!("[[tracks]]\nnote = \"{}\"", note);
+        let error = toml::from_str::<EuclidSettings>(&toml).unwrap_err();
+        assert!(error.to_string().contains("is not a note name"), "{}: {}", note, error);
+    }
+}
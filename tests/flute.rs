@@ -0,0 +1,54 @@
+// The blown-pipe voices: every voice gets its pipe as it starts, empty, whether it's
+// the first to play in its slot or takes over a pipe another note has finished with
+
+use rodio_synth::flute::{Flute, FluteSettings};
+use rodio_synth::preset::Preset;
+use rodio_synth::stereo::Frame;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000;
+const NOTE: usize = 4_000; // Frames each note is held
+const GAP: usize = 2_000; // Frames after its release, long enough to finish
+
+fn flute() -> Preset {
+    Preset::parse("[envelope]\nattack = 0.05\nrelease = 0.05\n[flute]\nenabled = true").expect("Test preset should parse")
+}
+
+// Plays `notes` one after another on a single voice, returning what each sounded
+fn play(notes: &[u8]) -> Vec<Vec<Frame>> {
+    let mut commands = Vec::new();
+    for (index, &note) in notes.iter().enumerate() {
+        let at = index * (NOTE + GAP);
+        commands.push((at, SynthCommand::NoteOn(note)));
+        commands.push((at + NOTE, SynthCommand::NoteOff(note)));
+    }
+    let frames = Synthesizer::offline(SAMPLE_RATE, &flute()).with_polyphony(1).render(&commands, notes.len() * (NOTE + GAP));
+    frames.chunks(NOTE + GAP).map(<[Frame]>::to_vec).collect()
+}
+
+#[test]
+fn the_pipe_speaks() {
+    let note = &play(&[69])[0];
+    let peak = note[..NOTE].iter().map(|frame| frame[0].abs()).fold(0.0, f32::max);
+    assert!(peak > 0.05, "{}", peak);
+    assert!(note[NOTE + GAP / 2..].iter().all(|frame| frame[0].abs() < 1e-3), "It should have stopped");
+}
+
+#[test]
+fn every_note_in_the_slot_sounds() {
+    for note in play(&[69, 57, 69]) {
+        assert!(note[..NOTE].iter().any(|frame| frame[0].abs() > 0.05));
+    }
+}
+
+#[test]
+fn a_reset_pipe_plays_as_a_new_one() {
+    let settings = FluteSettings::default();
+    let blow = |flute: &mut Flute, frequency| (0..2_000).map(|_| flute.next_sample(frequency, 1.0, &settings, SAMPLE_RATE)).collect::<Vec<_>>();
+    let fresh = blow(&mut Flute::new(SAMPLE_RATE, 7), 440.0);
+
+    let mut reused = Flute::new(SAMPLE_RATE, 3);
+    blow(&mut reused, 220.0);
+    reused.reset(7);
+    assert_eq!(blow(&mut reused, 440.0), fresh);
+}
@@ -0,0 +1,41 @@
+// The spectral freeze: once frozen, what was playing carries on as a pad after the
+// input stops, until it's thawed
+#![cfg(feature = "freeze")]
+
+use rodio_synth::effects::freeze::{Freeze, FreezeSettings};
+use rodio_synth::effects::Effect;
+
+const SAMPLE_RATE: u32 = 48_000;
+
+// The loudest of `frames` frames of the freeze's output with silence going in
+fn peak_of_silence(freeze: &mut Freeze, frames: usize) -> f32 {
+    (0..frames).map(|_| freeze.process([0.0; 2])[0].abs()).fold(0.0, f32::max)
+}
+
+fn play_sine(freeze: &mut Freeze) {
+    for i in 0..8_192 {
+        let sample = (2.0 * std::f32::consts::PI * 440.0 * i as f32 / SAMPLE_RATE as f32).sin() * 0.5;
+        freeze.process([sample; 2]);
+    }
+}
+
+#[test]
+fn frozen_sound_holds_after_the_input_stops() {
+    let mut freeze = Freeze::new(FreezeSettings::default(), SAMPLE_RATE);
+    play_sine(&mut freeze);
+    assert!(freeze.set_param("frozen", 1.0));
+    peak_of_silence(&mut freeze, 4_096); // The first grains fade in
+    let held = peak_of_silence(&mut freeze, 48_000);
+    assert!(held > 0.1, "{}", held);
+
+    freeze.set_param("frozen", 0.0);
+    peak_of_silence(&mut freeze, 4_096); // Grains already started finish
+    assert_eq!(peak_of_silence(&mut freeze, 4_096), 0.0);
+}
+
+#[test]
+fn nothing_is_held_until_frozen() {
+    let mut freeze = Freeze::new(FreezeSettings::default(), SAMPLE_RATE);
+    play_sine(&mut freeze);
+    assert_eq!(peak_of_silence(&mut freeze, 8_192), 0.0);
+}
@@ -0,0 +1,60 @@
+// Parameter glides: only recorded parameters glide, and they arrive at their targets
+
+use rodio_synth::glide::{Glides, SmoothingCurve, SmoothingSettings};
+
+const SAMPLE_RATE: u32 = 48_000;
+
+// Runs the glides for `frames` frames and returns the last value handed out for `path`
+fn run(glides: &mut Glides, path: &str, frames: usize) -> Option<f32> {
+    let mut last = None;
+    for _ in 0..frames {
+        glides.advance(|moved, value| {
+            if moved == path {
+                last = Some(value);
+            }
+        });
+    }
+    last
+}
+
+#[test]
+fn unrecorded_parameters_are_left_to_the_caller() {
+    let mut glides = Glides::new(SAMPLE_RATE, &SmoothingSettings::default());
+    assert!(!glides.set("filter.cutoff", 500.0));
+    assert!(!glides.set("filter.cutoff", 600.0)); // Still not recorded by trying
+    assert_eq!(run(&mut glides, "filter.cutoff", 1000), None);
+}
+
+#[test]
+fn recorded_parameters_glide_to_their_target() {
+    let settings = SmoothingSettings { curve: SmoothingCurve::Linear, time: 10.0 };
+    let mut glides = Glides::new(SAMPLE_RATE, &settings);
+    glides.start_at("filter.cutoff", 1000.0);
+    assert!(glides.set("filter.cutoff", 2000.0));
+
+    let halfway = run(&mut glides, "filter.cutoff", 240).unwrap();
+    assert!(halfway > 1000.0 && halfway < 2000.0, "{}", halfway);
+    assert_eq!(run(&mut glides, "filter.cutoff", 480), Some(2000.0));
+}
+
+#[test]
+fn start_at_moves_a_recorded_parameter_without_gliding() {
+    let mut glides = Glides::new(SAMPLE_RATE, &SmoothingSettings::default());
+    glides.start_at("pitch.bend", 0.0);
+    glides.start_at("pitch.bend", 2.0);
+    assert_eq!(run(&mut glides, "pitch.bend", 100), None);
+
+    assert!(glides.set("pitch.bend", 3.0));
+    let first = run(&mut glides, "pitch.bend", 32).unwrap(); // The next block
+    assert!(first > 2.0 && first < 3.0, "{}", first);
+}
+
+#[test]
+fn pitch_bend_and_the_wheel_are_smoothed() {
+    let settings = SmoothingSettings::default();
+    for path in ["pitch.bend", "pitch.wheel", "filter.cutoff", "parts.1.volume"] {
+        assert!(settings.applies_to(path), "{}", path);
+    }
+    assert!(!settings.applies_to("pitch.bend_range"));
+    assert!(!SmoothingSettings { time: 0.0, ..settings }.applies_to("pitch.wheel"));
+}
@@ -0,0 +1,107 @@
+// Golden-file tests: short command sequences are rendered offline and compared with
+// reference WAVs in tests/golden/, so a change to mixing, envelopes or filters can't
+// alter the sound unnoticed. When a change is meant to alter it, listen to the new
+// output and update the references with `UPDATE_GOLDEN=1 cargo test --test golden`.
+//
+// Builds with the `f64` feature have references of their own in tests/golden/f64/: their
+// phases don't drift the way they do in f32, which moves a square wave's edges by a sample
+// here and there within half a second. Update those with `--features f64` as well.
+
+use std::path::PathBuf;
+
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer, Waveform};
+
+const SAMPLE_RATE: u32 = 22_050; // Low, to keep the reference files small
+// Largest difference per sample, room for float rounding across platforms
+const TOLERANCE: f32 = 1e-4;
+const REFERENCES: &str = if cfg!(feature = "f64") { "golden/f64" } else { "golden" };
+
+// Renders `seconds` of stereo audio as interleaved samples, with command times in seconds
+fn render(preset: &str, waveform: Waveform, commands: Vec<(f32, SynthCommand)>, seconds: f32) -> Vec<f32> {
+    let preset = Preset::parse(preset).expect("Test preset should parse");
+    let frame = |seconds: f32| (seconds * SAMPLE_RATE as f32).round() as usize;
+    let commands: Vec<_> = commands.into_iter().map(|(at, command)| (frame(at), command)).collect();
+    let mut synth = Synthesizer::offline(SAMPLE_RATE, &preset).with_waveform(waveform);
+    synth.render(&commands, frame(seconds)).concat()
+}
+
+fn check(name: &str, samples: &[f32]) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", REFERENCES, &format!("{}.wav", name)].iter().collect();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).expect("Failed to create reference");
+        for &sample in samples {
+            writer.write_sample(sample).expect("Failed to write reference");
+        }
+        writer.finalize().expect("Failed to write reference");
+        return;
+    }
+
+    let mut reader = hound::WavReader::open(&path).unwrap_or_else(|e| panic!("No reference at {}: {} (run with UPDATE_GOLDEN=1)", path.display(), e));
+    let reference: Vec<f32> = reader.samples::<f32>().map(|sample| sample.expect("Failed to read reference")).collect();
+    assert_eq!(samples.len(), reference.len(), "{}: length differs from the reference", name);
+    if let Some((index, (got, expected))) = samples.iter().zip(&reference).enumerate().find(|(_, (got, expected))| (*got - *expected).abs() > TOLERANCE) {
+        panic!("{}: frame {} ({} channel) is {} where the reference has {}", name, index / 2, ["left", "right"][index % 2], got, expected);
+    }
+}
+
+#[test]
+fn sine_chord() {
+    let commands = vec![
+        (0.0, SynthCommand::NoteOn(60)),
+        (0.0, SynthCommand::NoteOn(64)),
+        (0.0, SynthCommand::NoteOn(67)),
+        (0.25, SynthCommand::NoteOff(60)),
+        (0.25, SynthCommand::NoteOff(64)),
+        (0.25, SynthCommand::NoteOff(67)),
+    ];
+    check("sine_chord", &render("", Waveform::Sine, commands, 0.5));
+}
+
+#[test]
+fn repeated_note_release() {
+    let preset = "
+        [envelope]
+        attack = 0.02
+        release = 0.15
+    ";
+    let commands = vec![
+        (0.0, SynthCommand::NoteOn(57)),
+        (0.1, SynthCommand::NoteOff(57)),
+        (0.15, SynthCommand::NoteOn(57)), // While the first is still releasing
+        (0.3, SynthCommand::NoteOff(57)),
+    ];
+    check("repeated_note_release", &render(preset, Waveform::Square, commands, 0.5));
+}
+
+#[test]
+fn filtered_saw() {
+    let preset = "
+        [filter]
+        enabled = true
+        cutoff = 800.0
+        resonance = 2.0
+    ";
+    let commands = vec![(0.0, SynthCommand::NoteOn(48)), (0.3, SynthCommand::NoteOff(48))];
+    check("filtered_saw", &render(preset, Waveform::Saw, commands, 0.5));
+}
+
+#[test]
+fn effects_chain() {
+    let preset = "
+        [[effects]]
+        type = \"distortion\"
+        drive = 6.0
+
+        [[effects]]
+        type = \"chorus\"
+    ";
+    let commands = vec![(0.0, SynthCommand::NoteOn(52)), (0.3, SynthCommand::NoteOff(52))];
+    check("effects_chain", &render(preset, Waveform::Triangle, commands, 0.5));
+}
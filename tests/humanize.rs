@@ -0,0 +1,55 @@
+// The humanizer's imperfections, and how the synth plays a humanized note's detune
+
+use rodio_synth::humanize::{HumanizeSettings, Humanizer};
+use rodio_synth::preset::Preset;
+use rodio_synth::stereo::Frame;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+fn render(commands: Vec<SynthCommand>) -> Vec<Frame> {
+    let commands: Vec<_> = commands.into_iter().map(|command| (0, command)).collect();
+    Synthesizer::offline(SAMPLE_RATE, &Preset::default()).render(&commands, 2_000)
+}
+
+#[test]
+fn notes_stray_only_as_far_as_the_settings_allow() {
+    let settings = HumanizeSettings { velocity: 0.2, pitch: 30.0, seed: Some(1), ..HumanizeSettings::default() };
+    let mut humanizer = Humanizer::new(settings);
+    for _ in 0..100 {
+        match humanizer.before_note(0.5) {
+            Some(SynthCommand::SetVelocity(velocity)) => assert!((0.3..=0.7).contains(&velocity), "{}", velocity),
+            command => panic!("{:?}", command),
+        }
+        match humanizer.note_on(60) {
+            SynthCommand::DetunedNoteOn(60, semitones) => assert!(semitones.abs() <= 0.3, "{}", semitones),
+            command => panic!("{:?}", command),
+        }
+    }
+}
+
+#[test]
+fn nothing_strays_by_default() {
+    let mut humanizer = Humanizer::new(HumanizeSettings::default());
+    assert_eq!(humanizer.before_note(0.5), None);
+    assert_eq!(humanizer.note_on(60), SynthCommand::NoteOn(60));
+    assert_eq!(humanizer.after_note(0.5), None);
+}
+
+#[test]
+fn the_same_seed_strays_the_same_way() {
+    let settings = HumanizeSettings { timing: 20.0, pitch: 30.0, seed: Some(7), ..HumanizeSettings::default() };
+    let (mut first, mut second) = (Humanizer::new(settings.clone()), Humanizer::new(settings));
+    for _ in 0..10 {
+        assert_eq!(first.delay(), second.delay());
+        assert_eq!(first.note_on(60), second.note_on(60));
+    }
+}
+
+#[test]
+fn a_detuned_note_leaves_the_others_in_tune() {
+    // A semitone's detune plays A4 at the pitch of B flat, and the note after it is in tune
+    let detuned = render(vec![SynthCommand::DetunedNoteOn(69, 1.0), SynthCommand::NoteOn(60)]);
+    assert_eq!(detuned, render(vec![SynthCommand::NoteOn(70), SynthCommand::NoteOn(60)]));
+    assert_eq!(render(vec![SynthCommand::DetunedNoteOn(69, 0.0)]), render(vec![SynthCommand::NoteOn(69)]));
+}
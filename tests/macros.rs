@@ -0,0 +1,30 @@
+// Macros: a target the synth has no parameter for is left out when the preset loads,
+// and the macro goes on moving the rest.
+
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+#[test]
+fn a_macro_moves_its_targets_past_an_unknown_one() {
+    let preset = Preset::parse(
+        r#"
+        [[macros]]
+        value = 1.0
+        targets = [
+            { param = "filter.cutof", min = 100.0, max = 8000.0 },
+            { param = "master.volume", min = 0.0, max = 1.0 },
+        ]
+        "#,
+    )
+    .expect("Test preset should parse");
+    let mut synth = Synthesizer::offline(SAMPLE_RATE, &preset);
+
+    let loud = synth.render(&[(0, SynthCommand::NoteOn(60))], SAMPLE_RATE as usize / 2);
+    assert!(loud.iter().any(|frame| frame[0].abs() > 0.01), "The note should sound with the macro up");
+
+    let quiet = synth.render(&[(0, SynthCommand::SetMacro(0, 0.0))], SAMPLE_RATE as usize);
+    let tail = &quiet[quiet.len() - 100..];
+    assert!(tail.iter().all(|frame| frame[0].abs() < 1e-4), "The macro should have turned the volume down");
+}
@@ -0,0 +1,79 @@
+// Reading Standard MIDI files as timed synth commands, and writing them back
+
+use rodio_synth::midi_file;
+use rodio_synth::SynthCommand;
+
+// A format 0 file at 96 ticks a beat: a tempo of `micros` per beat, then middle C held
+// for one beat
+fn one_note(micros: u32) -> Vec<u8> {
+    let mut track = vec![0x00, 0xFF, 0x51, 0x03];
+    track.extend_from_slice(&micros.to_be_bytes()[1..]);
+    track.extend_from_slice(&[0x00, 0x90, 60, 100, 0x60, 0x80, 60, 64, 0x00, 0xFF, 0x2F, 0x00]);
+    let mut bytes = b"MThd\0\0\0\x06\0\0\0\x01\0\x60MTrk".to_vec();
+    bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    bytes.extend(track);
+    bytes
+}
+
+fn read(name: &str, bytes: &[u8]) -> Vec<(f32, SynthCommand)> {
+    let path = std::env::temp_dir().join(format!("rodio-synth-{}-{}.mid", name, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let commands = midi_file::read(&path);
+    let _ = std::fs::remove_file(&path);
+    commands.unwrap()
+}
+
+#[test]
+fn tempo_times_the_notes() {
+    let commands = read("tempo", &one_note(1_000_000));
+    assert_eq!(
+        commands,
+        [
+            (0.0, SynthCommand::SetTempo(60.0)),
+            (0.0, SynthCommand::SetVelocity(100.0 / 127.0)),
+            (0.0, SynthCommand::NoteOn(60)),
+            (1.0, SynthCommand::NoteOff(60)),
+        ]
+    );
+}
+
+#[test]
+fn a_zero_tempo_is_ignored() {
+    let commands = read("zero-tempo", &one_note(0));
+    assert!(commands.iter().all(|(_, command)| !matches!(command, SynthCommand::SetTempo(_))), "{:?}", commands);
+    assert_eq!(commands.last(), Some(&(0.5, SynthCommand::NoteOff(60)))); // Still 120 BPM
+}
+
+#[test]
+fn malformed_files_are_invalid_data() {
+    let path = std::env::temp_dir().join(format!("rodio-synth-malformed-{}.mid", std::process::id()));
+    std::fs::write(&path, b"MThd, but not really").unwrap();
+    let error = midi_file::read(&path).unwrap_err();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn written_files_read_back() {
+    let commands = vec![
+        (0.0, SynthCommand::SetVelocity(100.0 / 127.0)),
+        (0.0, SynthCommand::NoteOn(64)),
+        (0.25, SynthCommand::NoteOff(64)),
+        (0.5, SynthCommand::ChannelNoteOn(3, 40)),
+        (1.0, SynthCommand::ChannelNoteOff(3, 40)),
+    ];
+    let path = std::env::temp_dir().join(format!("rodio-synth-written-{}.mid", std::process::id()));
+    midi_file::write(&commands, 120.0, &path).unwrap();
+    let read = midi_file::read(&path);
+    let _ = std::fs::remove_file(&path);
+
+    // Each note on comes back with its velocity, and the file starts with its tempo
+    let is_setting = |command: &SynthCommand| matches!(command, SynthCommand::SetVelocity(_) | SynthCommand::SetTempo(_));
+    let notes: Vec<_> = read.unwrap().into_iter().filter(|(_, command)| !is_setting(command)).collect();
+    let expected: Vec<_> = commands.iter().filter(|(_, command)| !is_setting(command)).collect();
+    assert_eq!(notes.len(), expected.len(), "{:?}", notes);
+    for ((at, command), (expected_at, expected)) in notes.iter().zip(expected) {
+        assert_eq!(command, expected);
+        assert!((at - expected_at).abs() < 0.001, "{:?} at {}, not {}", command, at, expected_at);
+    }
+}
@@ -0,0 +1,120 @@
+// Switching presets through `switch::build_presets`, which builds them before they reach
+// the synth: the built preset plays just as one loaded on the synth's own thread would.
+
+use std::sync::mpsc;
+
+use rodio_synth::diagnostics::{self, Diagnostic};
+use rodio_synth::preset::Preset;
+use rodio_synth::stereo::Frame;
+use rodio_synth::switch::{build_presets, BuiltSwitch, PresetSwitch, KEEP_SECONDS};
+use rodio_synth::{SynthCommand, Synthesizer, DEFAULT_POLYPHONY};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+fn slow_attack() -> Box<Preset> {
+    let mut preset = Preset::default();
+    preset.envelope.attack = 0.5;
+    Box::new(preset)
+}
+
+// Sends `commands` through the builder, then plays what comes out of it on a synth that
+// starts with the default preset
+fn play(commands: Vec<SynthCommand>, frames: usize) -> Vec<Frame> {
+    let (tx, rx) = mpsc::channel();
+    let built = build_presets(rx, SAMPLE_RATE, DEFAULT_POLYPHONY);
+    let (synth_tx, synth_rx) = mpsc::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), synth_rx);
+    for command in commands {
+        tx.send(command).expect("The builder should be listening");
+        synth_tx.send(built.recv().expect("The builder should pass every command on")).unwrap();
+    }
+    (0..frames).map(|_| synth.render_frame()).collect()
+}
+
+#[test]
+fn preset_commands_arrive_built() {
+    let (tx, rx) = mpsc::channel();
+    let built = build_presets(rx, SAMPLE_RATE, DEFAULT_POLYPHONY);
+    tx.send(SynthCommand::LoadPreset(slow_attack())).unwrap();
+    tx.send(SynthCommand::CrossfadePreset(slow_attack(), 2.0)).unwrap();
+    tx.send(SynthCommand::At(10, Box::new(SynthCommand::QueuePreset(slow_attack())))).unwrap();
+    tx.send(SynthCommand::NoteOn(60)).unwrap();
+
+    let switch = |command| match command {
+        SynthCommand::Built(built) => built.switch,
+        command => panic!("{:?}", command),
+    };
+    assert_eq!(switch(built.recv().unwrap()), BuiltSwitch::Load);
+    assert_eq!(switch(built.recv().unwrap()), BuiltSwitch::Crossfade(2.0));
+    assert_eq!(built.recv().unwrap(), SynthCommand::AtNext(10));
+    assert_eq!(switch(built.recv().unwrap()), BuiltSwitch::Queue);
+    assert_eq!(built.recv().unwrap(), SynthCommand::NoteOn(60));
+}
+
+#[test]
+fn built_preset_plays_like_one_loaded_in_place() {
+    let mut synth = Synthesizer::offline(SAMPLE_RATE, &Preset::default());
+    synth.load_preset(&slow_attack());
+    let commands = [(0, SynthCommand::NoteOn(69))];
+    let expected = synth.render(&commands, 400);
+
+    let frames = play(vec![SynthCommand::LoadPreset(slow_attack()), SynthCommand::NoteOn(69)], 400);
+    assert_eq!(frames, expected);
+    assert!(frames[399][0].abs() < 0.2, "The slow attack should still be rising");
+}
+
+#[test]
+fn queued_built_preset_waits_for_the_next_note() {
+    let commands = vec![SynthCommand::QueuePreset(slow_attack()), SynthCommand::NoteOn(69)];
+    let queued = play(commands, 400);
+    let loaded = play(vec![SynthCommand::LoadPreset(slow_attack()), SynthCommand::NoteOn(69)], 400);
+    assert_eq!(queued, loaded);
+
+    // The default preset's quick attack sounds otherwise, so the switch did happen
+    let default = play(vec![SynthCommand::NoteOn(69)], 400);
+    assert_ne!(default, loaded);
+}
+
+#[test]
+fn kept_notes_are_released_after_keep_seconds() {
+    let (_tx, rx) = mpsc::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), rx).with_preset_switch(PresetSwitch::Keep, 0.0);
+    let commands = [(0, SynthCommand::NoteOn(69)), (100, SynthCommand::LoadPreset(slow_attack()))];
+    let frames = synth.render(&commands, SAMPLE_RATE as usize);
+    assert!(frames[SAMPLE_RATE as usize - 1][0].abs() > 0.0, "The old preset should still hold its note");
+
+    // Never let go of, the note is released once the old preset has held it long enough
+    let frames = synth.render(&[], ((KEEP_SECONDS + 2.0) * SAMPLE_RATE as f32) as usize);
+    assert!(frames[frames.len() - 1000..].iter().all(|frame| frame == &[0.0, 0.0]));
+}
+
+#[test]
+fn keeping_too_many_presets_switches_with_a_cut() {
+    let (_tx, rx) = mpsc::channel();
+    let (diagnostics_tx, diagnostics) = diagnostics::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), rx)
+        .with_preset_switch(PresetSwitch::Keep, 0.0)
+        .with_diagnostics(diagnostics_tx);
+    // Each preset switched away from holds a note, so none of them is done yet
+    let commands: Vec<_> = (0..20)
+        .flat_map(|i| [(i * 10, SynthCommand::NoteOn(48 + i as u8)), (i * 10 + 1, SynthCommand::LoadPreset(slow_attack()))])
+        .collect();
+    synth.render(&commands, 400);
+
+    let cuts = diagnostics.try_iter().filter(|d| matches!(d, Diagnostic::SwitchCut(_))).collect::<Vec<_>>();
+    assert!(!cuts.is_empty(), "Some of the switches should have been cuts");
+    assert!(cuts.iter().all(|cut| cut == &Diagnostic::SwitchCut(PresetSwitch::Keep)));
+}
+
+#[test]
+fn crossfading_over_too_many_presets_switches_with_a_cut() {
+    let (_tx, rx) = mpsc::channel();
+    let (diagnostics_tx, diagnostics) = diagnostics::channel();
+    let mut synth = Synthesizer::new(SAMPLE_RATE, &Preset::default(), rx).with_diagnostics(diagnostics_tx);
+    let commands: Vec<_> = (0..20).map(|i| (i, SynthCommand::CrossfadePreset(slow_attack(), 2.0))).collect();
+    synth.render(&commands, 100);
+
+    let cuts = diagnostics.try_iter().filter(|d| matches!(d, Diagnostic::SwitchCut(_))).collect::<Vec<_>>();
+    assert!(!cuts.is_empty(), "Some of the switches should have been cuts");
+    assert!(cuts.iter().all(|cut| cut == &Diagnostic::SwitchCut(PresetSwitch::Crossfade)));
+}
@@ -0,0 +1,58 @@
+// Parsing of scale roots, as written in presets, and snapping notes into the scale
+
+use rodio_synth::notes::parse_pitch_class;
+use rodio_synth::scale::{PitchClass, ScaleKind, ScaleSettings};
+
+fn settings(toml: &str) -> Result<ScaleSettings, toml::de::Error> {
+    toml::from_str(toml)
+}
+
+#[test]
+fn pitch_classes_parse_with_and_without_accidentals() {
+    assert_eq!(parse_pitch_class("C"), Some(0));
+    assert_eq!(parse_pitch_class("F#"), Some(6));
+    assert_eq!(parse_pitch_class("Bb"), Some(10));
+    assert_eq!(parse_pitch_class(" a "), Some(9));
+    assert_eq!(parse_pitch_class("Cb"), Some(11));
+}
+
+#[test]
+fn malformed_pitch_classes_are_rejected() {
+    for name in ["", "H", "C4", "F##", "#"] {
+        assert_eq!(parse_pitch_class(name), None, "{:?}", name);
+    }
+}
+
+#[test]
+fn root_round_trips_through_a_preset() {
+    let scale = settings("enabled = true\nroot = \"Eb\"\nscale = \"minor\"").unwrap();
+    assert_eq!(scale.root, PitchClass(3));
+    assert_eq!(scale.scale, ScaleKind::Minor);
+
+    let written = toml::to_string(&scale).unwrap();
+    assert!(written.contains("root = \"D#\""), "{}", written);
+    assert_eq!(settings(&written).unwrap(), scale);
+}
+
+#[test]
+fn malformed_root_fails_to_load() {
+    let error = settings("root = \"H\"").unwrap_err();
+    assert!(error.to_string().contains("'H' is not a pitch class"), "{}", error);
+    assert!(settings("root = \"C#4\"").is_err());
+}
+
+#[test]
+fn quantize_snaps_to_the_nearest_scale_note_preferring_the_lower() {
+    let scale = ScaleSettings { enabled: true, root: PitchClass(2), scale: ScaleKind::Major, ..ScaleSettings::default() };
+    // D major: D E F# G A B C#
+    assert_eq!(scale.quantize(62), 62); // D4 is in the scale
+    assert_eq!(scale.quantize(65), 64); // F4 sits between E4 and F#4
+    assert_eq!(scale.quantize(72), 71); // C5 sits between B4 and C#5
+    assert_eq!(scale.quantize(0), 1);   // Nothing below C-1, so up to C#-1
+}
+
+#[test]
+fn empty_custom_scale_passes_notes_through() {
+    let scale = ScaleSettings { scale: ScaleKind::Custom, degrees: Vec::new(), ..ScaleSettings::default() };
+    assert_eq!(scale.quantize(61), 61);
+}
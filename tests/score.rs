@@ -0,0 +1,49 @@
+// Parsing of text scores and the timing of the commands they play
+
+use rodio_synth::score::{Score, ScoreEvent};
+use rodio_synth::SynthCommand;
+
+#[test]
+fn notes_chords_and_rests_take_the_current_length() {
+    let score = Score::parse("length 1/8\nC4 E4:1/4  # a comment\nr C4+E4+G4:1/2 r:1/1").unwrap();
+    assert_eq!(
+        score.events,
+        [
+            ScoreEvent::Notes(vec![60], 0.5),
+            ScoreEvent::Notes(vec![64], 1.0),
+            ScoreEvent::Rest(0.5),
+            ScoreEvent::Notes(vec![60, 64, 67], 2.0),
+            ScoreEvent::Rest(4.0),
+        ]
+    );
+}
+
+#[test]
+fn tempo_changes_time_what_follows() {
+    let score = Score::parse("C4 tempo 60 C4").unwrap();
+    assert_eq!(score.events[1], ScoreEvent::Tempo(60.0));
+    assert_eq!(score.seconds(120.0), 1.5);
+
+    let commands = score.commands(120.0);
+    assert_eq!(commands[0], (0.0, SynthCommand::NoteOn(60)));
+    assert_eq!(commands[2], (0.5, SynthCommand::SetTempo(60.0)));
+    assert_eq!(commands[3], (0.5, SynthCommand::NoteOn(60)));
+}
+
+#[test]
+fn tempos_outside_20_to_400_are_rejected() {
+    assert!(Score::parse("tempo 20\ntempo 400").is_ok());
+    for tempo in ["0.0001", "0", "-60", "19.9", "400.5", "inf", "NaN", "fast"] {
+        let error = Score::parse(&format!("C4\ntempo {}", tempo)).unwrap_err();
+        assert_eq!(error, "line 2: tempo needs a number of beats per minute from 20 to 400", "{}", tempo);
+    }
+    assert!(Score::parse("tempo").is_err());
+}
+
+#[test]
+fn malformed_items_report_their_line() {
+    assert_eq!(Score::parse("C4\n\nH4").unwrap_err(), "line 3: 'H4' is not a note name or rest");
+    assert_eq!(Score::parse("C4+X").unwrap_err(), "line 1: 'X' is not a note name or rest");
+    assert!(Score::parse("C4:1/0").unwrap_err().starts_with("line 1:"));
+    assert!(Score::parse("length").unwrap_err().starts_with("line 1: length needs"));
+}
@@ -0,0 +1,31 @@
+// Controls played in steps, glided between them and handed on a block at a time
+
+use rodio_synth::smoothed::SmoothedControl;
+
+// What `control` hands on over `frames` frames, with the frames it does so on
+fn run(control: &mut SmoothedControl, frames: usize) -> Vec<(usize, f32)> {
+    (0..frames).filter_map(|frame| control.advance().map(|value| (frame, value))).collect()
+}
+
+#[test]
+fn moves_are_handed_on_a_block_at_a_time() {
+    let mut control = SmoothedControl::new(0.0, 10.0, 8_000);
+    assert!(run(&mut control, 100).is_empty(), "At rest, nothing is set");
+
+    control.set(1.0);
+    let moves = run(&mut control, 800);
+    assert!(moves.windows(2).all(|pair| (pair[1].0 - pair[0].0) % 32 == 0 && pair[1].1 > pair[0].1));
+    let &(_, last) = moves.last().unwrap();
+    assert!((last - 1.0).abs() < 1e-3, "It should have settled: {}", last);
+    assert!(moves[0].1 < 0.5, "It should glide there: {}", moves[0].1);
+    assert!(run(&mut control, 800).len() <= 2, "Once settled, nothing more is set");
+}
+
+#[test]
+fn the_rest_can_be_handed_on_at_once() {
+    let mut control = SmoothedControl::new(0.0, 0.0, 8_000).with_rest_applied();
+    assert_eq!(run(&mut control, 100), [(0, 0.0)]);
+    control.set(0.5);
+    assert_eq!(control.coefficient(), 1.0); // No smoothing jumps straight there
+    assert_eq!(run(&mut control, 32), [(28, 0.5)]); // At the next block
+}
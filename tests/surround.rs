@@ -0,0 +1,61 @@
+// Playing in surround: the voices are placed round the room, and the front pair plays what
+// the stereo master chain adds to them
+
+use rodio_synth::effects::chorus::ChorusSettings;
+use rodio_synth::effects::EffectConfig;
+use rodio_synth::preset::Preset;
+use rodio_synth::surround::Layout;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+#[test]
+fn master_effect_tails_play_from_the_front() {
+    let mut preset = Preset { effects: vec![EffectConfig::Chorus(ChorusSettings::default())], ..Preset::default() };
+    preset.envelope.release = 0.001;
+    let mut synth = Synthesizer::offline(8_000, &preset).with_surround(Layout::Quad);
+    synth.render(&[(0, SynthCommand::NoteOn(69)), (800, SynthCommand::NoteOff(69))], 850);
+
+    // The note has ended, but the chorus still plays its delayed copies
+    let mut tail = 0.0;
+    for _ in 0..50 {
+        let stereo = synth.render_frame();
+        let surround = synth.surround_output().expect("Playing in surround");
+        assert_eq!(&surround[..2], &stereo[..]);
+        assert_eq!(&surround[2..], &[0.0, 0.0]);
+        tail += stereo[0].abs();
+    }
+    assert!(tail > 0.0, "The chorus tail should sound");
+}
+
+#[test]
+fn the_front_pair_plays_no_dry_voice_against_a_half_mixed_chorus() {
+    const FRAMES: usize = 2_000;
+    let chorus = |mix| Preset { effects: vec![EffectConfig::Chorus(ChorusSettings { mix, ..ChorusSettings::default() })], ..Preset::default() };
+    // The front pair of a held note, frame by frame, or the stereo output without surround
+    let front = |preset: &Preset, layout: Option<Layout>| -> Vec<[f32; 2]> {
+        let mut synth = Synthesizer::offline(8_000, preset);
+        if let Some(layout) = layout {
+            synth = synth.with_surround(layout);
+        }
+        synth.render(&[(0, SynthCommand::SetVelocity(0.5)), (0, SynthCommand::NoteOn(69))], 1); // Soft enough not to clip
+        (0..FRAMES)
+            .map(|_| {
+                let stereo = synth.render_frame();
+                synth.surround_output().map_or(stereo, |output| [output[0], output[1]])
+            })
+            .collect()
+    };
+
+    let half_mixed = front(&chorus(0.5), Some(Layout::Quad));
+    let no_effects = front(&Preset::default(), Some(Layout::Quad));
+    let all_wet = front(&chorus(1.0), None);
+
+    // What the chorus adds to the voices placed in the room is half its wet signal, with
+    // none of the dry voice taken away
+    for (frame, ((half, none), wet)) in half_mixed.iter().zip(&no_effects).zip(&all_wet).enumerate() {
+        for channel in 0..2 {
+            let added = half[channel] - none[channel];
+            assert!((added - wet[channel] * 0.5).abs() < 1e-4, "Frame {} channel {}: added {}, half the wet signal is {}", frame, channel, added, wet[channel] * 0.5);
+        }
+    }
+    assert!(all_wet.iter().any(|frame| frame[0].abs() > 0.01), "The chorus should sound");
+}
@@ -0,0 +1,62 @@
+// Insert effects in every voice: each note hears its chain afresh, with the edits made since
+// the preset loaded, however many notes the chain has been through before
+
+use rodio_synth::effects::bitcrusher::BitcrusherSettings;
+use rodio_synth::effects::chorus::ChorusSettings;
+use rodio_synth::effects::distortion::DistortionSettings;
+use rodio_synth::effects::flanger::FlangerSettings;
+use rodio_synth::effects::phaser::PhaserSettings;
+use rodio_synth::effects::{EffectConfig, EffectsChain};
+use rodio_synth::oversample::Oversampling;
+use rodio_synth::param_id::ParamId;
+use rodio_synth::preset::Preset;
+use rodio_synth::stereo::Frame;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000;
+const NOTE: usize = 2_000; // Frames a note is held for
+
+fn preset(mix: f32) -> Preset {
+    Preset {
+        voice_effects: vec![
+            EffectConfig::Chorus(ChorusSettings { mix, ..ChorusSettings::default() }),
+            EffectConfig::Flanger(FlangerSettings::default()),
+        ],
+        ..Preset::default()
+    }
+}
+
+fn note(synth: &mut Synthesizer) -> Vec<Frame> {
+    synth.render(&[(0, SynthCommand::NoteOn(69))], NOTE)
+}
+
+// A second of a sawtooth through `chain`
+fn run(chain: &mut EffectsChain) -> Vec<Frame> {
+    (0..SAMPLE_RATE).map(|frame| chain.process([(frame % 40) as f32 / 20.0 - 1.0; 2])).collect()
+}
+
+#[test]
+fn a_reset_chain_sounds_as_a_new_one() {
+    let configs = [
+        EffectConfig::Chorus(ChorusSettings::default()),
+        EffectConfig::Flanger(FlangerSettings::default()),
+        EffectConfig::Phaser(PhaserSettings::default()),
+        EffectConfig::Distortion(DistortionSettings::default()),
+        EffectConfig::Bitcrusher(BitcrusherSettings::default()),
+    ];
+    assert!(configs.iter().all(EffectConfig::per_voice));
+    let mut chain = EffectsChain::new(&configs, SAMPLE_RATE);
+    chain.set_oversampling(Oversampling::X2);
+    let first = run(&mut chain);
+    chain.reset();
+    assert_eq!(run(&mut chain), first);
+}
+
+#[test]
+fn edits_reach_chains_not_yet_sounding() {
+    let mut edited = Synthesizer::offline(SAMPLE_RATE, &preset(0.5));
+    edited.render(&[(0, SynthCommand::SetParam(ParamId::known("voice_effects.0.mix"), 1.0))], 1);
+    let mut loaded = Synthesizer::offline(SAMPLE_RATE, &preset(1.0));
+    loaded.render(&[], 1);
+    assert_eq!(note(&mut edited), note(&mut loaded));
+}
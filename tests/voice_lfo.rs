@@ -0,0 +1,18 @@
+// Each voice's own LFO: its parameters are there to edit before it moves anything, and
+// raising a depth moves the notes already playing
+
+use rodio_synth::param_id::ParamId;
+use rodio_synth::preset::Preset;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+#[test]
+fn depths_can_be_raised_from_nothing_under_held_notes() {
+    let preset = Preset::default();
+    assert!(preset.params().iter().any(|(path, value)| path == "voice_lfo.pan_depth" && *value == 0.0));
+
+    let mut synth = Synthesizer::offline(8_000, &preset);
+    let frames = synth.render(&[(0, SynthCommand::NoteOn(69))], 100);
+    assert!(frames.iter().all(|frame| (frame[0] - frame[1]).abs() < 1e-6), "A lone voice starts in the centre");
+    let frames = synth.render(&[(0, SynthCommand::SetParam(ParamId::known("voice_lfo.pan_depth"), 1.0))], 100);
+    assert!(frames.iter().any(|frame| (frame[0] - frame[1]).abs() > 1e-3), "The held note should move off centre");
+}
@@ -0,0 +1,90 @@
+// Voice stealing: with room for two voices, a third note takes over the voice its
+// policy picks, and the notes still held afterwards show which one that was.
+
+use std::sync::mpsc;
+
+use rodio_synth::diagnostics::{self, Diagnostic};
+use rodio_synth::preset::Preset;
+use rodio_synth::voices::StealPolicy;
+use rodio_synth::{SynthCommand, Synthesizer};
+
+const SAMPLE_RATE: u32 = 8_000;
+
+// Plays `notes` a tenth of a second apart, each as (note, velocity, released after
+// this many seconds), and returns the notes held at the end
+fn held_after(policy: StealPolicy, notes: &[(u8, f32, Option<f32>)]) -> Vec<u8> {
+    held_by(|synth| synth.with_steal_policy(policy), notes)
+}
+
+// As `held_after`, with the synth set up by `setup` instead of given a policy
+fn held_by(setup: impl FnOnce(Synthesizer) -> Synthesizer, notes: &[(u8, f32, Option<f32>)]) -> Vec<u8> {
+    let preset = Preset::parse("[envelope]\nattack = 0.01\nrelease = 2.0").expect("Test preset should parse");
+    let (snapshots_tx, snapshots) = mpsc::sync_channel(1000);
+    let mut synth = setup(Synthesizer::offline(SAMPLE_RATE, &preset).with_polyphony(2)).with_snapshots(snapshots_tx);
+
+    let frame = |seconds: f32| (seconds * SAMPLE_RATE as f32) as usize;
+    let mut commands = Vec::new();
+    for (index, &(note, velocity, release)) in notes.iter().enumerate() {
+        let at = index as f32 * 0.1;
+        commands.push((frame(at), SynthCommand::SetVelocity(velocity)));
+        commands.push((frame(at), SynthCommand::NoteOn(note)));
+        if let Some(after) = release {
+            commands.push((frame(at + after), SynthCommand::NoteOff(note)));
+        }
+    }
+    commands.sort_by_key(|&(at, _)| at);
+    synth.render(&commands, frame(notes.len() as f32 * 0.1 + 0.1));
+
+    let last = snapshots.try_iter().last().expect("The synth should have sent snapshots");
+    last.notes.iter().collect()
+}
+
+#[test]
+fn oldest_takes_the_first_note() {
+    let notes = [(64, 1.0, None), (60, 1.0, None), (67, 1.0, None)];
+    assert_eq!(held_after(StealPolicy::Oldest, &notes), [60, 67]);
+}
+
+#[test]
+fn the_oldest_is_taken_by_default() {
+    let notes = [(64, 1.0, None), (60, 1.0, None), (67, 1.0, None)];
+    assert_eq!(held_by(|synth| synth, &notes), [60, 67]);
+}
+
+#[test]
+fn quietest_takes_the_softest_note() {
+    let notes = [(60, 1.0, None), (64, 0.2, None), (67, 1.0, None)];
+    assert_eq!(held_after(StealPolicy::Quietest, &notes), [60, 67]);
+}
+
+#[test]
+fn lowest_takes_the_bottom_note() {
+    let notes = [(64, 1.0, None), (60, 1.0, None), (67, 1.0, None)];
+    assert_eq!(held_after(StealPolicy::Lowest, &notes), [64, 67]);
+}
+
+#[test]
+fn refuse_new_drops_the_new_note() {
+    let notes = [(64, 1.0, None), (60, 1.0, None), (67, 1.0, None)];
+    assert_eq!(held_after(StealPolicy::RefuseNew, &notes), [60, 64]);
+}
+
+#[test]
+fn releasing_voices_go_first_whatever_the_policy() {
+    // The second note is released before the third is played, but still rings louder
+    // than the first; it's newer and higher too, so no policy would pick it among held notes
+    let notes = [(60, 0.2, None), (72, 1.0, Some(0.05)), (67, 1.0, None)];
+    for policy in [StealPolicy::Oldest, StealPolicy::Quietest, StealPolicy::Lowest, StealPolicy::RefuseNew] {
+        assert_eq!(held_after(policy, &notes), [60, 67], "{:?}", policy);
+    }
+}
+
+#[test]
+fn stolen_voices_are_queued_for_the_log() {
+    let (diagnostics_tx, diagnostics) = diagnostics::channel();
+    let notes = [(64, 1.0, None), (60, 1.0, None), (67, 1.0, None)];
+    held_by(|synth| synth.with_diagnostics(diagnostics_tx), &notes);
+
+    let stolen: Vec<Diagnostic> = diagnostics.try_iter().filter(|d| matches!(d, Diagnostic::VoiceStolen { .. })).collect();
+    assert_eq!(stolen, [Diagnostic::VoiceStolen { note: 64, releasing: false, policy: StealPolicy::Oldest }]);
+}
@@ -0,0 +1,54 @@
+// AudioWorklet side of the browser demo. The compiled engine arrives as a
+// WebAssembly.Module in the processor options and is instantiated here, on the audio
+// thread, with no imports. Notes and parameter changes come in over the port.
+
+const BLOCK = 128; // Must match `BLOCK` in src/wasm.rs
+
+class RodioSynthProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    this.wasm = new WebAssembly.Instance(options.processorOptions.module, {}).exports;
+    this.synth = this.wasm.synth_new(sampleRate);
+    this.port.onmessage = (event) => this.handle(event.data);
+  }
+
+  // Copies a string into the module's memory for the duration of `use`
+  withString(text, use) {
+    const bytes = new TextEncoder().encode(text);
+    const ptr = this.wasm.synth_alloc(bytes.length);
+    new Uint8Array(this.wasm.memory.buffer, ptr, bytes.length).set(bytes);
+    const result = use(ptr, bytes.length);
+    this.wasm.synth_free(ptr, bytes.length);
+    return result;
+  }
+
+  handle(message) {
+    switch (message.type) {
+      case "note_on":
+        this.wasm.synth_note_on(this.synth, message.note);
+        break;
+      case "note_off":
+        this.wasm.synth_note_off(this.synth, message.note);
+        break;
+      case "param":
+        this.withString(message.path, (ptr, len) => this.wasm.synth_set_param(this.synth, ptr, len, message.value));
+        break;
+      case "preset":
+        if (!this.withString(message.toml, (ptr, len) => this.wasm.synth_load_preset(this.synth, ptr, len))) {
+          this.port.postMessage({ type: "error", message: "Preset didn't parse" });
+        }
+        break;
+    }
+  }
+
+  process(inputs, outputs) {
+    const [left, right] = outputs[0];
+    this.wasm.synth_render(this.synth);
+    // The memory can grow between calls, so the views are made fresh each block
+    left.set(new Float32Array(this.wasm.memory.buffer, this.wasm.synth_left(this.synth), BLOCK));
+    right.set(new Float32Array(this.wasm.memory.buffer, this.wasm.synth_right(this.synth), BLOCK));
+    return true;
+  }
+}
+
+registerProcessor("rodio-synth", RodioSynthProcessor);